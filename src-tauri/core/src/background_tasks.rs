@@ -1,6 +1,7 @@
 // src-tauri/src/background_tasks.rs
 // Background task management for long-running processes
 
+use crate::platform::sandbox::SandboxPolicy;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -52,6 +53,9 @@ pub struct SpawnBackgroundTaskRequest {
     pub command: String,
     pub cwd: Option<String>,
     pub max_timeout_ms: Option<u64>,
+    /// Sandbox policy from the task settings; never supplied by the frontend
+    #[serde(skip)]
+    pub sandbox: Option<SandboxPolicy>,
 }
 
 /// Response for spawn task
@@ -296,23 +300,27 @@ pub async fn spawn_background_task(
     let shell = crate::shell_utils::get_windows_shell();
 
     // Build command
-    let mut cmd = if cfg!(unix) {
-        let mut c = crate::shell_utils::new_async_command(&shell);
-        c.arg("-l").arg("-i").arg("-c").arg(&request.command);
-        c
+    let args: Vec<&str> = if cfg!(unix) {
+        vec!["-l", "-i", "-c", &request.command]
+    } else if crate::shell_utils::is_powershell(&shell) {
+        vec!["-Command", &request.command]
     } else {
-        let mut c = crate::shell_utils::new_async_command(&shell);
-        if crate::shell_utils::is_powershell(&shell) {
-            c.arg("-Command").arg(&request.command);
-        } else {
-            c.arg("/C").arg(&request.command);
-        }
-        c
+        vec!["/C", &request.command]
     };
 
-    if let Some(ref dir) = cwd {
-        cmd.current_dir(dir);
-    }
+    let mut cmd = match &request.sandbox {
+        Some(policy) => policy
+            .command(&shell, &args, cwd.as_deref())
+            .map_err(|denial| denial.message)?,
+        None => {
+            let mut c = crate::shell_utils::new_async_command(&shell);
+            c.args(&args);
+            if let Some(ref dir) = cwd {
+                c.current_dir(dir);
+            }
+            c
+        }
+    };
 
     cmd.stdout(Stdio::piped());
     cmd.stderr(Stdio::piped());
@@ -6,6 +6,8 @@
 //! 3. Handles tool calls and dispatches to platform tools
 //! 4. Manages the conversation flow until completion
 
+use crate::core::compaction;
+use crate::core::prompt_builder;
 use crate::core::tools::{ToolContext, ToolDispatchResult, ToolDispatcher, ToolRegistry};
 use crate::core::types::*;
 use crate::llm::ai_services::stream_runner::StreamRunner;
@@ -14,6 +16,7 @@ use crate::llm::types::{
     Message as LlmMessage, StreamEvent, StreamTextRequest, ToolDefinition as LlmToolDefinition,
 };
 use crate::storage::models::*;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -24,6 +27,12 @@ pub struct AgentLoop {
     event_sender: EventSender,
     registry: ProviderRegistry,
     api_keys: crate::llm::auth::api_key_manager::ApiKeyManager,
+    /// Token usage reported by the provider across iterations
+    usage: std::sync::Mutex<TaskUsage>,
+    /// Context size (prompt plus response tokens) of the latest provider response
+    context_tokens: std::sync::Mutex<Option<i64>>,
+    /// Model that served the latest request, which differs from the requested one after a fallback
+    selected_model: std::sync::Mutex<Option<String>>,
 }
 
 /// Context for a single agent loop execution
@@ -37,21 +46,37 @@ pub struct AgentLoopContext {
     pub messages: Vec<Message>,
     pub model: Option<String>,
     pub llm_state: Option<Arc<crate::llm::auth::api_key_manager::LlmState>>,
+    /// System prompt sent ahead of the session history
+    pub system_prompt: Option<String>,
+    /// Attachments referenced by `messages`, keyed by attachment ID
+    pub attachments: HashMap<AttachmentId, ResolvedAttachment>,
+}
+
+/// Attachment loaded for a request
+#[derive(Debug, Clone)]
+pub struct ResolvedAttachment {
+    pub filename: String,
+    pub mime_type: String,
+    /// Base64-encoded file content, `None` when the model can't take this media type
+    pub data: Option<String>,
 }
 
 /// Result of agent loop execution
 #[derive(Debug, Clone)]
 pub enum AgentLoopResult {
     /// Completed successfully with final response
-    Completed { message: String },
+    Completed {
+        message: String,
+        /// Reasoning parts produced ahead of the response
+        reasoning: Vec<MessagePart>,
+    },
     /// Tool calls returned, waiting for execution
     ToolCalls {
         accumulated_text: String,
+        reasoning: Vec<MessagePart>,
         tool_calls: Vec<ToolRequest>,
         finish_reason: Option<String>,
     },
-    /// Waiting for user approval of tool call
-    WaitingForApproval { request: ToolRequest },
     /// Waiting for tool result
     WaitingForToolResult { tool_call_id: ToolCallId },
     /// Error occurred
@@ -67,11 +92,49 @@ pub enum AgentLoopResult {
 struct StreamProcessorState {
     accumulated_text: String,
     tool_calls: Vec<ToolRequest>,
+    /// Reasoning blocks in the order the provider started them
+    reasoning: Vec<ReasoningBlock>,
     finish_reason: Option<String>,
     has_error: bool,
     error_message: Option<String>,
 }
 
+/// Reasoning block accumulated from stream events
+#[derive(Debug)]
+struct ReasoningBlock {
+    id: String,
+    text: String,
+    provider_metadata: Option<serde_json::Value>,
+}
+
+impl StreamProcessorState {
+    fn reasoning_block(&mut self, id: &str) -> &mut ReasoningBlock {
+        match self.reasoning.iter().position(|block| block.id == id) {
+            Some(index) => &mut self.reasoning[index],
+            None => {
+                self.reasoning.push(ReasoningBlock {
+                    id: id.to_string(),
+                    text: String::new(),
+                    provider_metadata: None,
+                });
+                self.reasoning.last_mut().expect("block was just pushed")
+            }
+        }
+    }
+
+    /// Reasoning as stored message parts. Blocks with neither text nor metadata are dropped.
+    fn take_reasoning(&mut self) -> Vec<MessagePart> {
+        std::mem::take(&mut self.reasoning)
+            .into_iter()
+            .filter(|block| !block.text.is_empty() || block.provider_metadata.is_some())
+            .map(|block| MessagePart::Reasoning {
+                text: block.text,
+                provider_metadata: block.provider_metadata,
+            })
+            .collect()
+    }
+}
+
 impl AgentLoop {
     pub fn new(
         config: AgentLoopConfig,
@@ -86,20 +149,42 @@ impl AgentLoop {
             event_sender,
             registry,
             api_keys,
+            usage: std::sync::Mutex::new(TaskUsage::default()),
+            context_tokens: std::sync::Mutex::new(None),
+            selected_model: std::sync::Mutex::new(None),
         }
     }
 
+    /// Configuration this loop was built with
+    pub fn config(&self) -> &AgentLoopConfig {
+        &self.config
+    }
+
+    /// Token usage accumulated by this loop so far
+    pub fn usage(&self) -> TaskUsage {
+        self.usage.lock().map(|u| *u).unwrap_or_default()
+    }
+
+    /// Context size reported by the latest provider response, if any
+    pub fn context_tokens(&self) -> Option<i64> {
+        self.context_tokens.lock().ok().and_then(|t| *t)
+    }
+
+    /// Model that served the latest request, if the stream reported one
+    pub fn selected_model(&self) -> Option<String> {
+        self.selected_model.lock().ok().and_then(|m| m.clone())
+    }
+
     /// Run the agent loop with full LLM integration
     pub async fn run(&self, ctx: &AgentLoopContext) -> Result<AgentLoopResult, String> {
         let messages = ctx.messages.clone();
 
         // Run a single iteration
         match self.run_iteration(ctx, &messages).await? {
-            AgentLoopResult::Completed { message } => Ok(AgentLoopResult::Completed { message }),
-            AgentLoopResult::ToolCalls { .. } => Ok(AgentLoopResult::MaxIterationsReached),
-            AgentLoopResult::WaitingForApproval { request } => {
-                Ok(AgentLoopResult::WaitingForApproval { request })
+            AgentLoopResult::Completed { message, reasoning } => {
+                Ok(AgentLoopResult::Completed { message, reasoning })
             }
+            AgentLoopResult::ToolCalls { .. } => Ok(AgentLoopResult::MaxIterationsReached),
             AgentLoopResult::WaitingForToolResult { tool_call_id } => {
                 Ok(AgentLoopResult::WaitingForToolResult { tool_call_id })
             }
@@ -115,11 +200,24 @@ impl AgentLoop {
         ctx: &AgentLoopContext,
         messages: &[Message],
     ) -> Result<AgentLoopResult, String> {
-        // Convert messages to LLM format
-        let llm_messages: Vec<LlmMessage> = messages
-            .iter()
-            .map(|m| self.convert_message_to_llm(m))
+        // Convert messages to LLM format. Stored prompt versions are replaced by the current one.
+        let system_message = ctx
+            .system_prompt
+            .as_ref()
+            .map(|content| LlmMessage::System {
+                content: content.clone(),
+                provider_options: None,
+            });
+        let llm_messages: Vec<LlmMessage> = system_message
+            .into_iter()
+            .chain(
+                messages
+                    .iter()
+                    .filter(|m| !prompt_builder::is_system_prompt_message(m))
+                    .map(|m| self.convert_message_to_llm(m, &ctx.attachments)),
+            )
             .collect();
+        let llm_messages = merge_assistant_turns(llm_messages);
 
         // Build tools for LLM
         let tools = if self.config.enable_tools {
@@ -141,9 +239,10 @@ impl AgentLoop {
             max_tokens: self.config.max_tokens.map(|t| t as i32),
             top_p: None,
             top_k: None,
-            provider_options: None,
+            provider_options: self.config.request_provider_options(),
             request_id: Some(ctx.task_id.clone()),
             trace_context: None,
+            response_format: None,
         };
 
         // Run stream
@@ -173,6 +272,7 @@ impl AgentLoop {
         // Handle tool calls
         if !state.tool_calls.is_empty() {
             return Ok(AgentLoopResult::ToolCalls {
+                reasoning: state.take_reasoning(),
                 accumulated_text: state.accumulated_text,
                 tool_calls: state.tool_calls,
                 finish_reason: state.finish_reason,
@@ -187,6 +287,7 @@ impl AgentLoop {
         }
 
         Ok(AgentLoopResult::Completed {
+            reasoning: state.take_reasoning(),
             message: state.accumulated_text,
         })
     }
@@ -214,24 +315,45 @@ impl AgentLoop {
                 input,
                 provider_metadata,
             } => {
-                let tool_request = ToolRequest {
-                    tool_call_id: tool_call_id.clone(),
-                    name: tool_name.clone(),
+                // The runtime announces the call once it resolves it
+                state.tool_calls.push(ToolRequest {
+                    tool_call_id,
+                    name: tool_name,
                     input,
                     provider_metadata,
-                };
-                state.tool_calls.push(tool_request.clone());
-
-                // Emit tool call requested event
-                let _ = self.event_sender.send(RuntimeEvent::ToolCallRequested {
+                });
+            }
+            StreamEvent::ToolCallStart {
+                tool_call_id,
+                tool_name,
+            } => {
+                let _ = self.event_sender.send(RuntimeEvent::ToolCallStart {
                     task_id: ctx.task_id.clone(),
-                    request: tool_request,
+                    session_id: ctx.session_id.clone(),
+                    tool_call_id,
+                    name: tool_name,
+                });
+            }
+            StreamEvent::ToolCallDelta {
+                tool_call_id,
+                arguments_delta,
+            } => {
+                let _ = self.event_sender.send(RuntimeEvent::ToolCallDelta {
+                    task_id: ctx.task_id.clone(),
+                    session_id: ctx.session_id.clone(),
+                    tool_call_id,
+                    arguments_delta,
                 });
             }
             StreamEvent::ReasoningStart {
                 id,
-                provider_metadata: _,
+                provider_metadata,
             } => {
+                let block = state.reasoning_block(&id);
+                if let Some(metadata) = provider_metadata {
+                    merge_metadata(&mut block.provider_metadata, metadata);
+                }
+
                 // Emit reasoning start event
                 let _ = self.event_sender.send(RuntimeEvent::ReasoningStart {
                     session_id: ctx.session_id.clone(),
@@ -241,8 +363,15 @@ impl AgentLoop {
             StreamEvent::ReasoningDelta {
                 id,
                 text,
-                provider_metadata: _,
+                provider_metadata,
             } => {
+                // Signatures and encrypted content arrive as deltas without text
+                let block = state.reasoning_block(&id);
+                block.text.push_str(&text);
+                if let Some(metadata) = provider_metadata {
+                    merge_metadata(&mut block.provider_metadata, metadata);
+                }
+
                 // Emit reasoning delta event
                 let _ = self.event_sender.send(RuntimeEvent::ReasoningDelta {
                     session_id: ctx.session_id.clone(),
@@ -264,6 +393,16 @@ impl AgentLoop {
                 cached_input_tokens,
                 cache_creation_input_tokens,
             } => {
+                if let Ok(mut usage) = self.usage.lock() {
+                    usage.input_tokens += i64::from(input_tokens);
+                    usage.output_tokens += i64::from(output_tokens);
+                    usage.cached_input_tokens += i64::from(cached_input_tokens.unwrap_or(0));
+                    usage.cache_creation_input_tokens +=
+                        i64::from(cache_creation_input_tokens.unwrap_or(0));
+                }
+                if let Ok(mut context_tokens) = self.context_tokens.lock() {
+                    *context_tokens = Some(i64::from(input_tokens) + i64::from(output_tokens));
+                }
                 let _ = self.event_sender.send(RuntimeEvent::Usage {
                     session_id: ctx.session_id.clone(),
                     input_tokens,
@@ -273,6 +412,11 @@ impl AgentLoop {
                     cache_creation_input_tokens,
                 });
             }
+            StreamEvent::ModelSelected { model, .. } => {
+                if let Ok(mut selected) = self.selected_model.lock() {
+                    *selected = Some(model);
+                }
+            }
             StreamEvent::Done { finish_reason } => {
                 state.finish_reason = finish_reason;
             }
@@ -285,7 +429,25 @@ impl AgentLoop {
     }
 
     /// Convert internal Message to LLM Message format
-    fn convert_message_to_llm(&self, message: &Message) -> LlmMessage {
+    fn convert_message_to_llm(
+        &self,
+        message: &Message,
+        attachments: &HashMap<AttachmentId, ResolvedAttachment>,
+    ) -> LlmMessage {
+        // Compaction summaries stand in for the turns they replaced
+        if compaction::is_compaction_message(message) {
+            if let MessageContent::Text { text } = &message.content {
+                return LlmMessage::User {
+                    content: crate::llm::types::MessageContent::Text(format!(
+                        "{}\n{}",
+                        compaction::SUMMARY_HEADING,
+                        text
+                    )),
+                    provider_options: None,
+                };
+            }
+        }
+
         match message.role {
             MessageRole::User => LlmMessage::User {
                 content: match &message.content {
@@ -299,7 +461,7 @@ impl AgentLoop {
                                 tool_call_id: call.id.clone(),
                                 tool_name: call.name.clone(),
                                 input: call.input.clone(),
-                                provider_metadata: None,
+                                provider_metadata: call.provider_metadata.clone(),
                             })
                             .collect();
                         crate::llm::types::MessageContent::Parts(parts)
@@ -313,6 +475,9 @@ impl AgentLoop {
                         }];
                         crate::llm::types::MessageContent::Parts(parts)
                     }
+                    MessageContent::Parts { parts } => {
+                        crate::llm::types::MessageContent::Parts(convert_parts(parts, attachments))
+                    }
                 },
                 provider_options: None,
             },
@@ -328,7 +493,7 @@ impl AgentLoop {
                                 tool_call_id: call.id.clone(),
                                 tool_name: call.name.clone(),
                                 input: call.input.clone(),
-                                provider_metadata: None,
+                                provider_metadata: call.provider_metadata.clone(),
                             })
                             .collect();
                         crate::llm::types::MessageContent::Parts(parts)
@@ -342,6 +507,9 @@ impl AgentLoop {
                         }];
                         crate::llm::types::MessageContent::Parts(parts)
                     }
+                    MessageContent::Parts { parts } => {
+                        crate::llm::types::MessageContent::Parts(convert_parts(parts, attachments))
+                    }
                 },
                 provider_options: None,
             },
@@ -354,6 +522,14 @@ impl AgentLoop {
                     MessageContent::ToolResult { result } => {
                         serde_json::to_string(result).unwrap_or_default()
                     }
+                    MessageContent::Parts { parts } => parts
+                        .iter()
+                        .filter_map(|part| match part {
+                            MessagePart::Text { text } => Some(text.as_str()),
+                            _ => None,
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n"),
                 },
                 provider_options: None,
             },
@@ -376,9 +552,10 @@ impl AgentLoop {
                             tool_call_id: call.id.clone(),
                             tool_name: call.name.clone(),
                             input: call.input.clone(),
-                            provider_metadata: None,
+                            provider_metadata: call.provider_metadata.clone(),
                         })
                         .collect(),
+                    MessageContent::Parts { parts } => convert_parts(parts, attachments),
                 };
                 LlmMessage::Tool {
                     content: parts,
@@ -394,6 +571,7 @@ impl AgentLoop {
 
         get_tool_definitions()
             .into_iter()
+            .filter(|(def, _)| self.config.is_tool_allowed(&def.name))
             .map(|(def, _)| LlmToolDefinition {
                 tool_type: "function".to_string(),
                 name: def.name,
@@ -417,6 +595,7 @@ impl AgentLoop {
             worktree_path: ctx.worktree_path.clone(),
             settings: ctx.settings.clone(),
             llm_state: ctx.llm_state.clone(),
+            event_sender: Some(self.event_sender.clone()),
         };
 
         // Check auto-approve settings
@@ -446,6 +625,7 @@ impl AgentLoop {
             worktree_path: ctx.worktree_path.clone(),
             settings: ctx.settings.clone(),
             llm_state: ctx.llm_state.clone(),
+            event_sender: Some(self.event_sender.clone()),
         };
 
         let result = self
@@ -456,6 +636,7 @@ impl AgentLoop {
         // Emit completion event
         let _ = self.event_sender.send(RuntimeEvent::ToolCallCompleted {
             task_id: ctx.task_id.clone(),
+            session_id: ctx.session_id.clone(),
             result: result.clone(),
         });
 
@@ -484,6 +665,9 @@ impl AgentLoop {
                 MessageContent::ToolResult { result } => {
                     format!("Tool result: {:?}", result)
                 }
+                MessageContent::Parts { parts } => {
+                    format!("Parts: {:?}", parts)
+                }
             };
 
             prompt.push_str(&format!("{}: {}\n", role_str, content_str));
@@ -502,6 +686,113 @@ impl AgentLoop {
     }
 }
 
+/// Convert stored message parts to LLM content parts
+fn convert_parts(
+    parts: &[MessagePart],
+    attachments: &HashMap<AttachmentId, ResolvedAttachment>,
+) -> Vec<crate::llm::types::ContentPart> {
+    parts
+        .iter()
+        .map(|part| match part {
+            MessagePart::Text { text } => {
+                crate::llm::types::ContentPart::Text { text: text.clone() }
+            }
+            MessagePart::Reasoning {
+                text,
+                provider_metadata,
+            } => crate::llm::types::ContentPart::Reasoning {
+                text: text.clone(),
+                provider_options: provider_metadata.clone(),
+            },
+            _ => attachment_part(part.attachment_id().unwrap_or_default(), attachments),
+        })
+        .collect()
+}
+
+/// Join consecutive assistant messages into one turn. Reasoning, text and tool calls
+/// are stored as separate messages, but providers expect reasoning to be replayed in
+/// the same turn as the tool calls it led to.
+fn merge_assistant_turns(messages: Vec<LlmMessage>) -> Vec<LlmMessage> {
+    use crate::llm::types::{ContentPart, MessageContent as LlmContent};
+
+    fn into_parts(content: LlmContent) -> Vec<ContentPart> {
+        match content {
+            LlmContent::Text(text) if text.is_empty() => Vec::new(),
+            LlmContent::Text(text) => vec![ContentPart::Text { text }],
+            LlmContent::Parts(parts) => parts,
+        }
+    }
+
+    let mut merged: Vec<LlmMessage> = Vec::with_capacity(messages.len());
+    for message in messages {
+        match (merged.last_mut(), message) {
+            (
+                Some(LlmMessage::Assistant {
+                    content: previous, ..
+                }),
+                LlmMessage::Assistant { content, .. },
+            ) => {
+                let mut parts = into_parts(std::mem::replace(previous, LlmContent::Parts(vec![])));
+                parts.extend(into_parts(content));
+                *previous = LlmContent::Parts(parts);
+            }
+            (_, message) => merged.push(message),
+        }
+    }
+    merged
+}
+
+/// Merge provider metadata from a later stream event into what a block already has.
+/// Objects merge key by key and nulls never overwrite a value.
+fn merge_metadata(target: &mut Option<serde_json::Value>, update: serde_json::Value) {
+    match (target.as_mut(), update) {
+        (_, serde_json::Value::Null) => {}
+        (Some(serde_json::Value::Object(existing)), serde_json::Value::Object(update)) => {
+            for (key, value) in update {
+                let mut slot = existing.remove(&key);
+                merge_metadata(&mut slot, value);
+                if let Some(slot) = slot {
+                    existing.insert(key, slot);
+                }
+            }
+        }
+        (_, update) => *target = Some(update),
+    }
+}
+
+/// Images and videos are inlined when the model accepts them; anything else
+/// is described in text so the model knows it exists.
+fn attachment_part(
+    attachment_id: &str,
+    attachments: &HashMap<AttachmentId, ResolvedAttachment>,
+) -> crate::llm::types::ContentPart {
+    let Some(attachment) = attachments.get(attachment_id) else {
+        return crate::llm::types::ContentPart::Text {
+            text: format!("[Attachment {} not found]", attachment_id),
+        };
+    };
+    match &attachment.data {
+        Some(data) if attachment.mime_type.starts_with("image/") => {
+            crate::llm::types::ContentPart::Image {
+                image: data.clone(),
+                mime_type: Some(attachment.mime_type.clone()),
+            }
+        }
+        Some(data) if attachment.mime_type.starts_with("video/") => {
+            crate::llm::types::ContentPart::Video {
+                video: data.clone(),
+                mime_type: Some(attachment.mime_type.clone()),
+            }
+        }
+        _ => crate::llm::types::ContentPart::Text {
+            text: format!(
+                "[Attachment {} ({})]",
+                attachment.filename, attachment.mime_type
+            ),
+        },
+    }
+}
+
 /// Factory for creating agent loops with different configurations
 pub struct AgentLoopFactory;
 
@@ -584,6 +875,8 @@ mod tests {
             messages: vec![],
             model: None,
             llm_state: None,
+            system_prompt: None,
+            attachments: HashMap::new(),
         };
 
         // Test that the loop runs without panicking
@@ -591,6 +884,207 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_build_tool_definitions_respects_config() {
+        let (mut agent_loop, _rx) = create_test_loop().await;
+        let all_tools = agent_loop.build_tool_definitions();
+        assert!(all_tools.iter().any(|t| t.name == "bash"));
+
+        agent_loop.config = AgentLoopConfig {
+            available_tools: vec!["readFile".to_string(), "bash".to_string()],
+            disallowed_tools: vec!["bash".to_string()],
+            ..Default::default()
+        };
+        let names: Vec<String> = agent_loop
+            .build_tool_definitions()
+            .into_iter()
+            .map(|t| t.name)
+            .collect();
+        assert_eq!(names, vec!["readFile".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_convert_message_inlines_resolved_attachments() {
+        let (agent_loop, _rx) = create_test_loop().await;
+        let message = Message {
+            id: "msg-1".to_string(),
+            session_id: "test".to_string(),
+            role: MessageRole::User,
+            content: MessageContent::Parts {
+                parts: vec![
+                    MessagePart::Text {
+                        text: "Compare these".to_string(),
+                    },
+                    MessagePart::Image {
+                        attachment_id: "att_png".to_string(),
+                    },
+                    MessagePart::Audio {
+                        attachment_id: "att_wav".to_string(),
+                    },
+                    MessagePart::File {
+                        attachment_id: "att_missing".to_string(),
+                    },
+                ],
+            },
+            created_at: 0,
+            tool_call_id: None,
+            parent_id: None,
+        };
+        let attachments = HashMap::from([
+            (
+                "att_png".to_string(),
+                ResolvedAttachment {
+                    filename: "shot.png".to_string(),
+                    mime_type: "image/png".to_string(),
+                    data: Some("aGVsbG8=".to_string()),
+                },
+            ),
+            (
+                "att_wav".to_string(),
+                ResolvedAttachment {
+                    filename: "note.wav".to_string(),
+                    mime_type: "audio/wav".to_string(),
+                    data: None,
+                },
+            ),
+        ]);
+
+        let LlmMessage::User { content, .. } =
+            agent_loop.convert_message_to_llm(&message, &attachments)
+        else {
+            panic!("expected user message");
+        };
+        let crate::llm::types::MessageContent::Parts(parts) = content else {
+            panic!("expected content parts");
+        };
+        let parts = serde_json::to_value(&parts).unwrap();
+        assert_eq!(
+            parts,
+            serde_json::json!([
+                { "type": "text", "text": "Compare these" },
+                { "type": "image", "image": "aGVsbG8=", "mimeType": "image/png" },
+                { "type": "text", "text": "[Attachment note.wav (audio/wav)]" },
+                { "type": "text", "text": "[Attachment att_missing not found]" }
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_process_stream_event_collects_reasoning() {
+        let (agent_loop, _rx) = create_test_loop().await;
+        let ctx = AgentLoopContext {
+            session_id: "test".to_string(),
+            task_id: "task".to_string(),
+            workspace_root: String::new(),
+            worktree_path: None,
+            settings: TaskSettings::default(),
+            messages: Vec::new(),
+            model: None,
+            llm_state: None,
+            system_prompt: None,
+            attachments: HashMap::new(),
+        };
+        let mut state = StreamProcessorState::default();
+        let events = vec![
+            StreamEvent::ReasoningStart {
+                id: "thinking_0".to_string(),
+                provider_metadata: None,
+            },
+            StreamEvent::ReasoningDelta {
+                id: "thinking_0".to_string(),
+                text: "Check the file".to_string(),
+                provider_metadata: None,
+            },
+            StreamEvent::ReasoningDelta {
+                id: "thinking_0".to_string(),
+                text: String::new(),
+                provider_metadata: Some(serde_json::json!({ "anthropic": { "signature": "sig" } })),
+            },
+            StreamEvent::ReasoningStart {
+                id: "rs_1:0".to_string(),
+                provider_metadata: Some(serde_json::json!({
+                    "openai": { "itemId": "rs_1", "reasoningEncryptedContent": null }
+                })),
+            },
+            StreamEvent::ReasoningDelta {
+                id: "rs_1:0".to_string(),
+                text: String::new(),
+                provider_metadata: Some(serde_json::json!({
+                    "openai": { "itemId": "rs_1", "reasoningEncryptedContent": "enc" }
+                })),
+            },
+            StreamEvent::ReasoningEnd {
+                id: "rs_1:0".to_string(),
+            },
+        ];
+        for event in events {
+            agent_loop.process_stream_event(&mut state, event, &ctx);
+        }
+
+        assert_eq!(
+            state.take_reasoning(),
+            vec![
+                MessagePart::Reasoning {
+                    text: "Check the file".to_string(),
+                    provider_metadata: Some(serde_json::json!({
+                        "anthropic": { "signature": "sig" }
+                    })),
+                },
+                MessagePart::Reasoning {
+                    text: String::new(),
+                    provider_metadata: Some(serde_json::json!({
+                        "openai": { "itemId": "rs_1", "reasoningEncryptedContent": "enc" }
+                    })),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_assistant_turns() {
+        use crate::llm::types::{ContentPart, MessageContent as LlmContent};
+
+        let assistant = |content| LlmMessage::Assistant {
+            content,
+            provider_options: None,
+        };
+        let messages = vec![
+            assistant(LlmContent::Parts(vec![ContentPart::Reasoning {
+                text: "think".to_string(),
+                provider_options: None,
+            }])),
+            assistant(LlmContent::Text("Reading it".to_string())),
+            assistant(LlmContent::Parts(vec![ContentPart::ToolCall {
+                tool_call_id: "call_1".to_string(),
+                tool_name: "readFile".to_string(),
+                input: serde_json::json!({}),
+                provider_metadata: None,
+            }])),
+            LlmMessage::User {
+                content: LlmContent::Text("next".to_string()),
+                provider_options: None,
+            },
+            assistant(LlmContent::Text("done".to_string())),
+        ];
+
+        let merged = merge_assistant_turns(messages);
+        assert_eq!(merged.len(), 3);
+        let LlmMessage::Assistant {
+            content: LlmContent::Parts(parts),
+            ..
+        } = &merged[0]
+        else {
+            panic!("expected merged assistant parts");
+        };
+        assert_eq!(parts.len(), 3);
+        assert!(matches!(parts[0], ContentPart::Reasoning { .. }));
+        assert!(matches!(parts[2], ContentPart::ToolCall { .. }));
+        assert!(matches!(
+            &merged[2],
+            LlmMessage::Assistant { content: LlmContent::Text(text), .. } if text == "done"
+        ));
+    }
+
     #[test]
     fn test_build_prompt() {
         let messages = vec![
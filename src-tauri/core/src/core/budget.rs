@@ -0,0 +1,108 @@
+//! Task Budgets
+//!
+//! Checks task and daily spending against the limits in `TaskSettings.budget`.
+//! The runtime checks before every model call, so a task stops at the first
+//! turn boundary after it goes over a limit.
+
+use crate::storage::{BudgetSettings, UsageTotals};
+
+/// Seconds in a UTC day
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// Whether any daily limit is set, which requires loading the day's totals
+pub fn has_daily_limit(budget: &BudgetSettings) -> bool {
+    budget.max_daily_cost_usd.is_some() || budget.max_daily_tokens.is_some()
+}
+
+/// Start of the UTC day containing `timestamp`
+pub fn day_start(timestamp: i64) -> i64 {
+    timestamp - timestamp.rem_euclid(SECONDS_PER_DAY)
+}
+
+/// Describe the first limit that `task` or `daily` usage has reached, if any
+pub fn budget_violation(
+    budget: &BudgetSettings,
+    task: &UsageTotals,
+    daily: &UsageTotals,
+) -> Option<String> {
+    if let Some(max) = budget.max_task_cost_usd {
+        if task.cost_usd >= max {
+            return Some(format!(
+                "Task budget exceeded: spent ${:.4} of ${:.4}",
+                task.cost_usd, max
+            ));
+        }
+    }
+    if let Some(max) = budget.max_task_tokens {
+        if task.total_tokens() >= max {
+            return Some(format!(
+                "Task token budget exceeded: used {} of {} tokens",
+                task.total_tokens(),
+                max
+            ));
+        }
+    }
+    if let Some(max) = budget.max_daily_cost_usd {
+        if daily.cost_usd >= max {
+            return Some(format!(
+                "Daily budget exceeded: spent ${:.4} of ${:.4} today",
+                daily.cost_usd, max
+            ));
+        }
+    }
+    if let Some(max) = budget.max_daily_tokens {
+        if daily.total_tokens() >= max {
+            return Some(format!(
+                "Daily token budget exceeded: used {} of {} tokens today",
+                daily.total_tokens(),
+                max
+            ));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(tokens: i64, cost_usd: f64) -> UsageTotals {
+        UsageTotals {
+            input_tokens: tokens,
+            cost_usd,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_budget_violation() {
+        let budget = BudgetSettings {
+            max_task_cost_usd: Some(1.0),
+            max_daily_tokens: Some(10_000),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            budget_violation(&budget, &usage(500, 0.5), &usage(5_000, 2.0)),
+            None
+        );
+        assert!(budget_violation(&budget, &usage(500, 1.0), &usage(0, 0.0))
+            .unwrap()
+            .starts_with("Task budget exceeded"));
+        assert!(
+            budget_violation(&budget, &usage(500, 0.5), &usage(10_000, 0.0))
+                .unwrap()
+                .starts_with("Daily token budget exceeded")
+        );
+        assert_eq!(
+            budget_violation(&BudgetSettings::default(), &usage(1, 9.0), &usage(1, 9.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_day_start() {
+        assert_eq!(day_start(86_400 + 3_600), 86_400);
+        assert_eq!(day_start(86_400), 86_400);
+    }
+}
@@ -0,0 +1,253 @@
+//! File Checkpoints
+//!
+//! Works out which files a file-editing tool call is about to change, so the
+//! runtime can snapshot them first, and writes snapshots back on restore.
+
+use crate::storage::FileCheckpoint;
+use crate::tools::multi_edit;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// A checkpoint compared with the file as it is now
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckpointDiff {
+    pub checkpoint: FileCheckpoint,
+    /// Unified diff from the snapshot to the current file
+    pub diff: String,
+    pub additions: usize,
+    pub deletions: usize,
+}
+
+/// Why a checkpoint restore did not happen
+#[derive(Debug, Clone, PartialEq)]
+pub enum RestoreError {
+    /// The task is still running, and restoring would race its own edits
+    TaskRunning,
+    /// The task has no such checkpoint
+    NotFound,
+    Other(String),
+}
+
+impl std::fmt::Display for RestoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TaskRunning => {
+                write!(f, "Task is still running; cancel it before restoring files")
+            }
+            Self::NotFound => write!(f, "Checkpoint not found"),
+            Self::Other(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for RestoreError {}
+
+impl From<String> for RestoreError {
+    fn from(msg: String) -> Self {
+        Self::Other(msg)
+    }
+}
+
+/// Files a tool call may create, change or delete; empty for tools that do
+/// not edit files. `tool_name` is the canonical name.
+pub fn checkpoint_targets(
+    tool_name: &str,
+    input: &serde_json::Value,
+    workspace_root: &str,
+) -> Vec<PathBuf> {
+    let mut paths: Vec<String> = Vec::new();
+    match tool_name {
+        "writeFile" | "editFile" => {
+            if let Some(path) = input.get("file_path").and_then(|v| v.as_str()) {
+                paths.push(path.to_string());
+            }
+        }
+        "multiEdit" => {
+            if let Some(files) = input.get("files").and_then(|v| v.as_array()) {
+                paths.extend(
+                    files
+                        .iter()
+                        .filter_map(|f| f.get("file_path")?.as_str().map(str::to_string)),
+                );
+            }
+            if let Some(patch) = input.get("patch").and_then(|v| v.as_str()) {
+                paths.extend(multi_edit::patch_paths(patch));
+            }
+        }
+        _ => {}
+    }
+
+    let mut targets = Vec::new();
+    for path in paths.iter().filter(|path| !path.is_empty()) {
+        let path = if Path::new(path).is_absolute() {
+            PathBuf::from(path)
+        } else {
+            Path::new(workspace_root).join(path)
+        };
+        let path = path.canonicalize().unwrap_or(path);
+        if !targets.contains(&path) {
+            targets.push(path);
+        }
+    }
+    targets
+}
+
+/// The checkpoints that undo a task's changes from `sequence` onwards: the
+/// earliest one at or after it for each file
+pub fn restore_points(checkpoints: &[FileCheckpoint], sequence: i64) -> Vec<&FileCheckpoint> {
+    let mut later: Vec<&FileCheckpoint> = checkpoints
+        .iter()
+        .filter(|c| c.sequence >= sequence)
+        .collect();
+    later.sort_by_key(|checkpoint| checkpoint.sequence);
+
+    let mut points: Vec<&FileCheckpoint> = Vec::new();
+    for checkpoint in later {
+        if !points.iter().any(|p| p.file_path == checkpoint.file_path) {
+            points.push(checkpoint);
+        }
+    }
+    points
+}
+
+/// Compare a snapshot with the file on disk; a missing file counts as empty
+pub async fn diff_against_current(
+    checkpoint: FileCheckpoint,
+    snapshot: Option<&[u8]>,
+) -> Result<CheckpointDiff, String> {
+    let current = match tokio::fs::read(&checkpoint.file_path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(format!("Failed to read {}: {}", checkpoint.file_path, e)),
+    };
+
+    let (diff, additions, deletions) = multi_edit::unified_diff(
+        Path::new(&checkpoint.file_path),
+        snapshot.unwrap_or_default(),
+        &current,
+    )
+    .map_err(|e| format!("Failed to diff {}: {}", checkpoint.file_path, e))?;
+
+    Ok(CheckpointDiff {
+        checkpoint,
+        diff,
+        additions,
+        deletions,
+    })
+}
+
+/// Put a file back as a checkpoint found it: write the snapshot through a
+/// temp file and rename, or delete the file if it did not exist yet
+pub async fn restore_file(path: &Path, snapshot: Option<&[u8]>) -> Result<(), String> {
+    let Some(content) = snapshot else {
+        return match tokio::fs::remove_file(path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(format!("Failed to delete {}: {}", path.display(), e)),
+        };
+    };
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| format!("Failed to create directory {}: {}", parent.display(), e))?;
+    }
+
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let temp_path = path.with_file_name(format!(
+        ".{}.talkcody-{}.tmp",
+        name,
+        uuid::Uuid::new_v4().simple()
+    ));
+    tokio::fs::write(&temp_path, content)
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    if let Err(e) = tokio::fs::rename(&temp_path, path).await {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        return Err(format!("Failed to restore {}: {}", path.display(), e));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn checkpoint(id: &str, file_path: &str, sequence: i64) -> FileCheckpoint {
+        FileCheckpoint {
+            id: id.to_string(),
+            task_id: "task-1".to_string(),
+            session_id: "session-1".to_string(),
+            tool_call_id: format!("call-{}", id),
+            tool_name: "editFile".to_string(),
+            file_path: file_path.to_string(),
+            existed: true,
+            size: 0,
+            sequence,
+            created_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_checkpoint_targets() {
+        let input = serde_json::json!({
+            "files": [{ "file_path": "a.txt", "edits": [] }],
+            "patch": "--- a/b.txt\n+++ b/b.txt\n@@ -1 +1 @@\n-x\n+y\n",
+        });
+        let targets = checkpoint_targets("multiEdit", &input, "/workspace");
+        assert_eq!(
+            targets,
+            vec![
+                PathBuf::from("/workspace/a.txt"),
+                PathBuf::from("/workspace/b.txt")
+            ]
+        );
+
+        let input = serde_json::json!({ "command": "ls" });
+        assert!(checkpoint_targets("bash", &input, "/workspace").is_empty());
+    }
+
+    #[test]
+    fn test_restore_points_use_earliest_checkpoint_per_file() {
+        let checkpoints = vec![
+            checkpoint("1", "/a", 1),
+            checkpoint("2", "/b", 2),
+            checkpoint("3", "/a", 3),
+            checkpoint("4", "/c", 4),
+        ];
+
+        let ids: Vec<&str> = restore_points(&checkpoints, 2)
+            .iter()
+            .map(|c| c.id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["2", "3", "4"]);
+    }
+
+    #[tokio::test]
+    async fn test_restore_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("file.txt");
+        std::fs::write(&path, "changed\n").unwrap();
+
+        let diff = diff_against_current(
+            checkpoint("1", &path.to_string_lossy(), 1),
+            Some(b"original\n".as_slice()),
+        )
+        .await
+        .unwrap();
+        assert_eq!((diff.additions, diff.deletions), (1, 1));
+
+        restore_file(&path, Some(b"original\n".as_slice()))
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "original\n");
+
+        restore_file(&path, None).await.unwrap();
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,327 @@
+//! Context Compaction
+//!
+//! Keeps long sessions inside the model's context window. When the last
+//! reported context size passes a threshold, older turns are summarized and
+//! stored as a compaction message. The compaction message points at the last
+//! message it covers through `parent_id`, so replaying the stored history
+//! yields the same view the model saw.
+
+use crate::core::prompt_builder;
+use crate::storage::{Message, MessageContent, MessagePart, MessageRole, SessionId};
+
+/// Prefix of stored compaction message IDs
+pub const COMPACTION_ID_PREFIX: &str = "compaction_";
+
+/// Context window assumed when the model config has no `context_length`
+pub const DEFAULT_CONTEXT_LENGTH: u32 = 128_000;
+
+/// Most recent messages that are always kept verbatim
+pub const KEEP_RECENT_MESSAGES: usize = 6;
+
+/// Maximum characters of a single tool result included in the transcript
+const MAX_TOOL_OUTPUT_CHARS: usize = 2_000;
+
+/// Heading used when the summary is sent to the model
+pub const SUMMARY_HEADING: &str = "Summary of the earlier conversation:";
+
+/// Whether a message is a stored compaction summary
+pub fn is_compaction_message(message: &Message) -> bool {
+    message.role == MessageRole::System && message.id.starts_with(COMPACTION_ID_PREFIX)
+}
+
+/// Whether the context has grown past `threshold` of the model's window.
+/// A threshold of zero or less disables compaction.
+pub fn should_compact(context_tokens: i64, context_length: u32, threshold: f32) -> bool {
+    threshold > 0.0 && context_tokens as f64 >= f64::from(context_length) * f64::from(threshold)
+}
+
+/// The messages the model sees: the latest compaction summary followed by
+/// everything stored after the message it covers
+pub fn active_messages(messages: &[Message]) -> Vec<Message> {
+    let Some(compaction) = messages.iter().rev().find(|m| is_compaction_message(m)) else {
+        return messages.to_vec();
+    };
+    let Some(boundary) = compaction
+        .parent_id
+        .as_ref()
+        .and_then(|id| messages.iter().position(|m| &m.id == id))
+    else {
+        return messages.to_vec();
+    };
+
+    std::iter::once(compaction.clone())
+        .chain(
+            messages[boundary + 1..]
+                .iter()
+                .filter(|m| !is_compaction_message(m))
+                .cloned(),
+        )
+        .collect()
+}
+
+/// Index splitting `messages` into a prefix to summarize and a tail to keep.
+/// The split never lands between tool calls and their results, nor inside
+/// the assistant turn (text or reasoning) that leads up to tool calls.
+/// Returns `None` when there is nothing worth summarizing.
+pub fn compaction_split(messages: &[Message], keep_recent: usize) -> Option<usize> {
+    let mut split = messages.len().checked_sub(keep_recent)?;
+    while split > 0
+        && split < messages.len()
+        && (is_tool_result(&messages[split])
+            || matches!(
+                messages[split - 1].content,
+                MessageContent::ToolCalls { .. }
+            )
+            || continues_into_tool_calls(messages, split))
+    {
+        split -= 1;
+    }
+
+    // A prefix holding only the previous summary has nothing new to compact
+    messages[..split]
+        .iter()
+        .any(|m| !is_compaction_message(m) && !prompt_builder::is_system_prompt_message(m))
+        .then_some(split)
+}
+
+/// Render messages as plain text for the compaction prompt
+pub fn render_transcript(messages: &[Message]) -> String {
+    messages
+        .iter()
+        .filter(|m| !prompt_builder::is_system_prompt_message(m))
+        .map(|m| {
+            if is_compaction_message(m) {
+                return format!("{}\n{}", SUMMARY_HEADING, message_text(m));
+            }
+            let role = match m.role {
+                MessageRole::User => "User",
+                MessageRole::Assistant => "Assistant",
+                MessageRole::System => "System",
+                MessageRole::Tool => "Tool",
+            };
+            let content = match &m.content {
+                MessageContent::Text { text } => text.clone(),
+                MessageContent::ToolCalls { calls } => calls
+                    .iter()
+                    .map(|call| format!("[tool call {}] {}", call.name, call.input))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                MessageContent::ToolResult { result } => {
+                    let output = result
+                        .output
+                        .as_ref()
+                        .map(|o| o.to_string())
+                        .or_else(|| result.error_message.clone())
+                        .unwrap_or_default();
+                    format!(
+                        "[tool result {}] {}",
+                        result.tool_name,
+                        truncate(&output, MAX_TOOL_OUTPUT_CHARS)
+                    )
+                }
+                MessageContent::Parts { parts } => parts
+                    .iter()
+                    .filter_map(|part| match part {
+                        MessagePart::Text { text } => Some(text.clone()),
+                        MessagePart::Image { attachment_id } => {
+                            Some(format!("[image {}]", attachment_id))
+                        }
+                        MessagePart::File { attachment_id } => {
+                            Some(format!("[file {}]", attachment_id))
+                        }
+                        MessagePart::Audio { attachment_id } => {
+                            Some(format!("[audio {}]", attachment_id))
+                        }
+                        // The summary covers what was done, not how the model got there
+                        MessagePart::Reasoning { .. } => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            };
+            format!("{}: {}", role, content)
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Build the compaction message covering everything up to and including `boundary_id`
+pub fn compaction_message(
+    session_id: &SessionId,
+    summary: &str,
+    boundary_id: &str,
+    created_at: i64,
+) -> Message {
+    Message {
+        id: format!("{}{}", COMPACTION_ID_PREFIX, uuid::Uuid::new_v4()),
+        session_id: session_id.clone(),
+        role: MessageRole::System,
+        content: MessageContent::Text {
+            text: summary.to_string(),
+        },
+        created_at,
+        tool_call_id: None,
+        parent_id: Some(boundary_id.to_string()),
+    }
+}
+
+/// Last message in `messages` that a new compaction can point at
+pub fn compaction_boundary(messages: &[Message]) -> Option<&Message> {
+    messages
+        .iter()
+        .rev()
+        .find(|m| !is_compaction_message(m) && !prompt_builder::is_system_prompt_message(m))
+}
+
+/// Whether the assistant messages from `index` on end in tool calls and the
+/// message before is part of the same assistant turn
+fn continues_into_tool_calls(messages: &[Message], index: usize) -> bool {
+    messages[index - 1].role == MessageRole::Assistant
+        && messages[index..]
+            .iter()
+            .take_while(|m| m.role == MessageRole::Assistant)
+            .any(|m| matches!(m.content, MessageContent::ToolCalls { .. }))
+}
+
+fn is_tool_result(message: &Message) -> bool {
+    message.role == MessageRole::Tool
+        || matches!(message.content, MessageContent::ToolResult { .. })
+}
+
+fn message_text(message: &Message) -> &str {
+    match &message.content {
+        MessageContent::Text { text } => text,
+        _ => "",
+    }
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let truncated: String = text.chars().take(max_chars).collect();
+    format!("{}... [truncated]", truncated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{StoredToolResult, ToolCall, ToolResultStatus};
+
+    fn text(id: &str, role: MessageRole, text: &str) -> Message {
+        Message {
+            id: id.to_string(),
+            session_id: "sess".to_string(),
+            role,
+            content: MessageContent::Text {
+                text: text.to_string(),
+            },
+            created_at: 0,
+            tool_call_id: None,
+            parent_id: None,
+        }
+    }
+
+    fn tool_calls(id: &str) -> Message {
+        Message {
+            content: MessageContent::ToolCalls {
+                calls: vec![ToolCall {
+                    id: format!("call_{}", id),
+                    name: "readFile".to_string(),
+                    input: serde_json::json!({"file_path": "/a.rs"}),
+                    provider_metadata: None,
+                }],
+            },
+            ..text(id, MessageRole::Assistant, "")
+        }
+    }
+
+    fn tool_result(id: &str, call_id: &str) -> Message {
+        Message {
+            content: MessageContent::ToolResult {
+                result: StoredToolResult {
+                    tool_call_id: call_id.to_string(),
+                    tool_name: "readFile".to_string(),
+                    input: None,
+                    output: Some(serde_json::json!("fn main() {}")),
+                    status: ToolResultStatus::Success,
+                    error_message: None,
+                },
+            },
+            ..text(id, MessageRole::Tool, "")
+        }
+    }
+
+    #[test]
+    fn test_should_compact_threshold() {
+        assert!(!should_compact(79_000, 100_000, 0.8));
+        assert!(should_compact(81_000, 100_000, 0.8));
+        assert!(!should_compact(99_000, 100_000, 0.0));
+    }
+
+    #[test]
+    fn test_split_keeps_tool_pairs_together() {
+        let messages = vec![
+            text("1", MessageRole::User, "refactor the parser"),
+            text("2", MessageRole::Assistant, "sure"),
+            tool_calls("3"),
+            tool_result("4", "call_3"),
+            text("5", MessageRole::Assistant, "done"),
+        ];
+
+        // Keeping 2 would leave the result without its call, so the call is
+        // kept too, along with the text that opened the assistant turn
+        assert_eq!(compaction_split(&messages, 2), Some(1));
+        assert_eq!(compaction_split(&messages, 3), Some(1));
+        assert_eq!(compaction_split(&messages, 4), Some(1));
+        assert_eq!(compaction_split(&messages, 10), None);
+    }
+
+    #[test]
+    fn test_split_keeps_reasoning_with_tool_calls() {
+        let reasoning = Message {
+            content: MessageContent::with_reasoning(
+                vec![MessagePart::Reasoning {
+                    text: "the parser lives in src".to_string(),
+                    provider_metadata: None,
+                }],
+                String::new(),
+            ),
+            ..text("4", MessageRole::Assistant, "")
+        };
+        let messages = vec![
+            text("1", MessageRole::User, "refactor the parser"),
+            text("2", MessageRole::Assistant, "sure"),
+            text("3", MessageRole::User, "go on"),
+            reasoning,
+            tool_calls("5"),
+            tool_result("6", "call_5"),
+            text("7", MessageRole::Assistant, "done"),
+        ];
+
+        // The split moves from the tool calls to the reasoning that opened the turn
+        assert_eq!(compaction_split(&messages, 3), Some(3));
+        assert_eq!(compaction_split(&messages, 4), Some(3));
+        // A split after a finished turn stays where it is
+        assert_eq!(compaction_split(&messages, 1), Some(6));
+    }
+
+    #[test]
+    fn test_active_messages_replays_compaction() {
+        let mut messages = vec![
+            text("1", MessageRole::User, "first"),
+            text("2", MessageRole::Assistant, "second"),
+            text("3", MessageRole::User, "third"),
+        ];
+        let summary = compaction_message(&"sess".to_string(), "summary", "2", 10);
+        messages.push(summary.clone());
+        messages.push(text("4", MessageRole::Assistant, "fourth"));
+
+        let active = active_messages(&messages);
+        let ids: Vec<&str> = active.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec![summary.id.as_str(), "3", "4"]);
+
+        // Only the summary is left before the split, so nothing new to compact
+        assert_eq!(compaction_split(&active[..1], 0), None);
+        assert!(render_transcript(&active).starts_with(SUMMARY_HEADING));
+    }
+}
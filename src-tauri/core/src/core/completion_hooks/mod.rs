@@ -22,6 +22,8 @@ pub struct HookContext {
     pub messages: Vec<Message>,
     pub full_text: String,
     pub settings: TaskSettings,
+    /// Number of extra iterations hooks have already triggered for this task
+    pub iteration: u32,
 }
 
 /// Result of a completion hook
@@ -48,6 +50,19 @@ pub trait CompletionHook: Send + Sync {
     async fn execute(&self, ctx: &HookContext) -> Result<HookResult, String>;
 }
 
+/// Hooks registered when the settings do not name any, in execution order
+pub const DEFAULT_COMPLETION_HOOKS: &[&str] = &["stop", "ralph", "auto_review"];
+
+/// Create a built-in hook from its name
+pub fn create_hook(name: &str) -> Option<Box<dyn CompletionHook>> {
+    match name {
+        "stop" => Some(Box::new(StopHook::new())),
+        "ralph" => Some(Box::new(RalphLoopHook::new())),
+        "auto_review" => Some(Box::new(AutoReviewHook::new())),
+        _ => None,
+    }
+}
+
 /// Pipeline of completion hooks
 pub struct CompletionHookPipeline {
     hooks: Vec<Box<dyn CompletionHook>>,
@@ -58,19 +73,42 @@ impl CompletionHookPipeline {
         Self { hooks: vec![] }
     }
 
+    /// Build the pipeline for a session from its settings.
+    /// Unknown hook names are skipped.
+    pub fn from_settings(settings: &TaskSettings) -> Self {
+        let names: Vec<&str> = match &settings.completion_hooks {
+            Some(names) => names.iter().map(String::as_str).collect(),
+            None => DEFAULT_COMPLETION_HOOKS.to_vec(),
+        };
+
+        let mut pipeline = Self::new();
+        for name in names {
+            match create_hook(name) {
+                Some(hook) => pipeline.add_hook(hook),
+                None => log::warn!("[CompletionHooks] Unknown completion hook: {}", name),
+            }
+        }
+        pipeline
+    }
+
     /// Add a hook to the pipeline
     pub fn add_hook(&mut self, hook: Box<dyn CompletionHook>) {
         self.hooks.push(hook);
     }
 
+    /// Names of the registered hooks, in execution order
+    pub fn hook_names(&self) -> Vec<&str> {
+        self.hooks.iter().map(|hook| hook.name()).collect()
+    }
+
     /// Run all hooks in sequence
     pub async fn run(&self, ctx: &HookContext) -> Result<HookResult, String> {
         for hook in &self.hooks {
             if hook.should_run(ctx).await {
                 match hook.execute(ctx).await {
-                    Ok(HookResult::Stop { .. }) => {
+                    Ok(result @ HookResult::Stop { .. }) => {
                         // Stop early if a hook requests it
-                        return hook.execute(ctx).await;
+                        return Ok(result);
                     }
                     Ok(result) => {
                         // Continue with other hooks
@@ -95,3 +133,106 @@ impl Default for CompletionHookPipeline {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Hook that always stops and counts its executions
+    struct CountingStopHook {
+        runs: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl CompletionHook for CountingStopHook {
+        fn name(&self) -> &str {
+            "counting_stop"
+        }
+
+        async fn should_run(&self, _ctx: &HookContext) -> bool {
+            true
+        }
+
+        async fn execute(&self, _ctx: &HookContext) -> Result<HookResult, String> {
+            self.runs.fetch_add(1, Ordering::SeqCst);
+            Ok(HookResult::Stop {
+                reason: "counted".to_string(),
+            })
+        }
+    }
+
+    fn hook_context(full_text: &str, settings: TaskSettings) -> HookContext {
+        HookContext {
+            task_id: "task_test".to_string(),
+            session_id: "sess_test".to_string(),
+            messages: vec![],
+            full_text: full_text.to_string(),
+            settings,
+            iteration: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stop_hook_executes_once() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let mut pipeline = CompletionHookPipeline::new();
+        pipeline.add_hook(Box::new(CountingStopHook { runs: runs.clone() }));
+
+        let result = pipeline
+            .run(&hook_context("anything", TaskSettings::default()))
+            .await
+            .unwrap();
+
+        assert!(matches!(result, HookResult::Stop { reason } if reason == "counted"));
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_pipeline_from_settings() {
+        let pipeline = CompletionHookPipeline::from_settings(&TaskSettings::default());
+        assert_eq!(pipeline.hook_names(), DEFAULT_COMPLETION_HOOKS.to_vec());
+
+        let settings = TaskSettings {
+            completion_hooks: Some(vec!["ralph".to_string(), "unknown".to_string()]),
+            ..Default::default()
+        };
+        let pipeline = CompletionHookPipeline::from_settings(&settings);
+        assert_eq!(pipeline.hook_names(), vec!["ralph"]);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_iterates_when_ralph_enabled() {
+        let mut settings = TaskSettings::default();
+        settings
+            .extra
+            .insert("ralphLoopEnabled".to_string(), serde_json::json!(true));
+        let pipeline = CompletionHookPipeline::from_settings(&settings);
+
+        let result = pipeline
+            .run(&hook_context(
+                "Let me also update the tests",
+                settings.clone(),
+            ))
+            .await
+            .unwrap();
+        assert!(matches!(result, HookResult::Iterate { .. }));
+
+        // Stop signals win over the Ralph loop because the stop hook runs first
+        let result = pipeline
+            .run(&hook_context(
+                "Let me wrap up. Task completed",
+                settings.clone(),
+            ))
+            .await
+            .unwrap();
+        assert!(matches!(result, HookResult::Stop { .. }));
+
+        // The Ralph loop gives up once its iteration budget is spent
+        let mut ctx = hook_context("Let me keep going", settings);
+        ctx.iteration = 3;
+        let result = pipeline.run(&ctx).await.unwrap();
+        assert!(matches!(result, HookResult::Continue { .. }));
+    }
+}
@@ -7,18 +7,16 @@ use super::{CompletionHook, HookContext, HookResult};
 
 /// Ralph loop hook for task evaluation
 pub struct RalphLoopHook {
-    _max_iterations: u32,
+    max_iterations: u32,
 }
 
 impl RalphLoopHook {
     pub fn new() -> Self {
-        Self { _max_iterations: 3 }
+        Self { max_iterations: 3 }
     }
 
     pub fn with_max_iterations(max_iterations: u32) -> Self {
-        Self {
-            _max_iterations: max_iterations,
-        }
+        Self { max_iterations }
     }
 
     /// Check if task needs more work
@@ -45,12 +43,14 @@ impl CompletionHook for RalphLoopHook {
     }
 
     async fn should_run(&self, ctx: &HookContext) -> bool {
-        // Check if Ralph loop is enabled in settings
-        ctx.settings
+        // Check if Ralph loop is enabled in settings and has iterations left
+        let enabled = ctx
+            .settings
             .extra
             .get("ralphLoopEnabled")
             .and_then(|v| v.as_bool())
-            .unwrap_or(false)
+            .unwrap_or(false);
+        enabled && ctx.iteration < self.max_iterations
     }
 
     async fn execute(&self, ctx: &HookContext) -> Result<HookResult, String> {
@@ -4,7 +4,11 @@
 //! and tool execution. This module is the heart of the cloud backend.
 
 pub mod agent_loop;
+pub mod budget;
+pub mod checkpoints;
+pub mod compaction;
 pub mod completion_hooks;
+pub mod prompt_builder;
 pub mod runtime;
 pub mod session;
 pub mod tool_definitions;
@@ -0,0 +1,244 @@
+//! System Prompt Builder
+//!
+//! Assembles the system message for a task from the agent definition,
+//! workspace facts and project instruction files.
+//! Ported from TypeScript prompt-composer.ts and its env/agents-md providers.
+
+use crate::directory_tree::{build_directory_tree, FileNode};
+use crate::git::repository::{discover_repository, get_current_branch};
+use crate::storage::models::{Agent, Message, MessageContent, MessageRole, SessionId};
+use std::path::Path;
+
+/// Prefix for ids of stored system prompt messages: `sysprompt_{session}_v{version}`
+pub const SYSTEM_PROMPT_ID_PREFIX: &str = "sysprompt_";
+
+/// Persona used when the task has no agent or the agent has no prompt
+const DEFAULT_PERSONA: &str = "You are TalkCody, an AI coding assistant. \
+Use the available tools to read, search and change code in the workspace, \
+and keep answers concise.";
+
+/// Instruction files looked up in the workspace root, in order of preference
+const INSTRUCTION_FILES: &[&str] = &["AGENTS.md", "CLAUDE.md", "GEMINI.md"];
+
+/// Maximum characters of project instructions included in the prompt
+const MAX_INSTRUCTION_CHARS: usize = 20_000;
+
+/// Directory levels shown in the workspace tree
+const TREE_DEPTH: usize = 2;
+
+/// Maximum entries shown in the workspace tree
+const MAX_TREE_ENTRIES: usize = 200;
+
+/// Build the system prompt text for a task
+pub fn build_system_prompt(agent: Option<&Agent>, workspace_root: &str) -> String {
+    let persona = agent
+        .and_then(|a| a.system_prompt.as_deref())
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .unwrap_or(DEFAULT_PERSONA);
+
+    let mut sections = vec![persona.to_string(), environment_section(workspace_root)];
+    if let Some(tree) = workspace_tree_section(workspace_root) {
+        sections.push(tree);
+    }
+    if let Some(instructions) = instructions_section(workspace_root) {
+        sections.push(instructions);
+    }
+    sections.join("\n\n")
+}
+
+/// Id of the stored system prompt message for a session and version
+pub fn system_prompt_message_id(session_id: &str, version: u32) -> String {
+    format!("{}{}_v{}", SYSTEM_PROMPT_ID_PREFIX, session_id, version)
+}
+
+/// Whether a message is a stored system prompt
+pub fn is_system_prompt_message(message: &Message) -> bool {
+    message.role == MessageRole::System && message.id.starts_with(SYSTEM_PROMPT_ID_PREFIX)
+}
+
+/// Find the latest stored system prompt in a session and its version
+pub fn latest_system_prompt(messages: &[Message]) -> Option<(u32, &str)> {
+    messages
+        .iter()
+        .rev()
+        .filter(|m| is_system_prompt_message(m))
+        .find_map(|m| {
+            let version = m.id.rsplit_once("_v")?.1.parse().ok()?;
+            match &m.content {
+                MessageContent::Text { text } => Some((version, text.as_str())),
+                _ => None,
+            }
+        })
+}
+
+/// Return a new system prompt message when `prompt` differs from the latest stored one
+pub fn next_system_prompt_message(
+    session_id: &SessionId,
+    messages: &[Message],
+    prompt: &str,
+) -> Option<Message> {
+    let version = match latest_system_prompt(messages) {
+        Some((_, text)) if text == prompt => return None,
+        Some((version, _)) => version + 1,
+        None => 1,
+    };
+
+    Some(Message {
+        id: system_prompt_message_id(session_id, version),
+        session_id: session_id.clone(),
+        role: MessageRole::System,
+        content: MessageContent::Text {
+            text: prompt.to_string(),
+        },
+        created_at: chrono::Utc::now().timestamp(),
+        tool_call_id: None,
+        parent_id: None,
+    })
+}
+
+fn environment_section(workspace_root: &str) -> String {
+    let branch = discover_repository(workspace_root)
+        .ok()
+        .and_then(|repo| get_current_branch(&repo).ok())
+        .map(|branch| branch.name);
+
+    let mut lines = vec![
+        "# Environment".to_string(),
+        format!("- Working directory: {}", workspace_root),
+    ];
+    match branch {
+        Some(branch) => lines.push(format!("- Git branch: {}", branch)),
+        None => lines.push("- Git repository: no".to_string()),
+    }
+    lines.push(format!("- Platform: {}", std::env::consts::OS));
+    lines.join("\n")
+}
+
+fn workspace_tree_section(workspace_root: &str) -> Option<String> {
+    let root = build_directory_tree(workspace_root.to_string(), Some(TREE_DEPTH)).ok()?;
+
+    let mut lines = vec![];
+    let mut omitted = 0;
+    render_tree(
+        root.children.as_deref().unwrap_or_default(),
+        0,
+        &mut lines,
+        &mut omitted,
+    );
+    if lines.is_empty() {
+        return None;
+    }
+    if omitted > 0 {
+        lines.push(format!("... ({} more entries)", omitted));
+    }
+
+    Some(format!(
+        "# Workspace Structure\n```\n{}\n```",
+        lines.join("\n")
+    ))
+}
+
+fn render_tree(nodes: &[FileNode], depth: usize, lines: &mut Vec<String>, omitted: &mut usize) {
+    for node in nodes {
+        if node.is_git_ignored == Some(true) {
+            continue;
+        }
+        if lines.len() >= MAX_TREE_ENTRIES {
+            *omitted += 1;
+            continue;
+        }
+
+        let indent = "  ".repeat(depth);
+        if node.is_directory {
+            lines.push(format!("{}{}/", indent, node.name));
+            if let Some(children) = &node.children {
+                render_tree(children, depth + 1, lines, omitted);
+            }
+        } else {
+            lines.push(format!("{}{}", indent, node.name));
+        }
+    }
+}
+
+fn instructions_section(workspace_root: &str) -> Option<String> {
+    // Only the first file found is used, matching the desktop app (AGENTS → CLAUDE → GEMINI)
+    INSTRUCTION_FILES.iter().find_map(|file_name| {
+        let content = std::fs::read_to_string(Path::new(workspace_root).join(file_name)).ok()?;
+        let content = content.trim();
+        if content.is_empty() {
+            return None;
+        }
+
+        let content = match content.char_indices().nth(MAX_INSTRUCTION_CHARS) {
+            Some((idx, _)) => format!("{}\n... (truncated)", &content[..idx]),
+            None => content.to_string(),
+        };
+        Some(format!(
+            "# Project Instructions ({})\n{}",
+            file_name, content
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn agent(system_prompt: Option<&str>) -> Agent {
+        Agent {
+            id: "agent_reviewer".to_string(),
+            name: "reviewer".to_string(),
+            model: String::new(),
+            system_prompt: system_prompt.map(String::from),
+            tools: vec![],
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_build_system_prompt_sections() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path().join("ws");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+        std::fs::write(root.join("CLAUDE.md"), "Prefer small commits.").unwrap();
+        let root = root.to_string_lossy().to_string();
+
+        let reviewer = agent(Some("You review code."));
+        let prompt = build_system_prompt(Some(&reviewer), &root);
+
+        assert!(prompt.starts_with("You review code."));
+        assert!(prompt.contains(&format!("- Working directory: {}", root)));
+        assert!(prompt.contains("- Git repository: no"));
+        assert!(prompt.contains("src/\n  main.rs"));
+        assert!(prompt.contains("# Project Instructions (CLAUDE.md)\nPrefer small commits."));
+
+        // AGENTS.md takes precedence over CLAUDE.md
+        std::fs::write(Path::new(&root).join("AGENTS.md"), "Run the tests.").unwrap();
+        let prompt = build_system_prompt(Some(&agent(None)), &root);
+        assert!(prompt.starts_with(DEFAULT_PERSONA));
+        assert!(prompt.contains("# Project Instructions (AGENTS.md)\nRun the tests."));
+        assert!(!prompt.contains("Prefer small commits."));
+    }
+
+    #[test]
+    fn test_next_system_prompt_message_versions() {
+        let session_id = "sess_1".to_string();
+
+        let first = next_system_prompt_message(&session_id, &[], "prompt one").unwrap();
+        assert_eq!(first.id, "sysprompt_sess_1_v1");
+        assert!(is_system_prompt_message(&first));
+
+        let messages = vec![first];
+        assert!(next_system_prompt_message(&session_id, &messages, "prompt one").is_none());
+
+        let second = next_system_prompt_message(&session_id, &messages, "prompt two").unwrap();
+        assert_eq!(second.id, "sysprompt_sess_1_v2");
+
+        let messages = vec![messages[0].clone(), second];
+        assert_eq!(latest_system_prompt(&messages), Some((2, "prompt two")));
+    }
+}
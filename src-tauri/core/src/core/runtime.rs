@@ -3,29 +3,56 @@
 //! The main runtime that orchestrates task execution, session management,
 //! agent loops, and tool dispatch. Owns the lifecycle of all runtime tasks.
 
-use crate::core::agent_loop::{AgentLoopContext, AgentLoopFactory, AgentLoopResult};
+use crate::core::agent_loop::{
+    AgentLoopContext, AgentLoopFactory, AgentLoopResult, ResolvedAttachment,
+};
+use crate::core::budget;
+use crate::core::checkpoints::{self, CheckpointDiff, RestoreError};
+use crate::core::compaction;
+use crate::core::completion_hooks::{create_hook, CompletionHookPipeline, HookContext, HookResult};
+use crate::core::prompt_builder;
 use crate::core::session::SessionManager;
+use crate::core::tool_definitions::{get_tool_metadata, ToolMetadata};
+use crate::core::tool_dependency_analyzer::ToolDependencyAnalyzer;
+use crate::core::tool_name_normalizer::{is_known_tool_name, normalize_tool_name};
 use crate::core::tools::{ToolContext, ToolRegistry};
 use crate::core::types::*;
+use crate::llm::ai_services::context_compaction_service::ContextCompactionService;
+use crate::llm::ai_services::pricing_service::PricingService;
+use crate::llm::ai_services::types::{ContextCompactionRequest, TokenUsage};
 use crate::llm::auth::api_key_manager::ApiKeyManager;
 use crate::llm::providers::provider_registry::ProviderRegistry;
+use crate::llm::types::ModelConfig;
 use crate::storage::{
-    Message, MessageContent, MessageRole, SessionId, SessionStatus, Storage, StoredToolResult,
-    TaskSettings, ToolCall, ToolResultStatus,
+    AgentLoopSettings, AgentSession, AttachmentId, BudgetSettings, FileCheckpoint, Message,
+    MessageContent, MessageRole, SandboxMode, SessionId, SessionStatus, Storage, StoredToolResult,
+    TaskRecord, TaskSettings, TaskStateTransition, ToolCall, ToolCallId, ToolResultStatus,
+    UsageFilter, UsageRecord, UsageTotals, WorkspaceInfo,
 };
-use std::collections::HashMap;
+use crate::tools::call_agent::{CallAgentRequest, CallAgentResult};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures::StreamExt;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{mpsc, watch, RwLock};
+
+/// Iteration budget for sub-agents started by callAgent
+const SUB_AGENT_MAX_ITERATIONS: u32 = 20;
+
+/// Largest attachment inlined into a model request
+const MAX_INLINE_ATTACHMENT_BYTES: i64 = 20 * 1024 * 1024;
 
 /// Core runtime that manages all tasks and sessions
 #[derive(Clone)]
 pub struct CoreRuntime {
     /// Storage layer
-    _storage: Storage,
+    storage: Storage,
     /// Session manager
     session_manager: Arc<SessionManager>,
     /// Tool registry
     tool_registry: Arc<ToolRegistry>,
+    /// Tool metadata used to plan concurrent tool execution
+    tool_metadata: Arc<HashMap<String, ToolMetadata>>,
     /// Active tasks
     tasks: Arc<RwLock<HashMap<RuntimeTaskId, TaskHandle>>>,
     /// Event broadcaster
@@ -36,6 +63,8 @@ pub struct CoreRuntime {
     provider_registry: ProviderRegistry,
     /// API key manager
     api_key_manager: ApiKeyManager,
+    /// Latest context size reported for each session, used to trigger compaction
+    context_tokens: Arc<RwLock<HashMap<SessionId, i64>>>,
 }
 
 /// Settings validator
@@ -67,6 +96,121 @@ impl SettingsValidator {
             );
         }
 
+        // Validate agent loop overrides
+        if let Some(ref agent_loop) = settings.agent_loop {
+            if agent_loop.max_iterations == Some(0) {
+                validation.add_error("agentLoop.maxIterations must be greater than 0".to_string());
+            }
+            if let Some(temperature) = agent_loop.temperature {
+                if !(0.0..=2.0).contains(&temperature) {
+                    validation.add_error(format!(
+                        "agentLoop.temperature must be between 0 and 2, got {}",
+                        temperature
+                    ));
+                }
+            }
+            if let Some(effort) = agent_loop.reasoning_effort.as_deref() {
+                if !REASONING_EFFORTS.contains(&effort) {
+                    validation.add_error(format!(
+                        "agentLoop.reasoningEffort must be one of {}, got {}",
+                        REASONING_EFFORTS.join(", "),
+                        effort
+                    ));
+                }
+            }
+            if let Some(threshold) = agent_loop.compaction_threshold {
+                if !(threshold > 0.0 && threshold <= 1.0) {
+                    validation.add_error(format!(
+                        "agentLoop.compactionThreshold must be greater than 0 and at most 1, got {}",
+                        threshold
+                    ));
+                }
+            }
+            if agent_loop
+                .reasoning_budget_tokens
+                .is_some_and(|budget| budget < 1024)
+            {
+                validation
+                    .add_error("agentLoop.reasoningBudgetTokens must be at least 1024".to_string());
+            }
+            if agent_loop
+                .provider_options
+                .as_ref()
+                .is_some_and(|options| !options.is_object())
+            {
+                validation.add_error("agentLoop.providerOptions must be an object".to_string());
+            }
+
+            let tools = agent_loop
+                .allowed_tools
+                .iter()
+                .chain(agent_loop.disallowed_tools.iter())
+                .flatten();
+            for tool in tools {
+                if !is_known_tool_name(tool) {
+                    validation.add_warning(format!("Unknown tool in agentLoop settings: {}", tool));
+                }
+            }
+        }
+
+        // Validate budgets
+        if let Some(ref budget) = settings.budget {
+            let cost_limits = [
+                ("maxTaskCostUsd", budget.max_task_cost_usd),
+                ("maxDailyCostUsd", budget.max_daily_cost_usd),
+            ];
+            for (name, limit) in cost_limits {
+                if limit.is_some_and(|limit| limit.is_nan() || limit <= 0.0) {
+                    validation.add_error(format!("budget.{} must be greater than 0", name));
+                }
+            }
+            let token_limits = [
+                ("maxTaskTokens", budget.max_task_tokens),
+                ("maxDailyTokens", budget.max_daily_tokens),
+            ];
+            for (name, limit) in token_limits {
+                if limit.is_some_and(|limit| limit <= 0) {
+                    validation.add_error(format!("budget.{} must be greater than 0", name));
+                }
+            }
+        }
+
+        // Validate sandbox policy
+        if let Some(ref sandbox) = settings.sandbox {
+            let limits = [
+                ("maxCpuSeconds", sandbox.max_cpu_seconds),
+                ("maxMemoryMb", sandbox.max_memory_mb),
+                ("maxProcesses", sandbox.max_processes),
+            ];
+            for (name, limit) in limits {
+                if limit == Some(0) {
+                    validation.add_error(format!("sandbox.{} must be greater than 0", name));
+                }
+            }
+            for path in sandbox.writable_paths.iter().flatten() {
+                if !std::path::Path::new(path).is_absolute() {
+                    validation.add_error(format!(
+                        "sandbox.writablePaths must be absolute, got {}",
+                        path
+                    ));
+                }
+            }
+            if sandbox.mode == Some(SandboxMode::Required) && !cfg!(target_os = "linux") {
+                validation.add_warning(
+                    "Sandbox mode is required but sandboxing is only supported on Linux; \
+                     shell commands will be refused"
+                        .to_string(),
+                );
+            }
+        }
+
+        // Validate completion hooks
+        for hook in settings.completion_hooks.iter().flatten() {
+            if create_hook(hook).is_none() {
+                validation.add_warning(format!("Unknown completion hook: {}", hook));
+            }
+        }
+
         validation
     }
 }
@@ -92,19 +236,40 @@ impl CoreRuntime {
         let tool_registry = Arc::new(ToolRegistry::create_default().await);
 
         Ok(Self {
-            _storage: storage,
+            storage,
             session_manager,
             tool_registry,
+            tool_metadata: Arc::new(get_tool_metadata()),
             tasks: Arc::new(RwLock::new(HashMap::new())),
             event_sender,
             _settings_validator: SettingsValidator::new(),
             provider_registry,
             api_key_manager,
+            context_tokens: Arc::new(RwLock::new(HashMap::new())),
         })
     }
 
     /// Start a new task
     pub async fn start_task(&self, input: TaskInput) -> Result<TaskHandle, String> {
+        self.start_task_with_events(input, self.event_sender.clone(), false)
+            .await
+    }
+
+    /// Start a new task unless the session already has an active one. The
+    /// check and the start happen under the tasks lock, so two concurrent
+    /// calls cannot both start a task on the same history.
+    pub async fn start_task_if_idle(&self, input: TaskInput) -> Result<TaskHandle, String> {
+        self.start_task_with_events(input, self.event_sender.clone(), true)
+            .await
+    }
+
+    /// Start a new task whose events go to `event_sender` instead of the runtime's broadcaster
+    async fn start_task_with_events(
+        &self,
+        input: TaskInput,
+        event_sender: EventSender,
+        require_idle: bool,
+    ) -> Result<TaskHandle, String> {
         // Validate settings if provided
         if let Some(ref settings) = input.settings {
             let validation = self._settings_validator.validate(settings);
@@ -160,20 +325,97 @@ impl CoreRuntime {
         // Store task handle
         {
             let mut tasks = self.tasks.write().await;
+            if require_idle && tasks.values().any(|t| t.session_id == session.id) {
+                return Err(format!(
+                    "Session '{}' already has an active task",
+                    session.id
+                ));
+            }
             tasks.insert(task_id.clone(), handle.clone());
         }
 
+        let model = input
+            .settings
+            .as_ref()
+            .and_then(|s| s.extra.get("model"))
+            .and_then(|v| v.as_str().map(String::from));
+        self.persist_task(&task, model).await;
+
         // Spawn task execution
+        self.spawn_task(task, input, task_state, action_rx, event_sender);
+
+        Ok(handle)
+    }
+
+    /// Build the system prompt for a task and store it in the session.
+    /// A new versioned message is only written when the prompt changed since the last task.
+    async fn prepare_system_prompt(
+        &self,
+        task: &RuntimeTask,
+        workspace_root: &str,
+        event_sender: &EventSender,
+    ) -> Option<String> {
+        let agent = match &task.agent_id {
+            Some(agent_id) => match self.storage.agents.get_agent(agent_id).await {
+                Ok(Some(agent)) => Some(agent),
+                Ok(None) => {
+                    log::warn!("Agent '{}' not found, using default prompt", agent_id);
+                    None
+                }
+                Err(e) => {
+                    log::warn!("Failed to load agent '{}': {}", agent_id, e);
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let root = workspace_root.to_string();
+        let prompt = tokio::task::spawn_blocking(move || {
+            prompt_builder::build_system_prompt(agent.as_ref(), &root)
+        })
+        .await
+        .ok()?;
+
+        let messages = self
+            .session_manager
+            .get_messages(&task.session_id, None, None)
+            .await
+            .unwrap_or_default();
+        if let Some(message) =
+            prompt_builder::next_system_prompt_message(&task.session_id, &messages, &prompt)
+        {
+            match self.session_manager.add_message(message.clone()).await {
+                Ok(()) => {
+                    let _ = event_sender.send(RuntimeEvent::MessageCreated {
+                        session_id: task.session_id.clone(),
+                        message,
+                    });
+                }
+                Err(e) => log::warn!("Failed to store system prompt: {}", e),
+            }
+        }
+
+        Some(prompt)
+    }
+
+    /// Spawn `run_task` in the background.
+    /// Kept out of the async fns because sub-agents start tasks from inside `run_task`.
+    fn spawn_task(
+        &self,
+        task: RuntimeTask,
+        input: TaskInput,
+        task_state: Arc<RwLock<RuntimeTaskState>>,
+        action_rx: mpsc::UnboundedReceiver<TaskAction>,
+        event_sender: EventSender,
+    ) {
         let runtime_clone = self.clone();
-        let event_sender = self.event_sender.clone();
 
         tokio::spawn(async move {
             runtime_clone
                 .run_task(task, input, task_state, action_rx, event_sender)
                 .await;
         });
-
-        Ok(handle)
     }
 
     /// Get a task handle by ID
@@ -199,6 +441,64 @@ impl CoreRuntime {
         Ok(())
     }
 
+    /// File checkpoints taken during a task, oldest first
+    pub async fn list_checkpoints(&self, task_id: &str) -> Result<Vec<FileCheckpoint>, String> {
+        self.storage.checkpoints.list_checkpoints(task_id).await
+    }
+
+    /// Compare one of a task's checkpoints with the file as it is now.
+    /// Returns `None` if the task has no such checkpoint.
+    pub async fn diff_checkpoint(
+        &self,
+        task_id: &str,
+        checkpoint_id: &str,
+    ) -> Result<Option<CheckpointDiff>, String> {
+        let Some(checkpoint) = self.task_checkpoint(task_id, checkpoint_id).await? else {
+            return Ok(None);
+        };
+        let snapshot = self.storage.checkpoints.read_snapshot(&checkpoint).await?;
+        checkpoints::diff_against_current(checkpoint, snapshot.as_deref())
+            .await
+            .map(Some)
+    }
+
+    /// Put one file back as the checkpoint found it. Returns the checkpoint
+    /// restored.
+    pub async fn restore_checkpoint(
+        &self,
+        task_id: &str,
+        checkpoint_id: &str,
+    ) -> Result<FileCheckpoint, RestoreError> {
+        self.ensure_task_inactive(task_id).await?;
+        let checkpoint = self
+            .task_checkpoint(task_id, checkpoint_id)
+            .await?
+            .ok_or(RestoreError::NotFound)?;
+        self.restore_file(&checkpoint).await?;
+        Ok(checkpoint)
+    }
+
+    /// Undo every file change the task made from the checkpoint onwards.
+    /// Returns the checkpoints restored, one per file.
+    pub async fn restore_task_to_checkpoint(
+        &self,
+        task_id: &str,
+        checkpoint_id: &str,
+    ) -> Result<Vec<FileCheckpoint>, RestoreError> {
+        self.ensure_task_inactive(task_id).await?;
+        let checkpoint = self
+            .task_checkpoint(task_id, checkpoint_id)
+            .await?
+            .ok_or(RestoreError::NotFound)?;
+
+        let all = self.storage.checkpoints.list_checkpoints(task_id).await?;
+        let points = checkpoints::restore_points(&all, checkpoint.sequence);
+        for point in &points {
+            self.restore_file(point).await?;
+        }
+        Ok(points.into_iter().cloned().collect())
+    }
+
     /// Get session manager
     pub fn session_manager(&self) -> Arc<SessionManager> {
         self.session_manager.clone()
@@ -215,7 +515,7 @@ impl CoreRuntime {
         mut task: RuntimeTask,
         input: TaskInput,
         task_state: Arc<RwLock<RuntimeTaskState>>,
-        _action_rx: mpsc::UnboundedReceiver<TaskAction>,
+        mut action_rx: mpsc::UnboundedReceiver<TaskAction>,
         event_sender: EventSender,
     ) {
         // Update task state to running
@@ -223,30 +523,47 @@ impl CoreRuntime {
         task.state = RuntimeTaskState::Running;
         task.started_at = Some(now);
         *task_state.write().await = RuntimeTaskState::Running;
+        self.record_task_state(&task.id, RuntimeTaskState::Running, None)
+            .await;
 
         // Emit state change event
         let _ = event_sender.send(RuntimeEvent::TaskStateChanged {
             task_id: task.id.clone(),
+            session_id: task.session_id.clone(),
             state: RuntimeTaskState::Running,
             previous_state: RuntimeTaskState::Pending,
         });
 
-        // Create agent loop with full LLM integration
-        let agent_loop = AgentLoopFactory::create_standard(
+        // Create agent loop with the task's loop configuration
+        let loop_config =
+            AgentLoopConfig::from_settings(&input.settings.clone().unwrap_or_default());
+        let agent_loop = AgentLoopFactory::create_with_config(
+            loop_config,
             self.tool_registry.clone(),
             event_sender.clone(),
             self.provider_registry.clone(),
             self.api_key_manager.clone(),
         );
 
+        let workspace_root = input
+            .workspace
+            .as_ref()
+            .map(|w| w.root_path.clone())
+            .unwrap_or_else(|| {
+                std::env::current_dir()
+                    .map(|p| p.to_string_lossy().to_string())
+                    .unwrap_or_else(|_| "/".to_string())
+            });
+        let system_prompt = self
+            .prepare_system_prompt(&task, &workspace_root, &event_sender)
+            .await;
+
         // Add initial user message
         let initial_message = Message {
             id: format!("msg_{}", uuid::Uuid::new_v4()),
             session_id: task.session_id.clone(),
             role: MessageRole::User,
-            content: MessageContent::Text {
-                text: input.initial_message,
-            },
+            content: MessageContent::with_attachments(input.initial_message, input.attachments),
             created_at: now,
             tool_call_id: None,
             parent_id: None,
@@ -277,17 +594,21 @@ impl CoreRuntime {
             message: initial_message,
         });
 
-        // Build agent loop context
-        let workspace_root = input
-            .workspace
-            .as_ref()
-            .map(|w| w.root_path.clone())
-            .unwrap_or_else(|| {
-                std::env::current_dir()
-                    .map(|p| p.to_string_lossy().to_string())
-                    .unwrap_or_else(|_| "/".to_string())
-            });
+        let model = input.settings.as_ref().and_then(|s| {
+            s.extra
+                .get("model")
+                .and_then(|v| v.as_str().map(|s| s.to_string()))
+        });
+        let session_messages = self
+            .session_manager
+            .get_messages(&task.session_id, None, None)
+            .await
+            .unwrap_or_default();
+        let attachments = self
+            .resolve_attachments(&task.session_id, model.as_deref(), &session_messages)
+            .await;
 
+        // Build agent loop context
         let ctx = AgentLoopContext {
             session_id: task.session_id.clone(),
             task_id: task.id.clone(),
@@ -297,30 +618,34 @@ impl CoreRuntime {
                 .as_ref()
                 .and_then(|w| w.worktree_path.clone()),
             settings: input.settings.clone().unwrap_or_default(),
-            messages: self
-                .session_manager
-                .get_messages(&task.session_id, None, None)
-                .await
-                .unwrap_or_default(),
-            model: input.settings.and_then(|s| {
-                s.extra
-                    .get("model")
-                    .and_then(|v| v.as_str().map(|s| s.to_string()))
-            }),
+            messages: session_messages.clone(),
+            model,
             llm_state: None,
+            system_prompt,
+            attachments,
         };
 
-        // Get current messages and run agent loop
-        let messages = self
-            .session_manager
-            .get_messages(&task.session_id, None, None)
-            .await
-            .unwrap_or_default();
-        let mut messages = messages;
-        let max_iterations = AgentLoopConfig::default().max_iterations;
+        // Run the agent loop on the current messages.
+        // Turns covered by a stored compaction are replaced by its summary.
+        let mut messages = compaction::active_messages(&session_messages);
+        let max_iterations = agent_loop.config().max_iterations;
         let mut iteration = 0u32;
+        let completion_hooks = CompletionHookPipeline::from_settings(&ctx.settings);
+        let mut hook_iterations = 0u32;
+        // Actions that arrived before the runtime asked for them (e.g. an early approval)
+        let mut pending_actions: HashMap<ToolCallId, TaskAction> = HashMap::new();
+        let mut recorded_usage = TaskUsage::default();
+        let mut task_usage = UsageTotals::default();
+        let budget = ctx.settings.budget.clone();
+        let project_id = input.project_id.clone();
 
         loop {
+            if drain_actions(&mut action_rx, &mut pending_actions) {
+                self.complete_task(&task, RuntimeTaskState::Cancelled, None, &event_sender)
+                    .await;
+                break;
+            }
+
             iteration += 1;
             if iteration > max_iterations {
                 self.complete_task(
@@ -333,14 +658,54 @@ impl CoreRuntime {
                 break;
             }
 
-            match agent_loop.run_iteration(&ctx, &messages).await {
-                Ok(AgentLoopResult::Completed { message }) => {
-                    // Add assistant message
+            if let Some(reason) = self.budget_violation(budget.as_ref(), &task_usage).await {
+                self.complete_task(&task, RuntimeTaskState::Failed, Some(reason), &event_sender)
+                    .await;
+                break;
+            }
+
+            if let Some(compacted) = self
+                .compact_if_needed(
+                    &task,
+                    ctx.model.as_deref(),
+                    agent_loop.config().compaction_threshold,
+                    &messages,
+                    &event_sender,
+                )
+                .await
+            {
+                messages = compacted;
+            }
+
+            let result = agent_loop.run_iteration(&ctx, &messages).await;
+            if let Some(tokens) = agent_loop.context_tokens() {
+                self.context_tokens
+                    .write()
+                    .await
+                    .insert(task.session_id.clone(), tokens);
+            }
+            let usage = agent_loop.usage();
+            if usage != recorded_usage {
+                let model = agent_loop.selected_model().or_else(|| ctx.model.clone());
+                self.record_usage(
+                    &task,
+                    project_id.clone(),
+                    model,
+                    usage.since(&recorded_usage),
+                    &mut task_usage,
+                )
+                .await;
+                recorded_usage = usage;
+            }
+
+            match result {
+                Ok(AgentLoopResult::Completed { message, reasoning }) => {
+                    // Add assistant message, keeping reasoning so it can be replayed
                     let assistant_message = Message {
                         id: format!("msg_{}", uuid::Uuid::new_v4()),
                         session_id: task.session_id.clone(),
                         role: MessageRole::Assistant,
-                        content: MessageContent::Text { text: message },
+                        content: MessageContent::with_reasoning(reasoning, message.clone()),
                         created_at: chrono::Utc::now().timestamp(),
                         tool_call_id: None,
                         parent_id: None,
@@ -356,23 +721,80 @@ impl CoreRuntime {
                     });
                     messages.push(assistant_message);
 
-                    self.complete_task(&task, RuntimeTaskState::Completed, None, &event_sender)
-                        .await;
+                    let hook_ctx = HookContext {
+                        task_id: task.id.clone(),
+                        session_id: task.session_id.clone(),
+                        messages: messages.clone(),
+                        full_text: message,
+                        settings: ctx.settings.clone(),
+                        iteration: hook_iterations,
+                    };
+                    match completion_hooks.run(&hook_ctx).await {
+                        Ok(HookResult::Iterate { context }) => {
+                            // Feed the hook's context back as a user turn and keep going
+                            hook_iterations += 1;
+                            let hook_message = Message {
+                                id: format!("msg_{}", uuid::Uuid::new_v4()),
+                                session_id: task.session_id.clone(),
+                                role: MessageRole::User,
+                                content: MessageContent::Text { text: context },
+                                created_at: chrono::Utc::now().timestamp(),
+                                tool_call_id: None,
+                                parent_id: None,
+                            };
+                            let _ = self.session_manager.add_message(hook_message.clone()).await;
+                            let _ = event_sender.send(RuntimeEvent::MessageCreated {
+                                session_id: task.session_id.clone(),
+                                message: hook_message.clone(),
+                            });
+                            messages.push(hook_message);
+                            continue;
+                        }
+                        Ok(HookResult::Stop { reason }) => {
+                            self.finish_task(
+                                &task,
+                                RuntimeTaskState::Completed,
+                                None,
+                                Some(reason),
+                                &event_sender,
+                            )
+                            .await;
+                        }
+                        Ok(HookResult::Continue { .. }) => {
+                            self.complete_task(
+                                &task,
+                                RuntimeTaskState::Completed,
+                                None,
+                                &event_sender,
+                            )
+                            .await;
+                        }
+                        Err(e) => {
+                            // A failing hook must not fail work the agent already finished
+                            log::warn!("[Runtime] Completion hook failed for {}: {}", task.id, e);
+                            self.complete_task(
+                                &task,
+                                RuntimeTaskState::Completed,
+                                None,
+                                &event_sender,
+                            )
+                            .await;
+                        }
+                    }
                     break;
                 }
                 Ok(AgentLoopResult::ToolCalls {
                     accumulated_text,
+                    reasoning,
                     tool_calls,
                     ..
                 }) => {
-                    if !accumulated_text.is_empty() {
+                    if !accumulated_text.is_empty() || !reasoning.is_empty() {
                         let assistant_message = Message {
                             id: format!("msg_{}", uuid::Uuid::new_v4()),
                             session_id: task.session_id.clone(),
                             role: MessageRole::Assistant,
-                            content: MessageContent::Text {
-                                text: accumulated_text,
-                            },
+                            content: MessageContent::with_reasoning(reasoning, accumulated_text),
                             created_at: chrono::Utc::now().timestamp(),
                             tool_call_id: None,
                             parent_id: None,
@@ -394,6 +816,7 @@ impl CoreRuntime {
                             id: call.tool_call_id.clone(),
                             name: call.name.clone(),
                             input: call.input.clone(),
+                            provider_metadata: call.provider_metadata.clone(),
                         })
                         .collect::<Vec<_>>();
                     let tool_calls_message = Message {
@@ -418,87 +841,53 @@ impl CoreRuntime {
                     });
                     messages.push(tool_calls_message);
 
-                    for call in tool_calls {
-                        let tool_context = ToolContext {
-                            session_id: ctx.session_id.clone(),
-                            task_id: ctx.task_id.clone(),
-                            workspace_root: ctx.workspace_root.clone(),
-                            worktree_path: ctx.worktree_path.clone(),
-                            settings: ctx.settings.clone(),
-                            llm_state: ctx.llm_state.clone(),
-                        };
+                    let tool_context = ToolContext {
+                        session_id: ctx.session_id.clone(),
+                        task_id: ctx.task_id.clone(),
+                        workspace_root: ctx.workspace_root.clone(),
+                        worktree_path: ctx.worktree_path.clone(),
+                        settings: ctx.settings.clone(),
+                        llm_state: ctx.llm_state.clone(),
+                        event_sender: Some(event_sender.clone()),
+                    };
 
-                        let auto_approve = ctx.settings.auto_approve_edits.unwrap_or(false);
-                        match self
-                            .tool_registry
-                            .clone()
-                            .requires_approval(&call.name)
-                            .await
-                        {
-                            true if !auto_approve => {
-                                *task_state.write().await = RuntimeTaskState::WaitingForUser;
-                                let _ = event_sender.send(RuntimeEvent::ToolCallRequested {
-                                    task_id: task.id.clone(),
-                                    request: call,
-                                });
-                                return;
-                            }
-                            _ => {
-                                let result = self
-                                    .tool_registry
-                                    .clone()
-                                    .execute(call.clone(), tool_context)
-                                    .await;
+                    let (mut results, cancelled) = self
+                        .execute_tool_calls(
+                            &task,
+                            &tool_calls,
+                            agent_loop.config(),
+                            &tool_context,
+                            &task_state,
+                            &mut action_rx,
+                            &mut pending_actions,
+                            &event_sender,
+                        )
+                        .await;
+
+                    // Persist results in the original call order so the transcript stays valid
+                    for call in &tool_calls {
+                        let result = match results.remove(&call.tool_call_id) {
+                            Some(result) => result,
+                            None => {
+                                // Close out calls that never ran because the task was cancelled
+                                let result = cancelled_tool_result(call);
                                 let _ = event_sender.send(RuntimeEvent::ToolCallCompleted {
                                     task_id: task.id.clone(),
-                                    result: result.clone(),
-                                });
-
-                                let stored_result = StoredToolResult {
-                                    tool_call_id: result.tool_call_id.clone(),
-                                    tool_name: result.name.clone().unwrap_or_default(),
-                                    input: None,
-                                    output: Some(result.output.clone()),
-                                    status: if result.success {
-                                        ToolResultStatus::Success
-                                    } else {
-                                        ToolResultStatus::Error
-                                    },
-                                    error_message: result.error.clone(),
-                                };
-
-                                let tool_result_message = Message {
-                                    id: format!("msg_{}", uuid::Uuid::new_v4()),
-                                    session_id: task.session_id.clone(),
-                                    role: MessageRole::Tool,
-                                    content: MessageContent::ToolResult {
-                                        result: stored_result,
-                                    },
-                                    created_at: chrono::Utc::now().timestamp(),
-                                    tool_call_id: Some(result.tool_call_id.clone()),
-                                    parent_id: None,
-                                };
-
-                                let _ = self
-                                    .session_manager
-                                    .add_message(tool_result_message.clone())
-                                    .await;
-                                let _ = event_sender.send(RuntimeEvent::MessageCreated {
                                     session_id: task.session_id.clone(),
-                                    message: tool_result_message.clone(),
+                                    result: result.clone(),
                                 });
-                                messages.push(tool_result_message);
+                                result
                             }
-                        }
+                        };
+                        self.record_tool_result(&task, result, &mut messages, &event_sender)
+                            .await;
+                    }
+
+                    if cancelled {
+                        self.complete_task(&task, RuntimeTaskState::Cancelled, None, &event_sender)
+                            .await;
+                        break;
                     }
-                }
-                Ok(AgentLoopResult::WaitingForApproval { request }) => {
-                    *task_state.write().await = RuntimeTaskState::WaitingForUser;
-                    let _ = event_sender.send(RuntimeEvent::ToolCallRequested {
-                        task_id: task.id.clone(),
-                        request,
-                    });
-                    break;
                 }
                 Ok(AgentLoopResult::Error { message }) => {
                     self.complete_task(
@@ -546,66 +935,1187 @@ impl CoreRuntime {
         // Remove from active tasks
         let mut tasks = self.tasks.write().await;
         tasks.remove(&task.id);
+        drop(tasks);
+
+        crate::shell_session::close(&task.id);
     }
 
-    /// Complete a task and emit events
-    async fn complete_task(
+    /// Summarize older turns once the session's context passes the compaction threshold.
+    /// Stores the summary as a compaction message and returns the compacted history,
+    /// or `None` when no compaction was needed or it failed.
+    async fn compact_if_needed(
         &self,
         task: &RuntimeTask,
-        final_state: RuntimeTaskState,
-        error: Option<String>,
+        model: Option<&str>,
+        threshold: f32,
+        messages: &[Message],
         event_sender: &EventSender,
-    ) {
-        let previous_state = match self.tasks.read().await.get(&task.id) {
-            Some(handle) => *handle.state.read().await,
-            None => RuntimeTaskState::Running,
-        };
+    ) -> Option<Vec<Message>> {
+        let tokens = *self.context_tokens.read().await.get(&task.session_id)?;
+        let context_length = self.context_length(model).await;
+        if !compaction::should_compact(tokens, context_length, threshold) {
+            return None;
+        }
 
-        // Update session status
-        let session_status = match final_state {
-            RuntimeTaskState::Completed => SessionStatus::Completed,
-            RuntimeTaskState::Failed => SessionStatus::Error,
-            RuntimeTaskState::Cancelled => SessionStatus::Cancelled,
-            _ => SessionStatus::Running,
-        };
+        let split = compaction::compaction_split(messages, compaction::KEEP_RECENT_MESSAGES)?;
+        let boundary = compaction::compaction_boundary(&messages[..split])?;
+        log::info!(
+            "[Runtime] Compacting session {}: {} of {} context tokens used, summarizing {} messages",
+            task.session_id,
+            tokens,
+            context_length,
+            split
+        );
 
-        let _ = self
-            .session_manager
-            .update_session_status(&task.session_id, session_status, None)
-            .await;
+        let request = ContextCompactionRequest {
+            conversation_history: compaction::render_transcript(&messages[..split]),
+            model: None,
+        };
+        let result = match ContextCompactionService::new()
+            .compact_context(request, &self.api_key_manager, &self.provider_registry)
+            .await
+        {
+            Ok(result) => result,
+            Err(e) => {
+                log::warn!("[Runtime] Context compaction failed for {}: {}", task.id, e);
+                return None;
+            }
+        };
 
-        // Emit completion event
-        let _ = event_sender.send(RuntimeEvent::TaskStateChanged {
-            task_id: task.id.clone(),
-            state: final_state,
-            previous_state,
+        let message = compaction::compaction_message(
+            &task.session_id,
+            &result.compressed_summary,
+            &boundary.id,
+            chrono::Utc::now().timestamp(),
+        );
+        if let Err(e) = self.session_manager.add_message(message.clone()).await {
+            log::warn!(
+                "[Runtime] Failed to store compaction for {}: {}",
+                task.id,
+                e
+            );
+            return None;
+        }
+        let _ = event_sender.send(RuntimeEvent::MessageCreated {
+            session_id: task.session_id.clone(),
+            message: message.clone(),
         });
+        // The next provider response reports the compacted size
+        self.context_tokens.write().await.remove(&task.session_id);
 
-        let _ = event_sender.send(RuntimeEvent::TaskCompleted {
+        Some(
+            std::iter::once(message)
+                .chain(messages[split..].iter().cloned())
+                .collect(),
+        )
+    }
+
+    /// Price the usage of the latest model call, store it and add it to the task totals
+    async fn record_usage(
+        &self,
+        task: &RuntimeTask,
+        project_id: Option<String>,
+        model: Option<String>,
+        usage: TaskUsage,
+        task_usage: &mut UsageTotals,
+    ) {
+        let cost_usd = match model.as_deref() {
+            Some(model) => self.usage_cost(model, &usage).await,
+            None => 0.0,
+        };
+
+        task_usage.input_tokens += usage.input_tokens;
+        task_usage.output_tokens += usage.output_tokens;
+        task_usage.cached_input_tokens += usage.cached_input_tokens;
+        task_usage.cache_creation_input_tokens += usage.cache_creation_input_tokens;
+        task_usage.cost_usd += cost_usd;
+        task_usage.requests += 1;
+
+        let record = UsageRecord {
+            id: format!("usage_{}", uuid::Uuid::new_v4()),
             task_id: task.id.clone(),
             session_id: task.session_id.clone(),
-        });
-
-        if let Some(err) = error {
-            log::error!("[Runtime] Task {} failed: {}", task.id, err);
-            let _ = event_sender.send(RuntimeEvent::Error {
-                task_id: Some(task.id.clone()),
-                session_id: Some(task.session_id.clone()),
-                message: err,
-            });
+            project_id,
+            model,
+            input_tokens: usage.input_tokens,
+            output_tokens: usage.output_tokens,
+            cached_input_tokens: usage.cached_input_tokens,
+            cache_creation_input_tokens: usage.cache_creation_input_tokens,
+            cost_usd,
+            created_at: chrono::Utc::now().timestamp(),
+        };
+        if let Err(e) = self.storage.usage.record_usage(&record).await {
+            log::warn!("[Runtime] Failed to record usage for {}: {}", task.id, e);
+        }
+        if let Err(e) = self.storage.tasks.update_usage(&task.id, task_usage).await {
+            log::warn!("[Runtime] Failed to update usage for {}: {}", task.id, e);
         }
     }
 
-    /// Find existing session for a task input
-    fn find_session_for_task(&self, input: &TaskInput) -> Option<SessionId> {
-        // If session_id is explicitly provided in input, use that
-        Some(input.session_id.clone())
+    /// Cost of `usage` in USD from the model's pricing, zero when unpriced
+    async fn usage_cost(&self, model: &str, usage: &TaskUsage) -> f64 {
+        let Ok(config) = self.api_key_manager.load_models_config().await else {
+            return 0.0;
+        };
+        let tokens = |value: i64| u32::try_from(value).unwrap_or(0);
+        let usage = TokenUsage {
+            input_tokens: tokens(usage.input_tokens),
+            output_tokens: tokens(usage.output_tokens),
+            cached_input_tokens: Some(tokens(usage.cached_input_tokens)),
+            cache_creation_input_tokens: Some(tokens(usage.cache_creation_input_tokens)),
+        };
+        PricingService::new()
+            .calculate_cost(model, &usage, &config.models)
+            .unwrap_or(0.0)
+    }
+
+    /// Reason to stop the task if it has gone over its task or daily budget
+    async fn budget_violation(
+        &self,
+        budget: Option<&BudgetSettings>,
+        task_usage: &UsageTotals,
+    ) -> Option<String> {
+        let budget = budget?;
+        let daily = if budget::has_daily_limit(budget) {
+            let filter = UsageFilter {
+                since: Some(budget::day_start(chrono::Utc::now().timestamp())),
+                ..Default::default()
+            };
+            match self.storage.usage.totals(&filter).await {
+                Ok(totals) => totals,
+                Err(e) => {
+                    log::warn!("[Runtime] Failed to load daily usage: {}", e);
+                    UsageTotals::default()
+                }
+            }
+        } else {
+            UsageTotals::default()
+        };
+        budget::budget_violation(budget, task_usage, &daily)
+    }
+
+    /// Entry for a model in the models config
+    async fn model_config(&self, model: Option<&str>) -> Option<ModelConfig> {
+        let model = model?;
+        let model_key = model.split('@').next().unwrap_or(model);
+        self.api_key_manager
+            .load_models_config()
+            .await
+            .ok()
+            .and_then(|mut config| config.models.remove(model_key))
+    }
+
+    /// Context window of a model from the models config
+    async fn context_length(&self, model: Option<&str>) -> u32 {
+        self.model_config(model)
+            .await
+            .and_then(|m| m.context_length)
+            .unwrap_or(compaction::DEFAULT_CONTEXT_LENGTH)
+    }
+
+    /// Load the session attachments referenced by `messages`.
+    /// File data is only read for media the model accepts.
+    async fn resolve_attachments(
+        &self,
+        session_id: &str,
+        model: Option<&str>,
+        messages: &[Message],
+    ) -> HashMap<AttachmentId, ResolvedAttachment> {
+        let ids: HashSet<&str> = messages
+            .iter()
+            .flat_map(|m| m.content.attachment_ids())
+            .collect();
+        if ids.is_empty() {
+            return HashMap::new();
+        }
+
+        let model_config = self.model_config(model).await;
+        let mut resolved = HashMap::new();
+        for id in ids {
+            let attachment = match self.storage.attachments.get_attachment(id).await {
+                Ok(Some(attachment)) if attachment.session_id == session_id => attachment,
+                Ok(_) => {
+                    log::warn!("Attachment '{}' not found in session {}", id, session_id);
+                    continue;
+                }
+                Err(e) => {
+                    log::warn!("Failed to load attachment '{}': {}", id, e);
+                    continue;
+                }
+            };
+
+            let inline = accepts_media(model_config.as_ref(), &attachment.mime_type)
+                && attachment.size <= MAX_INLINE_ATTACHMENT_BYTES;
+            let data = if inline {
+                match tokio::fs::read(&attachment.path).await {
+                    Ok(bytes) => Some(STANDARD.encode(bytes)),
+                    Err(e) => {
+                        log::warn!("Failed to read attachment '{}': {}", id, e);
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+
+            resolved.insert(
+                id.to_string(),
+                ResolvedAttachment {
+                    filename: attachment.filename,
+                    mime_type: attachment.mime_type,
+                    data,
+                },
+            );
+        }
+        resolved
+    }
+
+    /// Execute one turn's tool calls following the dependency analyzer's plan
+    ///
+    /// Stages and groups run in plan order; calls inside a concurrent group run in
+    /// parallel up to the group's cap once their approvals are settled.
+    /// `ToolCallCompleted` is emitted as each call finishes. Returns results keyed
+    /// by tool call id and whether the task was cancelled; calls that never ran
+    /// are missing from the map.
+    #[allow(clippy::too_many_arguments)]
+    async fn execute_tool_calls(
+        &self,
+        task: &RuntimeTask,
+        tool_calls: &[ToolRequest],
+        config: &AgentLoopConfig,
+        tool_context: &ToolContext,
+        task_state: &Arc<RwLock<RuntimeTaskState>>,
+        action_rx: &mut mpsc::UnboundedReceiver<TaskAction>,
+        pending_actions: &mut HashMap<ToolCallId, TaskAction>,
+        event_sender: &EventSender,
+    ) -> (HashMap<ToolCallId, ToolResult>, bool) {
+        let plan = ToolDependencyAnalyzer::new()
+            .with_workspace_root(&tool_context.workspace_root)
+            .analyze(tool_calls.to_vec(), &self.tool_metadata);
+        let mut results: HashMap<ToolCallId, ToolResult> = HashMap::new();
+
+        for group in plan.stages.into_iter().flat_map(|stage| stage.groups) {
+            if group.concurrent {
+                let mut runnable = Vec::with_capacity(group.tools.len());
+                for call in group.tools {
+                    match self
+                        .resolve_tool_call(
+                            task,
+                            &call,
+                            config,
+                            tool_context,
+                            task_state,
+                            action_rx,
+                            pending_actions,
+                            event_sender,
+                        )
+                        .await
+                    {
+                        ToolDisposition::Execute => runnable.push(call),
+                        ToolDisposition::Resolved(result) => {
+                            complete_tool_call(task, result, &mut results, event_sender)
+                        }
+                        ToolDisposition::Cancelled => return (results, true),
+                    }
+                }
+
+                let limit = group.max_concurrency.unwrap_or(runnable.len()).max(1);
+                if self
+                    .run_tool_calls(
+                        task,
+                        runnable,
+                        limit,
+                        tool_context,
+                        action_rx,
+                        pending_actions,
+                        &mut results,
+                        event_sender,
+                    )
+                    .await
+                {
+                    return (results, true);
+                }
+            } else {
+                for call in group.tools {
+                    let result = match self
+                        .resolve_tool_call(
+                            task,
+                            &call,
+                            config,
+                            tool_context,
+                            task_state,
+                            action_rx,
+                            pending_actions,
+                            event_sender,
+                        )
+                        .await
+                    {
+                        ToolDisposition::Execute => {
+                            if self
+                                .run_tool_calls(
+                                    task,
+                                    vec![call],
+                                    1,
+                                    tool_context,
+                                    action_rx,
+                                    pending_actions,
+                                    &mut results,
+                                    event_sender,
+                                )
+                                .await
+                            {
+                                return (results, true);
+                            }
+                            continue;
+                        }
+                        ToolDisposition::Resolved(result) => result,
+                        ToolDisposition::Cancelled => return (results, true),
+                    };
+                    complete_tool_call(task, result, &mut results, event_sender);
+                }
+            }
+        }
+
+        (results, false)
+    }
+
+    /// Run tool calls that are cleared to execute, up to `limit` at a time,
+    /// while still reading the task's action channel. A cancel is passed on to
+    /// running sub-agents, and actions for later tool calls are kept in
+    /// `pending_actions`. Returns whether the task was cancelled.
+    #[allow(clippy::too_many_arguments)]
+    async fn run_tool_calls(
+        &self,
+        task: &RuntimeTask,
+        calls: Vec<ToolRequest>,
+        limit: usize,
+        tool_context: &ToolContext,
+        action_rx: &mut mpsc::UnboundedReceiver<TaskAction>,
+        pending_actions: &mut HashMap<ToolCallId, TaskAction>,
+        results: &mut HashMap<ToolCallId, ToolResult>,
+        event_sender: &EventSender,
+    ) -> bool {
+        let (cancel_tx, cancel_rx) = watch::channel(false);
+        let cancel = &cancel_rx;
+        let mut running = futures::stream::iter(calls)
+            .map(|call| self.execute_tool(task, call, tool_context, cancel, event_sender))
+            .buffer_unordered(limit);
+
+        let mut cancelled = false;
+        loop {
+            tokio::select! {
+                result = running.next() => match result {
+                    Some(result) => complete_tool_call(task, result, results, event_sender),
+                    None => break,
+                },
+                Some(action) = action_rx.recv(), if !cancelled => match action.tool_call_id() {
+                    Some(id) => {
+                        pending_actions.insert(id.to_string(), action);
+                    }
+                    None => {
+                        cancelled = true;
+                        let _ = cancel_tx.send(true);
+                    }
+                },
+            }
+        }
+        cancelled
+    }
+
+    /// Run a tool call that is cleared to execute.
+    /// callAgent starts a child task; everything else goes through the tool registry.
+    /// `cancel` turns true when the parent task is cancelled.
+    async fn execute_tool(
+        &self,
+        task: &RuntimeTask,
+        call: ToolRequest,
+        tool_context: &ToolContext,
+        cancel: &watch::Receiver<bool>,
+        event_sender: &EventSender,
+    ) -> ToolResult {
+        let tool_name = normalize_tool_name(&call.name);
+        if tool_name != "callAgent" {
+            let tool_call_id = call.tool_call_id.clone();
+            let checkpointed = self
+                .checkpoint_files(task, &call, &tool_name, &tool_context.workspace_root)
+                .await;
+            let result = self.tool_registry.execute(call, tool_context.clone()).await;
+            // A failed edit leaves its files untouched, so its snapshots undo nothing
+            if checkpointed && !result.success {
+                if let Err(e) = self
+                    .storage
+                    .checkpoints
+                    .delete_tool_call_checkpoints(&task.id, &tool_call_id)
+                    .await
+                {
+                    log::warn!("Failed to drop checkpoints of {}: {}", tool_call_id, e);
+                }
+            }
+            return result;
+        }
+
+        let request = CallAgentRequest::from_input(&call.input);
+        let result = self
+            .run_sub_agent(task, &request, tool_context, cancel, event_sender)
+            .await
+            .unwrap_or_else(CallAgentResult::failure);
+
+        ToolResult {
+            tool_call_id: call.tool_call_id,
+            name: Some("callAgent".to_string()),
+            success: result.success,
+            output: serde_json::to_value(&result).unwrap_or_default(),
+            error: result.error,
+        }
+    }
+
+    /// Snapshot the files a file-editing tool call is about to change.
+    /// Returns whether any checkpoint was taken. A file that cannot be read is
+    /// skipped rather than holding up the tool call.
+    async fn checkpoint_files(
+        &self,
+        task: &RuntimeTask,
+        call: &ToolRequest,
+        tool_name: &str,
+        workspace_root: &str,
+    ) -> bool {
+        let mut checkpointed = false;
+        for path in checkpoints::checkpoint_targets(tool_name, &call.input, workspace_root) {
+            let content = match tokio::fs::read(&path).await {
+                Ok(content) => Some(content),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => {
+                    log::warn!("Failed to snapshot {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+
+            let checkpoint = FileCheckpoint {
+                id: format!("ckpt_{}", uuid::Uuid::new_v4()),
+                task_id: task.id.clone(),
+                session_id: task.session_id.clone(),
+                tool_call_id: call.tool_call_id.clone(),
+                tool_name: tool_name.to_string(),
+                file_path: path.to_string_lossy().to_string(),
+                existed: content.is_some(),
+                size: 0,
+                sequence: 0,
+                created_at: chrono::Utc::now().timestamp(),
+            };
+            match self
+                .storage
+                .checkpoints
+                .create_checkpoint(&checkpoint, content.as_deref())
+                .await
+            {
+                Ok(()) => checkpointed = true,
+                Err(e) => log::warn!("Failed to checkpoint {}: {}", path.display(), e),
+            }
+        }
+        checkpointed
+    }
+
+    /// A checkpoint by ID, if it belongs to the task
+    async fn task_checkpoint(
+        &self,
+        task_id: &str,
+        checkpoint_id: &str,
+    ) -> Result<Option<FileCheckpoint>, String> {
+        Ok(self
+            .storage
+            .checkpoints
+            .get_checkpoint(checkpoint_id)
+            .await?
+            .filter(|checkpoint| checkpoint.task_id == task_id))
+    }
+
+    /// Restoring under a running task would race its own edits
+    async fn ensure_task_inactive(&self, task_id: &str) -> Result<(), RestoreError> {
+        if self.tasks.read().await.contains_key(task_id) {
+            return Err(RestoreError::TaskRunning);
+        }
+        Ok(())
+    }
+
+    async fn restore_file(&self, checkpoint: &FileCheckpoint) -> Result<(), String> {
+        let snapshot = self.storage.checkpoints.read_snapshot(checkpoint).await?;
+        checkpoints::restore_file(
+            std::path::Path::new(&checkpoint.file_path),
+            snapshot.as_deref(),
+        )
+        .await
+    }
+
+    /// Run a sub-agent as a child task of `parent` and wait for its final message
+    ///
+    /// The child gets its own session linked to the parent, the agent's stored
+    /// system prompt and tool subset, and a fixed iteration budget. It is
+    /// cancelled when `cancel` turns true.
+    async fn run_sub_agent(
+        &self,
+        parent: &RuntimeTask,
+        request: &CallAgentRequest,
+        tool_context: &ToolContext,
+        cancel: &watch::Receiver<bool>,
+        event_sender: &EventSender,
+    ) -> Result<CallAgentResult, String> {
+        if request.agent_id.is_empty() {
+            return Err("agentId is required".to_string());
+        }
+        if request.task.trim().is_empty() {
+            return Err("task is required".to_string());
+        }
+
+        let agent = match self.storage.agents.get_agent(&request.agent_id).await? {
+            Some(agent) => agent,
+            None => self
+                .storage
+                .agents
+                .get_agent_by_name(&request.agent_id)
+                .await?
+                .ok_or_else(|| format!("Agent not found: {}", request.agent_id))?,
+        };
+
+        // Prefer the agent's own model, otherwise keep the parent's
+        let mut extra = HashMap::new();
+        let model = Some(agent.model.clone())
+            .filter(|m| !m.is_empty())
+            .map(serde_json::Value::from)
+            .or_else(|| tool_context.settings.extra.get("model").cloned());
+        if let Some(model) = model {
+            extra.insert("model".to_string(), model);
+        }
+
+        let settings = TaskSettings {
+            auto_approve_edits: tool_context.settings.auto_approve_edits,
+            agent_loop: Some(AgentLoopSettings {
+                max_iterations: Some(SUB_AGENT_MAX_ITERATIONS),
+                allowed_tools: Some(agent.tools.clone()).filter(|tools| !tools.is_empty()),
+                // Keep nesting to a single level
+                disallowed_tools: Some(vec!["callAgent".to_string()]),
+                ..Default::default()
+            }),
+            extra,
+            ..Default::default()
+        };
+
+        let project_id = self
+            .session_manager
+            .get_session(&parent.session_id)
+            .await?
+            .and_then(|s| s.project_id);
+        let session = self
+            .session_manager
+            .create_session_with_metadata(
+                project_id.clone(),
+                Some(format!("Sub-agent: {}", agent.name)),
+                Some(settings.clone()),
+                Some(serde_json::json!({
+                    "parentSessionId": parent.session_id,
+                    "parentTaskId": parent.id,
+                    "agentId": agent.id,
+                })),
+            )
+            .await?;
+
+        let now = chrono::Utc::now().timestamp();
+        self.storage
+            .agents
+            .create_agent_session(&AgentSession {
+                agent_id: agent.id.clone(),
+                session_id: session.id.clone(),
+                settings: settings.clone(),
+                created_at: now,
+            })
+            .await?;
+
+        let (child_sender, mut child_events) = mpsc::unbounded_channel();
+        let handle = self
+            .start_task_with_events(
+                TaskInput {
+                    session_id: session.id.clone(),
+                    agent_id: Some(agent.id.clone()),
+                    project_id,
+                    initial_message: request.prompt(),
+                    attachments: Vec::new(),
+                    settings: Some(settings),
+                    workspace: Some(WorkspaceInfo {
+                        root_path: tool_context.workspace_root.clone(),
+                        worktree_path: tool_context.worktree_path.clone(),
+                        repository_url: None,
+                        branch: None,
+                    }),
+                },
+                child_sender,
+                false,
+            )
+            .await?;
+
+        let (final_state, last_error) = follow_sub_agent(
+            parent,
+            &agent.id,
+            &handle,
+            &mut child_events,
+            cancel,
+            event_sender,
+        )
+        .await;
+        // The child session never runs again, so nothing could reach its
+        // background commands
+        crate::tools::bash_output::forget_session(&session.id).await;
+
+        let result = match final_state {
+            RuntimeTaskState::Completed => {
+                let messages = self
+                    .session_manager
+                    .get_messages(&session.id, None, None)
+                    .await?;
+                CallAgentResult {
+                    success: true,
+                    message: Some(sub_agent_reply(&messages)),
+                    error: None,
+                    session_id: Some(session.id),
+                }
+            }
+            RuntimeTaskState::Cancelled => CallAgentResult {
+                session_id: Some(session.id),
+                ..CallAgentResult::failure(format!("Sub-agent '{}' was cancelled", agent.name))
+            },
+            _ => CallAgentResult {
+                session_id: Some(session.id),
+                ..CallAgentResult::failure(
+                    last_error.unwrap_or_else(|| format!("Sub-agent '{}' failed", agent.name)),
+                )
+            },
+        };
+
+        Ok(result)
+    }
+
+    /// Apply the allow/deny lists and approval policy to a tool call before it runs
+    #[allow(clippy::too_many_arguments)]
+    async fn resolve_tool_call(
+        &self,
+        task: &RuntimeTask,
+        call: &ToolRequest,
+        config: &AgentLoopConfig,
+        tool_context: &ToolContext,
+        task_state: &Arc<RwLock<RuntimeTaskState>>,
+        action_rx: &mut mpsc::UnboundedReceiver<TaskAction>,
+        pending_actions: &mut HashMap<ToolCallId, TaskAction>,
+        event_sender: &EventSender,
+    ) -> ToolDisposition {
+        let _ = event_sender.send(RuntimeEvent::ToolCallRequested {
+            task_id: task.id.clone(),
+            session_id: task.session_id.clone(),
+            request: call.clone(),
+        });
+
+        if !config.is_tool_allowed(&call.name) {
+            return ToolDisposition::Resolved(disallowed_tool_result(call));
+        }
+
+        let auto_approve = tool_context.settings.auto_approve_edits.unwrap_or(false);
+        if auto_approve || !self.tool_registry.requires_approval(&call.name).await {
+            return ToolDisposition::Execute;
+        }
+
+        match self
+            .wait_for_tool_decision(
+                task,
+                call,
+                task_state,
+                action_rx,
+                pending_actions,
+                event_sender,
+            )
+            .await
+        {
+            ToolDecision::Approved => ToolDisposition::Execute,
+            ToolDecision::Rejected { reason } => {
+                ToolDisposition::Resolved(rejected_tool_result(call, reason))
+            }
+            ToolDecision::Provided { result } => {
+                ToolDisposition::Resolved(provided_tool_result(call, result))
+            }
+            ToolDecision::Cancelled => ToolDisposition::Cancelled,
+        }
+    }
+
+    /// Park the task until the user decides on a tool call that requires approval
+    async fn wait_for_tool_decision(
+        &self,
+        task: &RuntimeTask,
+        call: &ToolRequest,
+        task_state: &Arc<RwLock<RuntimeTaskState>>,
+        action_rx: &mut mpsc::UnboundedReceiver<TaskAction>,
+        pending_actions: &mut HashMap<ToolCallId, TaskAction>,
+        event_sender: &EventSender,
+    ) -> ToolDecision {
+        self.transition_task_state(
+            task,
+            task_state,
+            RuntimeTaskState::WaitingForUser,
+            event_sender,
+        )
+        .await;
+        let _ = self
+            .session_manager
+            .update_session_status(&task.session_id, SessionStatus::WaitingForAction, None)
+            .await;
+
+        let decision = loop {
+            let action = match pending_actions.remove(&call.tool_call_id) {
+                Some(action) => action,
+                None => match action_rx.recv().await {
+                    Some(action) => action,
+                    None => break ToolDecision::Cancelled,
+                },
+            };
+
+            if let Some(id) = action.tool_call_id().filter(|id| *id != call.tool_call_id) {
+                // Decision for a later call in this batch; keep it until we get there
+                let id = id.to_string();
+                pending_actions.insert(id, action);
+                continue;
+            }
+
+            break match action {
+                TaskAction::Approve { .. } => ToolDecision::Approved,
+                TaskAction::Reject { reason, .. } => ToolDecision::Rejected { reason },
+                TaskAction::ToolResult { result, .. } => ToolDecision::Provided { result },
+                TaskAction::Cancel => ToolDecision::Cancelled,
+            };
+        };
+
+        if decision != ToolDecision::Cancelled {
+            self.transition_task_state(task, task_state, RuntimeTaskState::Running, event_sender)
+                .await;
+            let _ = self
+                .session_manager
+                .update_session_status(&task.session_id, SessionStatus::Running, None)
+                .await;
+        }
+
+        decision
+    }
+
+    /// Update the shared task state and emit a state change event
+    async fn transition_task_state(
+        &self,
+        task: &RuntimeTask,
+        task_state: &Arc<RwLock<RuntimeTaskState>>,
+        new_state: RuntimeTaskState,
+        event_sender: &EventSender,
+    ) {
+        let previous_state = {
+            let mut state = task_state.write().await;
+            std::mem::replace(&mut *state, new_state)
+        };
+
+        if previous_state != new_state {
+            self.record_task_state(&task.id, new_state, None).await;
+            let _ = event_sender.send(RuntimeEvent::TaskStateChanged {
+                task_id: task.id.clone(),
+                session_id: task.session_id.clone(),
+                state: new_state,
+                previous_state,
+            });
+        }
+    }
+
+    /// Persist a tool result as a tool message
+    async fn record_tool_result(
+        &self,
+        task: &RuntimeTask,
+        result: ToolResult,
+        messages: &mut Vec<Message>,
+        event_sender: &EventSender,
+    ) {
+        let stored_result = StoredToolResult {
+            tool_call_id: result.tool_call_id.clone(),
+            tool_name: result.name.clone().unwrap_or_default(),
+            input: None,
+            output: Some(result.output.clone()),
+            status: if result.success {
+                ToolResultStatus::Success
+            } else {
+                ToolResultStatus::Error
+            },
+            error_message: result.error.clone(),
+        };
+
+        let tool_result_message = Message {
+            id: format!("msg_{}", uuid::Uuid::new_v4()),
+            session_id: task.session_id.clone(),
+            role: MessageRole::Tool,
+            content: MessageContent::ToolResult {
+                result: stored_result,
+            },
+            created_at: chrono::Utc::now().timestamp(),
+            tool_call_id: Some(result.tool_call_id.clone()),
+            parent_id: None,
+        };
+
+        let _ = self
+            .session_manager
+            .add_message(tool_result_message.clone())
+            .await;
+        let _ = event_sender.send(RuntimeEvent::MessageCreated {
+            session_id: task.session_id.clone(),
+            message: tool_result_message.clone(),
+        });
+        messages.push(tool_result_message);
+    }
+
+    /// Complete a task and emit events
+    async fn complete_task(
+        &self,
+        task: &RuntimeTask,
+        final_state: RuntimeTaskState,
+        error: Option<String>,
+        event_sender: &EventSender,
+    ) {
+        self.finish_task(task, final_state, error, None, event_sender)
+            .await;
+    }
+
+    /// Complete a task, recording why a completion hook stopped it
+    async fn finish_task(
+        &self,
+        task: &RuntimeTask,
+        final_state: RuntimeTaskState,
+        error: Option<String>,
+        stop_reason: Option<String>,
+        event_sender: &EventSender,
+    ) {
+        let previous_state = match self.tasks.read().await.get(&task.id) {
+            Some(handle) => *handle.state.read().await,
+            None => RuntimeTaskState::Running,
+        };
+
+        // Update session status
+        let session_status = match final_state {
+            RuntimeTaskState::Completed => SessionStatus::Completed,
+            RuntimeTaskState::Failed => SessionStatus::Error,
+            RuntimeTaskState::Cancelled => SessionStatus::Cancelled,
+            _ => SessionStatus::Running,
+        };
+
+        let _ = self
+            .session_manager
+            .update_session_status(&task.session_id, session_status, None)
+            .await;
+
+        self.record_task_state(&task.id, final_state, error.as_deref())
+            .await;
+
+        // Emit completion event
+        let _ = event_sender.send(RuntimeEvent::TaskStateChanged {
+            task_id: task.id.clone(),
+            session_id: task.session_id.clone(),
+            state: final_state,
+            previous_state,
+        });
+
+        let _ = event_sender.send(RuntimeEvent::TaskCompleted {
+            task_id: task.id.clone(),
+            session_id: task.session_id.clone(),
+            stop_reason,
+        });
+
+        if let Some(err) = error {
+            log::error!("[Runtime] Task {} failed: {}", task.id, err);
+            let _ = event_sender.send(RuntimeEvent::Error {
+                task_id: Some(task.id.clone()),
+                session_id: Some(task.session_id.clone()),
+                message: err,
+            });
+        }
+    }
+
+    /// Write the initial task record so the task outlives its in-memory handle
+    async fn persist_task(&self, task: &RuntimeTask, model: Option<String>) {
+        let record = TaskRecord {
+            id: task.id.clone(),
+            session_id: task.session_id.clone(),
+            agent_id: task.agent_id.clone(),
+            state: task.state.as_str().to_string(),
+            created_at: task.created_at,
+            started_at: task.started_at,
+            completed_at: task.completed_at,
+            error_message: task.error_message.clone(),
+            model,
+            input_tokens: 0,
+            output_tokens: 0,
+            cached_input_tokens: 0,
+            cache_creation_input_tokens: 0,
+            cost_usd: 0.0,
+            transitions: vec![TaskStateTransition {
+                state: task.state.as_str().to_string(),
+                at: task.created_at,
+            }],
+        };
+
+        if let Err(e) = self.storage.tasks.create_task(&record).await {
+            log::warn!("[Runtime] Failed to persist task {}: {}", task.id, e);
+        }
+    }
+
+    /// Append a state transition to the task record
+    async fn record_task_state(&self, task_id: &str, state: RuntimeTaskState, error: Option<&str>) {
+        let result = self
+            .storage
+            .tasks
+            .update_state(
+                task_id,
+                state.as_str(),
+                chrono::Utc::now().timestamp(),
+                state.is_terminal(),
+                error,
+            )
+            .await;
+        if let Err(e) = result {
+            log::warn!(
+                "[Runtime] Failed to record state for task {}: {}",
+                task_id,
+                e
+            );
+        }
+    }
+
+    /// Find existing session for a task input
+    fn find_session_for_task(&self, input: &TaskInput) -> Option<SessionId> {
+        // If session_id is explicitly provided in input, use that
+        Some(input.session_id.clone())
+    }
+}
+
+/// User decision on a tool call that required approval
+#[derive(Debug, Clone, PartialEq)]
+enum ToolDecision {
+    Approved,
+    Rejected { reason: Option<String> },
+    Provided { result: serde_json::Value },
+    Cancelled,
+}
+
+/// Relay a sub-agent's events to the parent until the child finishes, and cancel
+/// the child when `cancel` turns true. Returns the child's final state and the
+/// last error it reported.
+async fn follow_sub_agent(
+    parent: &RuntimeTask,
+    agent_id: &str,
+    handle: &TaskHandle,
+    child_events: &mut mpsc::UnboundedReceiver<RuntimeEvent>,
+    cancel: &watch::Receiver<bool>,
+    event_sender: &EventSender,
+) -> (RuntimeTaskState, Option<String>) {
+    let mut cancel = cancel.clone();
+    let mut watching_cancel = true;
+    let mut final_state = RuntimeTaskState::Failed;
+    let mut last_error = None;
+
+    loop {
+        let event = tokio::select! {
+            event = child_events.recv() => match event {
+                Some(event) => event,
+                None => break,
+            },
+            changed = cancel.changed(), if watching_cancel => {
+                match changed {
+                    Ok(()) if *cancel.borrow() => {
+                        watching_cancel = false;
+                        if let Err(e) = handle.cancel() {
+                            log::warn!("Failed to cancel sub-agent {}: {}", handle.task_id, e);
+                        }
+                    }
+                    Ok(()) => {}
+                    // The parent finished running tools; nothing left to forward
+                    Err(_) => watching_cancel = false,
+                }
+                continue;
+            }
+        };
+
+        let mut finished = false;
+        match &event {
+            RuntimeEvent::TaskStateChanged { task_id, state, .. } if *task_id == handle.task_id => {
+                final_state = *state;
+            }
+            RuntimeEvent::TaskCompleted { task_id, .. } => {
+                finished = *task_id == handle.task_id;
+            }
+            RuntimeEvent::Error { message, .. } => last_error = Some(message.clone()),
+            _ => {}
+        }
+        relay_sub_agent_event(parent, agent_id, event, event_sender);
+        if finished {
+            break;
+        }
+    }
+
+    (final_state, last_error)
+}
+
+/// Text of a sub-agent's last assistant message, handed back to the parent as
+/// the callAgent result
+fn sub_agent_reply(messages: &[Message]) -> String {
+    messages
+        .iter()
+        .rev()
+        .filter(|m| m.role == MessageRole::Assistant)
+        .find_map(|m| m.content.text())
+        .unwrap_or_default()
+}
+
+/// Forward a sub-agent event to the parent.
+///
+/// Token and tool events are also tagged with the sub-agent id for the parent session.
+/// The original event is forwarded as-is and stays scoped to the child session.
+fn relay_sub_agent_event(
+    parent: &RuntimeTask,
+    sub_agent_id: &str,
+    event: RuntimeEvent,
+    event_sender: &EventSender,
+) {
+    let tagged = matches!(
+        event,
+        RuntimeEvent::Token { .. }
+            | RuntimeEvent::ToolCallRequested { .. }
+            | RuntimeEvent::ToolOutput { .. }
+            | RuntimeEvent::ToolCallCompleted { .. }
+    );
+
+    if tagged {
+        let _ = event_sender.send(RuntimeEvent::SubAgentEvent {
+            session_id: parent.session_id.clone(),
+            task_id: parent.id.clone(),
+            sub_agent_id: sub_agent_id.to_string(),
+            event: Box::new(event.clone()),
+        });
+    }
+    let _ = event_sender.send(event);
+}
+
+/// How a tool call proceeds once allow-lists and approvals are settled
+enum ToolDisposition {
+    Execute,
+    Resolved(ToolResult),
+    Cancelled,
+}
+
+/// Whether a model takes media of this MIME type as input, per its `imageInput`/`videoInput` flags
+fn accepts_media(model_config: Option<&ModelConfig>, mime_type: &str) -> bool {
+    let Some(config) = model_config else {
+        return false;
+    };
+    if mime_type.starts_with("image/") {
+        config.image_input
+    } else if mime_type.starts_with("video/") {
+        config.video_input
+    } else {
+        false
+    }
+}
+
+/// Emit `ToolCallCompleted` and keep the result for in-order persistence
+fn complete_tool_call(
+    task: &RuntimeTask,
+    result: ToolResult,
+    results: &mut HashMap<ToolCallId, ToolResult>,
+    event_sender: &EventSender,
+) {
+    let _ = event_sender.send(RuntimeEvent::ToolCallCompleted {
+        task_id: task.id.clone(),
+        session_id: task.session_id.clone(),
+        result: result.clone(),
+    });
+    results.insert(result.tool_call_id.clone(), result);
+}
+
+/// Move queued actions into `pending`. Returns true if the task was cancelled.
+fn drain_actions(
+    action_rx: &mut mpsc::UnboundedReceiver<TaskAction>,
+    pending: &mut HashMap<ToolCallId, TaskAction>,
+) -> bool {
+    while let Ok(action) = action_rx.try_recv() {
+        match action.tool_call_id() {
+            Some(id) => {
+                pending.insert(id.to_string(), action);
+            }
+            None => return true,
+        }
+    }
+    false
+}
+
+/// Tool result fed back to the model when the user rejects a call
+fn rejected_tool_result(call: &ToolRequest, reason: Option<String>) -> ToolResult {
+    let message = match reason.as_deref().map(str::trim) {
+        Some(reason) if !reason.is_empty() => {
+            format!("User rejected the tool call: {}", reason)
+        }
+        _ => "User rejected the tool call".to_string(),
+    };
+
+    ToolResult {
+        tool_call_id: call.tool_call_id.clone(),
+        name: Some(call.name.clone()),
+        success: false,
+        output: serde_json::json!({
+            "rejected": true,
+            "reason": reason,
+        }),
+        error: Some(message),
+    }
+}
+
+/// Tool result supplied externally by the client
+fn provided_tool_result(call: &ToolRequest, result: serde_json::Value) -> ToolResult {
+    let error = result
+        .get("error")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+
+    ToolResult {
+        tool_call_id: call.tool_call_id.clone(),
+        name: Some(call.name.clone()),
+        success: error.is_none(),
+        output: result,
+        error,
+    }
+}
+
+/// Tool result returned when the model calls a tool outside the task's allowed set
+fn disallowed_tool_result(call: &ToolRequest) -> ToolResult {
+    ToolResult {
+        tool_call_id: call.tool_call_id.clone(),
+        name: Some(call.name.clone()),
+        success: false,
+        output: serde_json::Value::Null,
+        error: Some(format!(
+            "Tool '{}' is not available for this task",
+            call.name
+        )),
+    }
+}
+
+/// Tool result recorded for calls left unanswered when the task is cancelled
+fn cancelled_tool_result(call: &ToolRequest) -> ToolResult {
+    ToolResult {
+        tool_call_id: call.tool_call_id.clone(),
+        name: Some(call.name.clone()),
+        success: false,
+        output: serde_json::Value::Null,
+        error: Some("Task was cancelled before the tool call ran".to_string()),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::storage::SandboxSettings;
     use tempfile::TempDir;
 
     async fn create_test_runtime() -> (CoreRuntime, TempDir, mpsc::UnboundedReceiver<RuntimeEvent>)
@@ -635,6 +2145,252 @@ mod tests {
         // Runtime created successfully
     }
 
+    fn test_task(session_id: &str) -> RuntimeTask {
+        RuntimeTask {
+            id: "task_test".to_string(),
+            session_id: session_id.to_string(),
+            agent_id: None,
+            state: RuntimeTaskState::Running,
+            created_at: 0,
+            started_at: Some(0),
+            completed_at: None,
+            error_message: None,
+            metadata: HashMap::new(),
+        }
+    }
+
+    fn test_call(id: &str) -> ToolRequest {
+        ToolRequest {
+            tool_call_id: id.to_string(),
+            name: "writeFile".to_string(),
+            input: serde_json::json!({"file_path": "a.txt", "content": "hi"}),
+            provider_metadata: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_tool_decision_approve() {
+        let (runtime, _temp, mut events) = create_test_runtime().await;
+        let task = test_task("sess_test");
+        let task_state = Arc::new(RwLock::new(RuntimeTaskState::Running));
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut pending = HashMap::new();
+
+        // A decision for another call arrives first and must be kept for later
+        tx.send(TaskAction::Reject {
+            tool_call_id: "call_2".to_string(),
+            reason: None,
+        })
+        .unwrap();
+        tx.send(TaskAction::Approve {
+            tool_call_id: "call_1".to_string(),
+        })
+        .unwrap();
+
+        let decision = runtime
+            .wait_for_tool_decision(
+                &task,
+                &test_call("call_1"),
+                &task_state,
+                &mut rx,
+                &mut pending,
+                &runtime.event_sender,
+            )
+            .await;
+
+        assert_eq!(decision, ToolDecision::Approved);
+        assert_eq!(*task_state.read().await, RuntimeTaskState::Running);
+        assert!(pending.contains_key("call_2"));
+
+        let mut saw_waiting = false;
+        while let Ok(event) = events.try_recv() {
+            if let RuntimeEvent::TaskStateChanged { state, .. } = event {
+                saw_waiting |= state == RuntimeTaskState::WaitingForUser;
+            }
+        }
+        assert!(saw_waiting);
+
+        // The buffered rejection is picked up without reading the channel again
+        let decision = runtime
+            .wait_for_tool_decision(
+                &task,
+                &test_call("call_2"),
+                &task_state,
+                &mut rx,
+                &mut pending,
+                &runtime.event_sender,
+            )
+            .await;
+        assert_eq!(decision, ToolDecision::Rejected { reason: None });
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_tool_decision_cancel() {
+        let (runtime, _temp, _events) = create_test_runtime().await;
+        let task = test_task("sess_test");
+        let task_state = Arc::new(RwLock::new(RuntimeTaskState::Running));
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut pending = HashMap::new();
+
+        tx.send(TaskAction::Cancel).unwrap();
+
+        let decision = runtime
+            .wait_for_tool_decision(
+                &task,
+                &test_call("call_1"),
+                &task_state,
+                &mut rx,
+                &mut pending,
+                &runtime.event_sender,
+            )
+            .await;
+
+        assert_eq!(decision, ToolDecision::Cancelled);
+        assert_eq!(*task_state.read().await, RuntimeTaskState::WaitingForUser);
+    }
+
+    #[tokio::test]
+    async fn test_tool_call_requested_once_per_call() {
+        let (runtime, _temp, mut events) = create_test_runtime().await;
+        let task = test_task("sess_test");
+        let task_state = Arc::new(RwLock::new(RuntimeTaskState::Running));
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut pending = HashMap::new();
+
+        tx.send(TaskAction::Approve {
+            tool_call_id: "call_1".to_string(),
+        })
+        .unwrap();
+
+        let disposition = runtime
+            .resolve_tool_call(
+                &task,
+                &test_call("call_1"),
+                &AgentLoopConfig::default(),
+                &test_tool_context(&task),
+                &task_state,
+                &mut rx,
+                &mut pending,
+                &runtime.event_sender,
+            )
+            .await;
+        assert!(matches!(disposition, ToolDisposition::Execute));
+
+        let mut requested = 0;
+        while let Ok(event) = events.try_recv() {
+            if let RuntimeEvent::ToolCallRequested { request, .. } = event {
+                assert_eq!(request.tool_call_id, "call_1");
+                requested += 1;
+            }
+        }
+        assert_eq!(requested, 1);
+    }
+
+    #[test]
+    fn test_drain_actions() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut pending = HashMap::new();
+
+        tx.send(TaskAction::Approve {
+            tool_call_id: "call_1".to_string(),
+        })
+        .unwrap();
+        assert!(!drain_actions(&mut rx, &mut pending));
+        assert!(pending.contains_key("call_1"));
+
+        tx.send(TaskAction::Cancel).unwrap();
+        assert!(drain_actions(&mut rx, &mut pending));
+    }
+
+    #[test]
+    fn test_rejected_and_provided_tool_results() {
+        let call = test_call("call_1");
+
+        let rejected = rejected_tool_result(&call, Some("too risky".to_string()));
+        assert!(!rejected.success);
+        assert_eq!(rejected.tool_call_id, "call_1");
+        assert!(rejected.error.unwrap().contains("too risky"));
+
+        let provided = provided_tool_result(&call, serde_json::json!({"content": "done"}));
+        assert!(provided.success);
+        assert_eq!(provided.output["content"], "done");
+
+        let failed = provided_tool_result(&call, serde_json::json!({"error": "boom"}));
+        assert!(!failed.success);
+        assert_eq!(failed.error.as_deref(), Some("boom"));
+    }
+
+    #[tokio::test]
+    async fn test_execute_tool_calls_follows_plan() {
+        let (runtime, temp, mut events) = create_test_runtime().await;
+        let workspace = temp.path().join("workspace");
+        std::fs::create_dir_all(&workspace).unwrap();
+        std::fs::write(workspace.join("a.txt"), "alpha").unwrap();
+        std::fs::write(workspace.join("b.txt"), "beta").unwrap();
+
+        let task = test_task("sess_test");
+        let task_state = Arc::new(RwLock::new(RuntimeTaskState::Running));
+        let (_tx, mut rx) = mpsc::unbounded_channel();
+        let mut pending = HashMap::new();
+        let config = AgentLoopConfig {
+            disallowed_tools: vec!["glob".to_string()],
+            ..Default::default()
+        };
+        let tool_context = ToolContext {
+            session_id: task.session_id.clone(),
+            task_id: task.id.clone(),
+            workspace_root: workspace.to_string_lossy().to_string(),
+            worktree_path: None,
+            settings: TaskSettings::default(),
+            llm_state: None,
+            event_sender: None,
+        };
+
+        let read = |id: &str, file: &str| ToolRequest {
+            tool_call_id: id.to_string(),
+            name: "readFile".to_string(),
+            input: serde_json::json!({"file_path": workspace.join(file).to_string_lossy()}),
+            provider_metadata: None,
+        };
+        let calls = vec![
+            read("call_1", "a.txt"),
+            ToolRequest {
+                tool_call_id: "call_2".to_string(),
+                name: "glob".to_string(),
+                input: serde_json::json!({"pattern": "*.txt"}),
+                provider_metadata: None,
+            },
+            read("call_3", "b.txt"),
+        ];
+
+        let (results, cancelled) = runtime
+            .execute_tool_calls(
+                &task,
+                &calls,
+                &config,
+                &tool_context,
+                &task_state,
+                &mut rx,
+                &mut pending,
+                &runtime.event_sender,
+            )
+            .await;
+
+        assert!(!cancelled);
+        assert_eq!(results.len(), 3);
+        assert!(results["call_1"].success);
+        assert!(results["call_3"].success);
+        assert!(!results["call_2"].success);
+
+        let mut completed = 0;
+        while let Ok(event) = events.try_recv() {
+            if let RuntimeEvent::ToolCallCompleted { .. } = event {
+                completed += 1;
+            }
+        }
+        assert_eq!(completed, 3);
+    }
+
     #[tokio::test]
     async fn test_settings_validation() {
         let validator = SettingsValidator::new();
@@ -648,10 +2404,542 @@ mod tests {
             auto_approve_edits: Some(true),
             auto_approve_plan: Some(true),
             auto_code_review: None,
+            agent_loop: None,
+            completion_hooks: None,
+            budget: None,
+            sandbox: None,
             extra: HashMap::new(),
         };
         let result = validator.validate(&risky_settings);
         assert!(result.valid); // Still valid, just warnings
         assert_eq!(result.warnings.len(), 2);
     }
+
+    #[test]
+    fn test_settings_validation_agent_loop() {
+        let validator = SettingsValidator::new();
+
+        let settings = TaskSettings {
+            agent_loop: Some(AgentLoopSettings {
+                max_iterations: Some(0),
+                temperature: Some(3.0),
+                allowed_tools: Some(vec!["readFile".to_string(), "noSuchTool".to_string()]),
+                reasoning_effort: Some("extreme".to_string()),
+                reasoning_budget_tokens: Some(100),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let result = validator.validate(&settings);
+        assert!(!result.valid);
+        assert_eq!(result.errors.len(), 4);
+        assert_eq!(result.warnings.len(), 1);
+
+        for threshold in [0.0, -0.5, 1.5, f32::NAN] {
+            let settings = TaskSettings {
+                agent_loop: Some(AgentLoopSettings {
+                    compaction_threshold: Some(threshold),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            let result = validator.validate(&settings);
+            assert!(!result.valid, "threshold {}", threshold);
+            assert!(result.errors[0].contains("compactionThreshold"));
+        }
+        let settings = TaskSettings {
+            agent_loop: Some(AgentLoopSettings {
+                compaction_threshold: Some(1.0),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(validator.validate(&settings).valid);
+    }
+
+    #[test]
+    fn test_settings_validation_budget() {
+        let validator = SettingsValidator::new();
+
+        let settings = TaskSettings {
+            budget: Some(BudgetSettings {
+                max_task_cost_usd: Some(0.0),
+                max_daily_tokens: Some(-1),
+                max_daily_cost_usd: Some(5.0),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let result = validator.validate(&settings);
+        assert!(!result.valid);
+        assert_eq!(result.errors.len(), 2);
+    }
+
+    #[test]
+    fn test_settings_validation_sandbox() {
+        let validator = SettingsValidator::new();
+        let settings = TaskSettings {
+            sandbox: Some(SandboxSettings {
+                mode: Some(SandboxMode::Auto),
+                writable_paths: Some(vec!["relative/cache".to_string()]),
+                max_processes: Some(0),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let result = validator.validate(&settings);
+        assert!(!result.valid);
+        assert_eq!(result.errors.len(), 2);
+    }
+
+    #[test]
+    fn test_settings_validation_completion_hooks() {
+        let validator = SettingsValidator::new();
+
+        let settings = TaskSettings {
+            completion_hooks: Some(vec!["stop".to_string(), "noSuchHook".to_string()]),
+            ..Default::default()
+        };
+        let result = validator.validate(&settings);
+        assert!(result.valid);
+        assert_eq!(result.warnings, vec!["Unknown completion hook: noSuchHook"]);
+    }
+
+    fn test_tool_context(task: &RuntimeTask) -> ToolContext {
+        ToolContext {
+            session_id: task.session_id.clone(),
+            task_id: task.id.clone(),
+            workspace_root: "/tmp".to_string(),
+            worktree_path: None,
+            settings: TaskSettings::default(),
+            llm_state: None,
+            event_sender: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_call_agent_unknown_agent() {
+        let (runtime, _temp, _events) = create_test_runtime().await;
+        let task = test_task("sess_test");
+        let call = ToolRequest {
+            tool_call_id: "call_1".to_string(),
+            name: "call_agent".to_string(),
+            input: serde_json::json!({"agentId": "ghost", "task": "Look around"}),
+            provider_metadata: None,
+        };
+
+        let result = runtime
+            .execute_tool(
+                &task,
+                call,
+                &test_tool_context(&task),
+                &watch::channel(false).1,
+                &runtime.event_sender,
+            )
+            .await;
+
+        assert!(!result.success);
+        assert_eq!(result.name.as_deref(), Some("callAgent"));
+        assert_eq!(result.error.as_deref(), Some("Agent not found: ghost"));
+    }
+
+    #[tokio::test]
+    async fn test_run_sub_agent_creates_linked_session() {
+        let (runtime, _temp, _events) = create_test_runtime().await;
+        let parent_session = runtime
+            .session_manager
+            .create_session(Some("proj_1".to_string()), None, None)
+            .await
+            .unwrap();
+        let parent = test_task(&parent_session.id);
+        runtime
+            .storage
+            .agents
+            .create_agent(&crate::storage::Agent {
+                id: "agent_explorer".to_string(),
+                name: "explorer".to_string(),
+                model: String::new(),
+                system_prompt: Some("You explore codebases.".to_string()),
+                tools: vec!["readFile".to_string()],
+                created_at: 0,
+                updated_at: 0,
+            })
+            .await
+            .unwrap();
+
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let request = CallAgentRequest {
+            agent_id: "explorer".to_string(),
+            task: "Find the entry point".to_string(),
+            ..Default::default()
+        };
+        let result = runtime
+            .run_sub_agent(
+                &parent,
+                &request,
+                &test_tool_context(&parent),
+                &watch::channel(false).1,
+                &tx,
+            )
+            .await
+            .unwrap();
+
+        // No model is configured in tests, so the child fails, but its session is set up
+        assert!(!result.success);
+        let child_id = result.session_id.expect("child session id");
+        let child = runtime
+            .session_manager
+            .get_session(&child_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(child.project_id.as_deref(), Some("proj_1"));
+        assert_eq!(
+            child.metadata.unwrap()["parentSessionId"],
+            serde_json::json!(parent_session.id)
+        );
+
+        let messages = runtime
+            .session_manager
+            .get_messages(&child_id, None, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            messages[0].id,
+            prompt_builder::system_prompt_message_id(&child_id, 1)
+        );
+        match &messages[0].content {
+            MessageContent::Text { text } => assert!(text.starts_with("You explore codebases.")),
+            other => panic!("unexpected system prompt content: {:?}", other),
+        }
+        assert_eq!(messages[1].role, MessageRole::User);
+
+        let link = runtime
+            .storage
+            .agents
+            .get_agent_session(&child_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(link.agent_id, "agent_explorer");
+        let agent_loop = link.settings.agent_loop.unwrap();
+        assert_eq!(agent_loop.allowed_tools, Some(vec!["readFile".to_string()]));
+        assert_eq!(agent_loop.max_iterations, Some(SUB_AGENT_MAX_ITERATIONS));
+    }
+
+    #[test]
+    fn test_sub_agent_reply_reads_text_next_to_reasoning() {
+        use crate::storage::MessagePart;
+
+        let message = |id: &str, content: MessageContent| Message {
+            id: id.to_string(),
+            session_id: "sess_child".to_string(),
+            role: MessageRole::Assistant,
+            content,
+            created_at: 0,
+            tool_call_id: None,
+            parent_id: None,
+        };
+        let reasoning = |text: &str| MessagePart::Reasoning {
+            text: text.to_string(),
+            provider_metadata: None,
+        };
+
+        let messages = vec![
+            message(
+                "msg_1",
+                MessageContent::Text {
+                    text: "Looking".to_string(),
+                },
+            ),
+            message(
+                "msg_2",
+                MessageContent::with_reasoning(
+                    vec![reasoning("main.rs calls run")],
+                    "The entry point is src/main.rs".to_string(),
+                ),
+            ),
+            // Reasoning alone carries no reply
+            message(
+                "msg_3",
+                MessageContent::with_reasoning(vec![reasoning("done")], String::new()),
+            ),
+        ];
+
+        assert_eq!(sub_agent_reply(&messages), "The entry point is src/main.rs");
+    }
+
+    #[tokio::test]
+    async fn test_parent_cancel_reaches_sub_agent() {
+        let parent = test_task("sess_parent");
+        let (child_action_tx, mut child_actions) = mpsc::unbounded_channel();
+        let handle = TaskHandle {
+            task_id: "task_child".to_string(),
+            session_id: "sess_child".to_string(),
+            state: Arc::new(RwLock::new(RuntimeTaskState::Running)),
+            action_sender: Arc::new(child_action_tx),
+        };
+        let (child_tx, mut child_events) = mpsc::unbounded_channel();
+        let (cancel_tx, cancel_rx) = watch::channel(false);
+        let (parent_tx, _parent_rx) = mpsc::unbounded_channel();
+
+        let follow = follow_sub_agent(
+            &parent,
+            "agent_explorer",
+            &handle,
+            &mut child_events,
+            &cancel_rx,
+            &parent_tx,
+        );
+        let child = async {
+            // The parent is cancelled while the child is still running
+            cancel_tx.send(true).unwrap();
+            assert!(matches!(
+                child_actions.recv().await,
+                Some(TaskAction::Cancel)
+            ));
+            child_tx
+                .send(RuntimeEvent::TaskStateChanged {
+                    task_id: "task_child".to_string(),
+                    session_id: "sess_child".to_string(),
+                    state: RuntimeTaskState::Cancelled,
+                    previous_state: RuntimeTaskState::Running,
+                })
+                .unwrap();
+            child_tx
+                .send(RuntimeEvent::TaskCompleted {
+                    task_id: "task_child".to_string(),
+                    session_id: "sess_child".to_string(),
+                    stop_reason: None,
+                })
+                .unwrap();
+        };
+
+        let ((state, _), ()) = tokio::join!(follow, child);
+        assert_eq!(state, RuntimeTaskState::Cancelled);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cancel_is_read_while_tools_run() {
+        let (runtime, _temp, _events) = create_test_runtime().await;
+        let task = test_task("sess_test");
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut pending = HashMap::new();
+        let mut results = HashMap::new();
+        let call = ToolRequest {
+            tool_call_id: "call_1".to_string(),
+            name: "bash".to_string(),
+            input: serde_json::json!({"command": "sleep 0.2"}),
+            provider_metadata: None,
+        };
+
+        tx.send(TaskAction::Approve {
+            tool_call_id: "call_2".to_string(),
+        })
+        .unwrap();
+        tx.send(TaskAction::Cancel).unwrap();
+        let cancelled = runtime
+            .run_tool_calls(
+                &task,
+                vec![call],
+                1,
+                &test_tool_context(&task),
+                &mut rx,
+                &mut pending,
+                &mut results,
+                &runtime.event_sender,
+            )
+            .await;
+
+        assert!(cancelled);
+        assert!(results.contains_key("call_1"));
+        assert!(pending.contains_key("call_2"));
+    }
+
+    #[tokio::test]
+    async fn test_task_record_outlives_task() {
+        let (runtime, temp, mut events) = create_test_runtime().await;
+        let session = runtime
+            .session_manager
+            .create_session(None, None, None)
+            .await
+            .unwrap();
+
+        let handle = runtime
+            .start_task(TaskInput {
+                session_id: session.id.clone(),
+                agent_id: None,
+                project_id: None,
+                initial_message: "hello".to_string(),
+                attachments: Vec::new(),
+                settings: None,
+                workspace: Some(WorkspaceInfo {
+                    root_path: temp.path().to_string_lossy().to_string(),
+                    worktree_path: None,
+                    repository_url: None,
+                    branch: None,
+                }),
+            })
+            .await
+            .unwrap();
+
+        // No model is configured in tests, so the task fails after starting
+        tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            while let Some(event) = events.recv().await {
+                if matches!(event, RuntimeEvent::TaskCompleted { .. }) {
+                    break;
+                }
+            }
+        })
+        .await
+        .expect("task did not complete");
+
+        let record = runtime
+            .storage
+            .tasks
+            .get_task(&handle.task_id)
+            .await
+            .unwrap()
+            .expect("task record");
+        assert_eq!(record.session_id, session.id);
+        assert_eq!(record.state, "failed");
+        assert!(record.started_at.is_some());
+        assert!(record.completed_at.is_some());
+        assert!(record.error_message.is_some());
+        let states: Vec<&str> = record
+            .transitions
+            .iter()
+            .map(|t| t.state.as_str())
+            .collect();
+        assert_eq!(states, vec!["pending", "running", "failed"]);
+    }
+
+    #[tokio::test]
+    async fn test_restore_refuses_running_task() {
+        let (runtime, _temp, _events) = create_test_runtime().await;
+        let (action_tx, _actions) = mpsc::unbounded_channel();
+        runtime.tasks.write().await.insert(
+            "task_running".to_string(),
+            TaskHandle {
+                task_id: "task_running".to_string(),
+                session_id: "sess_running".to_string(),
+                state: Arc::new(RwLock::new(RuntimeTaskState::Running)),
+                action_sender: Arc::new(action_tx),
+            },
+        );
+
+        let error = runtime
+            .restore_checkpoint("task_running", "cp_1")
+            .await
+            .unwrap_err();
+        assert_eq!(error, RestoreError::TaskRunning);
+        let error = runtime
+            .restore_task_to_checkpoint("task_running", "cp_1")
+            .await
+            .unwrap_err();
+        assert_eq!(error, RestoreError::TaskRunning);
+
+        let error = runtime
+            .restore_checkpoint("task_idle", "cp_missing")
+            .await
+            .unwrap_err();
+        assert_eq!(error, RestoreError::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_start_task_if_idle_refuses_busy_session() {
+        let (runtime, _temp, _events) = create_test_runtime().await;
+        let session = runtime
+            .session_manager
+            .create_session(None, None, None)
+            .await
+            .unwrap();
+        let (action_tx, _actions) = mpsc::unbounded_channel();
+        runtime.tasks.write().await.insert(
+            "task_busy".to_string(),
+            TaskHandle {
+                task_id: "task_busy".to_string(),
+                session_id: session.id.clone(),
+                state: Arc::new(RwLock::new(RuntimeTaskState::Running)),
+                action_sender: Arc::new(action_tx),
+            },
+        );
+
+        let error = runtime
+            .start_task_if_idle(TaskInput {
+                session_id: session.id.clone(),
+                agent_id: None,
+                project_id: None,
+                initial_message: "hello".to_string(),
+                attachments: Vec::new(),
+                settings: None,
+                workspace: None,
+            })
+            .await
+            .unwrap_err();
+        assert!(error.contains("already has an active task"), "{}", error);
+        assert_eq!(runtime.list_active_tasks().await.len(), 1);
+    }
+
+    #[test]
+    fn test_relay_sub_agent_event() {
+        let task = test_task("sess_parent");
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        relay_sub_agent_event(
+            &task,
+            "agent_explorer",
+            RuntimeEvent::Token {
+                session_id: "sess_child".to_string(),
+                token: "hi".to_string(),
+            },
+            &tx,
+        );
+        relay_sub_agent_event(
+            &task,
+            "agent_explorer",
+            RuntimeEvent::ToolCallCompleted {
+                task_id: "task_child".to_string(),
+                session_id: "sess_child".to_string(),
+                result: rejected_tool_result(&test_call("call_1"), None),
+            },
+            &tx,
+        );
+
+        let events: Vec<RuntimeEvent> = std::iter::from_fn(|| rx.try_recv().ok()).collect();
+        assert_eq!(events.len(), 4);
+        assert!(matches!(
+            &events[0],
+            RuntimeEvent::SubAgentEvent { session_id, sub_agent_id, .. }
+                if session_id == "sess_parent" && sub_agent_id == "agent_explorer"
+        ));
+        assert!(
+            matches!(&events[1], RuntimeEvent::Token { session_id, .. } if session_id == "sess_child")
+        );
+        assert!(matches!(&events[2], RuntimeEvent::SubAgentEvent { .. }));
+        assert!(matches!(
+            &events[3],
+            RuntimeEvent::ToolCallCompleted { session_id, .. } if session_id == "sess_child"
+        ));
+    }
+
+    #[test]
+    fn test_accepts_media_follows_model_config() {
+        let config: ModelConfig = serde_json::from_value(serde_json::json!({
+            "name": "Vision",
+            "imageInput": true,
+            "providers": ["openai"],
+            "providerMappings": null,
+            "pricing": null,
+            "context_length": null
+        }))
+        .unwrap();
+
+        assert!(accepts_media(Some(&config), "image/jpeg"));
+        assert!(!accepts_media(Some(&config), "video/mp4"));
+        assert!(!accepts_media(Some(&config), "audio/wav"));
+        assert!(!accepts_media(None, "image/png"));
+    }
 }
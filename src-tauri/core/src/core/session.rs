@@ -37,6 +37,18 @@ impl SessionManager {
         project_id: Option<String>,
         title: Option<String>,
         settings: Option<TaskSettings>,
+    ) -> Result<Session, String> {
+        self.create_session_with_metadata(project_id, title, settings, None)
+            .await
+    }
+
+    /// Create a new session with metadata (e.g. a link to a parent session)
+    pub async fn create_session_with_metadata(
+        &self,
+        project_id: Option<String>,
+        title: Option<String>,
+        settings: Option<TaskSettings>,
+        metadata: Option<serde_json::Value>,
     ) -> Result<Session, String> {
         let now = chrono::Utc::now().timestamp();
         let session_id = format!("sess_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
@@ -49,7 +61,7 @@ impl SessionManager {
             created_at: now,
             updated_at: now,
             last_event_id: None,
-            metadata: None,
+            metadata,
         };
 
         // Persist session
@@ -178,6 +190,9 @@ impl SessionManager {
     pub async fn deactivate_session(&self, session_id: &str) -> Result<(), String> {
         let mut active = self.active_sessions.write().await;
         active.remove(session_id);
+        drop(active);
+
+        crate::tools::bash_output::forget_session(session_id).await;
         Ok(())
     }
 
@@ -271,6 +286,7 @@ impl SessionManager {
         let mut active = self.active_sessions.write().await;
         active.remove(session_id);
         drop(active);
+        crate::tools::bash_output::forget_session(session_id).await;
 
         // Delete attachments
         self.storage
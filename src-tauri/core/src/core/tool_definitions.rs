@@ -5,6 +5,7 @@
 
 use crate::core::types::ToolDefinition;
 use serde_json::json;
+use std::collections::HashMap;
 
 /// Tool category for dependency analysis
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -25,6 +26,14 @@ pub struct ToolMetadata {
     pub render_doing_ui: bool,
 }
 
+/// Get tool metadata keyed by canonical tool name
+pub fn get_tool_metadata() -> HashMap<String, ToolMetadata> {
+    get_tool_definitions()
+        .into_iter()
+        .map(|(definition, metadata)| (definition.name, metadata))
+        .collect()
+}
+
 /// Get all canonical tool definitions with metadata
 pub fn get_tool_definitions() -> Vec<(ToolDefinition, ToolMetadata)> {
     vec![
@@ -132,6 +141,60 @@ pub fn get_tool_definitions() -> Vec<(ToolDefinition, ToolMetadata)> {
                 render_doing_ui: true,
             },
         ),
+        (
+            ToolDefinition {
+                name: "multiEdit".to_string(),
+                description: "Change several files at once, all or nothing. Pass either edit blocks per file or a unified diff; every edit is checked before any file is written, and a failure leaves all files unchanged. Returns a combined diff.".to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "files": {
+                            "type": "array",
+                            "description": "Existing files to edit, each with its own edit blocks",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "file_path": {
+                                        "type": "string",
+                                        "description": "The absolute path of file you want to edit"
+                                    },
+                                    "edits": {
+                                        "type": "array",
+                                        "items": {
+                                            "type": "object",
+                                            "properties": {
+                                                "old_string": {
+                                                    "type": "string",
+                                                    "description": "EXACT text to replace. Must match perfectly including whitespace. Include 3-5 lines of context."
+                                                },
+                                                "new_string": {
+                                                    "type": "string",
+                                                    "description": "Replacement text. Can be empty to delete. Must have correct indentation."
+                                                }
+                                            },
+                                            "required": ["old_string", "new_string"]
+                                        }
+                                    }
+                                },
+                                "required": ["file_path", "edits"]
+                            }
+                        },
+                        "patch": {
+                            "type": "string",
+                            "description": "Unified diff with ---/+++ headers and @@ hunks. Use /dev/null to create or delete a file. Use instead of files."
+                        }
+                    }
+                }),
+                requires_approval: true,
+            },
+            ToolMetadata {
+                category: ToolCategory::Edit,
+                can_concurrent: false,
+                file_operation: true,
+                requires_approval: true,
+                render_doing_ui: true,
+            },
+        ),
         // Search tools
         (
             ToolDefinition {
@@ -226,7 +289,7 @@ pub fn get_tool_definitions() -> Vec<(ToolDefinition, ToolMetadata)> {
         (
             ToolDefinition {
                 name: "bash".to_string(),
-                description: "Execute shell commands safely on the system.".to_string(),
+                description: "Execute shell commands safely on the system. Commands run in a persistent shell session, so the working directory and environment carry over between calls.".to_string(),
                 parameters: json!({
                     "type": "object",
                     "properties": {
@@ -237,6 +300,10 @@ pub fn get_tool_definitions() -> Vec<(ToolDefinition, ToolMetadata)> {
                         "runInBackground": {
                             "type": "boolean",
                             "description": "Run command in background and return task ID"
+                        },
+                        "resetSession": {
+                            "type": "boolean",
+                            "description": "Start a fresh shell session before running the command. With an empty command, only resets the session"
                         }
                     },
                     "required": ["command"]
@@ -251,6 +318,54 @@ pub fn get_tool_definitions() -> Vec<(ToolDefinition, ToolMetadata)> {
                 render_doing_ui: true,
             },
         ),
+        (
+            ToolDefinition {
+                name: "bashOutput".to_string(),
+                description: "Read output a background bash command has produced since the last check, along with its status and exit code.".to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "taskId": {
+                            "type": "string",
+                            "description": "Task ID returned by bash with runInBackground"
+                        }
+                    },
+                    "required": ["taskId"]
+                }),
+                requires_approval: false,
+            },
+            ToolMetadata {
+                category: ToolCategory::Read,
+                can_concurrent: true,
+                file_operation: false,
+                requires_approval: false,
+                render_doing_ui: false,
+            },
+        ),
+        (
+            ToolDefinition {
+                name: "killBash".to_string(),
+                description: "Stop a background bash command started with runInBackground.".to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "taskId": {
+                            "type": "string",
+                            "description": "Task ID returned by bash with runInBackground"
+                        }
+                    },
+                    "required": ["taskId"]
+                }),
+                requires_approval: false,
+            },
+            ToolMetadata {
+                category: ToolCategory::Other,
+                can_concurrent: false,
+                file_operation: false,
+                requires_approval: false,
+                render_doing_ui: false,
+            },
+        ),
         // LSP tool
         (
             ToolDefinition {
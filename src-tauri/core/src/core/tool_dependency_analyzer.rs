@@ -4,8 +4,17 @@
 //! Ported from TypeScript tool-dependency-analyzer.ts
 
 use crate::core::tool_definitions::{ToolCategory, ToolMetadata};
+use crate::core::tool_name_normalizer::normalize_tool_name;
 use crate::core::types::ToolRequest;
+use crate::tools::multi_edit;
 use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+/// Maximum number of read-only tools executed at the same time
+pub const MAX_CONCURRENT_READS: usize = 8;
+
+/// Maximum number of concurrent non-file tools (e.g. callAgent) executed at the same time
+pub const MAX_CONCURRENT_OTHERS: usize = 4;
 
 /// Execution stage - a logical phase in the execution plan
 #[derive(Debug, Clone)]
@@ -54,203 +63,270 @@ pub struct ExecutionSummary {
 }
 
 /// Tool dependency analyzer
-pub struct ToolDependencyAnalyzer;
+#[derive(Debug, Clone, Default)]
+pub struct ToolDependencyAnalyzer {
+    /// Root that relative target paths are resolved against
+    workspace_root: Option<PathBuf>,
+}
 
 impl ToolDependencyAnalyzer {
     pub fn new() -> Self {
-        Self
+        Self::default()
+    }
+
+    /// Resolve relative target paths against the workspace root
+    pub fn with_workspace_root(mut self, workspace_root: impl Into<PathBuf>) -> Self {
+        self.workspace_root = Some(workspace_root.into());
+        self
     }
 
     /// Analyze tool calls and generate an execution plan
+    ///
+    /// The calls are split into stages wherever the category changes, in the
+    /// order the model made them, so a read that follows an edit sees the edit.
     pub fn analyze(
         &self,
         tool_calls: Vec<ToolRequest>,
         tool_metadata: &HashMap<String, ToolMetadata>,
     ) -> ExecutionPlan {
-        if tool_calls.is_empty() {
-            return ExecutionPlan {
-                stages: vec![],
-                summary: ExecutionSummary {
-                    total_tools: 0,
-                    total_stages: 0,
-                    total_groups: 0,
-                    concurrent_groups: 0,
-                },
-            };
-        }
-
-        // Categorize tools
-        let categorized = self.categorize_tool_calls(&tool_calls, tool_metadata);
-
-        // Build stages based on categories
         let mut stages: Vec<ExecutionStage> = vec![];
         let mut total_tools = 0;
         let mut total_groups = 0;
         let mut concurrent_groups = 0;
 
-        // Stage 1: Read operations (always concurrent)
-        if !categorized.read.is_empty() {
-            let (read_groups, read_tools, read_concurrent) =
-                self.create_read_groups(&categorized.read, tool_metadata);
-
-            total_tools += read_tools;
-            total_groups += read_groups.len();
-            concurrent_groups += read_concurrent;
-
-            stages.push(ExecutionStage {
-                name: "read-stage".to_string(),
-                description: "Read file contents and gather context".to_string(),
-                groups: read_groups,
-            });
-        }
-
-        // Stage 2: Write/Edit operations (group by target file)
-        let write_edit_tools: Vec<ToolRequest> = categorized
-            .write
-            .into_iter()
-            .chain(categorized.edit)
-            .collect();
-
-        if !write_edit_tools.is_empty() {
-            let (write_groups, write_tools, _write_concurrent) =
-                self.create_write_edit_groups(&write_edit_tools, tool_metadata);
-
-            total_tools += write_tools;
-            total_groups += write_groups.len();
-            // Write groups are sequential by default for safety
-
-            stages.push(ExecutionStage {
-                name: "write-edit-stage".to_string(),
-                description: "Write and edit files (sequential for safety)".to_string(),
-                groups: write_groups,
-            });
-        }
-
-        // Stage 3: Other operations (bash, etc.)
-        if !categorized.other.is_empty() {
-            let (other_groups, other_tools, other_concurrent) =
-                self.create_other_groups(&categorized.other, tool_metadata);
+        for (kind, tools) in self.split_by_category(&tool_calls, tool_metadata) {
+            let (name, description, (groups, stage_tools, stage_concurrent)) = match kind {
+                // Read operations (concurrent unless the tool opts out)
+                CallKind::Read => (
+                    "read-stage",
+                    "Read file contents and gather context",
+                    self.create_read_groups(&tools, tool_metadata),
+                ),
+                // Write/Edit operations (group by target file)
+                CallKind::WriteEdit => (
+                    "write-edit-stage",
+                    "Write and edit files (sequential for safety)",
+                    self.create_write_edit_groups(&tools, tool_metadata),
+                ),
+                // Other operations (bash, etc.)
+                CallKind::Other => (
+                    "other-stage",
+                    "Execute other operations",
+                    self.create_other_groups(&tools, tool_metadata),
+                ),
+            };
 
-            total_tools += other_tools;
-            total_groups += other_groups.len();
-            concurrent_groups += other_concurrent;
+            total_tools += stage_tools;
+            total_groups += groups.len();
+            concurrent_groups += stage_concurrent;
 
             stages.push(ExecutionStage {
-                name: "other-stage".to_string(),
-                description: "Execute other operations".to_string(),
-                groups: other_groups,
+                name: name.to_string(),
+                description: description.to_string(),
+                groups,
             });
         }
 
+        let total_stages = stages.len();
         ExecutionPlan {
             stages,
             summary: ExecutionSummary {
                 total_tools,
-                total_stages: total_groups, // Simplified
+                total_stages,
                 total_groups,
                 concurrent_groups,
             },
         }
     }
 
-    /// Categorize tool calls by their category
-    fn categorize_tool_calls(
+    /// Split tool calls into runs of consecutive calls of the same kind
+    fn split_by_category(
         &self,
         tool_calls: &[ToolRequest],
         tool_metadata: &HashMap<String, ToolMetadata>,
-    ) -> CategorizedTools {
-        let mut read = vec![];
-        let mut write = vec![];
-        let mut edit = vec![];
-        let mut other = vec![];
+    ) -> Vec<(CallKind, Vec<ToolRequest>)> {
+        let mut runs: Vec<(CallKind, Vec<ToolRequest>)> = vec![];
 
         for tool_call in tool_calls {
-            let metadata = tool_metadata.get(&tool_call.name);
-            let category = metadata.map(|m| &m.category);
-
-            match category {
-                Some(ToolCategory::Read) => read.push(tool_call.clone()),
-                Some(ToolCategory::Write) => write.push(tool_call.clone()),
-                Some(ToolCategory::Edit) => edit.push(tool_call.clone()),
-                _ => other.push(tool_call.clone()),
+            let category =
+                Self::lookup_metadata(&tool_call.name, tool_metadata).map(|m| m.category);
+            let kind = match category {
+                Some(ToolCategory::Read) => CallKind::Read,
+                // Writes and edits share a kind so calls on the same file keep their order
+                Some(ToolCategory::Write | ToolCategory::Edit) => CallKind::WriteEdit,
+                _ => CallKind::Other,
+            };
+
+            match runs.last_mut() {
+                Some((last, tools)) if *last == kind => tools.push(tool_call.clone()),
+                _ => runs.push((kind, vec![tool_call.clone()])),
             }
         }
 
-        CategorizedTools {
-            read,
-            write,
-            edit,
-            other,
-        }
+        runs
     }
 
-    /// Create read groups (all concurrent)
+    /// Create read groups (concurrent unless the tool opts out)
     fn create_read_groups(
         &self,
         tools: &[ToolRequest],
-        _tool_metadata: &HashMap<String, ToolMetadata>,
+        tool_metadata: &HashMap<String, ToolMetadata>,
     ) -> (Vec<ExecutionGroup>, usize, usize) {
-        // All read operations can run concurrently
-        let group = ExecutionGroup {
-            id: "read-group-1".to_string(),
-            concurrent: true,
-            max_concurrency: None,
-            tools: tools.to_vec(),
-            target_files: self.extract_target_files(tools),
-            reason: "Read operations are safe to run concurrently".to_string(),
-        };
+        let (concurrent_tools, sequential_tools): (Vec<ToolRequest>, Vec<ToolRequest>) =
+            tools.iter().cloned().partition(|tool| {
+                Self::lookup_metadata(&tool.name, tool_metadata)
+                    .map(|m| m.can_concurrent)
+                    .unwrap_or(false)
+            });
 
-        (vec![group], tools.len(), 1)
+        let mut groups = vec![];
+        let mut concurrent_groups = 0;
+
+        if !concurrent_tools.is_empty() {
+            concurrent_groups += 1;
+            groups.push(ExecutionGroup {
+                id: format!("read-group-{}", groups.len() + 1),
+                concurrent: true,
+                max_concurrency: Some(MAX_CONCURRENT_READS),
+                target_files: self.extract_target_files(&concurrent_tools),
+                tools: concurrent_tools,
+                reason: "Read operations are safe to run concurrently".to_string(),
+            });
+        }
+
+        if !sequential_tools.is_empty() {
+            groups.push(ExecutionGroup {
+                id: format!("read-group-{}", groups.len() + 1),
+                concurrent: false,
+                max_concurrency: Some(1),
+                target_files: self.extract_target_files(&sequential_tools),
+                tools: sequential_tools,
+                reason: "Read operations marked as non-concurrent".to_string(),
+            });
+        }
+
+        (groups, tools.len(), concurrent_groups)
     }
 
-    /// Helper to extract targets for a single tool
-    fn extract_single_tool_targets(&self, tool: &ToolRequest) -> Vec<String> {
-        let mut targets = vec![];
+    /// Files a write/edit call changes, resolved so that different spellings
+    /// of the same path compare equal
+    fn extract_write_targets(&self, tool: &ToolRequest) -> Vec<String> {
+        let mut paths = vec![];
 
         // Try common file path fields
         if let Some(path) = tool.input.get("path").and_then(|v| v.as_str()) {
-            targets.push(path.to_string());
+            paths.push(path.to_string());
         } else if let Some(file_path) = tool.input.get("file_path").and_then(|v| v.as_str()) {
-            targets.push(file_path.to_string());
+            paths.push(file_path.to_string());
         } else if let Some(file_path) = tool.input.get("filePath").and_then(|v| v.as_str()) {
-            targets.push(file_path.to_string());
+            paths.push(file_path.to_string());
         }
 
+        // multiEdit names its files in a list and in the patch headers
+        if let Some(files) = tool.input.get("files").and_then(|v| v.as_array()) {
+            paths.extend(
+                files
+                    .iter()
+                    .filter_map(|f| f.get("file_path")?.as_str().map(str::to_string)),
+            );
+        }
+        if let Some(patch) = tool.input.get("patch").and_then(|v| v.as_str()) {
+            paths.extend(multi_edit::patch_paths(patch));
+        }
+
+        let mut targets: Vec<String> = vec![];
+        for path in paths.iter().filter(|path| !path.is_empty()) {
+            let target = self.canonical_path(path);
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
         targets
     }
 
+    /// Absolute, symlink-free form of a path where it exists; a lexically
+    /// normalized one otherwise
+    fn canonical_path(&self, path: &str) -> String {
+        let path = match &self.workspace_root {
+            Some(root) if Path::new(path).is_relative() => root.join(path),
+            _ => PathBuf::from(path),
+        };
+        if let Ok(canonical) = path.canonicalize() {
+            return canonical.to_string_lossy().to_string();
+        }
+
+        let mut normalized = PathBuf::new();
+        for component in path.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    if !normalized.pop() {
+                        normalized.push("..");
+                    }
+                }
+                other => normalized.push(other),
+            }
+        }
+        normalized.to_string_lossy().to_string()
+    }
+
     /// Create write/edit groups (sequential by target file for safety)
+    ///
+    /// A call that touches several files joins every group for those files
+    /// into one, so it runs exactly once and after the earlier calls on each.
     fn create_write_edit_groups(
         &self,
         tools: &[ToolRequest],
         _tool_metadata: &HashMap<String, ToolMetadata>,
     ) -> (Vec<ExecutionGroup>, usize, usize) {
-        // Group by target file
-        let mut file_groups: HashMap<String, Vec<ToolRequest>> = HashMap::new();
+        // Group by target file, keeping files in the order they were first touched
+        let mut file_groups: Vec<(Vec<String>, Vec<ToolRequest>)> = vec![];
         let mut no_target_tools = vec![];
 
         for tool in tools {
-            let targets = self.extract_single_tool_targets(tool);
+            let targets = self.extract_write_targets(tool);
             if targets.is_empty() {
                 no_target_tools.push(tool.clone());
-            } else {
-                for target in targets {
-                    file_groups.entry(target).or_default().push(tool.clone());
+                continue;
+            }
+
+            let matching: Vec<usize> = file_groups
+                .iter()
+                .enumerate()
+                .filter(|(_, (files, _))| files.iter().any(|file| targets.contains(file)))
+                .map(|(index, _)| index)
+                .collect();
+            let Some(&first) = matching.first() else {
+                file_groups.push((targets, vec![tool.clone()]));
+                continue;
+            };
+
+            for &index in matching[1..].iter().rev() {
+                let (files, group_tools) = file_groups.remove(index);
+                let (first_files, first_tools) = &mut file_groups[first];
+                first_files.extend(files);
+                first_tools.extend(group_tools);
+            }
+            let (files, group_tools) = &mut file_groups[first];
+            for target in targets {
+                if !files.contains(&target) {
+                    files.push(target);
                 }
             }
+            group_tools.push(tool.clone());
         }
 
         let mut groups = vec![];
         let mut group_id = 0;
 
         // Create a group for each target file (sequential execution)
-        for (file, file_tools) in file_groups {
+        for (files, file_tools) in file_groups {
             group_id += 1;
             groups.push(ExecutionGroup {
                 id: format!("write-group-{}", group_id),
                 concurrent: false, // Sequential for safety
                 max_concurrency: Some(1),
-                target_files: vec![file],
+                target_files: files,
                 tools: file_tools,
                 reason: "Write/edit operations on same file must run sequentially".to_string(),
             });
@@ -273,22 +349,48 @@ impl ToolDependencyAnalyzer {
     }
 
     /// Create other groups (bash, etc.)
+    ///
+    /// Consecutive tools marked as concurrent share a group; everything else
+    /// runs on its own to preserve the order the model asked for.
     fn create_other_groups(
         &self,
         tools: &[ToolRequest],
-        _tool_metadata: &HashMap<String, ToolMetadata>,
+        tool_metadata: &HashMap<String, ToolMetadata>,
     ) -> (Vec<ExecutionGroup>, usize, usize) {
-        // By default, other operations run sequentially for safety
-        let group = ExecutionGroup {
-            id: "other-group-1".to_string(),
-            concurrent: false,
-            max_concurrency: Some(1),
-            tools: tools.to_vec(),
-            target_files: vec![],
-            reason: "Other operations run sequentially for safety".to_string(),
-        };
+        let mut groups: Vec<ExecutionGroup> = vec![];
+
+        for tool in tools {
+            let can_concurrent = Self::lookup_metadata(&tool.name, tool_metadata)
+                .map(|m| m.can_concurrent)
+                .unwrap_or(false);
+
+            if can_concurrent {
+                if let Some(group) = groups.last_mut().filter(|g| g.concurrent) {
+                    group.tools.push(tool.clone());
+                    continue;
+                }
+            }
+
+            groups.push(ExecutionGroup {
+                id: format!("other-group-{}", groups.len() + 1),
+                concurrent: can_concurrent,
+                max_concurrency: Some(if can_concurrent {
+                    MAX_CONCURRENT_OTHERS
+                } else {
+                    1
+                }),
+                tools: vec![tool.clone()],
+                target_files: vec![],
+                reason: if can_concurrent {
+                    "Tools marked as concurrent".to_string()
+                } else {
+                    "Tool marked as non-concurrent".to_string()
+                },
+            });
+        }
 
-        (vec![group], tools.len(), 0)
+        let concurrent_groups = groups.iter().filter(|g| g.concurrent).count();
+        (groups, tools.len(), concurrent_groups)
     }
 
     /// Extract target file paths from tool input
@@ -308,25 +410,30 @@ impl ToolDependencyAnalyzer {
 
         targets
     }
-}
 
-/// Categorized tool calls
-struct CategorizedTools {
-    read: Vec<ToolRequest>,
-    write: Vec<ToolRequest>,
-    edit: Vec<ToolRequest>,
-    other: Vec<ToolRequest>,
+    /// Look up tool metadata, accepting snake_case and other name variants
+    fn lookup_metadata<'a>(
+        name: &str,
+        tool_metadata: &'a HashMap<String, ToolMetadata>,
+    ) -> Option<&'a ToolMetadata> {
+        tool_metadata
+            .get(name)
+            .or_else(|| tool_metadata.get(&normalize_tool_name(name)))
+    }
 }
 
-impl Default for ToolDependencyAnalyzer {
-    fn default() -> Self {
-        Self::new()
-    }
+/// How a run of tool calls is scheduled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CallKind {
+    Read,
+    WriteEdit,
+    Other,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::tool_definitions::get_tool_metadata;
 
     #[test]
     fn test_empty_tool_calls() {
@@ -373,4 +480,160 @@ mod tests {
         assert_eq!(plan.stages[0].groups.len(), 1);
         assert!(plan.stages[0].groups[0].concurrent);
     }
+
+    fn request(id: &str, name: &str, input: serde_json::Value) -> ToolRequest {
+        ToolRequest {
+            tool_call_id: id.to_string(),
+            name: name.to_string(),
+            input,
+            provider_metadata: None,
+        }
+    }
+
+    fn ids(group: &ExecutionGroup) -> Vec<&str> {
+        group
+            .tools
+            .iter()
+            .map(|t| t.tool_call_id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_read_group_concurrency_cap() {
+        let analyzer = ToolDependencyAnalyzer::new();
+        let metadata = get_tool_metadata();
+
+        let tools = vec![
+            request("1", "readFile", serde_json::json!({"file_path": "/a.ts"})),
+            request("2", "glob", serde_json::json!({"pattern": "*.ts"})),
+            request("3", "code_search", serde_json::json!({"pattern": "foo"})),
+        ];
+
+        let plan = analyzer.analyze(tools, &metadata);
+        assert_eq!(plan.stages.len(), 1);
+        assert_eq!(plan.stages[0].name, "read-stage");
+
+        let group = &plan.stages[0].groups[0];
+        assert!(group.concurrent);
+        assert_eq!(group.max_concurrency, Some(MAX_CONCURRENT_READS));
+        assert_eq!(ids(group), vec!["1", "2", "3"]);
+        assert_eq!(plan.summary.concurrent_groups, 1);
+    }
+
+    #[test]
+    fn test_write_edit_same_file_keeps_order() {
+        let analyzer = ToolDependencyAnalyzer::new();
+        let metadata = get_tool_metadata();
+
+        let tools = vec![
+            request("1", "editFile", serde_json::json!({"file_path": "/b.ts"})),
+            request("2", "writeFile", serde_json::json!({"file_path": "/a.ts"})),
+            request("3", "editFile", serde_json::json!({"file_path": "/b.ts"})),
+            request("4", "editFile", serde_json::json!({"file_path": "/a.ts"})),
+        ];
+
+        let plan = analyzer.analyze(tools, &metadata);
+        let groups = &plan.stages[0].groups;
+        assert_eq!(groups.len(), 2);
+        assert!(groups.iter().all(|g| !g.concurrent));
+        assert_eq!(groups[0].target_files, vec!["/b.ts"]);
+        assert_eq!(ids(&groups[0]), vec!["1", "3"]);
+        assert_eq!(groups[1].target_files, vec!["/a.ts"]);
+        assert_eq!(ids(&groups[1]), vec!["2", "4"]);
+    }
+
+    #[test]
+    fn test_read_after_edit_runs_after_it() {
+        let analyzer = ToolDependencyAnalyzer::new();
+        let metadata = get_tool_metadata();
+
+        let tools = vec![
+            request("1", "editFile", serde_json::json!({"file_path": "/a.rs"})),
+            request("2", "readFile", serde_json::json!({"file_path": "/a.rs"})),
+            request("3", "multiEdit", serde_json::json!({"files": []})),
+        ];
+
+        let plan = analyzer.analyze(tools, &metadata);
+        let names: Vec<&str> = plan.stages.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["write-edit-stage", "read-stage", "write-edit-stage"]
+        );
+        assert_eq!(ids(&plan.stages[1].groups[0]), vec!["2"]);
+    }
+
+    #[test]
+    fn test_write_groups_use_canonical_paths() {
+        let analyzer = ToolDependencyAnalyzer::new().with_workspace_root("/workspace");
+        let metadata = get_tool_metadata();
+        let patch = "--- a/b.rs\n+++ b/b.rs\n@@ -1 +1 @@\n-x\n+y\n";
+
+        let tools = vec![
+            request("1", "editFile", serde_json::json!({"file_path": "./a.rs"})),
+            request("2", "writeFile", serde_json::json!({"file_path": "b.rs"})),
+            request("3", "editFile", serde_json::json!({"file_path": "a.rs"})),
+            request("4", "multiEdit", serde_json::json!({"patch": patch})),
+            request(
+                "5",
+                "multiEdit",
+                serde_json::json!({"files": [{"file_path": "/workspace/a.rs", "edits": []}]}),
+            ),
+        ];
+
+        let plan = analyzer.analyze(tools, &metadata);
+        assert_eq!(plan.stages.len(), 1);
+        let groups = &plan.stages[0].groups;
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].target_files, vec!["/workspace/a.rs"]);
+        assert_eq!(ids(&groups[0]), vec!["1", "3", "5"]);
+        assert_eq!(groups[1].target_files, vec!["/workspace/b.rs"]);
+        assert_eq!(ids(&groups[1]), vec!["2", "4"]);
+    }
+
+    #[test]
+    fn test_multi_file_edit_joins_groups() {
+        let analyzer = ToolDependencyAnalyzer::new();
+        let metadata = get_tool_metadata();
+
+        let tools = vec![
+            request("1", "editFile", serde_json::json!({"file_path": "/a.rs"})),
+            request("2", "editFile", serde_json::json!({"file_path": "/b.rs"})),
+            request(
+                "3",
+                "multiEdit",
+                serde_json::json!({"files": [
+                    {"file_path": "/a.rs", "edits": []},
+                    {"file_path": "/b.rs", "edits": []}
+                ]}),
+            ),
+        ];
+
+        let plan = analyzer.analyze(tools, &metadata);
+        let groups = &plan.stages[0].groups;
+        assert_eq!(groups.len(), 1);
+        assert_eq!(ids(&groups[0]), vec!["1", "2", "3"]);
+    }
+
+    #[test]
+    fn test_other_groups_respect_can_concurrent() {
+        let analyzer = ToolDependencyAnalyzer::new();
+        let metadata = get_tool_metadata();
+
+        let tools = vec![
+            request("1", "callAgent", serde_json::json!({})),
+            request("2", "callAgent", serde_json::json!({})),
+            request("3", "bash", serde_json::json!({"command": "ls"})),
+            request("4", "unknownTool", serde_json::json!({})),
+        ];
+
+        let plan = analyzer.analyze(tools, &metadata);
+        assert_eq!(plan.stages.len(), 1);
+        let groups = &plan.stages[0].groups;
+        assert_eq!(groups.len(), 3);
+        assert!(groups[0].concurrent);
+        assert_eq!(ids(&groups[0]), vec!["1", "2"]);
+        assert!(!groups[1].concurrent);
+        assert!(!groups[2].concurrent);
+        assert_eq!(plan.summary.total_tools, 4);
+    }
 }
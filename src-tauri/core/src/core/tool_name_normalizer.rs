@@ -10,11 +10,14 @@ pub const CANONICAL_TOOL_NAMES: &[&str] = &[
     "readFile",
     "writeFile",
     "editFile",
+    "multiEdit",
     "glob",
     "codeSearch",
     "listFiles",
     "lsp",
     "bash",
+    "bashOutput",
+    "killBash",
     "webFetch",
     "webSearch",
     "callAgent",
@@ -77,6 +80,15 @@ fn legacy_aliases() -> HashMap<&'static str, &'static str> {
         ("bash-tool", "bash"),
         ("web_fetch", "webFetch"),
         ("web-fetch", "webFetch"),
+        ("multi_edit", "multiEdit"),
+        ("multi-edit", "multiEdit"),
+        ("apply_patch", "multiEdit"),
+        ("apply-patch", "multiEdit"),
+        ("applypatch", "multiEdit"),
+        ("bash_output", "bashOutput"),
+        ("bash-output", "bashOutput"),
+        ("kill_bash", "killBash"),
+        ("kill-bash", "killBash"),
         ("web_search", "webSearch"),
         ("web-search", "webSearch"),
         ("call_agent", "callAgent"),
@@ -19,6 +19,8 @@ pub struct ToolContext {
     pub settings: TaskSettings,
     /// Optional LLM state for tools that need AI services (image generation, etc.)
     pub llm_state: Option<Arc<LlmState>>,
+    /// Receives progress events, such as bash output, while a tool runs
+    pub event_sender: Option<EventSender>,
 }
 
 /// Result of tool execution
@@ -38,9 +40,9 @@ pub type ToolHandler = Arc<
 
 use crate::llm::auth::api_key_manager::LlmState;
 use crate::tools::{
-    ask_user_questions, bash_tool, call_agent, code_search, edit_file, exit_plan_mode, github_pr,
-    glob_tool, image_generation, install_skill, list_files, read_file, todo_write, web_fetch,
-    web_search, write_file,
+    ask_user_questions, bash_output, bash_tool, call_agent, code_search, edit_file, exit_plan_mode,
+    github_pr, glob_tool, image_generation, install_skill, kill_bash, list_files, multi_edit,
+    read_file, todo_write, web_fetch, web_search, write_file,
 };
 
 /// Tool registry containing all available tools
@@ -332,6 +334,45 @@ async fn execute_tool_by_name(
                 },
             }
         }
+        "multiEdit" | "multi_edit" | "applyPatch" | "apply_patch" => {
+            let files: Vec<multi_edit::FileEdits> = request
+                .input
+                .get("files")
+                .and_then(|v| v.as_array())
+                .map(|arr| {
+                    arr.iter()
+                        .filter_map(|f| {
+                            Some(multi_edit::FileEdits {
+                                file_path: f.get("file_path")?.as_str()?.to_string(),
+                                edits: f
+                                    .get("edits")?
+                                    .as_array()?
+                                    .iter()
+                                    .filter_map(|e| {
+                                        Some(edit_file::EditBlock {
+                                            old_string: e.get("old_string")?.as_str()?.to_string(),
+                                            new_string: e.get("new_string")?.as_str()?.to_string(),
+                                        })
+                                    })
+                                    .collect(),
+                            })
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            let patch = request.input.get("patch").and_then(|v| v.as_str());
+
+            let result = multi_edit::execute(files, patch, &ctx).await;
+            ToolExecutionOutput {
+                success: result.success,
+                data: serde_json::to_value(&result).unwrap_or_default(),
+                error: if result.success {
+                    None
+                } else {
+                    Some(result.message)
+                },
+            }
+        }
         "listFiles" | "list_files" | "list_directory" => {
             let directory_path = request
                 .input
@@ -409,8 +450,48 @@ async fn execute_tool_by_name(
                 .get("runInBackground")
                 .and_then(|v| v.as_bool())
                 .unwrap_or(false);
+            let reset_session = request
+                .input
+                .get("resetSession")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+
+            let result = bash_tool::execute(
+                command,
+                run_in_background,
+                reset_session,
+                &ctx,
+                &request.tool_call_id,
+            )
+            .await;
+            ToolExecutionOutput {
+                success: result.success,
+                data: serde_json::to_value(&result).unwrap_or_default(),
+                error: result.error,
+            }
+        }
+        "bashOutput" | "bash_output" => {
+            let task_id = request
+                .input
+                .get("taskId")
+                .and_then(|v| v.as_str())
+                .unwrap_or("");
 
-            let result = bash_tool::execute(command, run_in_background, &ctx).await;
+            let result = bash_output::execute(task_id, &ctx).await;
+            ToolExecutionOutput {
+                success: result.success,
+                data: serde_json::to_value(&result).unwrap_or_default(),
+                error: result.error,
+            }
+        }
+        "killBash" | "kill_bash" => {
+            let task_id = request
+                .input
+                .get("taskId")
+                .and_then(|v| v.as_str())
+                .unwrap_or("");
+
+            let result = kill_bash::execute(task_id, &ctx).await;
             ToolExecutionOutput {
                 success: result.success,
                 data: serde_json::to_value(&result).unwrap_or_default(),
@@ -527,28 +608,8 @@ async fn execute_tool_by_name(
         }
         // Agent tools
         "callAgent" | "call_agent" => {
-            let agent_id = request
-                .input
-                .get("agentId")
-                .and_then(|v| v.as_str())
-                .unwrap_or("");
-            let task = request
-                .input
-                .get("task")
-                .and_then(|v| v.as_str())
-                .unwrap_or("");
-            let context = request.input.get("context").and_then(|v| v.as_str());
-            let targets: Option<Vec<String>> = request
-                .input
-                .get("targets")
-                .and_then(|v| v.as_array())
-                .map(|arr| {
-                    arr.iter()
-                        .filter_map(|v| v.as_str().map(String::from))
-                        .collect()
-                });
-
-            let result = call_agent::execute(agent_id, task, context, targets, &ctx).await;
+            let call_request = call_agent::CallAgentRequest::from_input(&request.input);
+            let result = call_agent::execute(call_request, &ctx).await;
             ToolExecutionOutput {
                 success: result.success,
                 data: serde_json::to_value(&result).unwrap_or_default(),
@@ -1,6 +1,8 @@
 //! Core Runtime Types
 //! Types used by the core runtime for task/session lifecycle and agent loop
 
+use crate::core::tool_name_normalizer::normalize_tool_name;
+use crate::platform::types::OutputStream;
 use crate::storage::models::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -44,6 +46,43 @@ impl RuntimeTaskState {
             RuntimeTaskState::Completed | RuntimeTaskState::Failed | RuntimeTaskState::Cancelled
         )
     }
+
+    /// Serialized name of the state, as stored in task records
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RuntimeTaskState::Pending => "pending",
+            RuntimeTaskState::Running => "running",
+            RuntimeTaskState::WaitingForUser => "waitingForUser",
+            RuntimeTaskState::Completed => "completed",
+            RuntimeTaskState::Failed => "failed",
+            RuntimeTaskState::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// Token usage accumulated over a task's LLM calls
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskUsage {
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    /// Input tokens served from the provider's prompt cache
+    pub cached_input_tokens: i64,
+    /// Input tokens written to the provider's prompt cache
+    pub cache_creation_input_tokens: i64,
+}
+
+impl TaskUsage {
+    /// Usage added since `earlier` was taken
+    pub fn since(&self, earlier: &TaskUsage) -> TaskUsage {
+        TaskUsage {
+            input_tokens: self.input_tokens - earlier.input_tokens,
+            output_tokens: self.output_tokens - earlier.output_tokens,
+            cached_input_tokens: self.cached_input_tokens - earlier.cached_input_tokens,
+            cache_creation_input_tokens: self.cache_creation_input_tokens
+                - earlier.cache_creation_input_tokens,
+        }
+    }
 }
 
 /// A runtime task representing an agent execution
@@ -69,6 +108,9 @@ pub struct TaskInput {
     pub agent_id: Option<AgentId>,
     pub project_id: Option<String>,
     pub initial_message: String,
+    /// Image, file and audio parts sent along with `initial_message`
+    #[serde(default)]
+    pub attachments: Vec<MessagePart>,
     pub settings: Option<TaskSettings>,
     pub workspace: Option<WorkspaceInfo>,
 }
@@ -93,6 +135,18 @@ pub enum TaskAction {
     Cancel,
 }
 
+impl TaskAction {
+    /// Tool call this action targets, if any
+    pub fn tool_call_id(&self) -> Option<&str> {
+        match self {
+            TaskAction::Approve { tool_call_id }
+            | TaskAction::Reject { tool_call_id, .. }
+            | TaskAction::ToolResult { tool_call_id, .. } => Some(tool_call_id),
+            TaskAction::Cancel => None,
+        }
+    }
+}
+
 /// Request for tool execution
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -136,8 +190,40 @@ pub struct AgentLoopConfig {
     pub temperature: f32,
     /// Whether to enable tool use
     pub enable_tools: bool,
-    /// Tools available to the agent
+    /// Tools available to the agent (empty means all registered tools)
     pub available_tools: Vec<String>,
+    /// Tools the agent may never call
+    #[serde(default)]
+    pub disallowed_tools: Vec<String>,
+    /// Fraction of the context window at which older turns are compacted
+    #[serde(default = "default_compaction_threshold")]
+    pub compaction_threshold: f32,
+    /// Provider request options passed through to the LLM protocol
+    #[serde(default)]
+    pub provider_options: Option<serde_json::Value>,
+    /// Reasoning effort ("minimal", "low", "medium" or "high")
+    #[serde(default)]
+    pub reasoning_effort: Option<String>,
+    /// Token budget for extended thinking
+    #[serde(default)]
+    pub reasoning_budget_tokens: Option<u32>,
+}
+
+fn default_compaction_threshold() -> f32 {
+    0.8
+}
+
+/// Reasoning efforts accepted in settings
+pub const REASONING_EFFORTS: &[&str] = &["minimal", "low", "medium", "high"];
+
+/// Thinking budget used when only an effort is configured
+fn budget_for_effort(effort: &str) -> u32 {
+    match effort {
+        "minimal" => 1024,
+        "low" => 2048,
+        "high" => 24576,
+        _ => 8192,
+    }
 }
 
 impl Default for AgentLoopConfig {
@@ -148,10 +234,115 @@ impl Default for AgentLoopConfig {
             temperature: 0.7,
             enable_tools: true,
             available_tools: vec![],
+            disallowed_tools: vec![],
+            compaction_threshold: default_compaction_threshold(),
+            provider_options: None,
+            reasoning_effort: None,
+            reasoning_budget_tokens: None,
         }
     }
 }
 
+impl AgentLoopConfig {
+    /// Build a loop config from task settings, falling back to defaults for unset fields
+    pub fn from_settings(settings: &TaskSettings) -> Self {
+        let mut config = Self::default();
+        let Some(overrides) = settings.agent_loop.as_ref() else {
+            return config;
+        };
+
+        if let Some(max_iterations) = overrides.max_iterations {
+            config.max_iterations = max_iterations;
+        }
+        if let Some(temperature) = overrides.temperature {
+            config.temperature = temperature;
+        }
+        if overrides.max_tokens.is_some() {
+            config.max_tokens = overrides.max_tokens;
+        }
+        if let Some(enable_tools) = overrides.enable_tools {
+            config.enable_tools = enable_tools;
+        }
+        if let Some(allowed) = overrides.allowed_tools.as_ref() {
+            config.available_tools = allowed.iter().map(|t| normalize_tool_name(t)).collect();
+        }
+        if let Some(disallowed) = overrides.disallowed_tools.as_ref() {
+            config.disallowed_tools = disallowed.iter().map(|t| normalize_tool_name(t)).collect();
+        }
+        if let Some(threshold) = overrides.compaction_threshold {
+            config.compaction_threshold = threshold;
+        }
+        if overrides.provider_options.is_some() {
+            config.provider_options = overrides.provider_options.clone();
+        }
+        if overrides.reasoning_effort.is_some() {
+            config.reasoning_effort = overrides.reasoning_effort.clone();
+        }
+        if overrides.reasoning_budget_tokens.is_some() {
+            config.reasoning_budget_tokens = overrides.reasoning_budget_tokens;
+        }
+
+        config
+    }
+
+    /// Provider options for a request, with the reasoning settings mapped onto each
+    /// protocol's own option. Options set explicitly in `provider_options` win.
+    pub fn request_provider_options(&self) -> Option<serde_json::Value> {
+        if self.reasoning_effort.is_none() && self.reasoning_budget_tokens.is_none() {
+            return self.provider_options.clone();
+        }
+
+        let mut options = match &self.provider_options {
+            Some(serde_json::Value::Object(map)) => map.clone(),
+            _ => serde_json::Map::new(),
+        };
+        let budget = self
+            .reasoning_budget_tokens
+            .or_else(|| self.reasoning_effort.as_deref().map(budget_for_effort));
+        let mut defaults = vec![];
+        if let Some(budget) = budget {
+            defaults.push((
+                "anthropic",
+                "thinking",
+                serde_json::json!({ "type": "enabled", "budget_tokens": budget }),
+            ));
+            defaults.push((
+                "google",
+                "thinkingConfig",
+                serde_json::json!({ "thinkingBudget": budget, "includeThoughts": true }),
+            ));
+        }
+        if let Some(effort) = &self.reasoning_effort {
+            defaults.push(("openai", "reasoningEffort", serde_json::json!(effort)));
+        }
+
+        for (provider, key, value) in defaults {
+            let entry = options
+                .entry(provider.to_string())
+                .or_insert_with(|| serde_json::json!({}));
+            if let Some(entry) = entry.as_object_mut() {
+                entry.entry(key.to_string()).or_insert(value);
+            }
+        }
+        Some(serde_json::Value::Object(options))
+    }
+
+    /// Check whether the agent may call the given tool under this config
+    pub fn is_tool_allowed(&self, name: &str) -> bool {
+        if !self.enable_tools {
+            return false;
+        }
+
+        let name = normalize_tool_name(name);
+        let matches = |tools: &[String]| tools.iter().any(|t| normalize_tool_name(t) == name);
+
+        if matches(&self.disallowed_tools) {
+            return false;
+        }
+        self.available_tools.is_empty() || matches(&self.available_tools)
+    }
+}
+
 /// Event produced by the runtime for streaming
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "camelCase")]
@@ -159,6 +350,7 @@ pub enum RuntimeEvent {
     /// Task state changed
     TaskStateChanged {
         task_id: RuntimeTaskId,
+        session_id: SessionId,
         state: RuntimeTaskState,
         previous_state: RuntimeTaskState,
     },
@@ -196,14 +388,38 @@ pub enum RuntimeEvent {
         session_id: SessionId,
         finish_reason: Option<String>,
     },
+    /// Model started writing a tool call
+    ToolCallStart {
+        task_id: RuntimeTaskId,
+        session_id: SessionId,
+        tool_call_id: ToolCallId,
+        name: String,
+    },
+    /// Partial JSON arguments of a tool call still being written
+    ToolCallDelta {
+        task_id: RuntimeTaskId,
+        session_id: SessionId,
+        tool_call_id: ToolCallId,
+        arguments_delta: String,
+    },
     /// Tool execution requested
     ToolCallRequested {
         task_id: RuntimeTaskId,
+        session_id: SessionId,
         request: ToolRequest,
     },
+    /// Output chunk from a tool that is still running, e.g. a bash command
+    ToolOutput {
+        task_id: RuntimeTaskId,
+        session_id: SessionId,
+        tool_call_id: ToolCallId,
+        stream: OutputStream,
+        chunk: String,
+    },
     /// Tool execution completed
     ToolCallCompleted {
         task_id: RuntimeTaskId,
+        session_id: SessionId,
         result: ToolResult,
     },
     /// Error occurred
@@ -216,6 +432,17 @@ pub enum RuntimeEvent {
     TaskCompleted {
         task_id: RuntimeTaskId,
         session_id: SessionId,
+        /// Why a completion hook ended the task, if one did
+        stop_reason: Option<String>,
+    },
+    /// Event from a sub-agent started by callAgent, relayed to the parent session
+    SubAgentEvent {
+        /// Parent session the sub-agent reports into
+        session_id: SessionId,
+        /// Parent task that called the sub-agent
+        task_id: RuntimeTaskId,
+        sub_agent_id: AgentId,
+        event: Box<RuntimeEvent>,
     },
 }
 
@@ -316,6 +543,102 @@ mod tests {
         assert!(!RuntimeTaskState::Running.is_terminal());
     }
 
+    #[test]
+    fn test_agent_loop_config_from_settings() {
+        let config = AgentLoopConfig::from_settings(&TaskSettings::default());
+        assert_eq!(config.max_iterations, 50);
+        assert!(config.is_tool_allowed("readFile"));
+
+        let settings = TaskSettings {
+            agent_loop: Some(AgentLoopSettings {
+                max_iterations: Some(5),
+                temperature: Some(0.1),
+                max_tokens: Some(2048),
+                enable_tools: None,
+                allowed_tools: Some(vec!["read_file".to_string(), "bash".to_string()]),
+                disallowed_tools: Some(vec!["bash".to_string()]),
+                compaction_threshold: Some(0.5),
+                provider_options: Some(serde_json::json!({ "openai": { "promptCacheKey": "k" } })),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let config = AgentLoopConfig::from_settings(&settings);
+        assert_eq!(config.max_iterations, 5);
+        assert_eq!(config.temperature, 0.1);
+        assert_eq!(config.max_tokens, Some(2048));
+        assert_eq!(config.compaction_threshold, 0.5);
+        assert_eq!(
+            config.provider_options,
+            Some(serde_json::json!({ "openai": { "promptCacheKey": "k" } }))
+        );
+        assert_eq!(config.available_tools, vec!["readFile", "bash"]);
+
+        // Legacy aliases resolve to the same tool
+        assert!(config.is_tool_allowed("readFile"));
+        assert!(config.is_tool_allowed("read_file"));
+        // Deny-list wins over allow-list
+        assert!(!config.is_tool_allowed("bash"));
+        // Not on the allow-list
+        assert!(!config.is_tool_allowed("writeFile"));
+    }
+
+    #[test]
+    fn test_request_provider_options_maps_reasoning() {
+        let config = AgentLoopConfig {
+            provider_options: Some(serde_json::json!({
+                "openai": { "promptCacheKey": "k" },
+                "google": { "thinkingConfig": { "thinkingBudget": 0 } }
+            })),
+            reasoning_effort: Some("high".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.request_provider_options(),
+            Some(serde_json::json!({
+                "openai": { "promptCacheKey": "k", "reasoningEffort": "high" },
+                "anthropic": { "thinking": { "type": "enabled", "budget_tokens": 24576 } },
+                // Explicit provider options are kept
+                "google": { "thinkingConfig": { "thinkingBudget": 0 } }
+            }))
+        );
+
+        let config = AgentLoopConfig {
+            reasoning_budget_tokens: Some(4000),
+            ..Default::default()
+        };
+        let options = config.request_provider_options().unwrap();
+        assert_eq!(options["anthropic"]["thinking"]["budget_tokens"], 4000);
+        assert!(options.get("openai").is_none());
+
+        assert_eq!(AgentLoopConfig::default().request_provider_options(), None);
+    }
+
+    #[test]
+    fn test_agent_loop_config_tools_disabled() {
+        let config = AgentLoopConfig {
+            enable_tools: false,
+            ..Default::default()
+        };
+        assert!(!config.is_tool_allowed("readFile"));
+    }
+
+    #[test]
+    fn test_task_action_tool_call_id() {
+        let approve = TaskAction::Approve {
+            tool_call_id: "call_1".to_string(),
+        };
+        assert_eq!(approve.tool_call_id(), Some("call_1"));
+
+        let reject = TaskAction::Reject {
+            tool_call_id: "call_2".to_string(),
+            reason: None,
+        };
+        assert_eq!(reject.tool_call_id(), Some("call_2"));
+
+        assert_eq!(TaskAction::Cancel.tool_call_id(), None);
+    }
+
     #[test]
     fn test_settings_validation() {
         let mut validation = SettingsValidation::valid();
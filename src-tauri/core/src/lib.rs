@@ -32,6 +32,7 @@ pub mod lsp;
 pub mod oauth_callback_server;
 pub mod script_executor;
 pub mod search;
+pub mod shell_session;
 pub mod shell_utils;
 pub mod telegram_gateway;
 pub mod terminal;
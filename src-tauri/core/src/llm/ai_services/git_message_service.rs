@@ -4,6 +4,8 @@ use crate::llm::ai_services::stream_runner::StreamRunner;
 use crate::llm::ai_services::types::{GitMessageContext, GitMessageResult};
 use crate::llm::auth::api_key_manager::ApiKeyManager;
 use crate::llm::providers::provider_registry::ProviderRegistry;
+use crate::llm::types::ResponseFormat;
+use serde_json::json;
 use std::time::Duration;
 
 pub struct GitMessageService;
@@ -46,10 +48,16 @@ impl GitMessageService {
 
         let request = StreamCollector::create_completion_request(model_identifier, prompt);
         let runner = StreamRunner::new(registry.clone(), api_keys.clone());
-        let result =
-            StreamCollector::collect_with_runner(&runner, request, Duration::from_secs(30)).await?;
+        let text = StreamCollector::collect_text_field_with_runner(
+            &runner,
+            request,
+            Self::response_format(),
+            "message",
+            Duration::from_secs(30),
+        )
+        .await?;
 
-        let message = self.post_process_message(&result.text);
+        let message = self.post_process_message(&text);
         if message.is_empty() {
             return Err("Empty commit message generated".to_string());
         }
@@ -89,6 +97,21 @@ impl GitMessageService {
         )
     }
 
+    /// Structured output format for the commit message
+    fn response_format() -> ResponseFormat {
+        ResponseFormat {
+            name: "commit_message".to_string(),
+            description: Some("Return the commit message".to_string()),
+            schema: json!({
+                "type": "object",
+                "properties": { "message": { "type": "string" } },
+                "required": ["message"],
+                "additionalProperties": false
+            }),
+            strict: true,
+        }
+    }
+
     fn post_process_message(&self, raw: &str) -> String {
         let trimmed = raw.trim();
         if trimmed.is_empty() {
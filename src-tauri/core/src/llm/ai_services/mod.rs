@@ -6,5 +6,6 @@ pub mod pricing_service;
 pub mod prompt_enhancement_service;
 pub mod stream_collector;
 pub mod stream_runner;
+pub mod structured_output;
 pub mod task_title_service;
 pub mod types;
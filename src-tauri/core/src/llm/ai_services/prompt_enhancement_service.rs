@@ -4,8 +4,10 @@ use crate::llm::ai_services::stream_runner::StreamRunner;
 use crate::llm::ai_services::types::{PromptEnhancementRequest, PromptEnhancementResult};
 use crate::llm::auth::api_key_manager::ApiKeyManager;
 use crate::llm::providers::provider_registry::ProviderRegistry;
+use crate::llm::types::ResponseFormat;
 use crate::search::RipgrepSearch;
 use regex::Regex;
+use serde_json::json;
 use std::collections::HashSet;
 use std::time::Duration;
 
@@ -144,11 +146,16 @@ impl PromptEnhancementService {
         let llm_request = StreamCollector::create_completion_request(model_identifier, full_prompt);
 
         let runner = StreamRunner::new(registry.clone(), api_keys.clone());
-        let result =
-            StreamCollector::collect_with_runner(&runner, llm_request, Duration::from_secs(60))
-                .await?;
+        let text = StreamCollector::collect_text_field_with_runner(
+            &runner,
+            llm_request,
+            response_format(),
+            "enhancedPrompt",
+            Duration::from_secs(60),
+        )
+        .await?;
 
-        let enhanced_prompt = result.text.trim().to_string();
+        let enhanced_prompt = text.trim().to_string();
         if enhanced_prompt.is_empty() {
             return Err("Empty enhanced prompt generated".to_string());
         }
@@ -336,6 +343,21 @@ fn search_codebase(project_path: &str, queries: &[String]) -> Vec<String> {
     snippets
 }
 
+/// Structured output format for the enhanced prompt
+fn response_format() -> ResponseFormat {
+    ResponseFormat {
+        name: "enhanced_prompt".to_string(),
+        description: Some("Return the enhanced prompt".to_string()),
+        schema: json!({
+            "type": "object",
+            "properties": { "enhancedPrompt": { "type": "string" } },
+            "required": ["enhancedPrompt"],
+            "additionalProperties": false
+        }),
+        strict: true,
+    }
+}
+
 /// Build the system prompt by injecting context into the template
 fn build_system_prompt(context: &str) -> String {
     SYSTEM_PROMPT_TEMPLATE.replace("${context}", context)
@@ -1,8 +1,13 @@
 use crate::llm::ai_services::stream_runner::StreamRunner;
-use crate::llm::types::{Message, StreamEvent, StreamTextRequest};
+use crate::llm::ai_services::structured_output::{parse_json_response, validate_json};
+use crate::llm::types::{Message, MessageContent, ResponseFormat, StreamEvent, StreamTextRequest};
 use futures_util::StreamExt;
+use serde_json::Value;
 use std::time::{Duration, Instant};
 
+/// Attempts at a structured response, including repair retries
+const STRUCTURED_OUTPUT_ATTEMPTS: u32 = 2;
+
 /// Collects text deltas from a stream and returns the complete text
 /// This is used for non-streaming operations that need the full response
 pub struct StreamCollector;
@@ -99,6 +104,127 @@ impl StreamCollector {
         })
     }
 
+    /// Collect a structured response for `request.response_format`.
+    /// The response is parsed and validated against the schema; on failure the
+    /// model is shown the error and asked once more for a corrected response.
+    pub async fn collect_json_with_runner(
+        runner: &StreamRunner,
+        mut request: StreamTextRequest,
+        timeout: Duration,
+    ) -> Result<JsonCollectResult, String> {
+        let format = request
+            .response_format
+            .clone()
+            .ok_or_else(|| "Structured output requires a response format".to_string())?;
+        let start_time = Instant::now();
+        let mut last_error = String::new();
+
+        for attempt in 1..=STRUCTURED_OUTPUT_ATTEMPTS {
+            let mut text = String::new();
+            let mut tool_input: Option<Value> = None;
+            let mut stream_error: Option<String> = None;
+
+            runner
+                .stream(request.clone(), timeout, |event| match event {
+                    StreamEvent::TextDelta { text: delta } => text.push_str(&delta),
+                    StreamEvent::ToolCall {
+                        tool_name, input, ..
+                    } if tool_name == format.name => tool_input = Some(input),
+                    StreamEvent::Error { message } => stream_error = Some(message),
+                    _ => {}
+                })
+                .await?;
+
+            if let Some(message) = stream_error {
+                return Err(format!("Stream error: {}", message));
+            }
+
+            let raw = match &tool_input {
+                Some(input) => input.to_string(),
+                None => text.trim().to_string(),
+            };
+            match Self::validate_response(tool_input, &raw, &format) {
+                Ok(value) => {
+                    return Ok(JsonCollectResult {
+                        value,
+                        attempts: attempt,
+                        total_time_ms: start_time.elapsed().as_millis() as u64,
+                    });
+                }
+                Err(error) => {
+                    log::warn!(
+                        "Structured output attempt {} for '{}' failed: {}",
+                        attempt,
+                        format.name,
+                        error
+                    );
+                    request.messages.push(Message::Assistant {
+                        content: MessageContent::Text(raw),
+                        provider_options: None,
+                    });
+                    request.messages.push(Message::User {
+                        content: MessageContent::Text(Self::repair_prompt(&error)),
+                        provider_options: None,
+                    });
+                    last_error = error;
+                }
+            }
+        }
+
+        Err(format!(
+            "Structured output failed after {} attempts: {}",
+            STRUCTURED_OUTPUT_ATTEMPTS, last_error
+        ))
+    }
+
+    /// Collect `field` from a structured response, falling back to the plain
+    /// text response when the provider rejects structured output or the
+    /// response cannot be repaired
+    pub async fn collect_text_field_with_runner(
+        runner: &StreamRunner,
+        request: StreamTextRequest,
+        format: ResponseFormat,
+        field: &str,
+        timeout: Duration,
+    ) -> Result<String, String> {
+        let structured = StreamTextRequest {
+            response_format: Some(format),
+            ..request.clone()
+        };
+        match Self::collect_json_with_runner(runner, structured, timeout).await {
+            Ok(result) => match result.value.get(field).and_then(|v| v.as_str()) {
+                Some(text) => return Ok(text.trim().to_string()),
+                None => log::warn!("Structured output has no '{}' string", field),
+            },
+            Err(e) => log::warn!("Structured output failed, using plain text: {}", e),
+        }
+
+        Ok(Self::collect_with_runner(runner, request, timeout)
+            .await?
+            .text)
+    }
+
+    fn validate_response(
+        tool_input: Option<Value>,
+        raw: &str,
+        format: &ResponseFormat,
+    ) -> Result<Value, String> {
+        let value = match tool_input {
+            Some(input) => input,
+            None => parse_json_response(raw)?,
+        };
+        validate_json(&value, &format.schema)?;
+        Ok(value)
+    }
+
+    fn repair_prompt(error: &str) -> String {
+        format!(
+            "Your previous response did not match the required JSON schema: {}\n\
+             Respond again with only the corrected JSON.",
+            error
+        )
+    }
+
     /// Create a simple text completion request with a single user message
     pub fn create_completion_request(model: String, prompt: String) -> StreamTextRequest {
         StreamTextRequest {
@@ -116,6 +242,7 @@ impl StreamCollector {
             provider_options: None,
             request_id: None,
             trace_context: None,
+            response_format: None,
         }
     }
 }
@@ -128,6 +255,14 @@ pub struct CollectResult {
     pub delta_count: u32,
 }
 
+#[derive(Debug, Clone)]
+pub struct JsonCollectResult {
+    pub value: Value,
+    /// Number of requests made, 2 when a repair retry was needed
+    pub attempts: u32,
+    pub total_time_ms: u64,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -232,4 +367,39 @@ mod tests {
         assert_eq!(request.messages.len(), 1);
         assert!(request.stream.unwrap_or(false));
     }
+
+    #[test]
+    fn validate_response_accepts_tool_input_or_text() {
+        let format = ResponseFormat {
+            name: "task_title".to_string(),
+            description: None,
+            schema: serde_json::json!({
+                "type": "object",
+                "properties": { "title": { "type": "string" } },
+                "required": ["title"]
+            }),
+            strict: true,
+        };
+
+        let from_tool = StreamCollector::validate_response(
+            Some(serde_json::json!({ "title": "Fix Login Bug" })),
+            "",
+            &format,
+        )
+        .unwrap();
+        assert_eq!(from_tool["title"], "Fix Login Bug");
+
+        let from_text = StreamCollector::validate_response(
+            None,
+            "```json\n{\"title\": \"Fix Login Bug\"}\n```",
+            &format,
+        )
+        .unwrap();
+        assert_eq!(from_text, from_tool);
+
+        let error = StreamCollector::validate_response(None, "{\"name\": 1}", &format).unwrap_err();
+        assert!(
+            StreamCollector::repair_prompt(&error).contains("missing required property 'title'")
+        );
+    }
 }
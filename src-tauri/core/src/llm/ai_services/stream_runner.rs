@@ -27,7 +27,7 @@ impl StreamRunner {
     where
         F: FnMut(StreamEvent) + Send,
     {
-        let (_model_key, provider_id, provider_model_name) =
+        let (model_key, provider_id, provider_model_name) =
             self.resolve_model_info(&request.model).await?;
 
         let provider = self
@@ -35,6 +35,11 @@ impl StreamRunner {
             .create_provider(&provider_id)
             .ok_or_else(|| format!("Provider not found: {}", provider_id))?;
         let provider_config = provider.config();
+        on_event(StreamEvent::ModelSelected {
+            model: model_key,
+            provider_id: provider_id.clone(),
+            fallback: false,
+        });
 
         let provider_ctx = ProviderContext {
             provider_config,
@@ -48,6 +53,7 @@ impl StreamRunner {
             top_k: request.top_k,
             provider_options: request.provider_options.as_ref(),
             trace_context: request.trace_context.as_ref(),
+            response_format: request.response_format.as_ref(),
         };
 
         let built_request = provider.build_complete_request(&provider_ctx).await?;
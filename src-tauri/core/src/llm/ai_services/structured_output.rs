@@ -0,0 +1,168 @@
+// Structured output helpers
+// Parses model responses as JSON and checks them against the requested schema
+
+use serde_json::Value;
+
+/// Parse a model response as JSON.
+/// Tolerates markdown code fences and prose around a single JSON object.
+pub fn parse_json_response(raw: &str) -> Result<Value, String> {
+    let trimmed = strip_code_fence(raw.trim());
+    if let Ok(value) = serde_json::from_str(trimmed) {
+        return Ok(value);
+    }
+
+    let start = trimmed.find(['{', '[']);
+    let end = trimmed.rfind(['}', ']']);
+    match (start, end) {
+        (Some(start), Some(end)) if start < end => serde_json::from_str(&trimmed[start..=end])
+            .map_err(|e| format!("Response is not valid JSON: {}", e)),
+        _ => Err("Response does not contain a JSON value".to_string()),
+    }
+}
+
+/// Check `value` against the subset of JSON Schema used for structured output:
+/// `type`, `enum`, `properties`, `required`, `additionalProperties: false` and `items`
+pub fn validate_json(value: &Value, schema: &Value) -> Result<(), String> {
+    validate_at(value, schema, "$")
+}
+
+fn validate_at(value: &Value, schema: &Value, path: &str) -> Result<(), String> {
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(|n| n.as_str()).collect(),
+            _ => vec![],
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|name| matches_type(value, name)) {
+            return Err(format!(
+                "{}: expected {}, got {}",
+                path,
+                allowed.join(" or "),
+                type_name(value)
+            ));
+        }
+    }
+
+    if let Some(options) = schema.get("enum").and_then(|e| e.as_array()) {
+        if !options.contains(value) {
+            return Err(format!(
+                "{}: {} is not one of {}",
+                path,
+                value,
+                Value::from(options.clone())
+            ));
+        }
+    }
+
+    if let Value::Object(object) = value {
+        let properties = schema.get("properties").and_then(|p| p.as_object());
+        if let Some(required) = schema.get("required").and_then(|r| r.as_array()) {
+            for key in required.iter().filter_map(|k| k.as_str()) {
+                if !object.contains_key(key) {
+                    return Err(format!("{}: missing required property '{}'", path, key));
+                }
+            }
+        }
+        for (key, item) in object {
+            match properties.and_then(|p| p.get(key)) {
+                Some(item_schema) => validate_at(item, item_schema, &format!("{}.{}", path, key))?,
+                None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                    return Err(format!("{}: unexpected property '{}'", path, key));
+                }
+                None => {}
+            }
+        }
+    }
+
+    if let (Value::Array(items), Some(item_schema)) = (value, schema.get("items")) {
+        for (index, item) in items.iter().enumerate() {
+            validate_at(item, item_schema, &format!("{}[{}]", path, index))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn strip_code_fence(text: &str) -> &str {
+    let Some(rest) = text.strip_prefix("```") else {
+        return text;
+    };
+    // Drop the language tag on the opening fence line
+    let body = rest.split_once('\n').map(|(_, body)| body).unwrap_or(rest);
+    body.trim_end().strip_suffix("```").unwrap_or(body).trim()
+}
+
+fn matches_type(value: &Value, name: &str) -> bool {
+    match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parse_json_response_strips_fences_and_prose() {
+        assert_eq!(
+            parse_json_response("```json\n{\"title\": \"Fix Bug\"}\n```").unwrap(),
+            json!({ "title": "Fix Bug" })
+        );
+        assert_eq!(
+            parse_json_response("Here you go: {\"title\": \"Fix Bug\"} Done.").unwrap(),
+            json!({ "title": "Fix Bug" })
+        );
+        assert!(parse_json_response("Fix Bug").is_err());
+    }
+
+    #[test]
+    fn validate_json_reports_path_of_first_error() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "title": { "type": "string" },
+                "tags": { "type": "array", "items": { "enum": ["bug", "feature"] } }
+            },
+            "required": ["title"],
+            "additionalProperties": false
+        });
+
+        assert!(validate_json(&json!({ "title": "Fix", "tags": ["bug"] }), &schema).is_ok());
+        assert_eq!(
+            validate_json(&json!({ "tags": [] }), &schema).unwrap_err(),
+            "$: missing required property 'title'"
+        );
+        assert_eq!(
+            validate_json(&json!({ "title": 3 }), &schema).unwrap_err(),
+            "$.title: expected string, got number"
+        );
+        assert!(
+            validate_json(&json!({ "title": "Fix", "tags": ["chore"] }), &schema)
+                .unwrap_err()
+                .starts_with("$.tags[0]:")
+        );
+        assert_eq!(
+            validate_json(&json!({ "title": "Fix", "extra": 1 }), &schema).unwrap_err(),
+            "$: unexpected property 'extra'"
+        );
+    }
+}
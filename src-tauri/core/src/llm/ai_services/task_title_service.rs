@@ -4,6 +4,8 @@ use crate::llm::ai_services::stream_runner::StreamRunner;
 use crate::llm::ai_services::types::{TitleGenerationRequest, TitleGenerationResult};
 use crate::llm::auth::api_key_manager::ApiKeyManager;
 use crate::llm::providers::provider_registry::ProviderRegistry;
+use crate::llm::types::ResponseFormat;
+use serde_json::json;
 use std::time::Duration;
 
 pub struct TaskTitleService;
@@ -55,10 +57,16 @@ impl TaskTitleService {
         let request = StreamCollector::create_completion_request(model_identifier, prompt);
 
         let runner = StreamRunner::new(registry.clone(), api_keys.clone());
-        let result =
-            StreamCollector::collect_with_runner(&runner, request, Duration::from_secs(30)).await?;
+        let text = StreamCollector::collect_text_field_with_runner(
+            &runner,
+            request,
+            Self::response_format(),
+            "title",
+            Duration::from_secs(30),
+        )
+        .await?;
 
-        let title = self.post_process_title(&result.text);
+        let title = self.post_process_title(&text);
         if title.is_empty() {
             return Err("Empty title generated".to_string());
         }
@@ -66,6 +74,21 @@ impl TaskTitleService {
         Ok(TitleGenerationResult { title })
     }
 
+    /// Structured output format for the task title
+    fn response_format() -> ResponseFormat {
+        ResponseFormat {
+            name: "task_title".to_string(),
+            description: Some("Return the task title".to_string()),
+            schema: json!({
+                "type": "object",
+                "properties": { "title": { "type": "string" } },
+                "required": ["title"],
+                "additionalProperties": false
+            }),
+            strict: true,
+        }
+    }
+
     fn post_process_title(&self, raw: &str) -> String {
         let trimmed = raw.trim();
         if trimmed.is_empty() {
@@ -21,7 +21,7 @@ use crate::llm::types::{
     ImageGenerationRequest, ImageGenerationResponse, ModelsConfiguration, StreamResponse,
     StreamTextRequest, TranscriptionRequest, TranscriptionResponse,
 };
-use tauri::{Manager, State, Window};
+use tauri::{Emitter, Manager, State, Window};
 
 #[tauri::command]
 pub async fn llm_get_provider_configs(
@@ -86,6 +86,23 @@ pub async fn llm_list_available_models(
     ModelRegistry::compute_available_models(&api_keys, &registry).await
 }
 
+#[tauri::command]
+pub async fn llm_refresh_local_models(
+    app: tauri::AppHandle,
+    state: State<'_, LlmState>,
+) -> Result<Vec<AvailableModel>, String> {
+    let registry = state.registry.lock().await;
+    let api_keys = state.api_keys.lock().await;
+    let models = ModelRegistry::refresh_local_models(&api_keys, &registry).await?;
+    if let Err(error) = app.emit("modelsUpdated", ()) {
+        log::warn!(
+            "[llm_refresh_local_models] Failed to emit modelsUpdated: {}",
+            error
+        );
+    }
+    Ok(models)
+}
+
 #[tauri::command]
 pub async fn llm_register_custom_provider(
     config: CustomProviderConfig,
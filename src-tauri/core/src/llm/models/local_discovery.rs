@@ -0,0 +1,558 @@
+use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::providers::provider::BaseProvider;
+use crate::llm::types::{AvailableModel, ModelConfig, ModelsConfiguration, ProviderConfig};
+use futures::future::join_all;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// Local servers answer quickly or not at all; keep the model list responsive.
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(3);
+const CACHE_TTL: Duration = Duration::from_secs(60);
+const LOCAL_PROVIDERS: [&str; 2] = ["ollama", "lmstudio"];
+
+static CACHE: OnceLock<Mutex<HashMap<String, CachedModels>>> = OnceLock::new();
+
+struct CachedModels {
+    base_url: String,
+    fetched_at: Instant,
+    models: Vec<DiscoveredModel>,
+}
+
+/// A model reported by a locally running inference server.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DiscoveredModel {
+    pub id: String,
+    pub context_length: Option<u32>,
+    pub tool_call: Option<bool>,
+    pub image_input: bool,
+}
+
+#[derive(Deserialize)]
+struct OllamaTagsResponse {
+    #[serde(default)]
+    models: Vec<OllamaTag>,
+}
+
+#[derive(Deserialize)]
+struct OllamaTag {
+    name: String,
+    #[serde(default)]
+    details: Option<OllamaTagDetails>,
+}
+
+#[derive(Deserialize, Default)]
+struct OllamaTagDetails {
+    #[serde(default)]
+    families: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Default)]
+struct OllamaShowResponse {
+    #[serde(default)]
+    capabilities: Option<Vec<String>>,
+    #[serde(default)]
+    model_info: Option<HashMap<String, Value>>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiModelsResponse {
+    #[serde(default)]
+    data: Vec<Value>,
+}
+
+pub fn is_local_provider(provider_id: &str) -> bool {
+    LOCAL_PROVIDERS.contains(&provider_id)
+}
+
+fn cache() -> &'static Mutex<HashMap<String, CachedModels>> {
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+pub async fn clear_cache() {
+    cache().lock().await.clear();
+}
+
+/// Return the models served by a local provider, reusing a recent result when
+/// the base URL has not changed. Unreachable servers yield an empty list.
+pub async fn cached_models(
+    provider: &ProviderConfig,
+    api_keys: &ApiKeyManager,
+) -> Vec<DiscoveredModel> {
+    let base_url = match BaseProvider::new(provider.clone())
+        .resolve_base_url_with_fallback(api_keys)
+        .await
+    {
+        Ok(url) => url,
+        Err(error) => {
+            log::warn!(
+                "[LocalDiscovery] Failed to resolve base URL for {}: {}",
+                provider.id,
+                error
+            );
+            return Vec::new();
+        }
+    };
+
+    let mut cache = cache().lock().await;
+    if let Some(entry) = cache.get(&provider.id) {
+        if entry.base_url == base_url && entry.fetched_at.elapsed() < CACHE_TTL {
+            return entry.models.clone();
+        }
+    }
+
+    let models = match discover_models(&provider.id, &base_url).await {
+        Ok(models) => {
+            log::info!(
+                "[LocalDiscovery] Found {} models for {} at {}",
+                models.len(),
+                provider.id,
+                base_url
+            );
+            models
+        }
+        Err(error) => {
+            log::debug!("[LocalDiscovery] {} unavailable: {}", provider.id, error);
+            Vec::new()
+        }
+    };
+    cache.insert(
+        provider.id.clone(),
+        CachedModels {
+            base_url,
+            fetched_at: Instant::now(),
+            models: models.clone(),
+        },
+    );
+    models
+}
+
+pub async fn discover_models(
+    provider_id: &str,
+    base_url: &str,
+) -> Result<Vec<DiscoveredModel>, String> {
+    let client = Client::builder()
+        .timeout(DISCOVERY_TIMEOUT)
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+
+    match provider_id {
+        "ollama" => discover_ollama(&client, base_url).await,
+        _ => discover_openai_compatible(&client, base_url).await,
+    }
+}
+
+/// Ollama's native API lives at the server root, next to the `/v1` compatibility layer.
+fn ollama_root(base_url: &str) -> &str {
+    let trimmed = base_url.trim_end_matches('/');
+    trimmed.strip_suffix("/v1").unwrap_or(trimmed)
+}
+
+fn openai_models_url(base_url: &str) -> String {
+    let trimmed = base_url.trim_end_matches('/');
+    if trimmed.ends_with("/v1") {
+        format!("{}/models", trimmed)
+    } else {
+        format!("{}/v1/models", trimmed)
+    }
+}
+
+async fn get_json(client: &Client, url: &str) -> Result<Value, String> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach {}: {}", url, e))?;
+    read_json(response, url).await
+}
+
+async fn read_json(response: reqwest::Response, url: &str) -> Result<Value, String> {
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("Request to {} failed ({})", url, status));
+    }
+    response
+        .json::<Value>()
+        .await
+        .map_err(|e| format!("Failed to parse response from {}: {}", url, e))
+}
+
+async fn discover_ollama(client: &Client, base_url: &str) -> Result<Vec<DiscoveredModel>, String> {
+    let root = ollama_root(base_url);
+    let tags: OllamaTagsResponse =
+        serde_json::from_value(get_json(client, &format!("{}/api/tags", root)).await?)
+            .map_err(|e| format!("Failed to parse Ollama tags: {}", e))?;
+
+    let show_url = format!("{}/api/show", root);
+    let details = join_all(tags.models.iter().map(|tag| {
+        let show_url = show_url.clone();
+        async move {
+            let response = client
+                .post(&show_url)
+                .json(&json!({ "model": tag.name }))
+                .send()
+                .await
+                .map_err(|e| format!("Failed to reach {}: {}", show_url, e))?;
+            let value = read_json(response, &show_url).await?;
+            serde_json::from_value::<OllamaShowResponse>(value)
+                .map_err(|e| format!("Failed to parse Ollama model details: {}", e))
+        }
+    }))
+    .await;
+
+    let mut models = Vec::new();
+    for (tag, show) in tags.models.into_iter().zip(details) {
+        let show = show.unwrap_or_else(|error| {
+            log::debug!("[LocalDiscovery] No details for {}: {}", tag.name, error);
+            OllamaShowResponse::default()
+        });
+        if let Some(model) = ollama_model(tag, show) {
+            models.push(model);
+        }
+    }
+    Ok(models)
+}
+
+fn ollama_model(tag: OllamaTag, show: OllamaShowResponse) -> Option<DiscoveredModel> {
+    let families = tag.details.unwrap_or_default().families.unwrap_or_default();
+    let capabilities = show.capabilities;
+    if let Some(capabilities) = &capabilities {
+        if capabilities.iter().any(|c| c == "embedding")
+            && !capabilities.iter().any(|c| c == "completion")
+        {
+            return None;
+        }
+    }
+
+    let image_input = match &capabilities {
+        Some(capabilities) => capabilities.iter().any(|c| c == "vision"),
+        None => families.iter().any(|f| f == "clip" || f == "mllama"),
+    };
+    let context_length = show.model_info.as_ref().and_then(|info| {
+        info.iter()
+            .find(|(key, _)| key.ends_with(".context_length"))
+            .and_then(|(_, value)| value.as_u64())
+            .map(|value| u32::try_from(value).unwrap_or(u32::MAX))
+    });
+
+    Some(DiscoveredModel {
+        id: tag.name,
+        context_length,
+        tool_call: capabilities.map(|c| c.iter().any(|c| c == "tools")),
+        image_input,
+    })
+}
+
+async fn discover_openai_compatible(
+    client: &Client,
+    base_url: &str,
+) -> Result<Vec<DiscoveredModel>, String> {
+    let url = openai_models_url(base_url);
+    let response: OpenAiModelsResponse = serde_json::from_value(get_json(client, &url).await?)
+        .map_err(|e| format!("Failed to parse model list: {}", e))?;
+    Ok(response
+        .data
+        .iter()
+        .filter_map(openai_compatible_model)
+        .collect())
+}
+
+/// `/v1/models` only guarantees `id`; LM Studio and similar servers may add
+/// `type`, context length and capability fields, which are used when present.
+fn openai_compatible_model(entry: &Value) -> Option<DiscoveredModel> {
+    let id = entry.get("id").and_then(|v| v.as_str())?.to_string();
+    let model_type = entry.get("type").and_then(|v| v.as_str());
+    if model_type == Some("embeddings") || id.starts_with("text-embedding") {
+        return None;
+    }
+
+    let context_length = ["max_context_length", "context_length", "context_window"]
+        .iter()
+        .find_map(|key| entry.get(*key).and_then(|v| v.as_u64()))
+        .map(|value| u32::try_from(value).unwrap_or(u32::MAX));
+    let capabilities = entry.get("capabilities").and_then(|v| v.as_array());
+    let has_capability =
+        |name: &str| capabilities.is_some_and(|caps| caps.iter().any(|c| c.as_str() == Some(name)));
+
+    Some(DiscoveredModel {
+        id,
+        context_length,
+        tool_call: capabilities.map(|_| has_capability("tool_use") || has_capability("tools")),
+        image_input: model_type == Some("vlm") || has_capability("vision"),
+    })
+}
+
+/// Add discovered models to the configuration so they flow through the same
+/// availability rules as synced models. Models already mapped to the provider
+/// through `providerMappings` are left alone.
+pub fn merge_into_config(
+    config: &mut ModelsConfiguration,
+    provider_id: &str,
+    models: &[DiscoveredModel],
+) {
+    for model in models {
+        let mapped = config.models.values().any(|cfg| {
+            cfg.providers.iter().any(|p| p == provider_id)
+                && cfg
+                    .provider_mappings
+                    .as_ref()
+                    .and_then(|mappings| mappings.get(provider_id))
+                    .is_some_and(|name| *name == model.id)
+        });
+        if mapped {
+            continue;
+        }
+
+        match config.models.get_mut(&model.id) {
+            Some(existing) => {
+                if !existing.providers.iter().any(|p| p == provider_id) {
+                    existing.providers.push(provider_id.to_string());
+                }
+            }
+            None => {
+                config.models.insert(
+                    model.id.clone(),
+                    ModelConfig {
+                        name: model.id.clone(),
+                        image_input: model.image_input,
+                        image_output: false,
+                        audio_input: false,
+                        video_input: false,
+                        interleaved: false,
+                        providers: vec![provider_id.to_string()],
+                        provider_mappings: None,
+                        pricing: None,
+                        context_length: model.context_length,
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// Overlay server-reported capabilities on the computed list.
+pub fn apply_capabilities(
+    available: &mut [AvailableModel],
+    discovered: &HashMap<String, Vec<DiscoveredModel>>,
+) {
+    for model in available.iter_mut() {
+        let Some(found) = discovered
+            .get(&model.provider)
+            .and_then(|models| models.iter().find(|m| m.id == model.key))
+        else {
+            continue;
+        };
+        if found.context_length.is_some() {
+            model.context_length = found.context_length;
+        }
+        model.tool_call = found.tool_call;
+        model.image_input |= found.image_input;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    struct LocalServer {
+        base_url: String,
+        running: Arc<AtomicBool>,
+        handle: Option<thread::JoinHandle<()>>,
+    }
+
+    impl LocalServer {
+        fn start(routes: HashMap<&'static str, Value>) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+            let addr = listener.local_addr().expect("addr");
+            let server = tiny_http::Server::from_listener(listener, None).expect("server");
+            let running = Arc::new(AtomicBool::new(true));
+            let running_flag = running.clone();
+            let handle = thread::spawn(move || {
+                while running_flag.load(Ordering::SeqCst) {
+                    if let Ok(Some(mut request)) = server.recv_timeout(Duration::from_millis(20)) {
+                        let mut body = String::new();
+                        let _ = request.as_reader().read_to_string(&mut body);
+                        let key = match serde_json::from_str::<Value>(&body) {
+                            Ok(value) => match value.get("model").and_then(|v| v.as_str()) {
+                                Some(model) => format!("{}#{}", request.url(), model),
+                                None => request.url().to_string(),
+                            },
+                            Err(_) => request.url().to_string(),
+                        };
+                        let response = match routes.get(key.as_str()) {
+                            Some(value) => tiny_http::Response::from_string(value.to_string()),
+                            None => tiny_http::Response::from_string("").with_status_code(404),
+                        };
+                        let _ = request.respond(response);
+                    }
+                }
+            });
+            Self {
+                base_url: format!("http://{}", addr),
+                running,
+                handle: Some(handle),
+            }
+        }
+    }
+
+    impl Drop for LocalServer {
+        fn drop(&mut self) {
+            self.running.store(false, Ordering::SeqCst);
+            if let Some(handle) = self.handle.take() {
+                let _ = handle.join();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn discovers_ollama_models_with_capabilities() {
+        let server = LocalServer::start(HashMap::from([
+            (
+                "/api/tags",
+                json!({ "models": [
+                    { "name": "llama3.1:8b", "details": { "families": ["llama"] } },
+                    { "name": "llava:7b", "details": { "families": ["llama", "clip"] } },
+                    { "name": "nomic-embed-text:latest" }
+                ]}),
+            ),
+            (
+                "/api/show#llama3.1:8b",
+                json!({
+                    "capabilities": ["completion", "tools"],
+                    "model_info": { "llama.context_length": 131072 }
+                }),
+            ),
+            (
+                "/api/show#nomic-embed-text:latest",
+                json!({ "capabilities": ["embedding"] }),
+            ),
+        ]));
+
+        let models = discover_models("ollama", &format!("{}/v1", server.base_url))
+            .await
+            .expect("discover");
+
+        assert_eq!(
+            models,
+            vec![
+                DiscoveredModel {
+                    id: "llama3.1:8b".to_string(),
+                    context_length: Some(131072),
+                    tool_call: Some(true),
+                    image_input: false,
+                },
+                DiscoveredModel {
+                    id: "llava:7b".to_string(),
+                    context_length: None,
+                    tool_call: None,
+                    image_input: true,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn discovers_openai_compatible_models() {
+        let server = LocalServer::start(HashMap::from([(
+            "/v1/models",
+            json!({ "object": "list", "data": [
+                { "id": "qwen2.5-coder-7b", "object": "model" },
+                {
+                    "id": "qwen2-vl-7b",
+                    "type": "vlm",
+                    "max_context_length": 32768,
+                    "capabilities": ["tool_use"]
+                },
+                { "id": "text-embedding-nomic-embed-text-v1.5", "object": "model" }
+            ]}),
+        )]));
+
+        let models = discover_models("lmstudio", &server.base_url)
+            .await
+            .expect("discover");
+
+        assert_eq!(models.len(), 2);
+        assert_eq!(models[0].id, "qwen2.5-coder-7b");
+        assert_eq!(models[0].tool_call, None);
+        assert_eq!(models[1].context_length, Some(32768));
+        assert_eq!(models[1].tool_call, Some(true));
+        assert!(models[1].image_input);
+    }
+
+    #[tokio::test]
+    async fn unreachable_server_is_an_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        drop(listener);
+
+        let result = discover_models("ollama", &format!("http://{}", addr)).await;
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn merge_into_config_adds_provider_and_skips_mapped_models() {
+        let mut config = ModelsConfiguration {
+            version: "1".to_string(),
+            models: HashMap::from([(
+                "llama-3".to_string(),
+                ModelConfig {
+                    name: "Llama 3".to_string(),
+                    image_input: false,
+                    image_output: false,
+                    audio_input: false,
+                    video_input: false,
+                    interleaved: false,
+                    providers: vec!["ollama".to_string()],
+                    provider_mappings: Some(HashMap::from([(
+                        "ollama".to_string(),
+                        "llama3:8b".to_string(),
+                    )])),
+                    pricing: None,
+                    context_length: None,
+                },
+            )]),
+        };
+        let discovered = vec![
+            DiscoveredModel {
+                id: "llama3:8b".to_string(),
+                ..Default::default()
+            },
+            DiscoveredModel {
+                id: "qwen3:14b".to_string(),
+                context_length: Some(40960),
+                ..Default::default()
+            },
+        ];
+
+        merge_into_config(&mut config, "ollama", &discovered);
+
+        assert_eq!(config.models.len(), 2);
+        let added = config.models.get("qwen3:14b").expect("added model");
+        assert_eq!(added.providers, vec!["ollama"]);
+        assert_eq!(added.context_length, Some(40960));
+    }
+
+    #[test]
+    fn url_helpers_handle_v1_suffix() {
+        assert_eq!(
+            ollama_root("http://127.0.0.1:11434/v1/"),
+            "http://127.0.0.1:11434"
+        );
+        assert_eq!(
+            openai_models_url("http://127.0.0.1:1234/v1"),
+            "http://127.0.0.1:1234/v1/models"
+        );
+        assert_eq!(
+            openai_models_url("http://127.0.0.1:1234"),
+            "http://127.0.0.1:1234/v1/models"
+        );
+    }
+}
@@ -1,2 +1,3 @@
+pub mod local_discovery;
 pub mod model_registry;
 pub mod model_sync;
@@ -1,4 +1,5 @@
 use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::models::local_discovery::{self, DiscoveredModel};
 use crate::llm::providers::provider_registry::ProviderRegistry;
 use crate::llm::types::{AvailableModel, CustomProvidersConfiguration, ModelsConfiguration};
 use std::collections::HashMap;
@@ -44,7 +45,7 @@ impl ModelRegistry {
         api_keys: &ApiKeyManager,
         registry: &ProviderRegistry,
     ) -> Result<Vec<AvailableModel>, String> {
-        let models = Self::load_models_config(api_keys).await?;
+        let mut models = Self::load_models_config(api_keys).await?;
         log::info!(
             "[ModelRegistry] Loaded {} models from config",
             models.models.len()
@@ -81,12 +82,19 @@ impl ModelRegistry {
             registered_providers
         );
 
-        let available = Self::compute_available_models_internal(
+        let discovered =
+            Self::discover_local_models(api_keys, &api_key_map, registry, &custom_providers).await;
+        for (provider_id, found) in &discovered {
+            local_discovery::merge_into_config(&mut models, provider_id, found);
+        }
+
+        let mut available = Self::compute_available_models_internal(
             &models,
             &api_key_map,
             registry,
             &custom_providers,
         );
+        local_discovery::apply_capabilities(&mut available, &discovered);
         log::info!(
             "[ModelRegistry] Computed {} available models",
             available.len()
@@ -94,6 +102,37 @@ impl ModelRegistry {
         Ok(available)
     }
 
+    /// Drop cached local server listings and recompute the available models.
+    pub async fn refresh_local_models(
+        api_keys: &ApiKeyManager,
+        registry: &ProviderRegistry,
+    ) -> Result<Vec<AvailableModel>, String> {
+        local_discovery::clear_cache().await;
+        Self::compute_available_models(api_keys, registry).await
+    }
+
+    /// Query enabled local providers (Ollama, LM Studio) for the models they serve.
+    async fn discover_local_models(
+        api_keys: &ApiKeyManager,
+        api_key_map: &HashMap<String, String>,
+        registry: &ProviderRegistry,
+        custom_providers: &CustomProvidersConfiguration,
+    ) -> HashMap<String, Vec<DiscoveredModel>> {
+        let mut discovered = HashMap::new();
+        for provider in registry.providers() {
+            if !local_discovery::is_local_provider(&provider.id)
+                || !Self::provider_available(&provider.id, api_key_map, registry, custom_providers)
+            {
+                continue;
+            }
+            let models = local_discovery::cached_models(&provider, api_keys).await;
+            if !models.is_empty() {
+                discovered.insert(provider.id.clone(), models);
+            }
+        }
+        discovered
+    }
+
     fn compute_available_models_internal(
         config: &ModelsConfiguration,
         api_keys: &HashMap<String, String>,
@@ -118,6 +157,8 @@ impl ModelRegistry {
                             audio_input: model_cfg.audio_input,
                             video_input: model_cfg.video_input,
                             input_pricing: model_cfg.pricing.as_ref().map(|p| p.input.clone()),
+                            context_length: model_cfg.context_length,
+                            tool_call: None,
                         });
                     }
                 }
@@ -140,6 +181,8 @@ impl ModelRegistry {
                             audio_input: model_cfg.audio_input,
                             video_input: model_cfg.video_input,
                             input_pricing: model_cfg.pricing.as_ref().map(|p| p.input.clone()),
+                            context_length: model_cfg.context_length,
+                            tool_call: None,
                         });
                     }
                 }
@@ -207,6 +250,31 @@ impl ModelRegistry {
         ))
     }
 
+    /// All available providers for a configured model, in config order.
+    /// Unknown models have no alternates, so this returns an empty list.
+    pub fn get_model_providers(
+        model_key: &str,
+        api_keys: &HashMap<String, String>,
+        registry: &ProviderRegistry,
+        custom_providers: &CustomProvidersConfiguration,
+        config: &ModelsConfiguration,
+    ) -> Vec<String> {
+        config
+            .models
+            .get(model_key)
+            .map(|model_cfg| {
+                model_cfg
+                    .providers
+                    .iter()
+                    .filter(|provider_id| {
+                        Self::provider_available(provider_id, api_keys, registry, custom_providers)
+                    })
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
     fn provider_available(
         provider_id: &str,
         api_keys: &HashMap<String, String>,
@@ -598,6 +666,49 @@ mod tests {
         assert!(!available.is_empty());
     }
 
+    #[test]
+    fn discovered_local_models_become_available_with_capabilities() {
+        let mut config = build_models_config();
+        let registry = ProviderRegistry::new(vec![provider_config(
+            "ollama",
+            crate::llm::types::AuthType::None,
+        )]);
+        let api_keys = HashMap::from([("ollama".to_string(), "enabled".to_string())]);
+        let custom_providers = CustomProvidersConfiguration {
+            version: "1".to_string(),
+            providers: HashMap::new(),
+        };
+        let discovered = HashMap::from([(
+            "ollama".to_string(),
+            vec![DiscoveredModel {
+                id: "qwen3:8b".to_string(),
+                context_length: Some(40960),
+                tool_call: Some(true),
+                image_input: false,
+            }],
+        )]);
+
+        for (provider_id, found) in &discovered {
+            local_discovery::merge_into_config(&mut config, provider_id, found);
+        }
+        let mut available = ModelRegistry::compute_available_models_internal(
+            &config,
+            &api_keys,
+            &registry,
+            &custom_providers,
+        );
+        local_discovery::apply_capabilities(&mut available, &discovered);
+
+        let local = available
+            .iter()
+            .find(|model| model.key == "qwen3:8b")
+            .expect("discovered model");
+        assert_eq!(local.provider, "ollama");
+        assert_eq!(local.context_length, Some(40960));
+        assert_eq!(local.tool_call, Some(true));
+        assert!(available.iter().any(|model| model.key == "gpt-4o"));
+    }
+
     #[test]
     fn get_model_provider_prefers_model_config_providers_over_registry_order() {
         let mut config = build_models_config();
@@ -630,4 +741,39 @@ mod tests {
         assert_eq!(model, "gpt-4o");
         assert_eq!(provider, "openai");
     }
+
+    #[test]
+    fn get_model_providers_lists_every_available_provider() {
+        let config = build_models_config();
+        let registry = ProviderRegistry::new(vec![
+            provider_config("openai", crate::llm::types::AuthType::Bearer),
+            provider_config("ollama", crate::llm::types::AuthType::None),
+        ]);
+        let api_keys = HashMap::from([
+            ("openai".to_string(), "key".to_string()),
+            ("ollama".to_string(), "enabled".to_string()),
+        ]);
+        let custom_providers = CustomProvidersConfiguration {
+            version: "1".to_string(),
+            providers: HashMap::new(),
+        };
+
+        let providers = ModelRegistry::get_model_providers(
+            "gpt-4o",
+            &api_keys,
+            &registry,
+            &custom_providers,
+            &config,
+        );
+        assert_eq!(providers, vec!["openai", "ollama"]);
+
+        let unknown = ModelRegistry::get_model_providers(
+            "unknown-model",
+            &api_keys,
+            &registry,
+            &custom_providers,
+            &config,
+        );
+        assert!(unknown.is_empty());
+    }
 }
@@ -1,5 +1,7 @@
 use crate::llm::protocols::{LlmProtocol, ProtocolStreamState, ToolCallAccum};
-use crate::llm::types::{ContentPart, Message, MessageContent, StreamEvent, ToolDefinition};
+use crate::llm::types::{
+    ContentPart, Message, MessageContent, ResponseFormat, StreamEvent, ToolDefinition,
+};
 use serde_json::{json, Value};
 use std::collections::HashMap;
 
@@ -64,12 +66,13 @@ impl ClaudeProtocol {
                         ContentPart::Text { text } => {
                             mapped.push(json!({ "type": "text", "text": text }));
                         }
-                        ContentPart::Image { image } => {
+                        ContentPart::Image { image, mime_type } => {
+                            let mime = mime_type.as_deref().unwrap_or("image/png");
                             mapped.push(json!({
                                 "type": "image",
                                 "source": {
                                     "type": "base64",
-                                    "media_type": "image/png",
+                                    "media_type": mime,
                                     "data": image
                                 }
                             }));
@@ -95,15 +98,21 @@ impl ClaudeProtocol {
                             text,
                             provider_options,
                         } => {
-                            let mut thinking = json!({ "type": "thinking", "text": text });
-                            if let Some(opts) = provider_options {
-                                if let Some(signature) =
-                                    opts.get("anthropic").and_then(|v| v.get("signature"))
-                                {
-                                    thinking["signature"] = signature.clone();
-                                }
+                            // Thinking is only accepted back with the signature Claude issued,
+                            // so reasoning from other providers is left out
+                            let anthropic =
+                                provider_options.as_ref().and_then(|o| o.get("anthropic"));
+                            if let Some(data) = anthropic.and_then(|a| a.get("redactedData")) {
+                                mapped.push(json!({ "type": "redacted_thinking", "data": data }));
+                            } else if let Some(signature) =
+                                anthropic.and_then(|a| a.get("signature"))
+                            {
+                                mapped.push(json!({
+                                    "type": "thinking",
+                                    "thinking": text,
+                                    "signature": signature
+                                }));
                             }
-                            mapped.push(thinking);
                         }
                     }
                 }
@@ -133,6 +142,75 @@ impl ClaudeProtocol {
         }
         Some(result)
     }
+
+    /// Claude has no JSON response mode, so structured output is a tool the
+    /// model is forced to call. The tool input is the response. Forced tool
+    /// use is not allowed together with extended thinking, so thinking is dropped.
+    pub fn apply_response_format(&self, body: &mut Value, format: &ResponseFormat) {
+        let tool = json!({
+            "name": format.name,
+            "description": format
+                .description
+                .clone()
+                .unwrap_or_else(|| "Respond with the structured result".to_string()),
+            "input_schema": format.schema
+        });
+        match body.get_mut("tools").and_then(|tools| tools.as_array_mut()) {
+            Some(tools) => tools.push(tool),
+            None => body["tools"] = json!([tool]),
+        }
+        body["tool_choice"] = json!({ "type": "tool", "name": format.name });
+        if let Some(obj) = body.as_object_mut() {
+            obj.remove("thinking");
+        }
+    }
+
+    /// Cache breakpoint from `provider_options.anthropic.cacheControl`.
+    /// Enabled by default; `false` disables it and an object replaces the default.
+    fn cache_control(&self, provider_options: Option<&Value>) -> Option<Value> {
+        match provider_options
+            .and_then(|options| options.get("anthropic"))
+            .and_then(|anthropic| anthropic.get("cacheControl"))
+        {
+            Some(Value::Bool(false)) | Some(Value::Null) => None,
+            Some(value @ Value::Object(_)) => Some(value.clone()),
+            _ => Some(json!({ "type": "ephemeral" })),
+        }
+    }
+
+    /// Mark the system prompt, the tool definitions and the latest turn as
+    /// cacheable so the next iteration reads the shared prefix from cache
+    fn apply_cache_breakpoints(&self, body: &mut Value, cache_control: &Value) {
+        if let Some(system) = body.get("system").and_then(|v| v.as_str()) {
+            body["system"] = json!([{
+                "type": "text",
+                "text": system,
+                "cache_control": cache_control
+            }]);
+        }
+        if let Some(last_tool) = body
+            .get_mut("tools")
+            .and_then(|v| v.as_array_mut())
+            .and_then(|tools| tools.last_mut())
+        {
+            last_tool["cache_control"] = cache_control.clone();
+        }
+        // Thinking blocks cannot carry a breakpoint, so use the last other block
+        if let Some(block) =
+            body.get_mut("messages")
+                .and_then(|v| v.as_array_mut())
+                .and_then(|messages| messages.last_mut())
+                .and_then(|message| message.get_mut("content"))
+                .and_then(|v| v.as_array_mut())
+                .and_then(|content| {
+                    content.iter_mut().rev().find(|block| {
+                        block.get("type").and_then(|t| t.as_str()) != Some("thinking")
+                    })
+                })
+        {
+            block["cache_control"] = cache_control.clone();
+        }
+    }
 }
 
 impl LlmProtocol for ClaudeProtocol {
@@ -191,6 +269,24 @@ impl LlmProtocol for ClaudeProtocol {
                 }
             }
         }
+        if let Some(budget) = body
+            .get("thinking")
+            .and_then(|t| t.get("budget_tokens"))
+            .and_then(|b| b.as_i64())
+        {
+            // Extended thinking rejects sampling overrides and needs room past the budget
+            if let Some(obj) = body.as_object_mut() {
+                obj.remove("temperature");
+                obj.remove("top_p");
+            }
+            let max_tokens = i64::from(max_tokens.unwrap_or(1024));
+            if max_tokens <= budget {
+                body["max_tokens"] = json!(budget + max_tokens);
+            }
+        }
+        if let Some(cache_control) = self.cache_control(provider_options) {
+            self.apply_cache_breakpoints(&mut body, &cache_control);
+        }
 
         if let Some(extra) = extra_body {
             if let Some(obj) = body.as_object_mut() {
@@ -242,16 +338,20 @@ impl LlmProtocol for ClaudeProtocol {
                                     .insert(index as usize, block_id.to_string());
                             }
 
-                            if block_type == "thinking" {
+                            if block_type == "thinking" || block_type == "redacted_thinking" {
+                                // Thinking blocks carry no ID, so the block index tells them apart
                                 let id = block
                                     .get("id")
                                     .and_then(|v| v.as_str())
-                                    .unwrap_or("thinking")
-                                    .to_string();
+                                    .map(|v| v.to_string())
+                                    .unwrap_or_else(|| format!("thinking_{}", index));
                                 state.current_thinking_id = Some(id.clone());
+                                let provider_metadata = block
+                                    .get("data")
+                                    .map(|data| json!({ "anthropic": { "redactedData": data } }));
                                 return Ok(Some(StreamEvent::ReasoningStart {
                                     id,
-                                    provider_metadata: None,
+                                    provider_metadata,
                                 }));
                             }
                             if block_type == "tool_use" {
@@ -277,12 +377,16 @@ impl LlmProtocol for ClaudeProtocol {
                                     id.clone(),
                                     ToolCallAccum {
                                         tool_call_id: id.clone(),
-                                        tool_name: name,
+                                        tool_name: name.clone(),
                                         arguments,
                                         thought_signature: None,
                                     },
                                 );
-                                state.tool_call_order.push(id);
+                                state.tool_call_order.push(id.clone());
+                                return Ok(Some(StreamEvent::ToolCallStart {
+                                    tool_call_id: id,
+                                    tool_name: name,
+                                }));
                             }
                         }
                     }
@@ -300,7 +404,11 @@ impl LlmProtocol for ClaudeProtocol {
                             }
                         }
                         "thinking_delta" => {
-                            if let Some(text) = delta.get("text").and_then(|v| v.as_str()) {
+                            let text = delta
+                                .get("thinking")
+                                .or_else(|| delta.get("text"))
+                                .and_then(|v| v.as_str());
+                            if let Some(text) = text {
                                 let id = state
                                     .current_thinking_id
                                     .clone()
@@ -345,6 +453,12 @@ impl LlmProtocol for ClaudeProtocol {
                                         delta.get("partial_json").and_then(|v| v.as_str())
                                     {
                                         acc.arguments.push_str(chunk);
+                                        if !chunk.is_empty() {
+                                            return Ok(Some(StreamEvent::ToolCallDelta {
+                                                tool_call_id: acc.tool_call_id.clone(),
+                                                arguments_delta: chunk.to_string(),
+                                            }));
+                                        }
                                     }
                                 }
                             }
@@ -355,6 +469,14 @@ impl LlmProtocol for ClaudeProtocol {
             }
             "content_block_stop" => {
                 let index = payload.get("index").and_then(|v| v.as_u64());
+                let is_thinking = index
+                    .and_then(|i| state.content_block_types.get(&(i as usize)))
+                    .is_some_and(|t| t == "thinking" || t == "redacted_thinking");
+                if is_thinking {
+                    if let Some(id) = state.current_thinking_id.take() {
+                        return Ok(Some(StreamEvent::ReasoningEnd { id }));
+                    }
+                }
                 let tool_id = payload
                     .get("content_block")
                     .and_then(|v| v.get("id"))
@@ -386,20 +508,20 @@ impl LlmProtocol for ClaudeProtocol {
                     state.finish_reason = Some(stop_reason.to_string());
                 }
                 if let Some(usage) = payload.get("usage") {
-                    let input_tokens = usage
-                        .get("input_tokens")
-                        .and_then(|v| v.as_i64())
-                        .unwrap_or(0);
-                    let output_tokens = usage
-                        .get("output_tokens")
-                        .and_then(|v| v.as_i64())
-                        .unwrap_or(0);
+                    let tokens = |key: &str| usage.get(key).and_then(|v| v.as_i64());
+                    let output_tokens = tokens("output_tokens").unwrap_or(0);
+                    let cached_input_tokens = tokens("cache_read_input_tokens");
+                    let cache_creation_input_tokens = tokens("cache_creation_input_tokens");
+                    // Anthropic reports cache reads and writes apart from input_tokens
+                    let input_tokens = tokens("input_tokens").unwrap_or(0)
+                        + cached_input_tokens.unwrap_or(0)
+                        + cache_creation_input_tokens.unwrap_or(0);
                     return Ok(Some(StreamEvent::Usage {
                         input_tokens: input_tokens as i32,
                         output_tokens: output_tokens as i32,
                         total_tokens: None,
-                        cached_input_tokens: None,
-                        cache_creation_input_tokens: None,
+                        cached_input_tokens: cached_input_tokens.map(|v| v as i32),
+                        cache_creation_input_tokens: cache_creation_input_tokens.map(|v| v as i32),
                     }));
                 }
             }
@@ -513,7 +635,11 @@ mod tests {
             &mut state,
         )
         .unwrap();
-        assert!(start_event.is_none());
+        assert!(matches!(
+            start_event,
+            Some(StreamEvent::ToolCallStart { ref tool_call_id, ref tool_name })
+                if tool_call_id == "call_1" && tool_name == "glob"
+        ));
 
         let delta = json!({
             "type": "content_block_delta",
@@ -530,7 +656,11 @@ mod tests {
             &mut state,
         )
         .unwrap();
-        assert!(delta_event.is_none());
+        assert!(matches!(
+            delta_event,
+            Some(StreamEvent::ToolCallDelta { ref arguments_delta, .. })
+                if arguments_delta.starts_with("{\"path\"")
+        ));
 
         let stop = json!({
             "type": "content_block_stop",
@@ -586,7 +716,9 @@ mod tests {
             Some(256),
             Some(0.9),
             None,
-            Some(&json!({ "anthropic": { "thinking": { "type": "enabled" } } })),
+            Some(&json!({
+                "anthropic": { "thinking": { "type": "enabled" }, "cacheControl": false }
+            })),
             Some(&json!({ "max_output_tokens": 128 })),
         )
         .expect("build request");
@@ -612,6 +744,137 @@ mod tests {
         assert_eq!(body.get("max_output_tokens"), Some(&json!(128)));
     }
 
+    #[test]
+    fn build_request_places_cache_breakpoints() {
+        let protocol = ClaudeProtocol;
+        let messages = vec![
+            Message::System {
+                content: "system".to_string(),
+                provider_options: None,
+            },
+            Message::User {
+                content: MessageContent::Text("first".to_string()),
+                provider_options: None,
+            },
+            Message::Assistant {
+                content: MessageContent::Parts(vec![
+                    ContentPart::Text {
+                        text: "answer".to_string(),
+                    },
+                    ContentPart::Reasoning {
+                        text: "thinking".to_string(),
+                        provider_options: None,
+                    },
+                ]),
+                provider_options: None,
+            },
+        ];
+        let tools = vec![
+            ToolDefinition {
+                tool_type: "function".to_string(),
+                name: "readFile".to_string(),
+                description: None,
+                parameters: json!({ "type": "object" }),
+                strict: false,
+            },
+            ToolDefinition {
+                tool_type: "function".to_string(),
+                name: "writeFile".to_string(),
+                description: None,
+                parameters: json!({ "type": "object" }),
+                strict: false,
+            },
+        ];
+        let ephemeral = json!({ "type": "ephemeral" });
+
+        let body = LlmProtocol::build_request(
+            &protocol,
+            "claude-3",
+            &messages,
+            Some(&tools),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .expect("build request");
+
+        assert_eq!(body["system"][0]["text"], json!("system"));
+        assert_eq!(body["system"][0]["cache_control"], ephemeral);
+        assert!(body["tools"][0].get("cache_control").is_none());
+        assert_eq!(body["tools"][1]["cache_control"], ephemeral);
+        assert!(body["messages"][0]["content"][0]
+            .get("cache_control")
+            .is_none());
+        assert_eq!(
+            body["messages"][1]["content"][0]["cache_control"],
+            ephemeral
+        );
+        assert!(body["messages"][1]["content"][1]
+            .get("cache_control")
+            .is_none());
+
+        let custom = json!({ "type": "ephemeral", "ttl": "1h" });
+        let body = LlmProtocol::build_request(
+            &protocol,
+            "claude-3",
+            &messages,
+            Some(&tools),
+            None,
+            None,
+            None,
+            None,
+            Some(&json!({ "anthropic": { "cacheControl": custom } })),
+            None,
+        )
+        .expect("build request");
+        assert_eq!(body["system"][0]["cache_control"], custom);
+        assert_eq!(body["tools"][1]["cache_control"], custom);
+    }
+
+    #[test]
+    fn parse_message_delta_reports_cache_usage() {
+        let protocol = ClaudeProtocol;
+        let mut state = ProtocolStreamState::default();
+        let delta = json!({
+            "type": "message_delta",
+            "delta": { "stop_reason": "end_turn" },
+            "usage": {
+                "input_tokens": 100,
+                "output_tokens": 20,
+                "cache_read_input_tokens": 800,
+                "cache_creation_input_tokens": 50
+            }
+        });
+
+        let event = LlmProtocol::parse_stream_event(
+            &protocol,
+            Some("message_delta"),
+            &delta.to_string(),
+            &mut state,
+        )
+        .expect("delta")
+        .expect("event");
+
+        match event {
+            StreamEvent::Usage {
+                input_tokens,
+                output_tokens,
+                cached_input_tokens,
+                cache_creation_input_tokens,
+                ..
+            } => {
+                assert_eq!(input_tokens, 950);
+                assert_eq!(output_tokens, 20);
+                assert_eq!(cached_input_tokens, Some(800));
+                assert_eq!(cache_creation_input_tokens, Some(50));
+            }
+            _ => panic!("Unexpected event"),
+        }
+    }
+
     #[test]
     fn parse_stream_emits_reasoning_signature_delta() {
         let protocol = ClaudeProtocol;
@@ -670,6 +933,151 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_stream_reads_thinking_blocks() {
+        let protocol = ClaudeProtocol;
+        let mut state = ProtocolStreamState::default();
+        let mut parse = |event: &str, payload: Value| {
+            LlmProtocol::parse_stream_event(
+                &protocol,
+                Some(event),
+                &payload.to_string(),
+                &mut state,
+            )
+            .expect("parse")
+        };
+
+        let start = parse(
+            "content_block_start",
+            json!({
+                "type": "content_block_start",
+                "index": 0,
+                "content_block": { "type": "thinking", "thinking": "" }
+            }),
+        );
+        assert!(matches!(
+            start,
+            Some(StreamEvent::ReasoningStart { ref id, .. }) if id == "thinking_0"
+        ));
+
+        let delta = parse(
+            "content_block_delta",
+            json!({
+                "type": "content_block_delta",
+                "index": 0,
+                "delta": { "type": "thinking_delta", "thinking": "Let me check" }
+            }),
+        );
+        assert!(matches!(
+            delta,
+            Some(StreamEvent::ReasoningDelta { ref text, .. }) if text == "Let me check"
+        ));
+
+        let stop = parse(
+            "content_block_stop",
+            json!({ "type": "content_block_stop", "index": 0 }),
+        );
+        assert!(matches!(
+            stop,
+            Some(StreamEvent::ReasoningEnd { ref id }) if id == "thinking_0"
+        ));
+
+        let redacted = parse(
+            "content_block_start",
+            json!({
+                "type": "content_block_start",
+                "index": 1,
+                "content_block": { "type": "redacted_thinking", "data": "opaque" }
+            }),
+        );
+        match redacted {
+            Some(StreamEvent::ReasoningStart {
+                id,
+                provider_metadata,
+            }) => {
+                assert_eq!(id, "thinking_1");
+                assert_eq!(
+                    provider_metadata,
+                    Some(json!({ "anthropic": { "redactedData": "opaque" } }))
+                );
+            }
+            _ => panic!("Unexpected event"),
+        }
+    }
+
+    #[test]
+    fn build_messages_replays_only_signed_thinking() {
+        let protocol = ClaudeProtocol;
+        let messages = vec![
+            Message::User {
+                content: MessageContent::Text("fix it".to_string()),
+                provider_options: None,
+            },
+            Message::Assistant {
+                content: MessageContent::Parts(vec![
+                    ContentPart::Reasoning {
+                        text: "Read the file first".to_string(),
+                        provider_options: Some(json!({ "anthropic": { "signature": "sig-1" } })),
+                    },
+                    ContentPart::Reasoning {
+                        text: "From another provider".to_string(),
+                        provider_options: None,
+                    },
+                    ContentPart::ToolCall {
+                        tool_call_id: "toolu_1".to_string(),
+                        tool_name: "readFile".to_string(),
+                        input: json!({ "file_path": "a.rs" }),
+                        provider_metadata: None,
+                    },
+                ]),
+                provider_options: None,
+            },
+        ];
+
+        let built = protocol.build_messages(&messages);
+        assert_eq!(built.len(), 2);
+        assert_eq!(
+            built[1]["content"],
+            json!([
+                { "type": "thinking", "thinking": "Read the file first", "signature": "sig-1" },
+                {
+                    "type": "tool_use",
+                    "id": "toolu_1",
+                    "name": "readFile",
+                    "input": { "file_path": "a.rs" }
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn build_request_with_thinking_drops_sampling_overrides() {
+        let protocol = ClaudeProtocol;
+        let messages = vec![Message::User {
+            content: MessageContent::Text("hi".to_string()),
+            provider_options: None,
+        }];
+        let body = LlmProtocol::build_request(
+            &protocol,
+            "claude-sonnet-4",
+            &messages,
+            None,
+            Some(0.7),
+            Some(4096),
+            Some(0.9),
+            None,
+            Some(&json!({
+                "anthropic": { "thinking": { "type": "enabled", "budget_tokens": 8192 } }
+            })),
+            None,
+        )
+        .expect("build request");
+
+        assert!(body.get("temperature").is_none());
+        assert!(body.get("top_p").is_none());
+        assert_eq!(body["max_tokens"], json!(12288));
+    }
+
     #[test]
     fn build_headers_prefers_oauth_token() {
         let protocol = ClaudeProtocol;
@@ -688,4 +1096,35 @@ mod tests {
         assert!(headers.get("x-api-key").is_none());
         assert_eq!(headers.get("X-Test"), Some(&"1".to_string()));
     }
+
+    #[test]
+    fn apply_response_format_forces_output_tool() {
+        let mut body = json!({
+            "model": "claude-3",
+            "messages": [],
+            "thinking": { "type": "enabled" }
+        });
+        let format = ResponseFormat {
+            name: "task_title".to_string(),
+            description: Some("The task title".to_string()),
+            schema: json!({ "type": "object" }),
+            strict: true,
+        };
+
+        ClaudeProtocol.apply_response_format(&mut body, &format);
+
+        assert_eq!(
+            body["tools"],
+            json!([{
+                "name": "task_title",
+                "description": "The task title",
+                "input_schema": { "type": "object" }
+            }])
+        );
+        assert_eq!(
+            body["tool_choice"],
+            json!({ "type": "tool", "name": "task_title" })
+        );
+        assert!(body.get("thinking").is_none());
+    }
 }
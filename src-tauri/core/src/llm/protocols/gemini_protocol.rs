@@ -0,0 +1,817 @@
+use crate::llm::protocols::{
+    header_builder::{HeaderBuildContext, ProtocolHeaderBuilder},
+    request_builder::{ProtocolRequestBuilder, RequestBuildContext},
+    stream_parser::{self, ProtocolStreamParser, StreamParseContext, StreamParseState},
+    LlmProtocol, ProtocolStreamState, ToolCallAccum,
+};
+use crate::llm::types::{ContentPart, Message, MessageContent, StreamEvent, ToolDefinition};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+/// Google Gemini `generateContent` protocol (streamed with `alt=sse`)
+pub struct GeminiProtocol;
+
+/// Prefix for tool call ids generated when Gemini does not send one
+pub const GEMINI_TOOL_CALL_ID_PREFIX: &str = "gemini_call_";
+
+/// JSON Schema keys the Gemini function declaration schema rejects
+const UNSUPPORTED_SCHEMA_KEYS: &[&str] = &["$schema", "additionalProperties"];
+
+impl GeminiProtocol {
+    /// Endpoint path for streaming a model, relative to the `v1beta` base URL
+    pub fn stream_endpoint_path(model: &str) -> String {
+        format!(
+            "models/{}:streamGenerateContent?alt=sse",
+            model.trim_start_matches("models/")
+        )
+    }
+
+    fn build_system_instruction(&self, messages: &[Message]) -> Option<Value> {
+        let parts: Vec<Value> = messages
+            .iter()
+            .filter_map(|msg| match msg {
+                Message::System { content, .. } if !content.trim().is_empty() => {
+                    Some(json!({ "text": content }))
+                }
+                _ => None,
+            })
+            .collect();
+        if parts.is_empty() {
+            return None;
+        }
+        Some(json!({ "parts": parts }))
+    }
+
+    fn build_contents(&self, messages: &[Message]) -> Vec<Value> {
+        let mut contents: Vec<Value> = Vec::new();
+
+        for msg in messages {
+            let (role, parts) = match msg {
+                Message::System { .. } => continue,
+                Message::User { content, .. } => ("user", self.convert_content(content)),
+                Message::Assistant { content, .. } => ("model", self.convert_content(content)),
+                Message::Tool { content, .. } => {
+                    let parts = content
+                        .iter()
+                        .filter_map(|part| match part {
+                            ContentPart::ToolResult {
+                                tool_name, output, ..
+                            } => Some(json!({
+                                "functionResponse": {
+                                    "name": tool_name,
+                                    "response": { "content": self.tool_output_to_string(output) }
+                                }
+                            })),
+                            _ => None,
+                        })
+                        .collect();
+                    ("user", parts)
+                }
+            };
+            if parts.is_empty() {
+                continue;
+            }
+
+            // Gemini expects alternating turns, and all function responses for a
+            // model turn in a single content, so merge consecutive same-role messages
+            match contents.last_mut() {
+                Some(last) if last.get("role").and_then(|v| v.as_str()) == Some(role) => {
+                    if let Some(existing) = last.get_mut("parts").and_then(|v| v.as_array_mut()) {
+                        existing.extend(parts);
+                    }
+                }
+                _ => contents.push(json!({ "role": role, "parts": parts })),
+            }
+        }
+
+        contents
+    }
+
+    fn convert_content(&self, content: &MessageContent) -> Vec<Value> {
+        match content {
+            MessageContent::Text(text) => {
+                if text.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![json!({ "text": text })]
+                }
+            }
+            MessageContent::Parts(parts) => {
+                let mut mapped = Vec::new();
+                for part in parts {
+                    match part {
+                        ContentPart::Text { text } => {
+                            if !text.is_empty() {
+                                mapped.push(json!({ "text": text }));
+                            }
+                        }
+                        ContentPart::Image { image, mime_type } => {
+                            let mime = mime_type.as_deref().unwrap_or("image/png");
+                            mapped.push(json!({
+                                "inlineData": { "mimeType": mime, "data": image }
+                            }));
+                        }
+                        ContentPart::Video { video, mime_type } => {
+                            mapped.push(json!({
+                                "inlineData": {
+                                    "mimeType": mime_type.as_deref().unwrap_or("video/mp4"),
+                                    "data": video
+                                }
+                            }));
+                        }
+                        ContentPart::ToolCall {
+                            tool_name,
+                            input,
+                            provider_metadata,
+                            ..
+                        } => {
+                            let mut call = json!({
+                                "functionCall": { "name": tool_name, "args": input }
+                            });
+                            if let Some(signature) = thought_signature(provider_metadata.as_ref()) {
+                                call["thoughtSignature"] = signature.clone();
+                            }
+                            mapped.push(call);
+                        }
+                        ContentPart::ToolResult { .. } => {}
+                        ContentPart::Reasoning {
+                            text,
+                            provider_options,
+                        } => {
+                            let signature = thought_signature(provider_options.as_ref());
+                            if text.is_empty() && signature.is_none() {
+                                continue;
+                            }
+                            let mut thought = json!({ "text": text, "thought": true });
+                            if let Some(signature) = signature {
+                                thought["thoughtSignature"] = signature.clone();
+                            }
+                            mapped.push(thought);
+                        }
+                    }
+                }
+                mapped
+            }
+        }
+    }
+
+    fn tool_output_to_string(&self, output: &Value) -> String {
+        if let Some(value) = output.get("value").and_then(|v| v.as_str()) {
+            return value.to_string();
+        }
+        output.to_string()
+    }
+
+    fn build_tools(&self, tools: Option<&[ToolDefinition]>) -> Option<Value> {
+        let tools = tools.filter(|t| !t.is_empty())?;
+        let declarations: Vec<Value> = tools
+            .iter()
+            .map(|tool| {
+                json!({
+                    "name": tool.name,
+                    "description": tool.description,
+                    "parameters": sanitize_schema(&tool.parameters)
+                })
+            })
+            .collect();
+        Some(json!([{ "functionDeclarations": declarations }]))
+    }
+
+    fn handle_part(&self, part: &Value, state: &mut StreamParseState) {
+        let signature = part
+            .get("thoughtSignature")
+            .and_then(|v| v.as_str())
+            .map(String::from);
+
+        if let Some(call) = part.get("functionCall") {
+            self.end_reasoning(state);
+            let tool_call_id = call
+                .get("id")
+                .and_then(|v| v.as_str())
+                .filter(|id| !id.is_empty())
+                .map(String::from)
+                .unwrap_or_else(|| {
+                    format!(
+                        "{}{}",
+                        GEMINI_TOOL_CALL_ID_PREFIX,
+                        uuid::Uuid::new_v4().simple()
+                    )
+                });
+            let tool_name = call
+                .get("name")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string();
+            let input = call.get("args").cloned().unwrap_or_else(|| json!({}));
+
+            state.tool_calls.insert(
+                tool_call_id.clone(),
+                ToolCallAccum {
+                    tool_call_id: tool_call_id.clone(),
+                    tool_name: tool_name.clone(),
+                    arguments: input.to_string(),
+                    thought_signature: signature.clone(),
+                },
+            );
+            state.tool_call_order.push(tool_call_id.clone());
+            state.emitted_tool_calls.insert(tool_call_id.clone());
+            state.pending_events.push(StreamEvent::ToolCall {
+                tool_call_id,
+                tool_name,
+                input,
+                provider_metadata: signature.map(google_signature_metadata),
+            });
+            return;
+        }
+
+        let text = part.get("text").and_then(|v| v.as_str()).unwrap_or("");
+        let is_thought = part.get("thought").and_then(|v| v.as_bool()) == Some(true);
+
+        if is_thought {
+            if !state.reasoning_started {
+                state.reasoning_started = true;
+                state.reasoning_id = Some(format!("reasoning_{}", uuid::Uuid::new_v4()));
+                state.pending_events.push(StreamEvent::ReasoningStart {
+                    id: state.reasoning_id.clone().unwrap_or_default(),
+                    provider_metadata: None,
+                });
+            }
+            let id = state.reasoning_id.clone().unwrap_or_default();
+            if !text.is_empty() {
+                state.pending_events.push(StreamEvent::ReasoningDelta {
+                    id: id.clone(),
+                    text: text.to_string(),
+                    provider_metadata: None,
+                });
+            }
+            if let Some(signature) = signature {
+                state.pending_events.push(StreamEvent::ReasoningDelta {
+                    id,
+                    text: String::new(),
+                    provider_metadata: Some(google_signature_metadata(signature)),
+                });
+            }
+            return;
+        }
+
+        if !text.is_empty() {
+            self.end_reasoning(state);
+            if !state.text_started {
+                state.text_started = true;
+                state.pending_events.push(StreamEvent::TextStart);
+            }
+            state.pending_events.push(StreamEvent::TextDelta {
+                text: text.to_string(),
+            });
+        }
+    }
+
+    fn end_reasoning(&self, state: &mut StreamParseState) {
+        if state.reasoning_started {
+            if let Some(id) = state.reasoning_id.clone() {
+                state.pending_events.push(StreamEvent::ReasoningEnd { id });
+            }
+            state.reasoning_started = false;
+        }
+    }
+
+    /// Map Gemini finish reasons onto the OpenAI-style names the agent loop uses
+    fn map_finish_reason(&self, reason: &str, has_tool_calls: bool) -> String {
+        match reason {
+            "STOP" if has_tool_calls => "tool_calls".to_string(),
+            "STOP" => "stop".to_string(),
+            "MAX_TOKENS" => "length".to_string(),
+            "SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII"
+            | "IMAGE_SAFETY" => "content_filter".to_string(),
+            other => other.to_lowercase(),
+        }
+    }
+}
+
+fn thought_signature(metadata: Option<&Value>) -> Option<&Value> {
+    metadata?
+        .get("google")
+        .and_then(|google| google.get("thoughtSignature"))
+}
+
+fn google_signature_metadata(signature: String) -> Value {
+    json!({ "google": { "thoughtSignature": signature } })
+}
+
+/// Strip JSON Schema keywords that Gemini's OpenAPI-subset schema rejects
+fn sanitize_schema(schema: &Value) -> Value {
+    match schema {
+        Value::Object(map) => {
+            let cleaned: Map<String, Value> = map
+                .iter()
+                .filter(|(key, _)| !UNSUPPORTED_SCHEMA_KEYS.contains(&key.as_str()))
+                .map(|(key, value)| (key.clone(), sanitize_schema(value)))
+                .collect();
+            Value::Object(cleaned)
+        }
+        Value::Array(items) => Value::Array(items.iter().map(sanitize_schema).collect()),
+        other => other.clone(),
+    }
+}
+
+// ============================================================================
+// Modular Trait Implementations
+// ============================================================================
+
+impl ProtocolRequestBuilder for GeminiProtocol {
+    fn build_request(&self, ctx: RequestBuildContext) -> Result<Value, String> {
+        let mut body = json!({
+            "contents": self.build_contents(ctx.messages),
+        });
+
+        if let Some(system) = self.build_system_instruction(ctx.messages) {
+            body["systemInstruction"] = system;
+        }
+        if let Some(tools) = self.build_tools(ctx.tools) {
+            body["tools"] = tools;
+        }
+
+        let mut generation_config = Map::new();
+        if let Some(temperature) = ctx.temperature {
+            generation_config.insert("temperature".to_string(), json!(temperature));
+        }
+        if let Some(max_tokens) = ctx.max_tokens {
+            generation_config.insert("maxOutputTokens".to_string(), json!(max_tokens));
+        }
+        if let Some(top_p) = ctx.top_p {
+            generation_config.insert("topP".to_string(), json!(top_p));
+        }
+        if let Some(top_k) = ctx.top_k {
+            generation_config.insert("topK".to_string(), json!(top_k));
+        }
+        if let Some(format) = ctx.response_format {
+            generation_config.insert("responseMimeType".to_string(), json!("application/json"));
+            generation_config.insert(
+                "responseSchema".to_string(),
+                sanitize_schema(&format.schema),
+            );
+        }
+        if let Some(google) = ctx.provider_options.and_then(|o| o.get("google")) {
+            if let Some(thinking) = google.get("thinkingConfig") {
+                generation_config.insert("thinkingConfig".to_string(), thinking.clone());
+            }
+            if let Some(safety) = google.get("safetySettings") {
+                body["safetySettings"] = safety.clone();
+            }
+        }
+        if !generation_config.is_empty() {
+            body["generationConfig"] = Value::Object(generation_config);
+        }
+
+        if let Some(extra) = ctx.extra_body {
+            if let Some(obj) = body.as_object_mut() {
+                if let Some(extra_obj) = extra.as_object() {
+                    for (k, v) in extra_obj {
+                        obj.insert(k.to_string(), v.clone());
+                    }
+                }
+            }
+        }
+
+        Ok(body)
+    }
+}
+
+impl ProtocolStreamParser for GeminiProtocol {
+    fn parse_stream_event(
+        &self,
+        ctx: StreamParseContext,
+        state: &mut StreamParseState,
+    ) -> Result<Option<StreamEvent>, String> {
+        let payload: Value = serde_json::from_str(ctx.data).map_err(|e| e.to_string())?;
+
+        if let Some(error) = payload.get("error") {
+            let message = error
+                .get("message")
+                .and_then(|v| v.as_str())
+                .map(String::from)
+                .unwrap_or_else(|| error.to_string());
+            return Ok(Some(StreamEvent::Error { message }));
+        }
+
+        let candidate = payload
+            .get("candidates")
+            .and_then(|v| v.as_array())
+            .and_then(|arr| arr.first());
+
+        if candidate.is_none() {
+            if let Some(reason) = payload
+                .get("promptFeedback")
+                .and_then(|f| f.get("blockReason"))
+                .and_then(|v| v.as_str())
+            {
+                return Ok(Some(StreamEvent::Error {
+                    message: format!("Prompt blocked by Gemini: {}", reason),
+                }));
+            }
+        }
+
+        if let Some(parts) = candidate
+            .and_then(|c| c.get("content"))
+            .and_then(|c| c.get("parts"))
+            .and_then(|v| v.as_array())
+        {
+            for part in parts {
+                self.handle_part(part, state);
+            }
+        }
+
+        let finish_reason = candidate
+            .and_then(|c| c.get("finishReason"))
+            .and_then(|v| v.as_str());
+        if let Some(reason) = finish_reason {
+            self.end_reasoning(state);
+            state.finish_reason =
+                Some(self.map_finish_reason(reason, !state.tool_call_order.is_empty()));
+
+            // Grounding results have no dedicated event; pass them through untouched
+            if let Some(grounding) = candidate.and_then(|c| c.get("groundingMetadata")) {
+                state.pending_events.push(StreamEvent::Raw {
+                    raw_value: json!({ "google": { "groundingMetadata": grounding } }).to_string(),
+                });
+            }
+
+            // Usage is cumulative across chunks, so only the final one is reported
+            if let Some(usage) = payload.get("usageMetadata") {
+                let count = |key: &str| usage.get(key).and_then(|v| v.as_i64());
+                state.pending_events.push(StreamEvent::Usage {
+                    input_tokens: count("promptTokenCount").unwrap_or(0) as i32,
+                    output_tokens: (count("candidatesTokenCount").unwrap_or(0)
+                        + count("thoughtsTokenCount").unwrap_or(0))
+                        as i32,
+                    total_tokens: count("totalTokenCount").map(|v| v as i32),
+                    cached_input_tokens: count("cachedContentTokenCount").map(|v| v as i32),
+                    cache_creation_input_tokens: None,
+                });
+            }
+
+            state.pending_events.push(StreamEvent::Done {
+                finish_reason: state.finish_reason.clone(),
+            });
+        }
+
+        if state.pending_events.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(state.pending_events.remove(0)))
+    }
+}
+
+impl ProtocolHeaderBuilder for GeminiProtocol {
+    fn build_base_headers(&self, ctx: HeaderBuildContext) -> HashMap<String, String> {
+        let mut headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), "application/json".to_string());
+        if let Some(key) = ctx.api_key {
+            headers.insert("x-goog-api-key".to_string(), key.to_string());
+        } else if let Some(token) = ctx.oauth_token {
+            headers.insert("Authorization".to_string(), format!("Bearer {}", token));
+        }
+        if let Some(extra) = ctx.extra_headers {
+            for (k, v) in extra {
+                headers.insert(k.to_string(), v.to_string());
+            }
+        }
+        headers
+    }
+}
+
+// ============================================================================
+// Legacy Trait Implementation (delegates to modular traits)
+// ============================================================================
+
+impl LlmProtocol for GeminiProtocol {
+    fn name(&self) -> &str {
+        "gemini"
+    }
+
+    fn endpoint_path(&self) -> &'static str {
+        "streamGenerateContent"
+    }
+
+    fn build_request(
+        &self,
+        model: &str,
+        messages: &[Message],
+        tools: Option<&[ToolDefinition]>,
+        temperature: Option<f32>,
+        max_tokens: Option<i32>,
+        top_p: Option<f32>,
+        top_k: Option<i32>,
+        provider_options: Option<&Value>,
+        extra_body: Option<&Value>,
+    ) -> Result<Value, String> {
+        let ctx = RequestBuildContext {
+            model,
+            messages,
+            tools,
+            temperature,
+            max_tokens,
+            top_p,
+            top_k,
+            provider_options,
+            extra_body,
+            response_format: None,
+        };
+        ProtocolRequestBuilder::build_request(self, ctx)
+    }
+
+    fn parse_stream_event(
+        &self,
+        event_type: Option<&str>,
+        data: &str,
+        state: &mut ProtocolStreamState,
+    ) -> Result<Option<StreamEvent>, String> {
+        let ctx = StreamParseContext { event_type, data };
+        let mut new_state = stream_parser::StreamParseState {
+            finish_reason: state.finish_reason.clone(),
+            text_started: state.text_started,
+            reasoning_started: state.reasoning_started,
+            reasoning_id: state.reasoning_id.clone(),
+            pending_events: std::mem::take(&mut state.pending_events),
+            tool_calls: std::mem::take(&mut state.tool_calls),
+            tool_call_order: std::mem::take(&mut state.tool_call_order),
+            emitted_tool_calls: std::mem::take(&mut state.emitted_tool_calls),
+            tool_call_index_map: std::mem::take(&mut state.tool_call_index_map),
+            content_block_types: std::mem::take(&mut state.content_block_types),
+            content_block_ids: std::mem::take(&mut state.content_block_ids),
+            current_thinking_id: state.current_thinking_id.clone(),
+            openai_reasoning: std::mem::take(&mut state.openai_reasoning),
+            openai_store: state.openai_store,
+        };
+
+        let result = ProtocolStreamParser::parse_stream_event(self, ctx, &mut new_state);
+
+        // Sync state back
+        state.finish_reason = new_state.finish_reason;
+        state.text_started = new_state.text_started;
+        state.reasoning_started = new_state.reasoning_started;
+        state.reasoning_id = new_state.reasoning_id;
+        state.pending_events = new_state.pending_events;
+        state.tool_calls = new_state.tool_calls;
+        state.tool_call_order = new_state.tool_call_order;
+        state.emitted_tool_calls = new_state.emitted_tool_calls;
+        state.tool_call_index_map = new_state.tool_call_index_map;
+        state.content_block_types = new_state.content_block_types;
+        state.content_block_ids = new_state.content_block_ids;
+        state.current_thinking_id = new_state.current_thinking_id;
+        state.openai_reasoning = new_state.openai_reasoning;
+        state.openai_store = new_state.openai_store;
+
+        result
+    }
+
+    fn build_headers(
+        &self,
+        api_key: Option<&str>,
+        oauth_token: Option<&str>,
+        extra_headers: Option<&HashMap<String, String>>,
+    ) -> HashMap<String, String> {
+        let ctx = HeaderBuildContext {
+            api_key,
+            oauth_token,
+            extra_headers,
+        };
+        ProtocolHeaderBuilder::build_base_headers(self, ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::types::ResponseFormat;
+
+    fn parse_all(protocol: &GeminiProtocol, chunks: &[Value]) -> Vec<StreamEvent> {
+        let mut state = StreamParseState::default();
+        let mut events = Vec::new();
+        for chunk in chunks {
+            let data = chunk.to_string();
+            let ctx = StreamParseContext {
+                event_type: None,
+                data: &data,
+            };
+            if let Some(event) =
+                ProtocolStreamParser::parse_stream_event(protocol, ctx, &mut state).unwrap()
+            {
+                events.push(event);
+            }
+            events.append(&mut state.pending_events);
+        }
+        events
+    }
+
+    #[test]
+    fn build_request_maps_roles_tools_and_signatures() {
+        let messages = vec![
+            Message::System {
+                content: "Be brief.".to_string(),
+                provider_options: None,
+            },
+            Message::User {
+                content: MessageContent::Parts(vec![
+                    ContentPart::Text {
+                        text: "What is in this image?".to_string(),
+                    },
+                    ContentPart::Image {
+                        image: "aGVsbG8=".to_string(),
+                        mime_type: None,
+                    },
+                ]),
+                provider_options: None,
+            },
+            Message::Assistant {
+                content: MessageContent::Parts(vec![ContentPart::ToolCall {
+                    tool_call_id: "call_1".to_string(),
+                    tool_name: "readFile".to_string(),
+                    input: json!({ "file_path": "a.txt" }),
+                    provider_metadata: Some(json!({ "google": { "thoughtSignature": "sig-1" } })),
+                }]),
+                provider_options: None,
+            },
+            Message::Tool {
+                content: vec![ContentPart::ToolResult {
+                    tool_call_id: "call_1".to_string(),
+                    tool_name: "readFile".to_string(),
+                    output: json!({ "type": "text", "value": "hello" }),
+                }],
+                provider_options: None,
+            },
+        ];
+        let tools = vec![ToolDefinition {
+            tool_type: "function".to_string(),
+            name: "readFile".to_string(),
+            description: Some("Read a file".to_string()),
+            parameters: json!({
+                "$schema": "http://json-schema.org/draft-07/schema#",
+                "type": "object",
+                "properties": { "file_path": { "type": "string" } },
+                "additionalProperties": false
+            }),
+            strict: false,
+        }];
+
+        let body = ProtocolRequestBuilder::build_request(
+            &GeminiProtocol,
+            RequestBuildContext {
+                model: "gemini-2.5-flash",
+                messages: &messages,
+                tools: Some(&tools),
+                temperature: None,
+                max_tokens: Some(1024),
+                top_p: None,
+                top_k: Some(40),
+                provider_options: Some(&json!({
+                    "google": { "thinkingConfig": { "includeThoughts": true } }
+                })),
+                extra_body: None,
+                response_format: None,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            body["systemInstruction"],
+            json!({ "parts": [{ "text": "Be brief." }] })
+        );
+        let contents = body["contents"].as_array().unwrap();
+        assert_eq!(contents.len(), 3);
+        assert_eq!(
+            contents[0]["parts"][1],
+            json!({ "inlineData": { "mimeType": "image/png", "data": "aGVsbG8=" } })
+        );
+        assert_eq!(contents[1]["role"], "model");
+        assert_eq!(contents[1]["parts"][0]["thoughtSignature"], "sig-1");
+        assert_eq!(
+            contents[2]["parts"][0]["functionResponse"],
+            json!({ "name": "readFile", "response": { "content": "hello" } })
+        );
+        assert_eq!(
+            body["tools"][0]["functionDeclarations"][0]["parameters"],
+            json!({ "type": "object", "properties": { "file_path": { "type": "string" } } })
+        );
+        assert_eq!(body["generationConfig"]["maxOutputTokens"], 1024);
+        assert_eq!(body["generationConfig"]["topK"], 40);
+        assert_eq!(
+            body["generationConfig"]["thinkingConfig"],
+            json!({ "includeThoughts": true })
+        );
+    }
+
+    #[test]
+    fn parse_stream_emits_tool_call_with_signature_and_done() {
+        let events = parse_all(
+            &GeminiProtocol,
+            &[json!({
+                "candidates": [{
+                    "content": {
+                        "role": "model",
+                        "parts": [{
+                            "functionCall": { "name": "glob", "args": { "pattern": "*.rs" } },
+                            "thoughtSignature": "sig-2"
+                        }]
+                    },
+                    "finishReason": "STOP"
+                }],
+                "usageMetadata": {
+                    "promptTokenCount": 10,
+                    "candidatesTokenCount": 5,
+                    "thoughtsTokenCount": 3,
+                    "totalTokenCount": 18
+                }
+            })],
+        );
+
+        match &events[0] {
+            StreamEvent::ToolCall {
+                tool_call_id,
+                tool_name,
+                input,
+                provider_metadata,
+            } => {
+                assert!(tool_call_id.starts_with(GEMINI_TOOL_CALL_ID_PREFIX));
+                assert_eq!(tool_name, "glob");
+                assert_eq!(input, &json!({ "pattern": "*.rs" }));
+                assert_eq!(
+                    provider_metadata,
+                    &Some(json!({ "google": { "thoughtSignature": "sig-2" } }))
+                );
+            }
+            other => panic!("Expected tool call, got {:?}", other),
+        }
+        assert!(matches!(
+            events[1],
+            StreamEvent::Usage {
+                input_tokens: 10,
+                output_tokens: 8,
+                total_tokens: Some(18),
+                ..
+            }
+        ));
+        match &events[2] {
+            StreamEvent::Done { finish_reason } => {
+                assert_eq!(finish_reason.as_deref(), Some("tool_calls"))
+            }
+            other => panic!("Expected done, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn build_headers_uses_goog_api_key() {
+        let headers = LlmProtocol::build_headers(&GeminiProtocol, Some("key"), None, None);
+        assert_eq!(headers.get("x-goog-api-key"), Some(&"key".to_string()));
+        assert!(!headers.contains_key("Authorization"));
+        assert_eq!(
+            GeminiProtocol::stream_endpoint_path("models/gemini-2.5-flash"),
+            "models/gemini-2.5-flash:streamGenerateContent?alt=sse"
+        );
+    }
+
+    #[test]
+    fn build_request_sets_response_schema() {
+        let messages = vec![Message::User {
+            content: MessageContent::Text("title?".to_string()),
+            provider_options: None,
+        }];
+        let format = ResponseFormat {
+            name: "task_title".to_string(),
+            description: None,
+            schema: json!({
+                "$schema": "http://json-schema.org/draft-07/schema#",
+                "type": "object",
+                "properties": { "title": { "type": "string" } },
+                "additionalProperties": false
+            }),
+            strict: true,
+        };
+
+        let body = ProtocolRequestBuilder::build_request(
+            &GeminiProtocol,
+            RequestBuildContext {
+                model: "gemini-2.5-flash",
+                messages: &messages,
+                tools: None,
+                temperature: None,
+                max_tokens: None,
+                top_p: None,
+                top_k: None,
+                provider_options: None,
+                extra_body: None,
+                response_format: Some(&format),
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            body["generationConfig"]["responseMimeType"],
+            "application/json"
+        );
+        assert_eq!(
+            body["generationConfig"]["responseSchema"],
+            json!({ "type": "object", "properties": { "title": { "type": "string" } } })
+        );
+    }
+}
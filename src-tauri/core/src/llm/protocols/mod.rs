@@ -93,5 +93,6 @@ pub struct ToolCallAccum {
 }
 
 pub mod claude_protocol;
+pub mod gemini_protocol;
 pub mod openai_protocol;
 pub mod openai_responses_protocol;
@@ -67,10 +67,11 @@ impl OpenAiProtocol {
                         ContentPart::Text { text } => {
                             mapped.push(json!({ "type": "text", "text": text }));
                         }
-                        ContentPart::Image { image } => {
+                        ContentPart::Image { image, mime_type } => {
+                            let mime = mime_type.as_deref().unwrap_or("image/png");
                             mapped.push(json!({
                                 "type": "image_url",
-                                "image_url": { "url": format!("data:image/png;base64,{}", image) }
+                                "image_url": { "url": format!("data:{};base64,{}", mime, image) }
                             }));
                         }
                         ContentPart::Video { video, mime_type } => {
@@ -114,6 +115,7 @@ impl OpenAiProtocol {
         provider_options: Option<&Value>,
     ) -> Value {
         let mut content_value = Value::Null;
+        let mut reasoning_chunks: Vec<&str> = Vec::new();
 
         match content {
             MessageContent::Text(text) => {
@@ -136,15 +138,15 @@ impl OpenAiProtocol {
                         }
                         ContentPart::Reasoning { text, .. } => {
                             if !text.trim().is_empty() {
-                                text_chunks.push(text.clone());
-                                rich_parts.push(json!({ "type": "text", "text": text }));
+                                reasoning_chunks.push(text);
                             }
                         }
-                        ContentPart::Image { image } => {
+                        ContentPart::Image { image, mime_type } => {
+                            let mime = mime_type.as_deref().unwrap_or("image/png");
                             has_image = true;
                             rich_parts.push(json!({
                                 "type": "image_url",
-                                "image_url": { "url": format!("data:image/png;base64,{}", image) }
+                                "image_url": { "url": format!("data:{};base64,{}", mime, image) }
                             }));
                         }
                         ContentPart::Video { video, mime_type } => {
@@ -215,6 +217,12 @@ impl OpenAiProtocol {
             }
         }
 
+        // Reasoning models served over chat completions (DeepSeek, Kimi, MiniMax) expect
+        // their reasoning back alongside tool calls
+        if !reasoning_chunks.is_empty() {
+            message["reasoning_content"] = json!(reasoning_chunks.join(""));
+        }
+
         if let Some(options) = provider_options {
             if let Some(openai_compat) = options.get("openaiCompatible") {
                 if let Some(reasoning_content) = openai_compat.get("reasoning_content") {
@@ -296,6 +304,10 @@ impl OpenAiProtocol {
                 .and_then(|v| v.as_str())
                 .unwrap_or_default();
             let args_value = function.and_then(|f| f.get("arguments"));
+            let started = state
+                .tool_calls
+                .get(&key)
+                .is_some_and(|acc| !acc.tool_name.is_empty());
 
             let acc = state
                 .tool_calls
@@ -317,10 +329,20 @@ impl OpenAiProtocol {
             if !name.is_empty() {
                 acc.tool_name = name.to_string();
             }
+            if !started && !acc.tool_name.is_empty() {
+                state.pending_events.push(StreamEvent::ToolCallStart {
+                    tool_call_id: acc.tool_call_id.clone(),
+                    tool_name: acc.tool_name.clone(),
+                });
+            }
             if let Some(args_val) = args_value {
                 if let Some(args_str) = args_val.as_str() {
                     if !args_str.is_empty() {
                         acc.arguments.push_str(args_str);
+                        state.pending_events.push(StreamEvent::ToolCallDelta {
+                            tool_call_id: acc.tool_call_id.clone(),
+                            arguments_delta: args_str.to_string(),
+                        });
                     }
                 } else if acc.arguments.is_empty() {
                     acc.arguments = args_val.to_string();
@@ -421,12 +443,29 @@ impl ProtocolRequestBuilder for OpenAiProtocol {
         if let Some(top_k) = ctx.top_k {
             body["top_k"] = json!(top_k);
         }
+        if let Some(format) = ctx.response_format {
+            let mut json_schema = json!({
+                "name": format.name,
+                "schema": format.schema,
+                "strict": format.strict
+            });
+            if let Some(description) = &format.description {
+                json_schema["description"] = json!(description);
+            }
+            body["response_format"] = json!({
+                "type": "json_schema",
+                "json_schema": json_schema
+            });
+        }
 
         if let Some(options) = ctx.provider_options {
             if let Some(openai_opts) = options.get("openai") {
                 if let Some(reasoning) = openai_opts.get("reasoningEffort") {
                     body["reasoning_effort"] = reasoning.clone();
                 }
+                if let Some(cache_key) = openai_opts.get("promptCacheKey") {
+                    body["prompt_cache_key"] = cache_key.clone();
+                }
             }
             if let Some(openrouter_opts) = options.get("openrouter") {
                 if let Some(effort) = openrouter_opts.get("effort") {
@@ -498,6 +537,12 @@ impl ProtocolStreamParser for OpenAiProtocol {
                 .and_then(|v| v.as_i64())
                 .unwrap_or(0);
             let total_tokens = usage.get("total_tokens").and_then(|v| v.as_i64());
+            // Moonshot reports cached tokens at the top level of usage
+            let cached_input_tokens = usage
+                .get("prompt_tokens_details")
+                .and_then(|v| v.get("cached_tokens"))
+                .or_else(|| usage.get("cached_tokens"))
+                .and_then(|v| v.as_i64());
 
             let has_meaningful_data =
                 input_tokens > 0 || output_tokens > 0 || total_tokens.is_some_and(|v| v > 0);
@@ -507,7 +552,7 @@ impl ProtocolStreamParser for OpenAiProtocol {
                     input_tokens: input_tokens as i32,
                     output_tokens: output_tokens as i32,
                     total_tokens: total_tokens.map(|v| v as i32),
-                    cached_input_tokens: None,
+                    cached_input_tokens: cached_input_tokens.map(|v| v as i32),
                     cache_creation_input_tokens: None,
                 });
             }
@@ -640,6 +685,7 @@ impl LlmProtocol for OpenAiProtocol {
             top_k,
             provider_options,
             extra_body,
+            response_format: None,
         };
         ProtocolRequestBuilder::build_request(self, ctx)
     }
@@ -708,6 +754,7 @@ impl LlmProtocol for OpenAiProtocol {
 mod tests {
     use super::*;
     use crate::llm::protocols::ProtocolStreamState;
+    use crate::llm::types::ResponseFormat;
     use serde_json::json;
     use std::collections::HashMap;
 
@@ -1135,7 +1182,7 @@ mod tests {
             None,
             None,
             Some(&json!({
-                "openai": { "reasoningEffort": "medium" },
+                "openai": { "reasoningEffort": "medium", "promptCacheKey": "sess-1" },
                 "openrouter": { "effort": "low" }
             })),
             Some(&json!({ "extra_param": true })),
@@ -1143,6 +1190,7 @@ mod tests {
         .expect("build request");
 
         assert_eq!(body.get("reasoning_effort"), Some(&json!("medium")));
+        assert_eq!(body.get("prompt_cache_key"), Some(&json!("sess-1")));
         assert!(body.get("reasoning").is_none());
         assert_eq!(body.get("extra_param"), Some(&json!(true)));
         assert_eq!(body.get("max_tokens"), Some(&json!(120)));
@@ -1206,15 +1254,33 @@ mod tests {
             "choices": [{ "finish_reason": "tool_calls", "delta": {} }]
         });
 
-        let _ = LlmProtocol::parse_stream_event(&protocol, None, &first.to_string(), &mut state)
-            .expect("parse first");
-        let _ = LlmProtocol::parse_stream_event(&protocol, None, &second.to_string(), &mut state)
-            .expect("parse second");
+        let mut events: Vec<StreamEvent> = Vec::new();
         state.text_started = true;
-        let event = LlmProtocol::parse_stream_event(&protocol, None, &done.to_string(), &mut state)
-            .expect("parse done")
-            .expect("event");
+        for chunk in [first, second, done] {
+            let parsed =
+                LlmProtocol::parse_stream_event(&protocol, None, &chunk.to_string(), &mut state)
+                    .expect("parse");
+            events.extend(parsed);
+            events.append(&mut state.pending_events);
+        }
 
+        assert!(matches!(
+            &events[0],
+            StreamEvent::ToolCallStart { tool_call_id, tool_name }
+                if tool_call_id == "call_1" && tool_name == "readFile"
+        ));
+        let deltas: String = events
+            .iter()
+            .filter_map(|event| match event {
+                StreamEvent::ToolCallDelta {
+                    arguments_delta, ..
+                } => Some(arguments_delta.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(deltas, "{\"path\":\"/tmp\",\"pattern\":\"**/*.rs\"}");
+
+        let event = events.pop().expect("event");
         match event {
             StreamEvent::ToolCall {
                 tool_call_id,
@@ -1427,4 +1493,72 @@ mod tests {
             ),
         }
     }
+
+    #[test]
+    fn build_request_maps_response_format_to_json_schema() {
+        let messages = vec![Message::User {
+            content: MessageContent::Text("title?".to_string()),
+            provider_options: None,
+        }];
+        let format = ResponseFormat {
+            name: "task_title".to_string(),
+            description: None,
+            schema: json!({ "type": "object", "required": ["title"] }),
+            strict: true,
+        };
+
+        let body = ProtocolRequestBuilder::build_request(
+            &OpenAiProtocol,
+            RequestBuildContext {
+                model: "gpt-4o",
+                messages: &messages,
+                tools: None,
+                temperature: None,
+                max_tokens: None,
+                top_p: None,
+                top_k: None,
+                provider_options: None,
+                extra_body: None,
+                response_format: Some(&format),
+            },
+        )
+        .expect("build request");
+
+        assert_eq!(
+            body["response_format"],
+            json!({
+                "type": "json_schema",
+                "json_schema": {
+                    "name": "task_title",
+                    "schema": { "type": "object", "required": ["title"] },
+                    "strict": true
+                }
+            })
+        );
+
+        let described = ResponseFormat {
+            description: Some("A short task title".to_string()),
+            ..format
+        };
+        let body = ProtocolRequestBuilder::build_request(
+            &OpenAiProtocol,
+            RequestBuildContext {
+                model: "gpt-4o",
+                messages: &messages,
+                tools: None,
+                temperature: None,
+                max_tokens: None,
+                top_p: None,
+                top_k: None,
+                provider_options: None,
+                extra_body: None,
+                response_format: Some(&described),
+            },
+        )
+        .expect("build request");
+        assert_eq!(
+            body["response_format"]["json_schema"]["description"],
+            "A short task title"
+        );
+    }
 }
@@ -6,6 +6,7 @@ use crate::llm::protocols::{
 };
 use crate::llm::types::{ContentPart, Message, MessageContent, StreamEvent, ToolDefinition};
 use serde_json::{json, Value};
+use std::collections::HashMap;
 
 pub struct OpenAiResponsesProtocol;
 
@@ -52,10 +53,11 @@ impl OpenAiResponsesProtocol {
                                 mapped.push(json!({ "type": "input_text", "text": text }));
                             }
                         }
-                        ContentPart::Image { image } => {
+                        ContentPart::Image { image, mime_type } => {
+                            let mime = mime_type.as_deref().unwrap_or("image/png");
                             mapped.push(json!({
                                 "type": "input_image",
-                                "image_url": format!("data:image/png;base64,{}", image)
+                                "image_url": format!("data:{};base64,{}", mime, image)
                             }));
                         }
                         _ => {}
@@ -69,6 +71,8 @@ impl OpenAiResponsesProtocol {
     fn append_assistant_items(content: &MessageContent, input_items: &mut Vec<Value>) {
         if let MessageContent::Parts(parts) = content {
             let mut pending_parts: Vec<Value> = Vec::new();
+            // Summary parts of one reasoning item arrive as separate blocks
+            let mut reasoning_items: HashMap<String, usize> = HashMap::new();
 
             for part in parts {
                 match part {
@@ -77,15 +81,52 @@ impl OpenAiResponsesProtocol {
                             pending_parts.push(json!({ "type": "output_text", "text": text }));
                         }
                     }
-                    ContentPart::Reasoning { text, .. } => {
+                    ContentPart::Reasoning {
+                        text,
+                        provider_options,
+                    } => {
+                        let Some(openai) = provider_options.as_ref().and_then(|o| o.get("openai"))
+                        else {
+                            continue;
+                        };
+                        let item_id = openai
+                            .get("itemId")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default()
+                            .to_string();
+                        let encrypted_content = openai
+                            .get("reasoningEncryptedContent")
+                            .filter(|v| v.is_string());
+                        let index = match reasoning_items.get(&item_id) {
+                            Some(index) => *index,
+                            None => {
+                                if !pending_parts.is_empty() {
+                                    input_items.push(json!({
+                                        "type": "message",
+                                        "role": "assistant",
+                                        "content": std::mem::take(&mut pending_parts)
+                                    }));
+                                }
+                                input_items.push(json!({ "type": "reasoning", "summary": [] }));
+                                reasoning_items.insert(item_id, input_items.len() - 1);
+                                input_items.len() - 1
+                            }
+                        };
+                        let item = &mut input_items[index];
                         if !text.trim().is_empty() {
-                            pending_parts.push(json!({ "type": "output_text", "text": text }));
+                            if let Some(summary) = item["summary"].as_array_mut() {
+                                summary.push(json!({ "type": "summary_text", "text": text }));
+                            }
+                        }
+                        if let Some(encrypted_content) = encrypted_content {
+                            item["encrypted_content"] = encrypted_content.clone();
                         }
                     }
-                    ContentPart::Image { image } => {
+                    ContentPart::Image { image, mime_type } => {
+                        let mime = mime_type.as_deref().unwrap_or("image/png");
                         pending_parts.push(json!({
                             "type": "input_image",
-                            "image_url": format!("data:image/png;base64,{}", image)
+                            "image_url": format!("data:{};base64,{}", mime, image)
                         }));
                     }
                     ContentPart::ToolCall {
@@ -135,6 +176,16 @@ impl OpenAiResponsesProtocol {
                     "content": pending_parts
                 }));
             }
+
+            // Requests are sent with `store: false`, so reasoning can only be replayed
+            // through its encrypted content
+            let mut index = 0;
+            input_items.retain(|item| {
+                let keep = !reasoning_items.values().any(|i| *i == index)
+                    || item.get("encrypted_content").is_some();
+                index += 1;
+                keep
+            });
         }
     }
 }
@@ -220,6 +271,17 @@ impl ProtocolRequestBuilder for OpenAiResponsesProtocol {
         if let Some(top_k) = ctx.top_k {
             body["top_k"] = json!(top_k);
         }
+        if let Some(format) = ctx.response_format {
+            body["text"]["format"] = json!({
+                "type": "json_schema",
+                "name": format.name,
+                "schema": format.schema,
+                "strict": format.strict
+            });
+            if let Some(description) = &format.description {
+                body["text"]["format"]["description"] = json!(description);
+            }
+        }
         if let Some(provider_options) = ctx.provider_options {
             if let Some(openai_opts) = provider_options.get("openai") {
                 if let Some(reasoning_effort) = openai_opts.get("reasoningEffort") {
@@ -229,6 +291,9 @@ impl ProtocolRequestBuilder for OpenAiResponsesProtocol {
                         }
                     }
                 }
+                if let Some(cache_key) = openai_opts.get("promptCacheKey") {
+                    body["prompt_cache_key"] = cache_key.clone();
+                }
             }
             if let Some(openrouter_opts) = provider_options.get("openrouter") {
                 if let Some(effort) = openrouter_opts.get("effort") {
@@ -365,11 +430,17 @@ pub(crate) fn parse_openai_oauth_event_legacy(
                 .and_then(|v| v.as_i64())
                 .unwrap_or(0);
             let total_tokens = usage.get("total_tokens").and_then(|v| v.as_i64());
+            // Moonshot reports cached tokens at the top level of usage
+            let cached_input_tokens = usage
+                .get("prompt_tokens_details")
+                .and_then(|v| v.get("cached_tokens"))
+                .or_else(|| usage.get("cached_tokens"))
+                .and_then(|v| v.as_i64());
             state.pending_events.push(StreamEvent::Usage {
                 input_tokens: input_tokens as i32,
                 output_tokens: output_tokens as i32,
                 total_tokens: total_tokens.map(|v| v as i32),
-                cached_input_tokens: None,
+                cached_input_tokens: cached_input_tokens.map(|v| v as i32),
                 cache_creation_input_tokens: None,
             });
         }
@@ -490,6 +561,12 @@ pub(crate) fn parse_openai_oauth_event_legacy(
                             if !name.is_empty() {
                                 acc.tool_name = name;
                             }
+                            if !acc.tool_name.is_empty() {
+                                state.pending_events.push(StreamEvent::ToolCallStart {
+                                    tool_call_id: acc.tool_call_id.clone(),
+                                    tool_name: acc.tool_name.clone(),
+                                });
+                            }
                             let index = item
                                 .get("index")
                                 .and_then(|v| v.as_u64())
@@ -905,11 +982,15 @@ pub(crate) fn parse_openai_oauth_event_legacy(
                         .and_then(|v| v.as_i64())
                         .unwrap_or(0);
                     let total_tokens = usage.get("total_tokens").and_then(|v| v.as_i64());
+                    let cached_input_tokens = usage
+                        .get("input_tokens_details")
+                        .and_then(|v| v.get("cached_tokens"))
+                        .and_then(|v| v.as_i64());
                     state.pending_events.push(StreamEvent::Usage {
                         input_tokens: input_tokens as i32,
                         output_tokens: output_tokens as i32,
                         total_tokens: total_tokens.map(|v| v as i32),
-                        cached_input_tokens: None,
+                        cached_input_tokens: cached_input_tokens.map(|v| v as i32),
                         cache_creation_input_tokens: None,
                     });
                 }
@@ -1051,6 +1132,10 @@ fn parse_openai_oauth_function_call_delta(payload: &Value, state: &mut ProtocolS
         });
     if !delta.is_empty() {
         acc.arguments.push_str(delta);
+        state.pending_events.push(StreamEvent::ToolCallDelta {
+            tool_call_id: acc.tool_call_id.clone(),
+            arguments_delta: delta.to_string(),
+        });
     }
     let index = payload
         .get("index")
@@ -1183,6 +1268,7 @@ impl LlmProtocol for OpenAiResponsesProtocol {
             top_k,
             provider_options,
             extra_body,
+            response_format: None,
         };
         ProtocolRequestBuilder::build_request(self, ctx)
     }
@@ -1,6 +1,6 @@
 // Protocol-level request building trait
 // Handles conversion from internal message types to provider-specific API format
-use crate::llm::types::{Message, ToolDefinition};
+use crate::llm::types::{Message, ResponseFormat, ToolDefinition};
 use serde_json::Value;
 
 /// Context for building a request
@@ -15,6 +15,7 @@ pub struct RequestBuildContext<'a> {
     pub top_k: Option<i32>,
     pub provider_options: Option<&'a Value>,
     pub extra_body: Option<&'a Value>,
+    pub response_format: Option<&'a ResponseFormat>,
 }
 
 /// Trait for building protocol-specific requests
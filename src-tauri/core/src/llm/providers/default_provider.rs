@@ -4,8 +4,8 @@
 
 use crate::llm::auth::api_key_manager::ApiKeyManager;
 use crate::llm::protocols::{
-    claude_protocol::ClaudeProtocol, header_builder::HeaderBuildContext,
-    openai_protocol::OpenAiProtocol,
+    claude_protocol::ClaudeProtocol, gemini_protocol::GeminiProtocol,
+    header_builder::HeaderBuildContext, openai_protocol::OpenAiProtocol,
 };
 use crate::llm::providers::provider::{
     BaseProvider, Provider, ProviderContext, ProviderCredentials as Creds,
@@ -59,6 +59,29 @@ impl ProtocolImpl for OpenAiProtocolWrapper {
     }
 }
 
+struct GeminiProtocolWrapper(GeminiProtocol);
+impl ProtocolImpl for GeminiProtocolWrapper {
+    fn build_base_headers(&self, ctx: HeaderBuildContext) -> HashMap<String, String> {
+        use crate::llm::protocols::ProtocolHeaderBuilder;
+        ProtocolHeaderBuilder::build_base_headers(&self.0, ctx)
+    }
+    fn build_request(
+        &self,
+        ctx: crate::llm::protocols::request_builder::RequestBuildContext,
+    ) -> Result<Value, String> {
+        use crate::llm::protocols::ProtocolRequestBuilder;
+        ProtocolRequestBuilder::build_request(&self.0, ctx)
+    }
+    fn parse_stream_event(
+        &self,
+        ctx: crate::llm::protocols::stream_parser::StreamParseContext,
+        state: &mut crate::llm::protocols::stream_parser::StreamParseState,
+    ) -> Result<Option<crate::llm::types::StreamEvent>, String> {
+        use crate::llm::protocols::ProtocolStreamParser;
+        ProtocolStreamParser::parse_stream_event(&self.0, ctx, state)
+    }
+}
+
 struct ClaudeProtocolWrapper(ClaudeProtocol);
 impl ProtocolImpl for ClaudeProtocolWrapper {
     fn build_base_headers(&self, ctx: HeaderBuildContext) -> HashMap<String, String> {
@@ -84,7 +107,7 @@ impl ProtocolImpl for ClaudeProtocolWrapper {
     ) -> Result<Value, String> {
         use crate::llm::protocols::LlmProtocol;
 
-        self.0.build_request(
+        let mut body = self.0.build_request(
             ctx.model,
             ctx.messages,
             ctx.tools,
@@ -94,7 +117,11 @@ impl ProtocolImpl for ClaudeProtocolWrapper {
             ctx.top_k,
             ctx.provider_options,
             ctx.extra_body,
-        )
+        )?;
+        if let Some(format) = ctx.response_format {
+            self.0.apply_response_format(&mut body, format);
+        }
+        Ok(body)
     }
     fn parse_stream_event(
         &self,
@@ -148,6 +175,7 @@ impl DefaultProvider {
         let protocol: Box<dyn ProtocolImpl> = match config.protocol {
             ProtocolType::OpenAiCompatible => Box::new(OpenAiProtocolWrapper(OpenAiProtocol)),
             ProtocolType::Claude => Box::new(ClaudeProtocolWrapper(ClaudeProtocol)),
+            ProtocolType::Gemini => Box::new(GeminiProtocolWrapper(GeminiProtocol)),
         };
 
         Self {
@@ -75,6 +75,7 @@ impl OpenAiProvider {
             top_k: ctx.top_k,
             provider_options: ctx.provider_options,
             extra_body: ctx.provider_config.extra_body.as_ref(),
+            response_format: ctx.response_format,
         };
         self.responses_protocol.build_request(request_ctx)
     }
@@ -182,6 +183,7 @@ impl Provider for OpenAiProvider {
                 top_k: ctx.top_k,
                 provider_options: ctx.provider_options,
                 extra_body: ctx.provider_config.extra_body.as_ref(),
+                response_format: ctx.response_format,
             };
             self.responses_protocol.build_request(request_ctx)
         } else {
@@ -196,6 +198,7 @@ impl Provider for OpenAiProvider {
                 top_k: ctx.top_k,
                 provider_options: ctx.provider_options,
                 extra_body: ctx.provider_config.extra_body.as_ref(),
+                response_format: ctx.response_format,
             };
             let mut body = self.protocol.build_request(request_ctx)?;
             // OpenAI native API requires max_completion_tokens instead of deprecated max_tokens
@@ -316,6 +319,7 @@ mod tests {
             provider_options: None,
             request_id: None,
             trace_context: None,
+            response_format: None,
         };
 
         let ctx = ProviderContext {
@@ -330,6 +334,7 @@ mod tests {
             top_k: request.top_k,
             provider_options: request.provider_options.as_ref(),
             trace_context: request.trace_context.as_ref(),
+            response_format: request.response_format.as_ref(),
         };
 
         let body = provider.build_oauth_request(&ctx).expect("request body");
@@ -608,6 +613,7 @@ mod tests {
             provider_options: None,
             request_id: None,
             trace_context: None,
+            response_format: None,
         };
 
         let ctx = ProviderContext {
@@ -622,6 +628,7 @@ mod tests {
             top_k: request.top_k,
             provider_options: request.provider_options.as_ref(),
             trace_context: request.trace_context.as_ref(),
+            response_format: request.response_format.as_ref(),
         };
 
         let body = provider.build_oauth_request(&ctx).expect("request body");
@@ -3,12 +3,15 @@
 
 use crate::llm::auth::api_key_manager::ApiKeyManager;
 use crate::llm::protocols::{
+    gemini_protocol::GeminiProtocol,
     header_builder::HeaderBuildContext,
     request_builder::RequestBuildContext,
     stream_parser::{StreamParseContext, StreamParseState},
 };
 use crate::llm::types::ProtocolType;
-use crate::llm::types::{Message, ProviderConfig, StreamEvent, ToolDefinition, TraceContext};
+use crate::llm::types::{
+    Message, ProviderConfig, ResponseFormat, StreamEvent, ToolDefinition, TraceContext,
+};
 use async_trait::async_trait;
 use serde_json::Value;
 use std::collections::HashMap;
@@ -28,6 +31,7 @@ pub struct ProviderContext<'a> {
     pub provider_options: Option<&'a Value>,
     #[allow(dead_code)]
     pub trace_context: Option<&'a TraceContext>,
+    pub response_format: Option<&'a ResponseFormat>,
 }
 
 /// Credentials for authentication
@@ -74,11 +78,12 @@ pub trait Provider: Send + Sync {
 
     /// Resolve the endpoint path
     /// Provider can override this for special endpoints (e.g., OpenAI OAuth uses 'codex/responses')
-    async fn resolve_endpoint_path(&self, _ctx: &ProviderContext<'_>) -> String {
+    async fn resolve_endpoint_path(&self, ctx: &ProviderContext<'_>) -> String {
         // Default to protocol's standard endpoint
         match self.protocol_type() {
             ProtocolType::OpenAiCompatible => "chat/completions".to_string(),
             ProtocolType::Claude => "messages".to_string(),
+            ProtocolType::Gemini => GeminiProtocol::stream_endpoint_path(ctx.model),
         }
     }
 
@@ -134,12 +139,13 @@ pub trait Provider: Send + Sync {
     /// Build the request body
     /// Provider can override this for special request formats (e.g., OpenAI OAuth/Codex)
     async fn build_request(&self, ctx: &ProviderContext<'_>) -> Result<Value, String> {
-        // Google OpenAI-compatible endpoint rejects top_k.
-        let drop_top_k = ctx.provider_config.id.eq_ignore_ascii_case("google")
-            || ctx
-                .provider_config
-                .base_url
-                .contains("generativelanguage.googleapis.com");
+        // Google OpenAI-compatible endpoint rejects top_k; the native protocol sends topK.
+        let drop_top_k = ctx.provider_config.protocol == ProtocolType::OpenAiCompatible
+            && (ctx.provider_config.id.eq_ignore_ascii_case("google")
+                || ctx
+                    .provider_config
+                    .base_url
+                    .contains("generativelanguage.googleapis.com"));
         let top_k = if drop_top_k { None } else { ctx.top_k };
         let request_ctx = RequestBuildContext {
             model: ctx.model,
@@ -151,6 +157,7 @@ pub trait Provider: Send + Sync {
             top_k,
             provider_options: ctx.provider_options,
             extra_body: ctx.provider_config.extra_body.as_ref(),
+            response_format: ctx.response_format,
         };
 
         self.build_protocol_request(request_ctx)
@@ -285,7 +285,7 @@ pub fn builtin_providers() -> Vec<ProviderConfig> {
         ProviderConfig {
             id: "google".to_string(),
             name: "Google AI".to_string(),
-            protocol: ProtocolType::OpenAiCompatible,
+            protocol: ProtocolType::Gemini,
             base_url: "https://generativelanguage.googleapis.com/v1beta".to_string(),
             api_key_name: "GOOGLE_API_KEY".to_string(),
             supports_oauth: false,
@@ -295,7 +295,7 @@ pub fn builtin_providers() -> Vec<ProviderConfig> {
             international_base_url: None,
             headers: None,
             extra_body: None,
-            auth_type: AuthType::Bearer,
+            auth_type: AuthType::ApiKey,
         },
         ProviderConfig {
             id: "volcengine".to_string(),
@@ -1,4 +1,7 @@
-use crate::llm::protocols::{claude_protocol::ClaudeProtocol, openai_protocol::OpenAiProtocol};
+use crate::llm::protocols::{
+    claude_protocol::ClaudeProtocol, gemini_protocol::GeminiProtocol,
+    openai_protocol::OpenAiProtocol,
+};
 use crate::llm::providers::{
     DefaultProvider, GithubCopilotProvider, KimiCodingProvider, MoonshotProvider, OpenAiProvider,
     Provider,
@@ -14,6 +17,8 @@ pub struct ProviderRegistry {
     openai_protocol: OpenAiProtocol,
     #[allow(dead_code)]
     claude_protocol: ClaudeProtocol,
+    #[allow(dead_code)]
+    gemini_protocol: GeminiProtocol,
 }
 
 impl std::fmt::Debug for ProviderRegistry {
@@ -30,6 +35,7 @@ impl Clone for ProviderRegistry {
             providers: self.providers.clone(),
             openai_protocol: OpenAiProtocol,
             claude_protocol: ClaudeProtocol,
+            gemini_protocol: GeminiProtocol,
         }
     }
 }
@@ -52,6 +58,7 @@ impl ProviderRegistry {
             providers,
             openai_protocol: OpenAiProtocol,
             claude_protocol: ClaudeProtocol,
+            gemini_protocol: GeminiProtocol,
         }
     }
 
@@ -93,6 +100,7 @@ impl ProviderRegistry {
                 Some(LegacyProtocolAdapter::new(&self.openai_protocol))
             }
             ProtocolType::Claude => Some(LegacyProtocolAdapter::new(&self.claude_protocol)),
+            ProtocolType::Gemini => Some(LegacyProtocolAdapter::new(&self.gemini_protocol)),
         }
     }
 }
@@ -0,0 +1,443 @@
+//! Model fallback chain and provider circuit breaker
+//! Lets StreamHandler fail over to another model/provider when the primary
+//! one is rate limited, overloaded or unreachable before the first token
+
+use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::models::model_registry::ModelRegistry;
+use crate::llm::providers::provider_registry::ProviderRegistry;
+use crate::llm::types::{CustomProvidersConfiguration, ModelsConfiguration};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Model types that can carry a fallback chain (`model_fallback_<type>`)
+pub const FALLBACK_MODEL_TYPES: &[&str] =
+    &["main", "small", "message_compaction", "plan", "code_review"];
+
+const MODEL_TYPE_SETTING_PREFIX: &str = "model_type_";
+const FALLBACK_SETTING_PREFIX: &str = "model_fallback_";
+
+/// Consecutive failures before a provider is taken out of rotation
+const CIRCUIT_FAILURE_THRESHOLD: u32 = 3;
+/// How long an open circuit keeps a provider out of rotation
+const CIRCUIT_COOLDOWN: Duration = Duration::from_secs(60);
+
+static CIRCUIT_BREAKER: OnceLock<ProviderCircuitBreaker> = OnceLock::new();
+
+/// A resolved model/provider pair that can serve a request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FallbackCandidate {
+    pub model_key: String,
+    pub provider_id: String,
+    pub provider_model_name: String,
+}
+
+impl FallbackCandidate {
+    pub fn identifier(&self) -> String {
+        format!("{}@{}", self.model_key, self.provider_id)
+    }
+}
+
+/// Parse a fallback chain setting.
+/// Accepts a JSON array or a list separated by commas, newlines or arrows,
+/// e.g. `claude-sonnet@anthropic → claude-sonnet@openRouter -> glm-4.7@zhipu`.
+pub fn parse_fallback_chain(raw: &str) -> Vec<String> {
+    if let Ok(entries) = serde_json::from_str::<Vec<String>>(raw) {
+        return entries
+            .into_iter()
+            .map(|entry| entry.trim().to_string())
+            .filter(|entry| !entry.is_empty())
+            .collect();
+    }
+
+    raw.replace("->", ",")
+        .replace('→', ",")
+        .split([',', '\n'])
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Load the configured fallback chain for the model type that `model_identifier` belongs to.
+/// A model belongs to a type when it is that type's configured model or appears in its chain.
+pub async fn load_configured_chain(
+    api_keys: &ApiKeyManager,
+    model_identifier: &str,
+) -> Vec<String> {
+    for model_type in FALLBACK_MODEL_TYPES {
+        let raw = match api_keys
+            .get_setting(&format!("{}{}", FALLBACK_SETTING_PREFIX, model_type))
+            .await
+        {
+            Ok(Some(raw)) if !raw.trim().is_empty() => raw,
+            _ => continue,
+        };
+        let chain = parse_fallback_chain(&raw);
+        if chain.iter().any(|entry| entry == model_identifier) {
+            return chain;
+        }
+
+        let configured_model = api_keys
+            .get_setting(&format!("{}{}", MODEL_TYPE_SETTING_PREFIX, model_type))
+            .await
+            .ok()
+            .flatten();
+        if configured_model.as_deref().map(str::trim) == Some(model_identifier) {
+            return chain;
+        }
+    }
+    Vec::new()
+}
+
+/// Build the ordered list of candidates for a request.
+/// The primary resolution comes first, then other providers for the same model
+/// (unless the request pinned a provider with `model@provider`), then the configured chain.
+/// Without a configured chain only the primary is returned, so it keeps its
+/// backoff retries instead of failing over.
+pub fn build_candidates(
+    model_identifier: &str,
+    configured_chain: &[String],
+    api_keys: &HashMap<String, String>,
+    registry: &ProviderRegistry,
+    custom_providers: &CustomProvidersConfiguration,
+    models: &ModelsConfiguration,
+) -> Result<Vec<FallbackCandidate>, String> {
+    let (model_key, provider_id) = ModelRegistry::get_model_provider(
+        model_identifier,
+        api_keys,
+        registry,
+        custom_providers,
+        models,
+    )?;
+
+    let mut pairs = vec![(model_key.clone(), provider_id)];
+    if !configured_chain.is_empty() && !model_identifier.contains('@') {
+        for provider_id in ModelRegistry::get_model_providers(
+            &model_key,
+            api_keys,
+            registry,
+            custom_providers,
+            models,
+        ) {
+            pairs.push((model_key.clone(), provider_id));
+        }
+    }
+    for entry in configured_chain {
+        match ModelRegistry::get_model_provider(entry, api_keys, registry, custom_providers, models)
+        {
+            Ok(pair) => pairs.push(pair),
+            Err(e) => log::warn!("[Fallback] Skipping chain entry {}: {}", entry, e),
+        }
+    }
+
+    let mut candidates: Vec<FallbackCandidate> = Vec::new();
+    for (model_key, provider_id) in pairs {
+        if candidates
+            .iter()
+            .any(|c| c.model_key == model_key && c.provider_id == provider_id)
+        {
+            continue;
+        }
+        let provider_model_name =
+            ModelRegistry::resolve_provider_model_name(&model_key, &provider_id, models);
+        candidates.push(FallbackCandidate {
+            model_key,
+            provider_id,
+            provider_model_name,
+        });
+    }
+    Ok(candidates)
+}
+
+/// Drop candidates whose provider circuit is open.
+/// When every provider is open the full list is kept, so a request is still attempted.
+pub fn filter_open_circuits(
+    candidates: Vec<FallbackCandidate>,
+    breaker: &ProviderCircuitBreaker,
+) -> Vec<FallbackCandidate> {
+    let available: Vec<FallbackCandidate> = candidates
+        .iter()
+        .filter(|c| breaker.is_available(&c.provider_id))
+        .cloned()
+        .collect();
+    if available.is_empty() {
+        candidates
+    } else {
+        available
+    }
+}
+
+/// Whether an HTTP error response should move on to the next candidate
+pub fn is_failover_response(status: u16, body: &str) -> bool {
+    if status == 429 || status >= 500 {
+        return true;
+    }
+    let body = body.to_ascii_lowercase();
+    status >= 400 && (body.contains("overloaded") || body.contains("rate limit"))
+}
+
+/// Whether the first chunk of a 200 response is an overload error event,
+/// as Anthropic-style APIs report overload inside the stream
+pub fn is_overload_chunk(chunk: &[u8]) -> bool {
+    let text = String::from_utf8_lossy(chunk).to_ascii_lowercase();
+    text.contains("error") && text.contains("overloaded")
+}
+
+#[derive(Debug, Default)]
+struct CircuitState {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+}
+
+/// Per-provider circuit breaker shared by all streaming requests
+pub struct ProviderCircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    states: Mutex<HashMap<String, CircuitState>>,
+}
+
+impl ProviderCircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold,
+            cooldown,
+            states: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn global() -> &'static ProviderCircuitBreaker {
+        CIRCUIT_BREAKER.get_or_init(|| {
+            ProviderCircuitBreaker::new(CIRCUIT_FAILURE_THRESHOLD, CIRCUIT_COOLDOWN)
+        })
+    }
+
+    pub fn is_available(&self, provider_id: &str) -> bool {
+        self.is_available_at(provider_id, Instant::now())
+    }
+
+    fn is_available_at(&self, provider_id: &str, now: Instant) -> bool {
+        let states = self.states.lock().unwrap_or_else(|e| e.into_inner());
+        match states.get(provider_id).and_then(|state| state.opened_at) {
+            Some(opened_at) => now.duration_since(opened_at) >= self.cooldown,
+            None => true,
+        }
+    }
+
+    pub fn record_success(&self, provider_id: &str) {
+        let mut states = self.states.lock().unwrap_or_else(|e| e.into_inner());
+        states.remove(provider_id);
+    }
+
+    pub fn record_failure(&self, provider_id: &str) {
+        self.record_failure_at(provider_id, Instant::now());
+    }
+
+    fn record_failure_at(&self, provider_id: &str, now: Instant) {
+        let mut states = self.states.lock().unwrap_or_else(|e| e.into_inner());
+        let state = states.entry(provider_id.to_string()).or_default();
+        state.consecutive_failures += 1;
+        if state.consecutive_failures >= self.failure_threshold {
+            if state.opened_at.is_none() {
+                log::warn!(
+                    "[Fallback] Opening circuit for provider {} after {} failures",
+                    provider_id,
+                    state.consecutive_failures
+                );
+            }
+            state.opened_at = Some(now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::types::{AuthType, ModelConfig, ProtocolType, ProviderConfig};
+
+    fn provider_config(id: &str) -> ProviderConfig {
+        ProviderConfig {
+            id: id.to_string(),
+            name: id.to_string(),
+            protocol: ProtocolType::OpenAiCompatible,
+            base_url: "https://example.com".to_string(),
+            api_key_name: "TEST_API_KEY".to_string(),
+            supports_oauth: false,
+            supports_coding_plan: false,
+            supports_international: false,
+            coding_plan_base_url: None,
+            international_base_url: None,
+            headers: None,
+            extra_body: None,
+            auth_type: AuthType::Bearer,
+        }
+    }
+
+    fn model_config(providers: &[&str]) -> ModelConfig {
+        ModelConfig {
+            name: "model".to_string(),
+            image_input: false,
+            image_output: false,
+            audio_input: false,
+            video_input: false,
+            interleaved: false,
+            providers: providers.iter().map(|p| p.to_string()).collect(),
+            provider_mappings: Some(HashMap::from([(
+                "openRouter".to_string(),
+                "anthropic/claude-sonnet".to_string(),
+            )])),
+            pricing: None,
+            context_length: None,
+        }
+    }
+
+    #[test]
+    fn parses_json_and_arrow_chains() {
+        assert_eq!(
+            parse_fallback_chain(r#"["a@x", " b@y "]"#),
+            vec!["a@x", "b@y"]
+        );
+        assert_eq!(
+            parse_fallback_chain(
+                "claude-sonnet@anthropic → claude-sonnet@openRouter -> glm-4.7@zhipu"
+            ),
+            vec![
+                "claude-sonnet@anthropic",
+                "claude-sonnet@openRouter",
+                "glm-4.7@zhipu"
+            ]
+        );
+        assert!(parse_fallback_chain("  ").is_empty());
+    }
+
+    #[test]
+    fn builds_candidates_from_providers_and_chain() {
+        let registry = ProviderRegistry::new(vec![
+            provider_config("anthropic"),
+            provider_config("openRouter"),
+            provider_config("zhipu"),
+        ]);
+        let api_keys = HashMap::from([
+            ("anthropic".to_string(), "key".to_string()),
+            ("openRouter".to_string(), "key".to_string()),
+            ("zhipu".to_string(), "key".to_string()),
+        ]);
+        let custom_providers = CustomProvidersConfiguration {
+            version: "1".to_string(),
+            providers: HashMap::new(),
+        };
+        let models = ModelsConfiguration {
+            version: "1".to_string(),
+            models: HashMap::from([
+                (
+                    "claude-sonnet".to_string(),
+                    model_config(&["anthropic", "openRouter"]),
+                ),
+                ("glm-4.7".to_string(), model_config(&["zhipu"])),
+            ]),
+        };
+        let chain = vec![
+            "claude-sonnet@openRouter".to_string(),
+            "glm-4.7@zhipu".to_string(),
+        ];
+
+        let candidates = build_candidates(
+            "claude-sonnet",
+            &chain,
+            &api_keys,
+            &registry,
+            &custom_providers,
+            &models,
+        )
+        .expect("candidates");
+        let ids: Vec<String> = candidates.iter().map(|c| c.identifier()).collect();
+        assert_eq!(
+            ids,
+            vec![
+                "claude-sonnet@anthropic",
+                "claude-sonnet@openRouter",
+                "glm-4.7@zhipu"
+            ]
+        );
+        assert_eq!(candidates[1].provider_model_name, "anthropic/claude-sonnet");
+
+        let pinned = build_candidates(
+            "claude-sonnet@anthropic",
+            &[],
+            &api_keys,
+            &registry,
+            &custom_providers,
+            &models,
+        )
+        .expect("candidates");
+        assert_eq!(pinned.len(), 1);
+
+        let unconfigured = build_candidates(
+            "claude-sonnet",
+            &[],
+            &api_keys,
+            &registry,
+            &custom_providers,
+            &models,
+        )
+        .expect("candidates");
+        let ids: Vec<String> = unconfigured.iter().map(|c| c.identifier()).collect();
+        assert_eq!(ids, vec!["claude-sonnet@anthropic"]);
+    }
+
+    #[test]
+    fn classifies_failover_responses() {
+        assert!(is_failover_response(429, ""));
+        assert!(is_failover_response(503, ""));
+        assert!(is_failover_response(
+            400,
+            r#"{"type":"error","error":{"type":"overloaded_error"}}"#
+        ));
+        assert!(!is_failover_response(401, "invalid api key"));
+        assert!(!is_failover_response(400, "invalid request"));
+        assert!(is_overload_chunk(
+            b"event: error\ndata: {\"type\":\"error\",\"error\":{\"type\":\"overloaded_error\"}}\n\n"
+        ));
+        assert!(!is_overload_chunk(b"data: {\"choices\":[]}\n\n"));
+    }
+
+    #[test]
+    fn circuit_opens_after_threshold_and_recovers() {
+        let breaker = ProviderCircuitBreaker::new(2, Duration::from_secs(30));
+        let now = Instant::now();
+
+        breaker.record_failure_at("anthropic", now);
+        assert!(breaker.is_available_at("anthropic", now));
+        breaker.record_failure_at("anthropic", now);
+        assert!(!breaker.is_available_at("anthropic", now));
+        assert!(breaker.is_available_at("anthropic", now + Duration::from_secs(31)));
+
+        breaker.record_success("anthropic");
+        assert!(breaker.is_available_at("anthropic", now));
+    }
+
+    #[test]
+    fn open_circuits_are_skipped_unless_all_are_open() {
+        let breaker = ProviderCircuitBreaker::new(1, Duration::from_secs(30));
+        let candidates = vec![
+            FallbackCandidate {
+                model_key: "m".to_string(),
+                provider_id: "a".to_string(),
+                provider_model_name: "m".to_string(),
+            },
+            FallbackCandidate {
+                model_key: "m".to_string(),
+                provider_id: "b".to_string(),
+                provider_model_name: "m".to_string(),
+            },
+        ];
+
+        breaker.record_failure("a");
+        let filtered = filter_open_circuits(candidates.clone(), &breaker);
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].provider_id, "b");
+
+        breaker.record_failure("b");
+        assert_eq!(filter_open_circuits(candidates, &breaker).len(), 2);
+    }
+}
@@ -1 +1,2 @@
+pub mod fallback;
 pub mod stream_handler;
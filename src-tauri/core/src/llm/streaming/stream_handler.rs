@@ -2,6 +2,10 @@ use crate::llm::auth::api_key_manager::ApiKeyManager;
 use crate::llm::protocols::stream_parser::StreamParseState;
 use crate::llm::providers::provider::ProviderContext;
 use crate::llm::providers::provider_registry::ProviderRegistry;
+use crate::llm::streaming::fallback::{
+    build_candidates, filter_open_circuits, is_failover_response, is_overload_chunk,
+    load_configured_chain, FallbackCandidate, ProviderCircuitBreaker,
+};
 use crate::llm::testing::fixtures::FixtureInput;
 use crate::llm::testing::{Recorder, RecordingContext, TestConfig, TestMode};
 use crate::llm::tracing::types::{float_attr, int_attr};
@@ -52,45 +56,17 @@ impl StreamHandler {
             request.model
         );
 
-        let (model_key, provider_id, provider_model_name) =
-            self.resolve_model_info(&request.model).await?;
-        log::info!(
-            "[LLM Stream {}] Resolved model: {}, provider: {}",
-            request_id,
-            model_key,
-            provider_id
-        );
-        let provider = self
-            .registry
-            .create_provider(&provider_id)
-            .ok_or_else(|| format!("Provider not found: {}", provider_id))?;
-        let provider_config = provider.config();
-        log::info!(
-            "[LLM Stream {}] Found provider: {} with protocol: {:?}",
-            request_id,
-            provider_config.name,
-            provider_config.protocol
-        );
-
-        let provider_ctx = ProviderContext {
-            provider_config,
-            api_key_manager: &self.api_keys,
-            model: &provider_model_name,
-            messages: &request.messages,
-            tools: request.tools.as_deref(),
-            temperature: request.temperature,
-            max_tokens: request.max_tokens,
-            top_p: request.top_p,
-            top_k: request.top_k,
-            provider_options: request.provider_options.as_ref(),
-            trace_context: request.trace_context.as_ref(),
-        };
-
-        let built_request = provider.build_complete_request(&provider_ctx).await?;
+        let candidates = self.resolve_candidates(&request.model).await?;
+        let primary = candidates
+            .first()
+            .cloned()
+            .ok_or_else(|| format!("No available provider for model {}", request.model))?;
         log::info!(
-            "[LLM Stream {}] Resolved base URL: {}",
+            "[LLM Stream {}] Resolved model: {}, provider: {} ({} candidates)",
             request_id,
-            built_request.url
+            primary.model_key,
+            primary.provider_id,
+            candidates.len()
         );
 
         // Initialize tracing span if trace_context is provided
@@ -136,11 +112,11 @@ impl StreamHandler {
             let mut attributes = HashMap::new();
             attributes.insert(
                 crate::llm::tracing::types::attributes::GEN_AI_REQUEST_MODEL.to_string(),
-                crate::llm::tracing::types::string_attr(&provider_model_name),
+                crate::llm::tracing::types::string_attr(&primary.provider_model_name),
             );
             attributes.insert(
                 crate::llm::tracing::types::attributes::GEN_AI_SYSTEM.to_string(),
-                crate::llm::tracing::types::string_attr(&provider_id),
+                crate::llm::tracing::types::string_attr(&primary.provider_id),
             );
 
             if let Some(t) = request.temperature {
@@ -190,77 +166,8 @@ impl StreamHandler {
             // );
         }
 
-        let headers = built_request.headers.clone();
-        let body = built_request.body.clone();
-
-        // Record request event for tracing
-        if let Some(ref span_id) = trace_span_id {
-            let trace_writer = window.app_handle().state::<Arc<TraceWriter>>();
-            trace_writer.add_event(
-                span_id.clone(),
-                crate::llm::tracing::types::attributes::HTTP_REQUEST_BODY.to_string(),
-                Some(body.clone()),
-            );
-        }
-
         let test_config = TestConfig::from_env();
 
-        let base_url = if test_config.mode != TestMode::Off {
-            test_config
-                .base_url_override
-                .clone()
-                .unwrap_or_else(|| built_request.url.clone())
-        } else {
-            built_request.url.clone()
-        };
-        let channel = Self::recording_channel(
-            &base_url,
-            provider_config,
-            built_request.url.contains("/codex/responses"),
-            test_config.base_url_override.as_deref(),
-        );
-        let endpoint_path = reqwest::Url::parse(&built_request.url)
-            .ok()
-            .map(|url| url.path().trim_start_matches('/').to_string())
-            .unwrap_or_default();
-        let url = if test_config.mode != TestMode::Off {
-            if let Some(override_url) = test_config.base_url_override.as_deref() {
-                format!("{}/{}", override_url.trim_end_matches('/'), endpoint_path)
-            } else {
-                built_request.url.clone()
-            }
-        } else {
-            built_request.url.clone()
-        };
-
-        let mut recorder = Recorder::from_test_config(
-            &test_config,
-            RecordingContext {
-                provider_id: provider_config.id.clone(),
-                protocol: format!("{:?}", provider_config.protocol),
-                model: provider_model_name.clone(),
-                endpoint_path: endpoint_path.to_string(),
-                url: url.clone(),
-                channel: channel.clone(),
-                request_headers: headers.clone(),
-                request_body: body.clone(),
-            },
-        );
-
-        if let Some(recorder) = recorder.as_mut() {
-            recorder.set_test_input(FixtureInput {
-                model: provider_model_name.clone(),
-                messages: request.messages.clone(),
-                tools: request.tools.clone(),
-                temperature: request.temperature,
-                max_tokens: request.max_tokens,
-                top_p: request.top_p,
-                top_k: request.top_k,
-                provider_options: request.provider_options.clone(),
-                extra_body: provider_config.extra_body.clone(),
-            });
-        }
-
         let client = HTTP_CLIENT.get_or_init(|| {
             reqwest::Client::builder()
                 .connect_timeout(Duration::from_secs(10))
@@ -274,57 +181,151 @@ impl StreamHandler {
         });
         log::debug!("[LLM Stream {}] HTTP client ready", request_id);
 
-        let mut req_builder = client.post(&url);
-        for (key, value) in headers {
-            req_builder = req_builder.header(&key, &value);
-        }
-        req_builder = req_builder
-            .header("Accept", "text/event-stream")
-            .json(&body);
-
-        // log::info!("[LLM Stream {}] Sending HTTP request...", request_id);
-
-        // Retry configuration: exponential backoff with max 3 retries
+        // Retry configuration: exponential backoff with max 3 retries.
+        // When a fallback candidate remains, fail over after the first attempt instead.
         const MAX_RETRIES: u32 = 3;
         const BASE_DELAY_MS: u64 = 1000;
+        // How long to wait for the first chunk before failing over
+        const FIRST_CHUNK_TIMEOUT: Duration = Duration::from_secs(60);
+
+        let breaker = ProviderCircuitBreaker::global();
+        let mut failover_errors: Vec<String> = Vec::new();
+        let mut candidate_index = 0;
+
+        let (
+            candidate,
+            provider,
+            built_request,
+            mut recorder,
+            status,
+            response_headers,
+            mut stream,
+            mut first_chunk,
+        ) = loop {
+            let candidate = candidates[candidate_index].clone();
+            candidate_index += 1;
+            let has_fallback = candidate_index < candidates.len();
+
+            let provider = self
+                .registry
+                .create_provider(&candidate.provider_id)
+                .ok_or_else(|| format!("Provider not found: {}", candidate.provider_id))?;
+            let provider_config = provider.config();
+            log::info!(
+                "[LLM Stream {}] Found provider: {} with protocol: {:?}",
+                request_id,
+                provider_config.name,
+                provider_config.protocol
+            );
 
-        let mut response = None;
-        let mut last_error: Option<String> = None;
+            let attempt_ctx = ProviderContext {
+                provider_config,
+                api_key_manager: &self.api_keys,
+                model: &candidate.provider_model_name,
+                messages: &request.messages,
+                tools: request.tools.as_deref(),
+                temperature: request.temperature,
+                max_tokens: request.max_tokens,
+                top_p: request.top_p,
+                top_k: request.top_k,
+                provider_options: request.provider_options.as_ref(),
+                trace_context: request.trace_context.as_ref(),
+                response_format: request.response_format.as_ref(),
+            };
 
-        for attempt in 0..=MAX_RETRIES {
-            if attempt > 0 {
-                let delay_ms = BASE_DELAY_MS * (1 << (attempt - 1)); // Exponential backoff: 1s, 2s, 4s
-                log::info!(
-                    "[LLM Stream {}] Retrying request (attempt {}/{}), waiting {}ms",
-                    request_id,
-                    attempt,
-                    MAX_RETRIES,
-                    delay_ms
-                );
-                tokio::time::sleep(Duration::from_millis(delay_ms)).await;
-            }
+            let built_request = provider.build_complete_request(&attempt_ctx).await?;
+            log::info!(
+                "[LLM Stream {}] Resolved base URL: {}",
+                request_id,
+                built_request.url
+            );
 
-            match req_builder.try_clone() {
-                Some(builder) => match builder.send().await {
-                    Ok(resp) => {
-                        response = Some(resp);
-                        break;
-                    }
-                    Err(e) => {
-                        let err_msg = format!("{}", e);
-                        log::warn!(
-                            "[LLM Stream {}] Request attempt {}/{} failed: {}",
-                            request_id,
-                            attempt + 1,
-                            MAX_RETRIES + 1,
-                            err_msg
-                        );
-                        last_error = Some(err_msg);
-                    }
+            let headers = built_request.headers.clone();
+            let body = built_request.body.clone();
+
+            let base_url = if test_config.mode != TestMode::Off {
+                test_config
+                    .base_url_override
+                    .clone()
+                    .unwrap_or_else(|| built_request.url.clone())
+            } else {
+                built_request.url.clone()
+            };
+            let channel = Self::recording_channel(
+                &base_url,
+                provider_config,
+                built_request.url.contains("/codex/responses"),
+                test_config.base_url_override.as_deref(),
+            );
+            let endpoint_path = reqwest::Url::parse(&built_request.url)
+                .ok()
+                .map(|url| url.path().trim_start_matches('/').to_string())
+                .unwrap_or_default();
+            let url = if test_config.mode != TestMode::Off {
+                if let Some(override_url) = test_config.base_url_override.as_deref() {
+                    format!("{}/{}", override_url.trim_end_matches('/'), endpoint_path)
+                } else {
+                    built_request.url.clone()
+                }
+            } else {
+                built_request.url.clone()
+            };
+
+            let mut recorder = Recorder::from_test_config(
+                &test_config,
+                RecordingContext {
+                    provider_id: provider_config.id.clone(),
+                    protocol: format!("{:?}", provider_config.protocol),
+                    model: candidate.provider_model_name.clone(),
+                    endpoint_path: endpoint_path.to_string(),
+                    url: url.clone(),
+                    channel: channel.clone(),
+                    request_headers: headers.clone(),
+                    request_body: body.clone(),
                 },
-                None => {
-                    // Request body cannot be cloned, try without cloning
-                    match req_builder.send().await {
+            );
+
+            if let Some(recorder) = recorder.as_mut() {
+                recorder.set_test_input(FixtureInput {
+                    model: candidate.provider_model_name.clone(),
+                    messages: request.messages.clone(),
+                    tools: request.tools.clone(),
+                    temperature: request.temperature,
+                    max_tokens: request.max_tokens,
+                    top_p: request.top_p,
+                    top_k: request.top_k,
+                    provider_options: request.provider_options.clone(),
+                    extra_body: provider_config.extra_body.clone(),
+                });
+            }
+
+            let mut req_builder = client.post(&url);
+            for (key, value) in headers {
+                req_builder = req_builder.header(&key, &value);
+            }
+            req_builder = req_builder
+                .header("Accept", "text/event-stream")
+                .json(&body);
+
+            let max_retries = if has_fallback { 0 } else { MAX_RETRIES };
+            let mut response = None;
+            let mut last_error: Option<String> = None;
+
+            for attempt in 0..=max_retries {
+                if attempt > 0 {
+                    let delay_ms = BASE_DELAY_MS * (1 << (attempt - 1)); // Exponential backoff: 1s, 2s, 4s
+                    log::info!(
+                        "[LLM Stream {}] Retrying request (attempt {}/{}), waiting {}ms",
+                        request_id,
+                        attempt,
+                        max_retries,
+                        delay_ms
+                    );
+                    tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+                }
+
+                match req_builder.try_clone() {
+                    Some(builder) => match builder.send().await {
                         Ok(resp) => {
                             response = Some(resp);
                             break;
@@ -335,59 +336,210 @@ impl StreamHandler {
                                 "[LLM Stream {}] Request attempt {}/{} failed: {}",
                                 request_id,
                                 attempt + 1,
-                                MAX_RETRIES + 1,
+                                max_retries + 1,
                                 err_msg
                             );
                             last_error = Some(err_msg);
-                            // Cannot retry without cloning
-                            break;
+                        }
+                    },
+                    None => {
+                        // Request body cannot be cloned, try without cloning
+                        match req_builder.send().await {
+                            Ok(resp) => {
+                                response = Some(resp);
+                                break;
+                            }
+                            Err(e) => {
+                                let err_msg = format!("{}", e);
+                                log::warn!(
+                                    "[LLM Stream {}] Request attempt {}/{} failed: {}",
+                                    request_id,
+                                    attempt + 1,
+                                    max_retries + 1,
+                                    err_msg
+                                );
+                                last_error = Some(err_msg);
+                                // Cannot retry without cloning
+                                break;
+                            }
                         }
                     }
                 }
             }
-        }
 
-        let response = response.ok_or_else(|| {
-            let err = last_error.unwrap_or_else(|| "Request failed after all retries".to_string());
-            log::error!("[LLM Stream {}] Request failed: {}", request_id, err);
-            format!("Request failed: {}", err)
-        })?;
+            let response = match response {
+                Some(response) => response,
+                None => {
+                    let err = last_error
+                        .unwrap_or_else(|| "Request failed after all retries".to_string());
+                    breaker.record_failure(&candidate.provider_id);
+                    if has_fallback {
+                        log::warn!(
+                            "[LLM Stream {}] {} failed ({}), failing over",
+                            request_id,
+                            candidate.identifier(),
+                            err
+                        );
+                        failover_errors.push(format!("{}: {}", candidate.identifier(), err));
+                        continue;
+                    }
+                    log::error!("[LLM Stream {}] Request failed: {}", request_id, err);
+                    return Err(format!("Request failed: {}", err));
+                }
+            };
+
+            let status = response.status().as_u16();
+            if status >= 400 {
+                let response_headers = response.headers().clone();
+                let text = response.text().await.unwrap_or_default();
+                if is_failover_response(status, &text) {
+                    breaker.record_failure(&candidate.provider_id);
+                    if has_fallback {
+                        log::warn!(
+                            "[LLM Stream {}] {} returned HTTP {}, failing over",
+                            request_id,
+                            candidate.identifier(),
+                            status
+                        );
+                        failover_errors.push(format!(
+                            "{}: HTTP {}",
+                            candidate.identifier(),
+                            status
+                        ));
+                        continue;
+                    }
+                }
+                log::error!(
+                    "[LLM Stream {}] HTTP error {}: {}",
+                    request_id,
+                    status,
+                    text
+                );
+                if let Some(recorder) = recorder.as_mut() {
+                    let _ = recorder.finish_error(status, &response_headers, &text);
+                }
+                // Record error in tracing span
+                if let Some(ref span_id) = trace_span_id {
+                    let trace_writer = window.app_handle().state::<Arc<TraceWriter>>();
+                    trace_writer.add_event(
+                        span_id.clone(),
+                        crate::llm::tracing::types::attributes::ERROR_TYPE.to_string(),
+                        Some(serde_json::json!({
+                            "error_type": "http_error",
+                            "status_code": status,
+                            "message": text,
+                        })),
+                    );
+                }
+                let error_event = StreamEvent::Error {
+                    message: format!("HTTP {}: {}", status, text),
+                };
+                let _ = window.emit(&event_name, &error_event);
+                return Err(format!("HTTP error {}", status));
+            }
 
-        let status = response.status().as_u16();
-        if status >= 400 {
             let response_headers = response.headers().clone();
-            let text = response.text().await.unwrap_or_default();
-            log::error!(
-                "[LLM Stream {}] HTTP error {}: {}",
-                request_id,
+            let mut stream = response.bytes_stream();
+
+            // Only wait for the first chunk here when there is somewhere to fail over to;
+            // otherwise the stream loop below applies its own timeout.
+            let mut first_chunk = None;
+            if has_fallback {
+                let failure = match timeout(FIRST_CHUNK_TIMEOUT, stream.next()).await {
+                    Ok(Some(Ok(bytes))) if is_overload_chunk(&bytes) => {
+                        Some("overloaded".to_string())
+                    }
+                    Ok(Some(Ok(bytes))) => {
+                        first_chunk = Some(bytes);
+                        None
+                    }
+                    Ok(Some(Err(e))) => Some(e.to_string()),
+                    Ok(None) => Some("stream closed before first chunk".to_string()),
+                    Err(_) => Some(format!(
+                        "no data received for {} seconds",
+                        FIRST_CHUNK_TIMEOUT.as_secs()
+                    )),
+                };
+                if let Some(reason) = failure {
+                    breaker.record_failure(&candidate.provider_id);
+                    log::warn!(
+                        "[LLM Stream {}] {} failed before first token ({}), failing over",
+                        request_id,
+                        candidate.identifier(),
+                        reason
+                    );
+                    failover_errors.push(format!("{}: {}", candidate.identifier(), reason));
+                    continue;
+                }
+            }
+
+            breaker.record_success(&candidate.provider_id);
+            break (
+                candidate,
+                provider,
+                built_request,
+                recorder,
                 status,
-                text
+                response_headers,
+                stream,
+                first_chunk,
+            );
+        };
+
+        let fallback = candidate != primary;
+        if fallback {
+            log::warn!(
+                "[LLM Stream {}] Served by fallback {} after: {}",
+                request_id,
+                candidate.identifier(),
+                failover_errors.join("; ")
+            );
+        }
+        let _ = window.emit(
+            &event_name,
+            &StreamEvent::ModelSelected {
+                model: candidate.model_key.clone(),
+                provider_id: candidate.provider_id.clone(),
+                fallback,
+            },
+        );
+
+        let provider_config = provider.config();
+        let provider_ctx = ProviderContext {
+            provider_config,
+            api_key_manager: &self.api_keys,
+            model: &candidate.provider_model_name,
+            messages: &request.messages,
+            tools: request.tools.as_deref(),
+            temperature: request.temperature,
+            max_tokens: request.max_tokens,
+            top_p: request.top_p,
+            top_k: request.top_k,
+            provider_options: request.provider_options.as_ref(),
+            trace_context: request.trace_context.as_ref(),
+            response_format: request.response_format.as_ref(),
+        };
+
+        // Record request and serving model for tracing
+        if let Some(ref span_id) = trace_span_id {
+            let trace_writer = window.app_handle().state::<Arc<TraceWriter>>();
+            trace_writer.add_event(
+                span_id.clone(),
+                "gen_ai.model_selected".to_string(),
+                Some(serde_json::json!({
+                    "model": candidate.model_key,
+                    "provider": candidate.provider_id,
+                    "fallback": fallback,
+                    "failover_errors": failover_errors,
+                })),
+            );
+            trace_writer.add_event(
+                span_id.clone(),
+                crate::llm::tracing::types::attributes::HTTP_REQUEST_BODY.to_string(),
+                Some(built_request.body.clone()),
             );
-            if let Some(recorder) = recorder.as_mut() {
-                let _ = recorder.finish_error(status, &response_headers, &text);
-            }
-            // Record error in tracing span
-            if let Some(ref span_id) = trace_span_id {
-                let trace_writer = window.app_handle().state::<Arc<TraceWriter>>();
-                trace_writer.add_event(
-                    span_id.clone(),
-                    crate::llm::tracing::types::attributes::ERROR_TYPE.to_string(),
-                    Some(serde_json::json!({
-                        "error_type": "http_error",
-                        "status_code": status,
-                        "message": text,
-                    })),
-                );
-            }
-            let error_event = StreamEvent::Error {
-                message: format!("HTTP {}: {}", status, text),
-            };
-            let _ = window.emit(&event_name, &error_event);
-            return Err(format!("HTTP error {}", status));
         }
 
-        let response_headers = response.headers().clone();
-        let mut stream = response.bytes_stream();
         let mut buffer: Vec<u8> = Vec::new();
         let mut state = StreamParseState::default();
         let mut chunk_count = 0;
@@ -399,7 +551,10 @@ impl StreamHandler {
 
         'stream_loop: loop {
             // Use timeout to prevent hanging on stream.next().await
-            let chunk_result = timeout(stream_timeout, stream.next()).await;
+            let chunk_result = match first_chunk.take() {
+                Some(bytes) => Ok(Some(Ok(bytes))),
+                None => timeout(stream_timeout, stream.next()).await,
+            };
 
             let chunk = match chunk_result {
                 Ok(Some(result)) => result,
@@ -778,31 +933,30 @@ impl StreamHandler {
         Ok(request_id)
     }
 
-    async fn resolve_model_info(
+    /// Resolve the model into an ordered list of candidates: the primary
+    /// model/provider first, then any fallbacks whose circuit is not open
+    async fn resolve_candidates(
         &self,
         model_identifier: &str,
-    ) -> Result<(String, String, String), String> {
+    ) -> Result<Vec<FallbackCandidate>, String> {
         let models = self.api_keys.load_models_config().await?;
         let api_keys = self.api_keys.load_api_keys().await?;
         let custom_providers = self.api_keys.load_custom_providers().await?;
-
-        let (model_key, provider_id) =
-            crate::llm::models::model_registry::ModelRegistry::get_model_provider(
-                model_identifier,
-                &api_keys,
-                &self.registry,
-                &custom_providers,
-                &models,
-            )?;
-
-        let provider_model_name =
-            crate::llm::models::model_registry::ModelRegistry::resolve_provider_model_name(
-                &model_key,
-                &provider_id,
-                &models,
-            );
-
-        Ok((model_key, provider_id, provider_model_name))
+        let chain = load_configured_chain(&self.api_keys, model_identifier).await;
+
+        let candidates = build_candidates(
+            model_identifier,
+            &chain,
+            &api_keys,
+            &self.registry,
+            &custom_providers,
+            &models,
+        )?;
+
+        Ok(filter_open_circuits(
+            candidates,
+            ProviderCircuitBreaker::global(),
+        ))
     }
 
     /// Find SSE delimiter in buffer, returns (index, delimiter_length)
@@ -1004,6 +1158,7 @@ mod tests {
             top_k: None,
             provider_options: None,
             trace_context: None,
+            response_format: None,
         };
 
         let base_url = provider
@@ -1052,6 +1207,7 @@ mod tests {
             provider_options: None,
             request_id: None,
             trace_context: None,
+            response_format: None,
         };
 
         let ctx = ProviderContext {
@@ -1066,6 +1222,7 @@ mod tests {
             top_k: request.top_k,
             provider_options: request.provider_options.as_ref(),
             trace_context: request.trace_context.as_ref(),
+            response_format: request.response_format.as_ref(),
         };
 
         let endpoint = provider.resolve_endpoint_path(&ctx).await;
@@ -1119,6 +1276,7 @@ mod tests {
             provider_options: None,
             request_id: None,
             trace_context: None,
+            response_format: None,
         };
 
         let ctx = ProviderContext {
@@ -1133,6 +1291,7 @@ mod tests {
             top_k: request.top_k,
             provider_options: request.provider_options.as_ref(),
             trace_context: request.trace_context.as_ref(),
+            response_format: request.response_format.as_ref(),
         };
 
         let endpoint = provider.resolve_endpoint_path(&ctx).await;
@@ -1209,6 +1368,7 @@ mod tests {
             provider_options: None,
             request_id: None,
             trace_context: None,
+            response_format: None,
         };
 
         let request_ctx = RequestBuildContext {
@@ -1221,6 +1381,7 @@ mod tests {
             top_k: request.top_k,
             provider_options: request.provider_options.as_ref(),
             extra_body: provider.config().extra_body.as_ref(),
+            response_format: None,
         };
         let body = OpenAiResponsesProtocol
             .build_request(request_ctx)
@@ -1303,6 +1464,44 @@ mod tests {
         assert!(state.emitted_tool_calls.contains("item_1"));
     }
 
+    #[test]
+    fn openai_oauth_streams_tool_call_start_and_argument_deltas() {
+        let mut state = ProtocolStreamState::default();
+        let added = json!({
+            "type": "response.output_item.added",
+            "item": {
+                "type": "function_call",
+                "id": "item_1",
+                "call_id": "call_1",
+                "name": "writeFile"
+            }
+        });
+        let delta = json!({
+            "type": "response.function_call_arguments.delta",
+            "item_id": "item_1",
+            "delta": "{\"content\":\"fn main"
+        });
+
+        let event = parse_openai_oauth_event_legacy(None, &added.to_string(), &mut state)
+            .expect("parse added")
+            .expect("event");
+        assert!(matches!(
+            event,
+            StreamEvent::ToolCallStart { ref tool_call_id, ref tool_name }
+                if tool_call_id == "call_1" && tool_name == "writeFile"
+        ));
+
+        let event = parse_openai_oauth_event_legacy(None, &delta.to_string(), &mut state)
+            .expect("parse delta")
+            .expect("event");
+        assert!(matches!(
+            event,
+            StreamEvent::ToolCallDelta { ref tool_call_id, ref arguments_delta }
+                if tool_call_id == "call_1" && arguments_delta == "{\"content\":\"fn main"
+        ));
+        assert!(!state.emitted_tool_calls.contains("item_1"));
+    }
+
     #[test]
     fn openai_oauth_function_call_done_emits_once() {
         let mut legacy_state = ProtocolStreamState::default();
@@ -1481,6 +1680,7 @@ mod tests {
             top_k: None,
             provider_options: None,
             trace_context: None,
+            response_format: None,
         };
 
         let base_url = provider
@@ -1760,6 +1960,7 @@ mod tests {
             provider_options: None,
             request_id: None,
             trace_context: None,
+            response_format: None,
         };
 
         let request_ctx = RequestBuildContext {
@@ -1772,6 +1973,7 @@ mod tests {
             top_k: request.top_k,
             provider_options: request.provider_options.as_ref(),
             extra_body: provider.config().extra_body.as_ref(),
+            response_format: None,
         };
         let body = OpenAiResponsesProtocol
             .build_request(request_ctx)
@@ -42,6 +42,7 @@ fn perf_openai_build_request() {
         top_k: Some(64),
         provider_options: None,
         extra_body: None,
+        response_format: None,
     };
 
     let iterations = 300;
@@ -175,6 +175,7 @@ fn redact_headers(headers: &HashMap<String, String>) -> HashMap<String, String>
         if lower == "authorization"
             || lower == "x-api-key"
             || lower == "api-key"
+            || lower == "x-goog-api-key"
             || lower.contains("token")
         {
             redacted.insert(lower, "REDACTED".to_string());
@@ -39,6 +39,7 @@ fn build_test_context(
         provider_options: None,
         request_id: None,
         trace_context: None,
+        response_format: None,
     };
 
     (provider, api_keys, request)
@@ -61,6 +62,7 @@ async fn google_provider_strips_top_k() {
         top_k: request.top_k,
         provider_options: request.provider_options.as_ref(),
         trace_context: request.trace_context.as_ref(),
+        response_format: request.response_format.as_ref(),
     };
 
     let body = provider.build_request(&ctx).await.expect("build request");
@@ -83,6 +85,7 @@ async fn non_google_provider_keeps_top_k() {
         top_k: request.top_k,
         provider_options: request.provider_options.as_ref(),
         trace_context: request.trace_context.as_ref(),
+        response_format: request.response_format.as_ref(),
     };
 
     let body = provider.build_request(&ctx).await.expect("build request");
@@ -1,8 +1,9 @@
 use super::fixtures::{load_fixture, parse_sse_body, ProviderFixture, RecordedResponse};
 use super::mock_server::MockProviderServer;
 use crate::llm::protocols::{
-    claude_protocol::ClaudeProtocol, openai_protocol::OpenAiProtocol,
-    openai_responses_protocol::OpenAiResponsesProtocol, LlmProtocol, ProtocolStreamState,
+    claude_protocol::ClaudeProtocol, gemini_protocol::GeminiProtocol,
+    openai_protocol::OpenAiProtocol, openai_responses_protocol::OpenAiResponsesProtocol,
+    LlmProtocol, ProtocolStreamState,
 };
 use serde_json::Value;
 use std::path::{Path, PathBuf};
@@ -22,6 +23,14 @@ fn fixtures_dir() -> PathBuf {
         .join(".llm-fixtures")
 }
 
+fn recordings_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src")
+        .join("llm")
+        .join("testing")
+        .join("recordings")
+}
+
 fn canonicalize_or_original(path: &Path) -> PathBuf {
     std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
 }
@@ -31,7 +40,15 @@ fn load_fixtures_for_test(
     protocol: &str,
     channel: &str,
 ) -> Vec<LoadedFixture> {
-    let dir = fixtures_dir();
+    load_fixtures_from_dir(&fixtures_dir(), provider_id, protocol, channel)
+}
+
+fn load_fixtures_from_dir(
+    dir: &Path,
+    provider_id: Option<&str>,
+    protocol: &str,
+    channel: &str,
+) -> Vec<LoadedFixture> {
     let suffix = format!("__{}.json", channel);
     // Match both the exact protocol and OpenAiCompatible variants
     // OpenAiCompatible providers use OpenAI-compatible protocol
@@ -42,7 +59,7 @@ fn load_fixtures_for_test(
     };
     let mut matches = Vec::new();
 
-    let entries = std::fs::read_dir(&dir)
+    let entries = std::fs::read_dir(dir)
         .unwrap_or_else(|err| panic!("Failed to read fixtures dir {}: {}", dir.display(), err));
     for entry in entries {
         let entry = entry.expect("read dir entry");
@@ -107,6 +124,7 @@ fn protocol_for_fixture(fixture: &ProviderFixture) -> Box<dyn LlmProtocol> {
         "openai" | "OpenAiCompatible" => Box::new(OpenAiProtocol),
         "openai_responses" => Box::new(OpenAiResponsesProtocol),
         "anthropic" => Box::new(ClaudeProtocol),
+        "Gemini" | "gemini" => Box::new(GeminiProtocol),
         other => panic!("Unknown protocol in fixture: {}", other),
     }
 }
@@ -170,6 +188,15 @@ fn normalize_events(events: &mut [Value]) {
                     );
                 }
             }
+            // Gemini rarely returns call ids, so the protocol generates them
+            if let Some(id) = obj.get("toolCallId").and_then(|v| v.as_str()) {
+                if id.starts_with("gemini_call_") {
+                    obj.insert(
+                        "toolCallId".to_string(),
+                        Value::String("gemini_call_<normalized>".to_string()),
+                    );
+                }
+            }
         }
     }
 }
@@ -274,6 +301,38 @@ fn claude_fixture_roundtrip() {
     }
 }
 
+#[test]
+fn gemini_fixture_roundtrip() {
+    let fixtures = load_fixtures_from_dir(&recordings_dir(), None, "Gemini", "api");
+    assert!(!fixtures.is_empty(), "expected bundled Gemini fixtures");
+    for loaded in fixtures {
+        let fixture_path = loaded.path;
+        let fixture = loaded.fixture;
+        let protocol = protocol_for_fixture(&fixture);
+        assert_request_matches_fixture(protocol.as_ref(), &fixture, &fixture_path);
+
+        let expected = fixture.expected_events.clone().expect("expected events");
+        let mut expected_json = serde_json::to_value(expected).expect("serialize expected");
+        let actual = collect_events(protocol.as_ref(), &fixture);
+        let mut actual_json = Value::Array(actual);
+
+        // Normalize both expected and actual events for comparison
+        if let Some(expected_arr) = expected_json.as_array_mut() {
+            normalize_events(expected_arr);
+        }
+        if let Some(actual_arr) = actual_json.as_array_mut() {
+            normalize_events(actual_arr);
+        }
+
+        assert_eq!(
+            expected_json,
+            actual_json,
+            "Fixture mismatch: {}",
+            fixture_path.display()
+        );
+    }
+}
+
 #[tokio::test]
 async fn mock_server_replays_openai_fixture() {
     let fixtures = load_fixtures_for_test(None, "openai", "custom");
@@ -6,6 +6,7 @@ use std::collections::HashMap;
 pub enum ProtocolType {
     OpenAiCompatible,
     Claude,
+    Gemini,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -97,6 +98,15 @@ pub struct AvailableModel {
     pub video_input: bool,
     #[serde(rename = "inputPricing")]
     pub input_pricing: Option<String>,
+    #[serde(
+        default,
+        rename = "contextLength",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub context_length: Option<u32>,
+    /// Whether the model accepts tool definitions; `None` when unknown.
+    #[serde(default, rename = "toolCall", skip_serializing_if = "Option::is_none")]
+    pub tool_call: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -130,6 +140,20 @@ pub struct StreamTextRequest {
     pub request_id: Option<String>,
     #[serde(rename = "traceContext")]
     pub trace_context: Option<TraceContext>,
+    #[serde(default, rename = "responseFormat")]
+    pub response_format: Option<ResponseFormat>,
+}
+
+/// Structured output mode: the model must answer with JSON matching `schema`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ResponseFormat {
+    /// Schema name, also used as the forced tool name on Claude
+    pub name: String,
+    pub description: Option<String>,
+    /// JSON Schema the response must match
+    pub schema: serde_json::Value,
+    #[serde(default)]
+    pub strict: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -175,7 +199,11 @@ pub enum ContentPart {
     #[serde(rename = "text")]
     Text { text: String },
     #[serde(rename = "image")]
-    Image { image: String },
+    Image {
+        image: String,
+        #[serde(default, rename = "mimeType")]
+        mime_type: Option<String>,
+    },
     #[serde(rename = "video")]
     Video {
         video: String,
@@ -234,6 +262,20 @@ pub enum StreamEvent {
         #[serde(default)]
         provider_metadata: Option<serde_json::Value>,
     },
+    /// A tool call began streaming; its arguments follow as `ToolCallDelta` events
+    ToolCallStart {
+        #[serde(rename = "toolCallId")]
+        tool_call_id: String,
+        #[serde(rename = "toolName")]
+        tool_name: String,
+    },
+    /// Raw JSON fragment of a tool call's arguments, in the order the model writes them
+    ToolCallDelta {
+        #[serde(rename = "toolCallId")]
+        tool_call_id: String,
+        #[serde(rename = "argumentsDelta")]
+        arguments_delta: String,
+    },
     ReasoningStart {
         id: String,
         #[serde(default)]
@@ -264,6 +306,13 @@ pub enum StreamEvent {
     Raw {
         raw_value: String,
     },
+    /// The model/provider that actually served the request,
+    /// emitted once before any content events
+    ModelSelected {
+        model: String,
+        provider_id: String,
+        fallback: bool,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
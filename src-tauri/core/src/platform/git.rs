@@ -43,9 +43,7 @@ impl GitPlatform {
 
     /// Get effective path (worktree or workspace root)
     fn get_effective_path(&self, ctx: &PlatformContext) -> std::path::PathBuf {
-        ctx.worktree_path
-            .clone()
-            .unwrap_or_else(|| ctx.workspace_root.clone())
+        ctx.effective_root().to_path_buf()
     }
 
     /// Check if directory is a git repository
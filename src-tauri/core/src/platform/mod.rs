@@ -6,6 +6,7 @@
 pub mod fs;
 pub mod git;
 pub mod lsp;
+pub mod sandbox;
 pub mod shell;
 pub mod types;
 
@@ -0,0 +1,513 @@
+//! Shell Sandbox
+//!
+//! Runs shell commands inside Linux namespaces using bubblewrap. The workspace
+//! and worktree stay writable, the rest of the filesystem is mounted read-only,
+//! networking can be cut off, and `prlimit` caps CPU time, memory and process
+//! count. Other platforms have no sandbox backend.
+//!
+//! The kernel counts RLIMIT_NPROC per user, so each sandbox gets its own user
+//! namespace: the process cap then covers only the sandboxed command and its
+//! children, not every process the user runs (Linux 5.14 and later).
+//!
+//! Paths registered with `hide_path`, such as the app data root holding API
+//! keys, are covered with an empty tmpfs inside every sandbox.
+
+use crate::shell_utils::new_async_command;
+use crate::storage::models::{SandboxMode, SandboxSettings};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+/// Exit status a shell reports when its child was killed by SIGXCPU or SIGKILL
+const SIGXCPU_EXIT: i32 = 128 + 24;
+const SIGKILL_EXIT: i32 = 128 + 9;
+
+/// Paths no sandboxed command may see
+static HIDDEN_PATHS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Hide `path` from every sandboxed command started from now on
+pub fn hide_path(path: &Path) {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let mut hidden = HIDDEN_PATHS.lock().unwrap();
+    if !hidden.contains(&path) {
+        hidden.push(path);
+    }
+}
+
+/// Resolved sandbox policy for one task
+#[derive(Debug, Clone, PartialEq)]
+pub struct SandboxPolicy {
+    pub writable_paths: Vec<PathBuf>,
+    pub allow_network: bool,
+    pub max_cpu_seconds: Option<u64>,
+    pub max_memory_mb: Option<u64>,
+    /// Process cap for the command and its children, counted inside the
+    /// sandbox's own user namespace
+    pub max_processes: Option<u64>,
+    /// Refuse to run when the sandbox cannot be set up
+    pub required: bool,
+}
+
+/// Why the sandbox refused or stopped a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SandboxDenialKind {
+    /// No sandbox backend on this host while the policy requires one
+    Unavailable,
+    /// A write outside the writable paths
+    Filesystem,
+    /// Network access while the network is disabled
+    Network,
+    /// A CPU, memory or process limit was hit
+    ResourceLimit,
+}
+
+/// Structured report of a sandbox denial, returned with the tool result
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SandboxDenial {
+    pub kind: SandboxDenialKind,
+    pub message: String,
+}
+
+impl SandboxDenial {
+    fn new(kind: SandboxDenialKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+impl SandboxPolicy {
+    /// Build the policy for a task, or `None` when sandboxing is off.
+    pub fn from_settings(
+        settings: Option<&SandboxSettings>,
+        workspace_root: &str,
+        worktree_path: Option<&str>,
+    ) -> Option<Self> {
+        let settings = settings?;
+        let mode = settings.mode.unwrap_or_default();
+        if mode == SandboxMode::Off {
+            return None;
+        }
+
+        let mut writable_paths = vec![PathBuf::from(workspace_root)];
+        writable_paths.extend(worktree_path.map(PathBuf::from));
+        writable_paths.extend(settings.writable_paths.iter().flatten().map(PathBuf::from));
+
+        Some(Self {
+            writable_paths,
+            allow_network: settings.allow_network.unwrap_or(false),
+            max_cpu_seconds: settings.max_cpu_seconds,
+            max_memory_mb: settings.max_memory_mb,
+            max_processes: settings.max_processes,
+            required: mode == SandboxMode::Required,
+        })
+    }
+
+    /// Whether commands will actually run isolated on this host
+    pub fn is_isolated(&self) -> bool {
+        bubblewrap().is_some()
+    }
+
+    /// Why commands under this policy are not fully isolated on this host, or
+    /// `None` when they are. Policies that are not required still run them.
+    pub fn fallback_reason(&self) -> Option<String> {
+        if bubblewrap().is_none() {
+            return Some(
+                "bubblewrap (bwrap) with unprivileged user namespaces is not available; \
+                 the command ran without a sandbox"
+                    .to_string(),
+            );
+        }
+        if self.has_limits() && prlimit().is_none() {
+            return Some("prlimit is not available; resource limits were not applied".to_string());
+        }
+        None
+    }
+
+    /// Check that the policy can be enforced. Only fails for required policies.
+    pub fn ensure_available(&self) -> Result<(), SandboxDenial> {
+        if !self.required {
+            return Ok(());
+        }
+        if bubblewrap().is_none() {
+            return Err(SandboxDenial::new(
+                SandboxDenialKind::Unavailable,
+                "Sandbox required but bubblewrap (bwrap) is not available; \
+                 it needs Linux with unprivileged user namespaces",
+            ));
+        }
+        if self.has_limits() && prlimit().is_none() {
+            return Err(SandboxDenial::new(
+                SandboxDenialKind::Unavailable,
+                "Sandbox required but prlimit is not available to enforce resource limits",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Argument vector that runs `program args` under this policy. Policies that
+    /// are not required fall back to running the program directly.
+    pub fn argv(
+        &self,
+        program: &str,
+        args: &[&str],
+        cwd: Option<&str>,
+    ) -> Result<Vec<String>, SandboxDenial> {
+        self.ensure_available()?;
+
+        let mut argv: Vec<String> = Vec::new();
+        match bubblewrap() {
+            Some(bwrap) => {
+                argv.push(bwrap.to_string_lossy().to_string());
+                argv.extend(self.bubblewrap_args(cwd));
+                argv.push("--".to_string());
+            }
+            None => log::warn!("[Sandbox] bubblewrap not available; running command unsandboxed"),
+        }
+        if self.has_limits() {
+            match prlimit() {
+                Some(prlimit) => {
+                    argv.push(prlimit.to_string_lossy().to_string());
+                    argv.extend(self.limit_args());
+                    argv.push("--".to_string());
+                }
+                None => log::warn!("[Sandbox] prlimit not found; resource limits not applied"),
+            }
+        }
+        argv.push(program.to_string());
+        argv.extend(args.iter().map(|arg| arg.to_string()));
+        Ok(argv)
+    }
+
+    /// Build a command that runs `program args` under this policy.
+    pub fn command(
+        &self,
+        program: &str,
+        args: &[&str],
+        cwd: Option<&str>,
+    ) -> Result<tokio::process::Command, SandboxDenial> {
+        let argv = self.argv(program, args, cwd)?;
+        let mut cmd = new_async_command(&argv[0]);
+        cmd.args(&argv[1..]);
+        if let Some(dir) = cwd {
+            cmd.current_dir(dir);
+        }
+        Ok(cmd)
+    }
+
+    /// Map a failed command's output to a sandbox denial, if the sandbox caused it.
+    pub fn classify_failure(&self, exit_code: i32, stderr: &str) -> Option<SandboxDenial> {
+        if exit_code == 0 {
+            return None;
+        }
+        let lower = stderr.to_lowercase();
+
+        if self.is_isolated() && lower.contains("read-only file system") {
+            return Some(SandboxDenial::new(
+                SandboxDenialKind::Filesystem,
+                "Write outside the workspace was denied by the sandbox",
+            ));
+        }
+
+        let network_errors = [
+            "network is unreachable",
+            "temporary failure in name resolution",
+            "could not resolve host",
+            "name or service not known",
+        ];
+        if self.is_isolated()
+            && !self.allow_network
+            && network_errors.iter().any(|error| lower.contains(error))
+        {
+            return Some(SandboxDenial::new(
+                SandboxDenialKind::Network,
+                "Network access is disabled by the sandbox",
+            ));
+        }
+
+        let cpu_exceeded = self.max_cpu_seconds.is_some()
+            && (exit_code == SIGXCPU_EXIT || exit_code == SIGKILL_EXIT);
+        let memory_exceeded = self.max_memory_mb.is_some()
+            && (lower.contains("cannot allocate memory") || lower.contains("out of memory"));
+        let processes_exceeded =
+            self.max_processes.is_some() && lower.contains("resource temporarily unavailable");
+        if cpu_exceeded || memory_exceeded || processes_exceeded {
+            return Some(SandboxDenial::new(
+                SandboxDenialKind::ResourceLimit,
+                "Command exceeded a sandbox resource limit",
+            ));
+        }
+
+        None
+    }
+
+    fn has_limits(&self) -> bool {
+        self.max_cpu_seconds.is_some()
+            || self.max_memory_mb.is_some()
+            || self.max_processes.is_some()
+    }
+
+    fn limit_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(seconds) = self.max_cpu_seconds {
+            args.push(format!("--cpu={}", seconds));
+        }
+        if let Some(megabytes) = self.max_memory_mb {
+            args.push(format!("--as={}", megabytes.saturating_mul(1024 * 1024)));
+        }
+        if let Some(processes) = self.max_processes {
+            args.push(format!("--nproc={}", processes));
+        }
+        args
+    }
+
+    fn bubblewrap_args(&self, cwd: Option<&str>) -> Vec<String> {
+        let mut args: Vec<String> = [
+            "--ro-bind",
+            "/",
+            "/",
+            "--dev",
+            "/dev",
+            "--proc",
+            "/proc",
+            "--tmpfs",
+            "/tmp",
+        ]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+
+        // Cover hidden paths such as the app data root with empty mounts
+        for path in HIDDEN_PATHS
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|path| path.exists())
+        {
+            let path = path.to_string_lossy().to_string();
+            args.extend(["--tmpfs".to_string(), path]);
+        }
+
+        // Binds come after the tmpfs mounts so workspaces under /tmp, or under
+        // a hidden path, stay visible.
+        for path in self.writable_paths.iter().filter(|path| path.exists()) {
+            let path = path.to_string_lossy().to_string();
+            args.extend(["--bind".to_string(), path.clone(), path]);
+        }
+
+        args.extend(
+            [
+                "--unshare-user",
+                "--unshare-pid",
+                "--unshare-ipc",
+                "--unshare-uts",
+                "--unshare-cgroup-try",
+            ]
+            .iter()
+            .map(|arg| arg.to_string()),
+        );
+        if !self.allow_network {
+            args.push("--unshare-net".to_string());
+        }
+        args.extend(["--die-with-parent".to_string(), "--new-session".to_string()]);
+        if let Some(dir) = cwd {
+            args.extend(["--chdir".to_string(), dir.to_string()]);
+        }
+        args
+    }
+}
+
+fn find_executable(name: &str) -> Option<PathBuf> {
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// bubblewrap, if installed and able to create namespaces on this host
+#[cfg(target_os = "linux")]
+fn bubblewrap() -> Option<&'static Path> {
+    use crate::shell_utils::new_command;
+    use std::process::Stdio;
+
+    static BWRAP: OnceLock<Option<PathBuf>> = OnceLock::new();
+    BWRAP
+        .get_or_init(|| {
+            let path = find_executable("bwrap")?;
+            // Unprivileged user namespaces can be disabled system-wide.
+            let works = new_command(&path.to_string_lossy())
+                .args([
+                    "--ro-bind",
+                    "/",
+                    "/",
+                    "--unshare-user",
+                    "--unshare-pid",
+                    "--",
+                    "true",
+                ])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|status| status.success());
+            works.then_some(path)
+        })
+        .as_deref()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn bubblewrap() -> Option<&'static Path> {
+    None
+}
+
+fn prlimit() -> Option<&'static Path> {
+    static PRLIMIT: OnceLock<Option<PathBuf>> = OnceLock::new();
+    PRLIMIT
+        .get_or_init(|| {
+            if cfg!(target_os = "linux") {
+                find_executable("prlimit")
+            } else {
+                None
+            }
+        })
+        .as_deref()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn policy(workspace: &Path) -> SandboxPolicy {
+        SandboxPolicy::from_settings(
+            Some(&SandboxSettings {
+                mode: Some(SandboxMode::Auto),
+                ..Default::default()
+            }),
+            &workspace.to_string_lossy(),
+            None,
+        )
+        .expect("policy")
+    }
+
+    #[test]
+    fn test_from_settings() {
+        assert!(SandboxPolicy::from_settings(None, "/ws", None).is_none());
+        let off = SandboxSettings {
+            mode: Some(SandboxMode::Off),
+            ..Default::default()
+        };
+        assert!(SandboxPolicy::from_settings(Some(&off), "/ws", None).is_none());
+
+        let settings = SandboxSettings {
+            mode: Some(SandboxMode::Required),
+            allow_network: Some(true),
+            writable_paths: Some(vec!["/cache".to_string()]),
+            max_memory_mb: Some(512),
+            ..Default::default()
+        };
+        let policy = SandboxPolicy::from_settings(Some(&settings), "/ws", Some("/wt")).unwrap();
+        assert!(policy.required);
+        assert!(policy.allow_network);
+        assert_eq!(
+            policy.writable_paths,
+            vec![
+                PathBuf::from("/ws"),
+                PathBuf::from("/wt"),
+                PathBuf::from("/cache")
+            ]
+        );
+        assert_eq!(policy.limit_args(), vec!["--as=536870912".to_string()]);
+    }
+
+    #[test]
+    fn test_bubblewrap_args() {
+        let temp_dir = TempDir::new().unwrap();
+        let workspace = temp_dir.path().to_string_lossy().to_string();
+        let args = policy(temp_dir.path()).bubblewrap_args(Some(&workspace));
+
+        let tmpfs = args.iter().position(|arg| arg == "/tmp").unwrap();
+        let bind = args.iter().position(|arg| arg == "--bind").unwrap();
+        assert!(tmpfs < bind);
+        assert_eq!(args[bind + 1], workspace);
+        assert!(args.contains(&"--unshare-net".to_string()));
+        // The process limit is counted per user namespace
+        assert!(args.contains(&"--unshare-user".to_string()));
+        assert_eq!(&args[args.len() - 2..], &["--chdir".to_string(), workspace]);
+    }
+
+    #[test]
+    fn test_fallback_reason_matches_isolation() {
+        let temp_dir = TempDir::new().unwrap();
+        let policy = policy(temp_dir.path());
+        assert_eq!(policy.fallback_reason().is_none(), policy.is_isolated());
+    }
+
+    #[test]
+    fn test_hidden_paths_are_masked() {
+        let temp_dir = TempDir::new().unwrap();
+        let data_root = temp_dir.path().join("data");
+        let workspace = data_root.join("workspace");
+        std::fs::create_dir_all(&workspace).unwrap();
+        hide_path(&data_root);
+
+        let args = policy(&workspace).bubblewrap_args(None);
+        let data_root = data_root
+            .canonicalize()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+        let tmpfs = args.iter().position(|arg| *arg == data_root).unwrap();
+        assert_eq!(args[tmpfs - 1], "--tmpfs");
+        // The workspace inside it is bound back on top
+        let bind = args.iter().position(|arg| arg == "--bind").unwrap();
+        assert!(tmpfs < bind);
+        assert_eq!(args[bind + 1], workspace.to_string_lossy());
+    }
+
+    #[test]
+    fn test_classify_failure() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut policy = policy(temp_dir.path());
+        policy.max_cpu_seconds = Some(1);
+
+        assert!(policy
+            .classify_failure(0, "read-only file system")
+            .is_none());
+        assert_eq!(
+            policy.classify_failure(SIGXCPU_EXIT, "").map(|d| d.kind),
+            Some(SandboxDenialKind::ResourceLimit)
+        );
+        assert!(policy.classify_failure(1, "No such file").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_write_outside_workspace_denied() {
+        let temp_dir = TempDir::new().unwrap();
+        let policy = policy(temp_dir.path());
+        if !policy.is_isolated() {
+            return;
+        }
+        let workspace = temp_dir.path().to_string_lossy().to_string();
+        let home = std::env::var("HOME").unwrap_or_else(|_| "/root".to_string());
+        let script = format!("touch inside.txt && touch {}/.talkcody-sandbox-probe", home);
+
+        let output = policy
+            .command("sh", &["-c", &script], Some(&workspace))
+            .unwrap()
+            .output()
+            .await
+            .unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let exit_code = output.status.code().unwrap_or(-1);
+
+        assert!(temp_dir.path().join("inside.txt").exists());
+        assert!(!Path::new(&home).join(".talkcody-sandbox-probe").exists());
+        assert_eq!(
+            policy.classify_failure(exit_code, &stderr).map(|d| d.kind),
+            Some(SandboxDenialKind::Filesystem)
+        );
+    }
+}
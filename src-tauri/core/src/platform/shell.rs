@@ -3,8 +3,12 @@
 //! Provides shell command execution with workspace validation and timeouts.
 //! Wraps existing shell utilities from the codebase.
 
+use crate::platform::sandbox::SandboxPolicy;
 use crate::platform::types::*;
+use crate::shell_utils::utf8_complete_prefix_len;
 use std::path::Path;
+use std::process::Stdio;
+use tokio::io::{AsyncRead, AsyncReadExt};
 
 /// Shell operations provider
 #[derive(Clone)]
@@ -44,6 +48,31 @@ impl ShellPlatform {
         command: &str,
         cwd: Option<&str>,
         ctx: &PlatformContext,
+    ) -> PlatformResult<ShellResult> {
+        self.execute_sandboxed(command, cwd, ctx, None).await
+    }
+
+    /// Execute a shell command, inside the sandbox when a policy is given
+    pub async fn execute_sandboxed(
+        &self,
+        command: &str,
+        cwd: Option<&str>,
+        ctx: &PlatformContext,
+        sandbox: Option<&SandboxPolicy>,
+    ) -> PlatformResult<ShellResult> {
+        self.execute_streaming(command, cwd, ctx, sandbox, None)
+            .await
+    }
+
+    /// Execute a shell command, passing stdout and stderr chunks to `on_output`
+    /// as they arrive. The full output is still returned once the command ends.
+    pub async fn execute_streaming(
+        &self,
+        command: &str,
+        cwd: Option<&str>,
+        ctx: &PlatformContext,
+        sandbox: Option<&SandboxPolicy>,
+        on_output: Option<OutputCallback<'_>>,
     ) -> PlatformResult<ShellResult> {
         // Validate working directory
         let working_dir = match cwd {
@@ -51,7 +80,7 @@ impl ShellPlatform {
                 Ok(validated) => Some(validated),
                 Err(e) => return PlatformResult::error(e),
             },
-            None => Some(ctx.workspace_root.to_string_lossy().to_string()),
+            None => Some(ctx.effective_root().to_string_lossy().to_string()),
         };
 
         // Check for dangerous commands
@@ -65,36 +94,69 @@ impl ShellPlatform {
         use crate::shell_utils::new_async_command;
         use tokio::time::{timeout, Duration};
 
-        let mut cmd = if cfg!(target_os = "windows") {
-            let mut c = new_async_command("cmd");
-            c.arg("/C").arg(command);
-            c
+        let (program, flag) = if cfg!(target_os = "windows") {
+            ("cmd", "/C")
         } else {
-            let mut c = new_async_command("sh");
-            c.arg("-c").arg(command);
-            c
+            ("sh", "-c")
         };
 
-        if let Some(dir) = working_dir {
-            cmd.current_dir(dir);
-        }
+        let mut cmd = match sandbox {
+            Some(policy) => match policy.command(program, &[flag, command], working_dir.as_deref())
+            {
+                Ok(cmd) => cmd,
+                Err(denial) => return PlatformResult::error(denial.message),
+            },
+            None => {
+                let mut c = new_async_command(program);
+                c.arg(flag).arg(command);
+                if let Some(dir) = working_dir {
+                    c.current_dir(dir);
+                }
+                c
+            }
+        };
 
         let timeout_duration = Duration::from_secs(ctx.shell_timeout_secs);
 
-        match timeout(timeout_duration, cmd.output()).await {
-            Ok(Ok(output)) => PlatformResult::success(ShellResult {
-                stdout: String::from_utf8_lossy(&output.stdout).to_string(),
-                stderr: String::from_utf8_lossy(&output.stderr).to_string(),
-                exit_code: output.status.code().unwrap_or(-1),
+        let mut child = match cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => return PlatformResult::error(format!("Failed to execute command: {}", e)),
+        };
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+
+        let run = async {
+            let (stdout, stderr, status) = tokio::join!(
+                read_output(stdout, OutputStream::Stdout, on_output),
+                read_output(stderr, OutputStream::Stderr, on_output),
+                child.wait()
+            );
+            status.map(|status| (stdout, stderr, status))
+        };
+
+        match timeout(timeout_duration, run).await {
+            Ok(Ok((stdout, stderr, status))) => PlatformResult::success(ShellResult {
+                stdout: String::from_utf8_lossy(&stdout).to_string(),
+                stderr: String::from_utf8_lossy(&stderr).to_string(),
+                exit_code: status.code().unwrap_or(-1),
                 timed_out: false,
             }),
             Ok(Err(e)) => PlatformResult::error(format!("Failed to execute command: {}", e)),
-            Err(_) => PlatformResult::success(ShellResult {
-                stdout: String::new(),
-                stderr: "Command timed out".to_string(),
-                exit_code: -1,
-                timed_out: true,
-            }),
+            Err(_) => {
+                let _ = child.kill().await;
+                PlatformResult::success(ShellResult {
+                    stdout: String::new(),
+                    stderr: "Command timed out".to_string(),
+                    exit_code: -1,
+                    timed_out: true,
+                })
+            }
         }
     }
 
@@ -164,6 +226,36 @@ impl ShellPlatform {
     }
 }
 
+/// Collect a child's output stream, forwarding complete UTF-8 chunks to `on_output`
+async fn read_output<R: AsyncRead + Unpin>(
+    reader: Option<R>,
+    stream: OutputStream,
+    on_output: Option<OutputCallback<'_>>,
+) -> Vec<u8> {
+    let mut collected = Vec::new();
+    let Some(mut reader) = reader else {
+        return collected;
+    };
+    let mut buffer = [0u8; 8192];
+    let mut streamed = 0;
+    loop {
+        match reader.read(&mut buffer).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => {
+                collected.extend_from_slice(&buffer[..n]);
+                if let Some(callback) = on_output {
+                    let end = streamed + utf8_complete_prefix_len(&collected[streamed..]);
+                    if end > streamed {
+                        callback(stream, &String::from_utf8_lossy(&collected[streamed..end]));
+                        streamed = end;
+                    }
+                }
+            }
+        }
+    }
+    collected
+}
+
 impl Default for ShellPlatform {
     fn default() -> Self {
         Self::new()
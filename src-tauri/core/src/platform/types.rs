@@ -18,6 +18,15 @@ pub struct PlatformContext {
     pub shell_timeout_secs: u64,
 }
 
+impl PlatformContext {
+    /// Directory commands run in: the worktree when there is one, else the workspace root
+    pub fn effective_root(&self) -> &std::path::Path {
+        self.worktree_path
+            .as_deref()
+            .unwrap_or(&self.workspace_root)
+    }
+}
+
 impl Default for PlatformContext {
     fn default() -> Self {
         Self {
@@ -111,6 +120,17 @@ pub struct ShellResult {
     pub timed_out: bool,
 }
 
+/// Stream a chunk of command output was written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// Receives command output while the command is still running
+pub type OutputCallback<'a> = &'a (dyn Fn(OutputStream, &str) + Send + Sync);
+
 /// LSP position
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
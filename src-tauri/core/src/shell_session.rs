@@ -0,0 +1,595 @@
+//! Persistent Shell Sessions
+//!
+//! Keeps one shell per agent task on a PTY so `cd`, exported variables,
+//! activated virtualenvs and `source`d scripts carry over between bash tool
+//! calls. Each command is run through `eval` and followed by a sentinel line
+//! that carries its exit code, which marks where the command's output ends.
+//! Commands read stdin from /dev/null and pagers are replaced by `cat`, so
+//! nothing waits for input the agent cannot give.
+
+use crate::platform::sandbox::SandboxPolicy;
+use crate::platform::types::{OutputCallback, OutputStream};
+use crate::shell_utils::utf8_complete_prefix_len;
+use crate::terminal::{spawn_pty_process, PtyProcess};
+use portable_pty::{ChildKiller, CommandBuilder, MasterPty, PtySize};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, Mutex as AsyncMutex};
+use tokio::time::{timeout_at, Duration, Instant};
+
+/// Time a new shell gets to answer its first sentinel
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+/// Time an interrupted command gets to return control to the shell
+const INTERRUPT_GRACE: Duration = Duration::from_secs(2);
+
+/// Sets up a quiet, non-echoing shell before the first command
+const INIT_SCRIPT: &str =
+    "stty -echo 2>/dev/null; unset HISTFILE; PS1=''; PS2=''; PROMPT_COMMAND=''";
+
+/// Result of one command run in a session
+#[derive(Debug, Clone)]
+pub struct SessionOutput {
+    /// stdout and stderr, interleaved as the terminal received them
+    pub output: String,
+    pub exit_code: i32,
+    pub timed_out: bool,
+    /// The shell exited (e.g. the command ran `exit`) and the session was discarded
+    pub session_closed: bool,
+}
+
+struct ShellSession {
+    writer: Box<dyn Write + Send>,
+    child: Box<dyn portable_pty::Child + Send + Sync>,
+    // Dropping the master closes the PTY
+    _master: Box<dyn MasterPty + Send>,
+    output: mpsc::UnboundedReceiver<Vec<u8>>,
+}
+
+struct SessionEntry {
+    session: Arc<AsyncMutex<ShellSession>>,
+    /// Kills the shell without waiting for a running command to release the session
+    killer: Box<dyn ChildKiller + Send + Sync>,
+}
+
+/// How reading up to a sentinel ended
+enum ReadOutcome {
+    Finished { output: String, exit_code: i32 },
+    TimedOut { output: String },
+    Closed { output: String },
+}
+
+type SessionRegistry = Arc<Mutex<HashMap<String, SessionEntry>>>;
+
+lazy_static::lazy_static! {
+    static ref SHELL_SESSIONS: SessionRegistry = Arc::new(Mutex::new(HashMap::new()));
+}
+
+/// Whether persistent sessions are available on this platform. Elsewhere the
+/// bash tool runs every command in a fresh process.
+pub fn is_supported() -> bool {
+    cfg!(unix)
+}
+
+/// Run `command` in the task's shell session, starting one in `cwd` if needed.
+/// A command that outlives `timeout` is interrupted and reported as timed out.
+/// Output is passed to `on_output` while the command runs; the terminal merges
+/// stderr into stdout, so every chunk is reported as stdout.
+pub async fn run(
+    task_id: &str,
+    command: &str,
+    cwd: &str,
+    sandbox: Option<&SandboxPolicy>,
+    timeout: Duration,
+    on_output: Option<OutputCallback<'_>>,
+) -> Result<SessionOutput, String> {
+    let session = get_or_spawn(task_id, cwd, sandbox).await?;
+    let mut shell = session.lock().await;
+
+    // Drop anything a previously interrupted command printed late
+    while shell.output.try_recv().is_ok() {}
+
+    let nonce = new_nonce();
+    shell.write(&format!(
+        "eval {} </dev/null; {}\n",
+        single_quote(command),
+        sentinel_command(&nonce)
+    ))?;
+
+    let (output, exit_code, timed_out, session_closed) = match shell
+        .read_until_sentinel(&nonce, Instant::now() + timeout, on_output)
+        .await
+    {
+        ReadOutcome::Finished { output, exit_code } => (output, exit_code, false, false),
+        ReadOutcome::Closed { output } => (output, -1, false, true),
+        ReadOutcome::TimedOut { output } => {
+            let recovered = shell.interrupt().await;
+            (output, -1, true, !recovered)
+        }
+    };
+
+    drop(shell);
+    if session_closed {
+        discard(task_id, &session);
+    }
+
+    Ok(SessionOutput {
+        output,
+        exit_code,
+        timed_out,
+        session_closed,
+    })
+}
+
+/// Kill the task's shell session. The next command starts a fresh shell in the
+/// task's worktree, or its workspace when it has none. Returns whether a
+/// session existed.
+pub fn close(task_id: &str) -> bool {
+    let entry = SHELL_SESSIONS.lock().unwrap().remove(task_id);
+    match entry {
+        Some(mut entry) => {
+            if let Err(e) = entry.killer.kill() {
+                log::warn!(
+                    "[ShellSession] Failed to kill shell for task {}: {}",
+                    task_id,
+                    e
+                );
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+async fn get_or_spawn(
+    task_id: &str,
+    cwd: &str,
+    sandbox: Option<&SandboxPolicy>,
+) -> Result<Arc<AsyncMutex<ShellSession>>, String> {
+    if let Some(entry) = SHELL_SESSIONS.lock().unwrap().get(task_id) {
+        return Ok(entry.session.clone());
+    }
+
+    let mut shell = ShellSession::spawn(cwd, sandbox)?;
+    let nonce = new_nonce();
+    shell.write(&format!("{}; {}\n", INIT_SCRIPT, sentinel_command(&nonce)))?;
+    match shell
+        .read_until_sentinel(&nonce, Instant::now() + STARTUP_TIMEOUT, None)
+        .await
+    {
+        ReadOutcome::Finished { .. } => {}
+        ReadOutcome::TimedOut { output } | ReadOutcome::Closed { output } => {
+            return Err(format!("Shell session failed to start: {}", output.trim()));
+        }
+    }
+
+    let killer = shell.child.clone_killer();
+    let session = Arc::new(AsyncMutex::new(shell));
+    let mut sessions = SHELL_SESSIONS.lock().unwrap();
+    // Another call for the same task may have started a shell meanwhile; the
+    // losing shell is killed when it is dropped.
+    let entry = sessions
+        .entry(task_id.to_string())
+        .or_insert(SessionEntry { session, killer });
+    Ok(entry.session.clone())
+}
+
+/// Forget `session` if it is still the task's registered session
+fn discard(task_id: &str, session: &Arc<AsyncMutex<ShellSession>>) {
+    let mut sessions = SHELL_SESSIONS.lock().unwrap();
+    if sessions
+        .get(task_id)
+        .is_some_and(|entry| Arc::ptr_eq(&entry.session, session))
+    {
+        sessions.remove(task_id);
+    }
+}
+
+impl ShellSession {
+    fn spawn(cwd: &str, sandbox: Option<&SandboxPolicy>) -> Result<Self, String> {
+        let (shell, shell_args): (&str, &[&str]) = if Path::new("/bin/bash").exists() {
+            ("/bin/bash", &["--noprofile", "--norc"])
+        } else {
+            ("/bin/sh", &[])
+        };
+        let argv = match sandbox {
+            Some(policy) => policy
+                .argv(shell, shell_args, Some(cwd))
+                .map_err(|denial| denial.message)?,
+            None => std::iter::once(shell)
+                .chain(shell_args.iter().copied())
+                .map(str::to_string)
+                .collect(),
+        };
+
+        let mut cmd = CommandBuilder::new(&argv[0]);
+        cmd.args(&argv[1..]);
+        cmd.cwd(cwd);
+        cmd.env("TERM", "dumb");
+        cmd.env("PS1", "");
+        cmd.env("PS2", "");
+        cmd.env("BASH_SILENCE_DEPRECATION_WARNING", "1");
+        // The terminal makes git and friends page their output and prompt for
+        // credentials; nobody is there to answer
+        cmd.env("PAGER", "cat");
+        cmd.env("GIT_PAGER", "cat");
+        cmd.env("GIT_TERMINAL_PROMPT", "0");
+
+        let PtyProcess {
+            writer,
+            mut reader,
+            child,
+            master,
+        } = spawn_pty_process(
+            cmd,
+            PtySize {
+                rows: 50,
+                cols: 200,
+                pixel_width: 0,
+                pixel_height: 0,
+            },
+        )?;
+
+        // A plain thread rather than spawn_blocking: the read only returns once
+        // the shell exits, and the runtime should not wait on it at shutdown.
+        let (tx, rx) = mpsc::unbounded_channel();
+        std::thread::Builder::new()
+            .name("shell-session-reader".to_string())
+            .spawn(move || {
+                let mut buffer = [0u8; 8192];
+                loop {
+                    match reader.read(&mut buffer) {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => {
+                            if tx.send(buffer[..n].to_vec()).is_err() {
+                                break;
+                            }
+                        }
+                    }
+                }
+            })
+            .map_err(|e| format!("Failed to start shell reader: {}", e))?;
+
+        Ok(Self {
+            writer,
+            child,
+            _master: master,
+            output: rx,
+        })
+    }
+
+    fn write(&mut self, input: &str) -> Result<(), String> {
+        self.writer
+            .write_all(input.as_bytes())
+            .and_then(|_| self.writer.flush())
+            .map_err(|e| format!("Failed to write to shell session: {}", e))
+    }
+
+    /// Collect output until the sentinel for `nonce` arrives, streaming it to
+    /// `on_output` as it comes in
+    async fn read_until_sentinel(
+        &mut self,
+        nonce: &str,
+        deadline: Instant,
+        on_output: Option<OutputCallback<'_>>,
+    ) -> ReadOutcome {
+        let marker = sentinel_marker(nonce);
+        // Bytes that may be the start of a sentinel split across chunks
+        let holdback = marker.len() + 16;
+        let mut buffer: Vec<u8> = Vec::new();
+        let mut marker_at: Option<usize> = None;
+        let mut streamed = 0;
+        loop {
+            match timeout_at(deadline, self.output.recv()).await {
+                Ok(Some(chunk)) => {
+                    // Only rescan the new bytes plus enough overlap for a split sentinel
+                    let scan_from = buffer.len().saturating_sub(holdback);
+                    buffer.extend_from_slice(&chunk);
+                    if marker_at.is_none() {
+                        marker_at = buffer[scan_from..]
+                            .windows(marker.len())
+                            .position(|window| window == marker.as_bytes())
+                            .map(|position| scan_from + position);
+                    }
+
+                    if let Some(callback) = on_output {
+                        // The CRLF printf puts ahead of the sentinel is not output
+                        let end = match marker_at {
+                            Some(position) => position.saturating_sub(2),
+                            None => buffer.len().saturating_sub(holdback),
+                        };
+                        stream_output(&buffer, &mut streamed, end, callback);
+                    }
+
+                    if marker_at.is_some() {
+                        let text = String::from_utf8_lossy(&buffer);
+                        if let Some((output, exit_code)) = parse_sentinel(&text, nonce) {
+                            return ReadOutcome::Finished { output, exit_code };
+                        }
+                    }
+                }
+                Ok(None) => {
+                    if let Some(callback) = on_output {
+                        stream_output(&buffer, &mut streamed, buffer.len(), callback);
+                    }
+                    return ReadOutcome::Closed {
+                        output: normalize(&String::from_utf8_lossy(&buffer)),
+                    };
+                }
+                Err(_) => {
+                    if let Some(callback) = on_output {
+                        stream_output(&buffer, &mut streamed, buffer.len(), callback);
+                    }
+                    return ReadOutcome::TimedOut {
+                        output: normalize(&String::from_utf8_lossy(&buffer)),
+                    };
+                }
+            }
+        }
+    }
+
+    /// Send Ctrl-C to the running command and wait for the shell to answer a
+    /// fresh sentinel. Returns false when the shell did not recover.
+    async fn interrupt(&mut self) -> bool {
+        if self.write("\x03").is_err() {
+            return false;
+        }
+        // SIGINT flushes the terminal's input queue; let that happen before
+        // queueing the sentinel.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let nonce = new_nonce();
+        if self
+            .write(&format!("{}\n", sentinel_command(&nonce)))
+            .is_err()
+        {
+            return false;
+        }
+        matches!(
+            self.read_until_sentinel(&nonce, Instant::now() + INTERRUPT_GRACE, None)
+                .await,
+            ReadOutcome::Finished { .. }
+        )
+    }
+}
+
+impl Drop for ShellSession {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+    }
+}
+
+/// Pass `buffer[streamed..end]` to `callback`, keeping CRLF pairs and UTF-8
+/// characters whole, and advance `streamed` past what was sent
+fn stream_output(buffer: &[u8], streamed: &mut usize, end: usize, callback: OutputCallback<'_>) {
+    if end <= *streamed {
+        return;
+    }
+    // Hold back a CR whose LF may still be on its way
+    let end = if buffer[end - 1] == b'\r' && !matches!(buffer.get(end), Some(&b) if b != b'\n') {
+        end - 1
+    } else {
+        end
+    };
+    let end = *streamed + utf8_complete_prefix_len(&buffer[*streamed..end]);
+    if end > *streamed {
+        callback(
+            OutputStream::Stdout,
+            &normalize(&String::from_utf8_lossy(&buffer[*streamed..end])),
+        );
+        *streamed = end;
+    }
+}
+
+fn new_nonce() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+fn sentinel_marker(nonce: &str) -> String {
+    format!("__TALKCODY_{}_", nonce)
+}
+
+/// Shell command that prints the sentinel with the last exit status. The
+/// marker is assembled by printf, so an echo of the command itself never matches.
+fn sentinel_command(nonce: &str) -> String {
+    format!("printf '\\n__TALKCODY_%s_%s__\\n' {} \"$?\"", nonce)
+}
+
+/// Split the output before the sentinel from the exit code it carries
+fn parse_sentinel(text: &str, nonce: &str) -> Option<(String, i32)> {
+    let marker = sentinel_marker(nonce);
+    let start = text.find(&marker)?;
+    let rest = &text[start + marker.len()..];
+    let end = rest.find("__")?;
+    let exit_code = rest[..end].parse().ok()?;
+
+    let output = normalize(&text[..start]);
+    // printf put a newline in front of the marker
+    let output = output.strip_suffix('\n').unwrap_or(&output).to_string();
+    Some((output, exit_code))
+}
+
+/// Undo the terminal's CRLF translation
+fn normalize(text: &str) -> String {
+    text.replace("\r\n", "\n")
+}
+
+/// Quote `value` as a single POSIX shell word
+fn single_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_sentinel() {
+        let text = "hello\r\nworld\r\n\r\n__TALKCODY_abc_3__\r\n";
+        assert_eq!(
+            parse_sentinel(text, "abc"),
+            Some(("hello\nworld\n".to_string(), 3))
+        );
+        assert_eq!(parse_sentinel(text, "other"), None);
+        assert_eq!(parse_sentinel("out\r\n__TALKCODY_abc_1", "abc"), None);
+    }
+
+    #[test]
+    fn test_stream_output_keeps_crlf_and_characters_whole() {
+        let streamed = Mutex::new(Vec::new());
+        let collect =
+            |_: OutputStream, chunk: &str| streamed.lock().unwrap().push(chunk.to_string());
+        let buffer = "a\r\né".as_bytes();
+        let mut position = 0;
+
+        stream_output(buffer, &mut position, 2, &collect);
+        assert_eq!(position, 1);
+        stream_output(buffer, &mut position, buffer.len() - 1, &collect);
+        assert_eq!(position, 3);
+        stream_output(buffer, &mut position, buffer.len(), &collect);
+        assert_eq!(*streamed.lock().unwrap(), vec!["a", "\n", "é"]);
+    }
+
+    #[test]
+    fn test_single_quote() {
+        assert_eq!(single_quote("echo hi"), "'echo hi'");
+        assert_eq!(single_quote("echo 'hi'"), "'echo '\\''hi'\\'''");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_session_keeps_state_between_commands() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::create_dir(temp_dir.path().join("sub")).unwrap();
+        let cwd = temp_dir.path().to_string_lossy().to_string();
+        let task_id = "shell-session-test-state";
+        let timeout = Duration::from_secs(10);
+
+        let first = run(
+            task_id,
+            "cd sub && export GREETING='it works'",
+            &cwd,
+            None,
+            timeout,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(first.exit_code, 0);
+
+        let streamed = Mutex::new(String::new());
+        let collect = |_: OutputStream, chunk: &str| streamed.lock().unwrap().push_str(chunk);
+        let second = run(
+            task_id,
+            "echo \"$GREETING\"; basename \"$PWD\"",
+            &cwd,
+            None,
+            timeout,
+            Some(&collect),
+        )
+        .await
+        .unwrap();
+        assert_eq!(second.output, "it works\nsub");
+        assert_eq!(*streamed.lock().unwrap(), second.output);
+
+        let failed = run(task_id, "false", &cwd, None, timeout, None)
+            .await
+            .unwrap();
+        assert_eq!(failed.exit_code, 1);
+
+        assert!(close(task_id));
+        let fresh = run(task_id, "basename \"$PWD\"", &cwd, None, timeout, None)
+            .await
+            .unwrap();
+        assert_ne!(fresh.output, "sub");
+        assert!(close(task_id));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_session_timeout_and_exit() {
+        let temp_dir = TempDir::new().unwrap();
+        let cwd = temp_dir.path().to_string_lossy().to_string();
+        let task_id = "shell-session-test-timeout";
+
+        let slow = run(
+            task_id,
+            "sleep 30",
+            &cwd,
+            None,
+            Duration::from_millis(300),
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(slow.timed_out);
+
+        let exited = run(task_id, "exit 4", &cwd, None, Duration::from_secs(10), None)
+            .await
+            .unwrap();
+        assert!(exited.session_closed);
+        assert!(!close(task_id));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_long_git_log_does_not_page() {
+        if std::process::Command::new("git")
+            .arg("--version")
+            .output()
+            .is_err()
+        {
+            return;
+        }
+        let temp_dir = TempDir::new().unwrap();
+        let cwd = temp_dir.path().to_string_lossy().to_string();
+        let task_id = "shell-session-test-git-log";
+
+        // More commits than the 50-row terminal shows on one page
+        let setup = run(
+            task_id,
+            "git init -q && for i in $(seq 1 80); do \
+             git -c user.name=t -c user.email=t@example.com -c commit.gpgsign=false \
+             commit -q --allow-empty -m \"commit $i\" || exit 1; done",
+            &cwd,
+            None,
+            Duration::from_secs(60),
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(setup.exit_code, 0, "{}", setup.output);
+
+        let log = run(
+            task_id,
+            "git log --oneline",
+            &cwd,
+            None,
+            Duration::from_secs(10),
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(!log.timed_out);
+        assert_eq!(log.exit_code, 0);
+        assert_eq!(log.output.lines().count(), 80);
+        assert!(log.output.ends_with("commit 1"));
+
+        // stdin is /dev/null, so a command reading it gets EOF instead of hanging
+        let read = run(
+            task_id,
+            "read line",
+            &cwd,
+            None,
+            Duration::from_secs(10),
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(!read.timed_out);
+        assert_eq!(read.exit_code, 1);
+        assert!(close(task_id));
+    }
+}
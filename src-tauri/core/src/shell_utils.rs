@@ -64,6 +64,17 @@ pub fn is_powershell(shell: &str) -> bool {
     shell.to_lowercase().contains("powershell") || shell.to_lowercase().contains("pwsh")
 }
 
+/// Length of the longest prefix of `bytes` that does not end inside a UTF-8
+/// character, so streamed output is never split mid-character
+pub fn utf8_complete_prefix_len(bytes: &[u8]) -> usize {
+    match std::str::from_utf8(bytes) {
+        Ok(_) => bytes.len(),
+        // `error_len() == None` means the input ended in the middle of a character
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        Err(_) => bytes.len(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -181,4 +192,13 @@ mod tests {
             std::env::remove_var("COMSPEC");
         }
     }
+
+    #[test]
+    fn test_utf8_complete_prefix_len() {
+        let text = "ok é".as_bytes();
+        assert_eq!(utf8_complete_prefix_len(text), text.len());
+        // Cut inside the two-byte "é"
+        assert_eq!(utf8_complete_prefix_len(&text[..text.len() - 1]), 3);
+        assert_eq!(utf8_complete_prefix_len(b""), 0);
+    }
 }
@@ -413,6 +413,10 @@ mod tests {
                 auto_approve_edits: Some(true),
                 auto_approve_plan: Some(false),
                 auto_code_review: None,
+                agent_loop: None,
+                completion_hooks: None,
+                budget: None,
+                sandbox: None,
                 extra: Default::default(),
             },
             created_at: chrono::Utc::now().timestamp(),
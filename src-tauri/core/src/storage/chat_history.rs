@@ -224,7 +224,8 @@ impl ChatHistoryRepository {
             }
         }
 
-        sql.push_str(" ORDER BY created_at DESC");
+        // Timestamps have second precision; rowid keeps insertion order within a second
+        sql.push_str(" ORDER BY created_at DESC, rowid DESC");
 
         if let Some(limit) = limit {
             sql.push_str(&format!(" LIMIT {}", limit));
@@ -279,6 +280,34 @@ impl ChatHistoryRepository {
         Ok(())
     }
 
+    /// Create several events in one statement
+    pub async fn create_events(&self, events: &[SessionEvent]) -> Result<(), String> {
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let sql = format!(
+            "INSERT INTO events (id, session_id, event_type, payload, created_at) VALUES {}",
+            vec!["(?, ?, ?, ?, ?)"; events.len()].join(", ")
+        );
+        let params = events
+            .iter()
+            .flat_map(|event| {
+                [
+                    serde_json::json!(event.id),
+                    serde_json::json!(event.session_id),
+                    serde_json::json!(event.event_type.as_str()),
+                    serde_json::json!(event.payload.to_string()),
+                    serde_json::json!(event.created_at),
+                ]
+            })
+            .collect();
+
+        self.db.execute(&sql, params).await?;
+
+        Ok(())
+    }
+
     /// Get events for a session, optionally after a specific event ID (for resume)
     pub async fn get_events(
         &self,
@@ -299,15 +328,28 @@ impl ChatHistoryRepository {
                 )
                 .await?;
 
-            if let Some(row) = after_result.rows.first() {
-                if let Some(created_at) = row.get("created_at").and_then(|v| v.as_i64()) {
-                    sql.push_str(" AND created_at > ?");
+            match after_result
+                .rows
+                .first()
+                .and_then(|row| row.get("created_at"))
+                .and_then(|v| v.as_i64())
+            {
+                Some(created_at) => {
+                    // Several events share a second, so break ties by id
+                    sql.push_str(" AND (created_at > ? OR (created_at = ? AND id > ?))");
                     params.push(serde_json::json!(created_at));
+                    params.push(serde_json::json!(created_at));
+                    params.push(serde_json::json!(after_id));
+                }
+                None => {
+                    // Event ids are time-ordered, so an unknown id still marks a position
+                    sql.push_str(" AND id > ?");
+                    params.push(serde_json::json!(after_id));
                 }
             }
         }
 
-        sql.push_str(" ORDER BY created_at ASC");
+        sql.push_str(" ORDER BY created_at ASC, id ASC");
 
         if let Some(limit) = limit {
             sql.push_str(&format!(" LIMIT {}", limit));
@@ -341,6 +383,41 @@ impl ChatHistoryRepository {
 
         Ok(result.rows_affected)
     }
+
+    /// Keep only the newest `keep` events of a session
+    pub async fn prune_session_events(&self, session_id: &str, keep: usize) -> Result<u64, String> {
+        let result = self
+            .db
+            .execute(
+                r#"
+                DELETE FROM events WHERE session_id = ? AND id NOT IN (
+                    SELECT id FROM events WHERE session_id = ?
+                    ORDER BY created_at DESC, id DESC LIMIT ?
+                )
+                "#,
+                vec![
+                    serde_json::json!(session_id),
+                    serde_json::json!(session_id),
+                    serde_json::json!(keep),
+                ],
+            )
+            .await?;
+
+        Ok(result.rows_affected)
+    }
+
+    /// Delete events of all sessions created before a timestamp
+    pub async fn delete_expired_events(&self, before_timestamp: i64) -> Result<u64, String> {
+        let result = self
+            .db
+            .execute(
+                "DELETE FROM events WHERE created_at < ?",
+                vec![serde_json::json!(before_timestamp)],
+            )
+            .await?;
+
+        Ok(result.rows_affected)
+    }
 }
 
 // ============== Row Conversions ==============
@@ -573,4 +650,100 @@ mod tests {
         assert_eq!(messages.len(), 1);
         assert_eq!(messages[0].id, "msg-1");
     }
+
+    #[tokio::test]
+    async fn test_get_events_after_id() {
+        let (db, _temp) = create_test_db().await;
+        let repo = ChatHistoryRepository::new(db);
+
+        let session = Session {
+            id: "test-session-4".to_string(),
+            project_id: None,
+            title: None,
+            status: SessionStatus::Created,
+            created_at: 0,
+            updated_at: 0,
+            last_event_id: None,
+            metadata: None,
+        };
+        repo.create_session(&session)
+            .await
+            .expect("Failed to create session");
+
+        for i in 1..=3 {
+            repo.create_event(&SessionEvent {
+                id: format!("evt_{}", i),
+                session_id: "test-session-4".to_string(),
+                event_type: EventType::Token,
+                payload: serde_json::json!({ "token": i.to_string() }),
+                created_at: 100,
+            })
+            .await
+            .expect("Failed to create event");
+        }
+
+        let ids = |events: Vec<SessionEvent>| events.into_iter().map(|e| e.id).collect::<Vec<_>>();
+
+        // Same-second events after a stored id
+        let events = repo
+            .get_events("test-session-4", Some("evt_1"), None)
+            .await
+            .unwrap();
+        assert_eq!(ids(events), vec!["evt_2", "evt_3"]);
+
+        // An id that was never persisted still marks a position
+        let events = repo
+            .get_events("test-session-4", Some("evt_25"), None)
+            .await
+            .unwrap();
+        assert_eq!(ids(events), vec!["evt_3"]);
+    }
+
+    #[tokio::test]
+    async fn test_event_batches_and_retention() {
+        let (db, _temp) = create_test_db().await;
+        let repo = ChatHistoryRepository::new(db);
+
+        let session = Session {
+            id: "test-session-5".to_string(),
+            project_id: None,
+            title: None,
+            status: SessionStatus::Created,
+            created_at: 0,
+            updated_at: 0,
+            last_event_id: None,
+            metadata: None,
+        };
+        repo.create_session(&session)
+            .await
+            .expect("Failed to create session");
+
+        let events: Vec<SessionEvent> = (1..=5)
+            .map(|i| SessionEvent {
+                id: format!("evt_{}", i),
+                session_id: "test-session-5".to_string(),
+                event_type: EventType::Token,
+                payload: serde_json::json!({ "token": i.to_string() }),
+                created_at: 100 * i,
+            })
+            .collect();
+        repo.create_events(&events)
+            .await
+            .expect("Failed to create events");
+
+        let ids = |events: Vec<SessionEvent>| events.into_iter().map(|e| e.id).collect::<Vec<_>>();
+
+        let pruned = repo
+            .prune_session_events("test-session-5", 3)
+            .await
+            .unwrap();
+        assert_eq!(pruned, 2);
+        let events = repo.get_events("test-session-5", None, None).await.unwrap();
+        assert_eq!(ids(events), vec!["evt_3", "evt_4", "evt_5"]);
+
+        let expired = repo.delete_expired_events(500).await.unwrap();
+        assert_eq!(expired, 2);
+        let events = repo.get_events("test-session-5", None, None).await.unwrap();
+        assert_eq!(ids(events), vec!["evt_5"]);
+    }
 }
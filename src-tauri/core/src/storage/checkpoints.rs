@@ -0,0 +1,270 @@
+//! File Checkpoints Repository
+//! Records snapshots of files taken before agent tools change them, in
+//! chat_history.db, and keeps the snapshot contents on the file system
+
+use crate::database::Database;
+use crate::storage::models::FileCheckpoint;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Repository for file checkpoint operations
+#[derive(Clone)]
+pub struct CheckpointsRepository {
+    db: Arc<Database>,
+    storage_root: PathBuf,
+}
+
+impl CheckpointsRepository {
+    pub fn new(db: Arc<Database>, storage_root: PathBuf) -> Self {
+        Self { db, storage_root }
+    }
+
+    /// Get the storage path for a checkpoint's snapshot
+    fn snapshot_path(&self, checkpoint: &FileCheckpoint) -> PathBuf {
+        self.storage_root
+            .join(&checkpoint.task_id)
+            .join(&checkpoint.id)
+    }
+
+    /// Store a snapshot and record it as the task's next checkpoint
+    ///
+    /// `content` is `None` when the file did not exist before the tool ran.
+    /// The `sequence` of the given checkpoint is ignored; it is assigned here.
+    pub async fn create_checkpoint(
+        &self,
+        checkpoint: &FileCheckpoint,
+        content: Option<&[u8]>,
+    ) -> Result<(), String> {
+        if let Some(data) = content {
+            let file_path = self.snapshot_path(checkpoint);
+            if let Some(parent) = file_path.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| format!("Failed to create checkpoint directory: {}", e))?;
+            }
+
+            // Write file atomically using temp file + rename
+            let temp_path = file_path.with_extension("tmp");
+            std::fs::write(&temp_path, data)
+                .map_err(|e| format!("Failed to write checkpoint file: {}", e))?;
+            std::fs::rename(&temp_path, &file_path)
+                .map_err(|e| format!("Failed to finalize checkpoint file: {}", e))?;
+        }
+
+        let sql = r#"
+            INSERT INTO file_checkpoints (id, task_id, session_id, tool_call_id, tool_name, file_path, existed, size, sequence, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?,
+                (SELECT COALESCE(MAX(sequence), 0) + 1 FROM file_checkpoints WHERE task_id = ?), ?)
+        "#;
+
+        self.db
+            .execute(
+                sql,
+                vec![
+                    serde_json::json!(checkpoint.id),
+                    serde_json::json!(checkpoint.task_id),
+                    serde_json::json!(checkpoint.session_id),
+                    serde_json::json!(checkpoint.tool_call_id),
+                    serde_json::json!(checkpoint.tool_name),
+                    serde_json::json!(checkpoint.file_path),
+                    serde_json::json!(checkpoint.existed),
+                    serde_json::json!(content.map(|data| data.len()).unwrap_or(0)),
+                    serde_json::json!(checkpoint.task_id),
+                    serde_json::json!(checkpoint.created_at),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get checkpoint metadata by ID
+    pub async fn get_checkpoint(
+        &self,
+        checkpoint_id: &str,
+    ) -> Result<Option<FileCheckpoint>, String> {
+        let result = self
+            .db
+            .query(
+                "SELECT * FROM file_checkpoints WHERE id = ?",
+                vec![serde_json::json!(checkpoint_id)],
+            )
+            .await?;
+
+        Ok(result.rows.first().map(row_to_checkpoint))
+    }
+
+    /// List a task's checkpoints, oldest first
+    pub async fn list_checkpoints(&self, task_id: &str) -> Result<Vec<FileCheckpoint>, String> {
+        let result = self
+            .db
+            .query(
+                "SELECT * FROM file_checkpoints WHERE task_id = ? ORDER BY sequence ASC",
+                vec![serde_json::json!(task_id)],
+            )
+            .await?;
+
+        Ok(result.rows.iter().map(row_to_checkpoint).collect())
+    }
+
+    /// Read the file contents captured by a checkpoint, or `None` if the file
+    /// did not exist at the time
+    pub async fn read_snapshot(
+        &self,
+        checkpoint: &FileCheckpoint,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if !checkpoint.existed {
+            return Ok(None);
+        }
+
+        let data = std::fs::read(self.snapshot_path(checkpoint))
+            .map_err(|e| format!("Failed to read checkpoint file: {}", e))?;
+
+        Ok(Some(data))
+    }
+
+    /// Delete the checkpoints taken for one tool call, e.g. when it failed
+    /// without changing anything
+    pub async fn delete_tool_call_checkpoints(
+        &self,
+        task_id: &str,
+        tool_call_id: &str,
+    ) -> Result<u64, String> {
+        let result = self
+            .db
+            .query(
+                "SELECT * FROM file_checkpoints WHERE task_id = ? AND tool_call_id = ?",
+                vec![serde_json::json!(task_id), serde_json::json!(tool_call_id)],
+            )
+            .await?;
+
+        for checkpoint in result.rows.iter().map(row_to_checkpoint) {
+            let _ = std::fs::remove_file(self.snapshot_path(&checkpoint));
+        }
+
+        let result = self
+            .db
+            .execute(
+                "DELETE FROM file_checkpoints WHERE task_id = ? AND tool_call_id = ?",
+                vec![serde_json::json!(task_id), serde_json::json!(tool_call_id)],
+            )
+            .await?;
+
+        Ok(result.rows_affected)
+    }
+}
+
+// ============== Row Conversion ==============
+
+fn row_to_checkpoint(row: &serde_json::Value) -> FileCheckpoint {
+    let text = |key: &str| {
+        row.get(key)
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string()
+    };
+    let number = |key: &str| row.get(key).and_then(|v| v.as_i64()).unwrap_or(0);
+
+    FileCheckpoint {
+        id: text("id"),
+        task_id: text("task_id"),
+        session_id: text("session_id"),
+        tool_call_id: text("tool_call_id"),
+        tool_name: text("tool_name"),
+        file_path: text("file_path"),
+        existed: number("existed") != 0,
+        size: number("size"),
+        sequence: number("sequence"),
+        created_at: number("created_at"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use tempfile::TempDir;
+
+    async fn create_test_repo() -> (CheckpointsRepository, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let db = Arc::new(Database::new(db_path.to_string_lossy().to_string()));
+        db.connect()
+            .await
+            .expect("Failed to connect to test database");
+
+        // Run migrations
+        let migrations = super::super::migrations::chat_history_migrations();
+        let runner = super::super::migrations::MigrationRunner::new(&db, &migrations);
+        runner.init().await.expect("Failed to init migrations");
+        runner.migrate().await.expect("Failed to run migrations");
+
+        let storage_root = temp_dir.path().join("checkpoints");
+        let repo = CheckpointsRepository::new(db, storage_root);
+
+        (repo, temp_dir)
+    }
+
+    fn checkpoint(id: &str, tool_call_id: &str, existed: bool) -> FileCheckpoint {
+        FileCheckpoint {
+            id: id.to_string(),
+            task_id: "task-1".to_string(),
+            session_id: "session-1".to_string(),
+            tool_call_id: tool_call_id.to_string(),
+            tool_name: "writeFile".to_string(),
+            file_path: "/workspace/a.txt".to_string(),
+            existed,
+            size: 0,
+            sequence: 0,
+            created_at: chrono::Utc::now().timestamp(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_and_list_checkpoints() {
+        let (repo, _temp) = create_test_repo().await;
+
+        repo.create_checkpoint(&checkpoint("cp-1", "call-1", false), None)
+            .await
+            .expect("Failed to create checkpoint");
+        repo.create_checkpoint(
+            &checkpoint("cp-2", "call-2", true),
+            Some(b"hello".as_slice()),
+        )
+        .await
+        .expect("Failed to create checkpoint");
+
+        let checkpoints = repo.list_checkpoints("task-1").await.unwrap();
+        assert_eq!(checkpoints.len(), 2);
+        assert_eq!(checkpoints[0].id, "cp-1");
+        assert_eq!(checkpoints[0].sequence, 1);
+        assert!(!checkpoints[0].existed);
+        assert_eq!(checkpoints[1].sequence, 2);
+        assert_eq!(checkpoints[1].size, 5);
+
+        assert_eq!(repo.read_snapshot(&checkpoints[0]).await.unwrap(), None);
+        assert_eq!(
+            repo.read_snapshot(&checkpoints[1]).await.unwrap(),
+            Some(b"hello".to_vec())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_tool_call_checkpoints() {
+        let (repo, _temp) = create_test_repo().await;
+
+        repo.create_checkpoint(&checkpoint("cp-1", "call-1", true), Some(b"one".as_slice()))
+            .await
+            .unwrap();
+        repo.create_checkpoint(&checkpoint("cp-2", "call-2", true), Some(b"two".as_slice()))
+            .await
+            .unwrap();
+
+        let deleted = repo
+            .delete_tool_call_checkpoints("task-1", "call-1")
+            .await
+            .unwrap();
+        assert_eq!(deleted, 1);
+        assert!(repo.get_checkpoint("cp-1").await.unwrap().is_none());
+        assert!(repo.get_checkpoint("cp-2").await.unwrap().is_some());
+    }
+}
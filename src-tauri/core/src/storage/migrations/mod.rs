@@ -214,6 +214,109 @@ pub fn chat_history_migrations() -> MigrationRegistry {
         down_sql: Some("DROP INDEX IF EXISTS idx_attachments_message;"),
     });
 
+    // Migration 6: Durable task records (state history, usage, model)
+    registry.register(Migration {
+        version: 6,
+        name: "create_tasks_table",
+        up_sql: r#"
+            CREATE TABLE tasks (
+                id TEXT PRIMARY KEY,
+                session_id TEXT NOT NULL,
+                agent_id TEXT,
+                state TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                started_at INTEGER,
+                completed_at INTEGER,
+                error_message TEXT,
+                model TEXT,
+                input_tokens INTEGER NOT NULL DEFAULT 0,
+                output_tokens INTEGER NOT NULL DEFAULT 0,
+                transitions TEXT NOT NULL DEFAULT '[]',
+                FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+            );
+            CREATE INDEX idx_tasks_session ON tasks(session_id);
+            CREATE INDEX idx_tasks_state ON tasks(state);
+            CREATE INDEX idx_tasks_created ON tasks(created_at);
+        "#,
+        down_sql: Some("DROP TABLE tasks;"),
+    });
+
+    // Migrations 7-8: Prompt cache usage per task.
+    // The runner executes one statement per migration, so each column gets its own.
+    registry.register(Migration {
+        version: 7,
+        name: "add_cached_input_tokens_to_tasks",
+        up_sql: "ALTER TABLE tasks ADD COLUMN cached_input_tokens INTEGER NOT NULL DEFAULT 0;",
+        down_sql: Some("ALTER TABLE tasks DROP COLUMN cached_input_tokens;"),
+    });
+
+    registry.register(Migration {
+        version: 8,
+        name: "add_cache_creation_input_tokens_to_tasks",
+        up_sql: r#"
+            ALTER TABLE tasks ADD COLUMN cache_creation_input_tokens INTEGER NOT NULL DEFAULT 0;
+        "#,
+        down_sql: Some("ALTER TABLE tasks DROP COLUMN cache_creation_input_tokens;"),
+    });
+
+    // Migration 9: Running cost per task
+    registry.register(Migration {
+        version: 9,
+        name: "add_cost_to_tasks",
+        up_sql: "ALTER TABLE tasks ADD COLUMN cost_usd REAL NOT NULL DEFAULT 0;",
+        down_sql: Some("ALTER TABLE tasks DROP COLUMN cost_usd;"),
+    });
+
+    // Migration 10: Per-call usage for cost reports and budgets
+    registry.register(Migration {
+        version: 10,
+        name: "create_usage_records_table",
+        up_sql: r#"
+            CREATE TABLE usage_records (
+                id TEXT PRIMARY KEY,
+                task_id TEXT NOT NULL,
+                session_id TEXT NOT NULL,
+                project_id TEXT,
+                model TEXT,
+                input_tokens INTEGER NOT NULL DEFAULT 0,
+                output_tokens INTEGER NOT NULL DEFAULT 0,
+                cached_input_tokens INTEGER NOT NULL DEFAULT 0,
+                cache_creation_input_tokens INTEGER NOT NULL DEFAULT 0,
+                cost_usd REAL NOT NULL DEFAULT 0,
+                created_at INTEGER NOT NULL
+            );
+        "#,
+        down_sql: Some("DROP TABLE usage_records;"),
+    });
+
+    // Migrations 11-12: File snapshots taken before agent edits
+    registry.register(Migration {
+        version: 11,
+        name: "create_file_checkpoints_table",
+        up_sql: r#"
+            CREATE TABLE file_checkpoints (
+                id TEXT PRIMARY KEY,
+                task_id TEXT NOT NULL,
+                session_id TEXT NOT NULL,
+                tool_call_id TEXT NOT NULL,
+                tool_name TEXT NOT NULL,
+                file_path TEXT NOT NULL,
+                existed INTEGER NOT NULL,
+                size INTEGER NOT NULL DEFAULT 0,
+                sequence INTEGER NOT NULL,
+                created_at INTEGER NOT NULL
+            );
+        "#,
+        down_sql: Some("DROP TABLE file_checkpoints;"),
+    });
+
+    registry.register(Migration {
+        version: 12,
+        name: "index_file_checkpoints_by_task",
+        up_sql: "CREATE INDEX idx_file_checkpoints_task ON file_checkpoints(task_id, sequence);",
+        down_sql: Some("DROP INDEX IF EXISTS idx_file_checkpoints_task;"),
+    });
+
     registry
 }
 
@@ -326,7 +429,7 @@ mod tests {
     #[test]
     fn test_chat_history_migrations_count() {
         let registry = chat_history_migrations();
-        assert_eq!(registry.migrations().len(), 5);
+        assert_eq!(registry.migrations().len(), 12);
     }
 
     #[test]
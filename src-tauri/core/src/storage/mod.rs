@@ -1,7 +1,8 @@
 //! Storage Layer for Cloud Backend
 //!
 //! Provides SQLite repositories for:
-//! - chat_history.db: Sessions, messages, events, attachments
+//! - chat_history.db: Sessions, messages, events, attachments, tasks, usage,
+//!   file checkpoints
 //! - agents.db: Agent configurations and agent-session associations  
 //! - settings.db: Application settings and task-specific settings
 //!
@@ -10,9 +11,12 @@
 pub mod agents;
 pub mod attachments;
 pub mod chat_history;
+pub mod checkpoints;
 pub mod migrations;
 pub mod models;
 pub mod settings;
+pub mod tasks;
+pub mod usage;
 
 use crate::database::Database;
 use std::path::PathBuf;
@@ -21,8 +25,11 @@ use std::sync::Arc;
 pub use agents::{AgentUpdates, AgentsRepository};
 pub use attachments::AttachmentsRepository;
 pub use chat_history::ChatHistoryRepository;
+pub use checkpoints::CheckpointsRepository;
 pub use models::*;
 pub use settings::SettingsRepository;
+pub use tasks::TasksRepository;
+pub use usage::UsageRepository;
 
 /// Main storage manager that owns all repositories
 /// Provides unified access to all database operations
@@ -36,6 +43,12 @@ pub struct Storage {
     pub settings: SettingsRepository,
     /// Attachments repository (chat_history.db + filesystem)
     pub attachments: AttachmentsRepository,
+    /// Task records repository (chat_history.db)
+    pub tasks: TasksRepository,
+    /// Usage and cost records repository (chat_history.db)
+    pub usage: UsageRepository,
+    /// File checkpoints repository (chat_history.db + filesystem)
+    pub checkpoints: CheckpointsRepository,
 }
 
 impl Storage {
@@ -76,9 +89,16 @@ impl Storage {
             .await
             .map_err(|e| format!("Failed to run database migrations: {}", e))?;
 
+        // API keys, settings and history stay out of reach of sandboxed commands
+        crate::platform::sandbox::hide_path(&data_root);
+
         // Create repositories
-        // Clone chat_history_db for attachments (both use the same DB)
+        // Clone chat_history_db for attachments, tasks, usage and checkpoints (all use the same DB)
         let chat_history_db_for_attachments = chat_history_db.clone();
+        let tasks = TasksRepository::new(chat_history_db.clone());
+        let usage = UsageRepository::new(chat_history_db.clone());
+        let checkpoints =
+            CheckpointsRepository::new(chat_history_db.clone(), data_root.join("checkpoints"));
         let chat_history = ChatHistoryRepository::new(chat_history_db);
         let agents = AgentsRepository::new(agents_db);
         let settings = SettingsRepository::new(settings_db);
@@ -90,6 +110,9 @@ impl Storage {
             agents,
             settings,
             attachments,
+            tasks,
+            usage,
+            checkpoints,
         })
     }
 
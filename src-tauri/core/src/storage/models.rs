@@ -136,6 +136,121 @@ pub enum MessageContent {
     ToolCalls { calls: Vec<ToolCall> },
     #[serde(rename = "tool_result")]
     ToolResult { result: StoredToolResult },
+    /// Text mixed with attachments, e.g. a user message with images
+    #[serde(rename = "parts")]
+    Parts { parts: Vec<MessagePart> },
+}
+
+impl MessageContent {
+    /// Text content, or parts when attachments come with the text
+    pub fn with_attachments(text: String, attachments: Vec<MessagePart>) -> Self {
+        if attachments.is_empty() {
+            return MessageContent::Text { text };
+        }
+        let text_part = (!text.is_empty()).then_some(MessagePart::Text { text });
+        MessageContent::Parts {
+            parts: text_part.into_iter().chain(attachments).collect(),
+        }
+    }
+
+    /// Text content, or parts when the model's reasoning comes with the text
+    pub fn with_reasoning(reasoning: Vec<MessagePart>, text: String) -> Self {
+        if reasoning.is_empty() {
+            return MessageContent::Text { text };
+        }
+        let text_part = (!text.is_empty()).then_some(MessagePart::Text { text });
+        MessageContent::Parts {
+            parts: reasoning.into_iter().chain(text_part).collect(),
+        }
+    }
+
+    /// The text of this content, with text parts joined together.
+    /// `None` for tool calls, tool results and parts without text.
+    pub fn text(&self) -> Option<String> {
+        match self {
+            MessageContent::Text { text } => Some(text.clone()),
+            MessageContent::Parts { parts } => {
+                let texts: Vec<&str> = parts
+                    .iter()
+                    .filter_map(|part| match part {
+                        MessagePart::Text { text } => Some(text.as_str()),
+                        _ => None,
+                    })
+                    .collect();
+                (!texts.is_empty()).then(|| texts.concat())
+            }
+            _ => None,
+        }
+    }
+
+    /// The model's reasoning carried in the parts, if any
+    pub fn reasoning_text(&self) -> Option<String> {
+        let MessageContent::Parts { parts } = self else {
+            return None;
+        };
+        let texts: Vec<&str> = parts
+            .iter()
+            .filter_map(|part| match part {
+                MessagePart::Reasoning { text, .. } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        (!texts.is_empty()).then(|| texts.concat())
+    }
+
+    /// IDs of the attachments referenced by this content
+    pub fn attachment_ids(&self) -> Vec<&str> {
+        match self {
+            MessageContent::Parts { parts } => parts
+                .iter()
+                .filter_map(MessagePart::attachment_id)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Part of a multimodal message. Media parts reference an uploaded `Attachment`;
+/// reasoning parts keep the provider metadata needed to replay them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum MessagePart {
+    Text {
+        text: String,
+    },
+    Image {
+        #[serde(rename = "attachmentId")]
+        attachment_id: AttachmentId,
+    },
+    File {
+        #[serde(rename = "attachmentId")]
+        attachment_id: AttachmentId,
+    },
+    Audio {
+        #[serde(rename = "attachmentId")]
+        attachment_id: AttachmentId,
+    },
+    Reasoning {
+        text: String,
+        /// Signatures or encrypted content the provider expects back, e.g. `anthropic.signature`
+        #[serde(
+            default,
+            rename = "providerMetadata",
+            skip_serializing_if = "Option::is_none"
+        )]
+        provider_metadata: Option<serde_json::Value>,
+    },
+}
+
+impl MessagePart {
+    pub fn attachment_id(&self) -> Option<&str> {
+        match self {
+            MessagePart::Text { .. } | MessagePart::Reasoning { .. } => None,
+            MessagePart::Image { attachment_id }
+            | MessagePart::File { attachment_id }
+            | MessagePart::Audio { attachment_id } => Some(attachment_id),
+        }
+    }
 }
 
 /// Stored format for tool call
@@ -178,6 +293,9 @@ pub struct ToolCall {
     pub id: ToolCallId,
     pub name: String,
     pub input: serde_json::Value,
+    /// Provider data to send back with the call, e.g. Gemini thought signatures
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider_metadata: Option<serde_json::Value>,
 }
 
 /// Event types for streaming
@@ -190,12 +308,32 @@ pub enum EventType {
     Token,
     /// Final message content
     MessageFinal,
+    /// Model started writing a tool call
+    ToolCallStart,
+    /// Partial arguments of a tool call
+    ToolCallDelta,
     /// Tool call requested
     ToolCall,
+    /// Output chunk from a running tool
+    ToolOutput,
     /// Tool execution result
     ToolResult,
     /// Error occurred
     Error,
+    /// Model started reasoning
+    ReasoningStart,
+    /// Reasoning text chunk
+    ReasoningDelta,
+    /// Model finished reasoning
+    ReasoningEnd,
+    /// Message stored in the session
+    MessageCreated,
+    /// Task moved to a new state
+    TaskStateChanged,
+    /// Task finished
+    TaskCompleted,
+    /// Event from a sub-agent, wrapped for the parent session
+    SubAgentEvent,
 }
 
 impl EventType {
@@ -204,9 +342,19 @@ impl EventType {
             EventType::Status => "status",
             EventType::Token => "token",
             EventType::MessageFinal => "message.final",
+            EventType::ToolCallStart => "tool.call.start",
+            EventType::ToolCallDelta => "tool.call.delta",
             EventType::ToolCall => "tool.call",
+            EventType::ToolOutput => "tool.output",
             EventType::ToolResult => "tool.result",
             EventType::Error => "error",
+            EventType::ReasoningStart => "reasoning.start",
+            EventType::ReasoningDelta => "reasoning.delta",
+            EventType::ReasoningEnd => "reasoning.end",
+            EventType::MessageCreated => "message.created",
+            EventType::TaskStateChanged => "task.state_changed",
+            EventType::TaskCompleted => "task.completed",
+            EventType::SubAgentEvent => "subagent.event",
         }
     }
 }
@@ -219,9 +367,19 @@ impl std::str::FromStr for EventType {
             "status" => Ok(EventType::Status),
             "token" => Ok(EventType::Token),
             "message.final" => Ok(EventType::MessageFinal),
+            "tool.call.start" => Ok(EventType::ToolCallStart),
+            "tool.call.delta" => Ok(EventType::ToolCallDelta),
             "tool.call" => Ok(EventType::ToolCall),
+            "tool.output" => Ok(EventType::ToolOutput),
             "tool.result" => Ok(EventType::ToolResult),
             "error" => Ok(EventType::Error),
+            "reasoning.start" => Ok(EventType::ReasoningStart),
+            "reasoning.delta" => Ok(EventType::ReasoningDelta),
+            "reasoning.end" => Ok(EventType::ReasoningEnd),
+            "message.created" => Ok(EventType::MessageCreated),
+            "task.state_changed" => Ok(EventType::TaskStateChanged),
+            "task.completed" => Ok(EventType::TaskCompleted),
+            "subagent.event" => Ok(EventType::SubAgentEvent),
             _ => Err(format!("Unknown event type: {}", s)),
         }
     }
@@ -272,11 +430,92 @@ pub struct TaskSettings {
     pub auto_approve_plan: Option<bool>,
     /// Enable auto code review
     pub auto_code_review: Option<bool>,
+    /// Agent loop overrides (iteration cap, sampling, tool filtering)
+    pub agent_loop: Option<AgentLoopSettings>,
+    /// Completion hooks to run when the agent finishes, by name (unset uses the defaults)
+    pub completion_hooks: Option<Vec<String>>,
+    /// Spending limits that stop the task when exceeded
+    pub budget: Option<BudgetSettings>,
+    /// Sandbox policy for shell commands (unset runs them unsandboxed)
+    pub sandbox: Option<SandboxSettings>,
     /// Additional custom settings
     #[serde(flatten)]
     pub extra: HashMap<String, serde_json::Value>,
 }
 
+/// Per-task overrides for the agent loop. Unset fields keep the runtime defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentLoopSettings {
+    /// Maximum number of model turns before the task stops
+    pub max_iterations: Option<u32>,
+    /// Sampling temperature
+    pub temperature: Option<f32>,
+    /// Maximum tokens per model response
+    pub max_tokens: Option<u32>,
+    /// Whether tools are offered to the model at all
+    pub enable_tools: Option<bool>,
+    /// Only these tools are offered to the model (empty or unset means all)
+    pub allowed_tools: Option<Vec<String>>,
+    /// These tools are never offered to the model
+    pub disallowed_tools: Option<Vec<String>>,
+    /// Fraction of the model's context window that triggers compaction, above 0 and at most 1
+    pub compaction_threshold: Option<f32>,
+    /// Provider request options, e.g. `anthropic.cacheControl` or `openai.promptCacheKey`
+    pub provider_options: Option<serde_json::Value>,
+    /// Reasoning effort for models that think before answering: "low", "medium" or "high"
+    pub reasoning_effort: Option<String>,
+    /// Token budget for extended thinking (overrides the budget implied by the effort)
+    pub reasoning_budget_tokens: Option<u32>,
+}
+
+/// Spending limits for a task. Unset limits are not enforced.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetSettings {
+    /// Maximum cost of this task in USD
+    pub max_task_cost_usd: Option<f64>,
+    /// Maximum input plus output tokens of this task
+    pub max_task_tokens: Option<i64>,
+    /// Maximum cost of all tasks in the current UTC day in USD
+    pub max_daily_cost_usd: Option<f64>,
+    /// Maximum input plus output tokens of all tasks in the current UTC day
+    pub max_daily_tokens: Option<i64>,
+}
+
+/// Sandbox policy for the bash tool and background tasks. The workspace and
+/// worktree stay writable; the rest of the filesystem is read-only.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SandboxSettings {
+    /// Whether commands run sandboxed (unset means off)
+    pub mode: Option<SandboxMode>,
+    /// Allow network access from sandboxed commands (default false)
+    pub allow_network: Option<bool>,
+    /// Additional absolute paths that stay writable
+    pub writable_paths: Option<Vec<String>>,
+    /// CPU time limit per command in seconds
+    pub max_cpu_seconds: Option<u64>,
+    /// Address space limit per command in megabytes
+    pub max_memory_mb: Option<u64>,
+    /// Maximum number of processes per command, counted within the command's
+    /// sandbox rather than across all of the user's processes
+    pub max_processes: Option<u64>,
+}
+
+/// How strictly shell commands are sandboxed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SandboxMode {
+    /// Run commands directly with the user's privileges
+    #[default]
+    Off,
+    /// Sandbox when the host supports it, otherwise run directly
+    Auto,
+    /// Refuse to run commands when no sandbox is available
+    Required,
+}
+
 /// Attachment/file upload metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -345,6 +584,173 @@ pub struct WorkspaceInfo {
     pub branch: Option<String>,
 }
 
+/// Persisted record of a runtime task, kept after the task ends
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskRecord {
+    pub id: TaskId,
+    pub session_id: SessionId,
+    pub agent_id: Option<AgentId>,
+    /// Runtime task state (`pending`, `running`, `waitingForUser`, `completed`, ...)
+    pub state: String,
+    pub created_at: i64,
+    pub started_at: Option<i64>,
+    pub completed_at: Option<i64>,
+    pub error_message: Option<String>,
+    /// Model the task ran with
+    pub model: Option<String>,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    /// Part of `input_tokens` read from the provider's prompt cache
+    #[serde(default)]
+    pub cached_input_tokens: i64,
+    /// Part of `input_tokens` written to the provider's prompt cache
+    #[serde(default)]
+    pub cache_creation_input_tokens: i64,
+    /// Cost of the task so far in USD
+    #[serde(default)]
+    pub cost_usd: f64,
+    /// Every state the task went through, oldest first
+    pub transitions: Vec<TaskStateTransition>,
+}
+
+impl TaskRecord {
+    /// Share of input tokens served from the prompt cache, if any input was sent
+    pub fn cache_hit_ratio(&self) -> Option<f64> {
+        (self.input_tokens > 0).then(|| self.cached_input_tokens as f64 / self.input_tokens as f64)
+    }
+}
+
+/// A single task state change
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskStateTransition {
+    pub state: String,
+    pub at: i64,
+}
+
+/// Token usage and cost of one model call, the unit usage reports aggregate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageRecord {
+    pub id: String,
+    pub task_id: TaskId,
+    pub session_id: SessionId,
+    pub project_id: Option<String>,
+    /// Model that served the call
+    pub model: Option<String>,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cached_input_tokens: i64,
+    pub cache_creation_input_tokens: i64,
+    pub cost_usd: f64,
+    pub created_at: i64,
+}
+
+/// Aggregated token and cost totals
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageTotals {
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cached_input_tokens: i64,
+    pub cache_creation_input_tokens: i64,
+    pub cost_usd: f64,
+    /// Number of model calls included
+    pub requests: i64,
+}
+
+impl UsageTotals {
+    /// Input plus output tokens, the unit token budgets are measured in
+    pub fn total_tokens(&self) -> i64 {
+        self.input_tokens + self.output_tokens
+    }
+}
+
+/// Dimension a usage report is grouped by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UsageGroupBy {
+    Task,
+    Session,
+    Project,
+    Model,
+    /// UTC calendar day (`YYYY-MM-DD`)
+    Day,
+}
+
+impl std::str::FromStr for UsageGroupBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "task" => Ok(UsageGroupBy::Task),
+            "session" => Ok(UsageGroupBy::Session),
+            "project" => Ok(UsageGroupBy::Project),
+            "model" => Ok(UsageGroupBy::Model),
+            "day" => Ok(UsageGroupBy::Day),
+            _ => Err(format!("Unknown usage grouping: {}", s)),
+        }
+    }
+}
+
+/// Filters for usage totals and reports
+#[derive(Debug, Clone, Default)]
+pub struct UsageFilter {
+    pub task_id: Option<TaskId>,
+    pub session_id: Option<SessionId>,
+    pub project_id: Option<String>,
+    pub model: Option<String>,
+    /// Only usage recorded at or after this unix timestamp
+    pub since: Option<i64>,
+    /// Only usage recorded before this unix timestamp
+    pub until: Option<i64>,
+}
+
+/// Totals for one group of a usage report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageReportEntry {
+    /// Group value (task ID, session ID, project ID, model or day); `None` when unset
+    pub key: Option<String>,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+}
+
+/// Filters for listing task records
+#[derive(Debug, Clone, Default)]
+pub struct TaskFilter {
+    pub session_id: Option<SessionId>,
+    pub state: Option<String>,
+    /// Only tasks created at or after this unix timestamp
+    pub created_after: Option<i64>,
+    /// Only tasks created before this unix timestamp
+    pub created_before: Option<i64>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+/// Snapshot of a file taken just before a tool call changed it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileCheckpoint {
+    pub id: String,
+    pub task_id: TaskId,
+    pub session_id: SessionId,
+    pub tool_call_id: String,
+    pub tool_name: String,
+    /// Absolute path of the changed file
+    pub file_path: String,
+    /// Whether the file existed before the tool ran; restoring a checkpoint
+    /// of a file that did not exist deletes it
+    pub existed: bool,
+    /// Size of the snapshot in bytes
+    pub size: i64,
+    /// Position of the checkpoint among its task's checkpoints, starting at 1
+    pub sequence: i64,
+    pub created_at: i64,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -369,6 +775,86 @@ mod tests {
         assert!(json.contains("\"text\":\"Hello\""));
     }
 
+    #[test]
+    fn test_message_content_parts_serialization() {
+        let content = MessageContent::Parts {
+            parts: vec![
+                MessagePart::Text {
+                    text: "What is this?".to_string(),
+                },
+                MessagePart::Image {
+                    attachment_id: "att_1".to_string(),
+                },
+            ],
+        };
+        let json = serde_json::to_value(&content).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "type": "parts",
+                "parts": [
+                    { "type": "text", "text": "What is this?" },
+                    { "type": "image", "attachmentId": "att_1" }
+                ]
+            })
+        );
+
+        let parsed: MessageContent = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.attachment_ids(), vec!["att_1"]);
+    }
+
+    #[test]
+    fn test_message_content_with_attachments() {
+        assert!(matches!(
+            MessageContent::with_attachments("hi".to_string(), vec![]),
+            MessageContent::Text { text } if text == "hi"
+        ));
+
+        let image = MessagePart::Image {
+            attachment_id: "att_1".to_string(),
+        };
+        let MessageContent::Parts { parts } =
+            MessageContent::with_attachments(String::new(), vec![image.clone()])
+        else {
+            panic!("expected parts");
+        };
+        assert_eq!(parts, vec![image]);
+    }
+
+    #[test]
+    fn test_message_content_with_reasoning() {
+        let reasoning = MessagePart::Reasoning {
+            text: "Check the file first".to_string(),
+            provider_metadata: Some(serde_json::json!({ "anthropic": { "signature": "sig" } })),
+        };
+        let content = MessageContent::with_reasoning(vec![reasoning], "Done".to_string());
+        let json = serde_json::to_value(&content).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "type": "parts",
+                "parts": [
+                    {
+                        "type": "reasoning",
+                        "text": "Check the file first",
+                        "providerMetadata": { "anthropic": { "signature": "sig" } }
+                    },
+                    { "type": "text", "text": "Done" }
+                ]
+            })
+        );
+        assert!(content.attachment_ids().is_empty());
+        assert_eq!(content.text().as_deref(), Some("Done"));
+        assert_eq!(
+            content.reasoning_text().as_deref(),
+            Some("Check the file first")
+        );
+
+        let tool_calls = MessageContent::ToolCalls { calls: vec![] };
+        assert_eq!(tool_calls.text(), None);
+        assert_eq!(tool_calls.reasoning_text(), None);
+    }
+
     #[test]
     fn test_task_settings_with_extra() {
         let mut settings = TaskSettings::default();
@@ -381,4 +867,29 @@ mod tests {
         assert!(json.contains("\"autoApproveEdits\":true"));
         assert!(json.contains("\"custom_key\""));
     }
+
+    #[test]
+    fn test_task_settings_agent_loop() {
+        let json = serde_json::json!({
+            "autoApproveEdits": false,
+            "agentLoop": {
+                "maxIterations": 10,
+                "temperature": 0.2,
+                "disallowedTools": ["bash"]
+            },
+            "model": "gpt-4o"
+        });
+
+        let settings: TaskSettings = serde_json::from_value(json).unwrap();
+        let agent_loop = settings.agent_loop.expect("agent loop settings");
+        assert_eq!(agent_loop.max_iterations, Some(10));
+        assert_eq!(agent_loop.temperature, Some(0.2));
+        assert_eq!(agent_loop.disallowed_tools, Some(vec!["bash".to_string()]));
+        assert_eq!(agent_loop.allowed_tools, None);
+        assert!(!settings.extra.contains_key("agentLoop"));
+        assert_eq!(
+            settings.extra.get("model"),
+            Some(&serde_json::json!("gpt-4o"))
+        );
+    }
 }
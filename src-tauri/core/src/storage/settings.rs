@@ -199,6 +199,18 @@ impl SettingsRepository {
         if updates.auto_code_review.is_some() {
             settings.auto_code_review = updates.auto_code_review;
         }
+        if updates.agent_loop.is_some() {
+            settings.agent_loop = updates.agent_loop;
+        }
+        if updates.completion_hooks.is_some() {
+            settings.completion_hooks = updates.completion_hooks;
+        }
+        if updates.budget.is_some() {
+            settings.budget = updates.budget;
+        }
+        if updates.sandbox.is_some() {
+            settings.sandbox = updates.sandbox;
+        }
 
         // Merge extra settings
         for (key, value) in updates.extra {
@@ -343,6 +355,10 @@ mod tests {
             auto_approve_edits: Some(true),
             auto_approve_plan: Some(false),
             auto_code_review: Some(true),
+            agent_loop: None,
+            completion_hooks: None,
+            budget: None,
+            sandbox: None,
             extra: Default::default(),
         };
 
@@ -371,6 +387,10 @@ mod tests {
             auto_approve_edits: Some(true),
             auto_approve_plan: Some(false),
             auto_code_review: None,
+            agent_loop: None,
+            completion_hooks: None,
+            budget: None,
+            sandbox: None,
             extra: Default::default(),
         };
         repo.set_task_settings("task-2", &initial).await.unwrap();
@@ -380,6 +400,10 @@ mod tests {
             auto_approve_edits: None,      // Keep existing
             auto_approve_plan: Some(true), // Update
             auto_code_review: Some(false), // Set new
+            agent_loop: None,
+            completion_hooks: None,
+            budget: None,
+            sandbox: None,
             extra: Default::default(),
         };
 
@@ -0,0 +1,382 @@
+//! Tasks Repository
+//! Handles durable task records in chat_history.db so task history
+//! survives after the runtime drops the in-memory task handle
+
+use crate::database::Database;
+use crate::storage::models::{TaskFilter, TaskRecord, UsageTotals};
+use std::sync::Arc;
+
+/// Repository for task record operations
+#[derive(Clone)]
+pub struct TasksRepository {
+    db: Arc<Database>,
+}
+
+impl TasksRepository {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self { db }
+    }
+
+    /// Create a new task record
+    pub async fn create_task(&self, task: &TaskRecord) -> Result<(), String> {
+        let sql = r#"
+            INSERT INTO tasks (id, session_id, agent_id, state, created_at, started_at, completed_at,
+                               error_message, model, input_tokens, output_tokens,
+                               cached_input_tokens, cache_creation_input_tokens, cost_usd,
+                               transitions)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#;
+
+        let transitions = serde_json::to_string(&task.transitions)
+            .map_err(|e| format!("Failed to serialize task transitions: {}", e))?;
+
+        self.db
+            .execute(
+                sql,
+                vec![
+                    serde_json::json!(task.id),
+                    serde_json::json!(task.session_id),
+                    serde_json::json!(task.agent_id),
+                    serde_json::json!(task.state),
+                    serde_json::json!(task.created_at),
+                    serde_json::json!(task.started_at),
+                    serde_json::json!(task.completed_at),
+                    serde_json::json!(task.error_message),
+                    serde_json::json!(task.model),
+                    serde_json::json!(task.input_tokens),
+                    serde_json::json!(task.output_tokens),
+                    serde_json::json!(task.cached_input_tokens),
+                    serde_json::json!(task.cache_creation_input_tokens),
+                    serde_json::json!(task.cost_usd),
+                    serde_json::json!(transitions),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get a task record by ID
+    pub async fn get_task(&self, task_id: &str) -> Result<Option<TaskRecord>, String> {
+        let result = self
+            .db
+            .query(
+                "SELECT * FROM tasks WHERE id = ?",
+                vec![serde_json::json!(task_id)],
+            )
+            .await?;
+
+        Ok(result.rows.first().map(row_to_task))
+    }
+
+    /// List task records, newest first
+    pub async fn list_tasks(&self, filter: &TaskFilter) -> Result<Vec<TaskRecord>, String> {
+        let mut sql = "SELECT * FROM tasks WHERE 1=1".to_string();
+        let mut params: Vec<serde_json::Value> = vec![];
+
+        if let Some(session_id) = &filter.session_id {
+            sql.push_str(" AND session_id = ?");
+            params.push(serde_json::json!(session_id));
+        }
+
+        if let Some(state) = &filter.state {
+            sql.push_str(" AND state = ?");
+            params.push(serde_json::json!(state));
+        }
+
+        if let Some(after) = filter.created_after {
+            sql.push_str(" AND created_at >= ?");
+            params.push(serde_json::json!(after));
+        }
+
+        if let Some(before) = filter.created_before {
+            sql.push_str(" AND created_at < ?");
+            params.push(serde_json::json!(before));
+        }
+
+        sql.push_str(" ORDER BY created_at DESC, rowid DESC");
+
+        if let Some(limit) = filter.limit {
+            sql.push_str(&format!(" LIMIT {}", limit));
+        }
+
+        if let Some(offset) = filter.offset {
+            sql.push_str(&format!(" OFFSET {}", offset));
+        }
+
+        let result = self.db.query(&sql, params).await?;
+
+        Ok(result.rows.iter().map(row_to_task).collect())
+    }
+
+    /// Record a state change.
+    /// `started_at` is set on the first `running` state; `completed_at` and the
+    /// error message are set when `completed` is true.
+    pub async fn update_state(
+        &self,
+        task_id: &str,
+        state: &str,
+        at: i64,
+        completed: bool,
+        error_message: Option<&str>,
+    ) -> Result<(), String> {
+        // One statement, so concurrent updates cannot drop each other's transitions
+        let result = self
+            .db
+            .execute(
+                r#"
+                UPDATE tasks SET
+                    state = ?,
+                    started_at = CASE WHEN ? = 'running' AND started_at IS NULL THEN ? ELSE started_at END,
+                    completed_at = CASE WHEN ? THEN ? ELSE completed_at END,
+                    error_message = CASE WHEN ? THEN ? ELSE error_message END,
+                    transitions = json_insert(COALESCE(transitions, '[]'), '$[#]', json_object('state', ?, 'at', ?))
+                WHERE id = ?
+                "#,
+                vec![
+                    serde_json::json!(state),
+                    serde_json::json!(state),
+                    serde_json::json!(at),
+                    serde_json::json!(completed),
+                    serde_json::json!(at),
+                    serde_json::json!(completed),
+                    serde_json::json!(error_message),
+                    serde_json::json!(state),
+                    serde_json::json!(at),
+                    serde_json::json!(task_id),
+                ],
+            )
+            .await?;
+
+        if result.rows_affected == 0 {
+            return Err(format!("Task '{}' not found", task_id));
+        }
+
+        Ok(())
+    }
+
+    /// Set the token usage and cost totals for a task
+    pub async fn update_usage(&self, task_id: &str, usage: &UsageTotals) -> Result<(), String> {
+        self.db
+            .execute(
+                r#"
+                UPDATE tasks SET input_tokens = ?, output_tokens = ?, cached_input_tokens = ?,
+                                 cache_creation_input_tokens = ?, cost_usd = ?
+                WHERE id = ?
+                "#,
+                vec![
+                    serde_json::json!(usage.input_tokens),
+                    serde_json::json!(usage.output_tokens),
+                    serde_json::json!(usage.cached_input_tokens),
+                    serde_json::json!(usage.cache_creation_input_tokens),
+                    serde_json::json!(usage.cost_usd),
+                    serde_json::json!(task_id),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+fn row_to_task(row: &serde_json::Value) -> TaskRecord {
+    let text = |key: &str| row.get(key).and_then(|v| v.as_str()).map(String::from);
+
+    TaskRecord {
+        id: text("id").unwrap_or_default(),
+        session_id: text("session_id").unwrap_or_default(),
+        agent_id: text("agent_id"),
+        state: text("state").unwrap_or_default(),
+        created_at: row.get("created_at").and_then(|v| v.as_i64()).unwrap_or(0),
+        started_at: row.get("started_at").and_then(|v| v.as_i64()),
+        completed_at: row.get("completed_at").and_then(|v| v.as_i64()),
+        error_message: text("error_message"),
+        model: text("model"),
+        input_tokens: row
+            .get("input_tokens")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(0),
+        output_tokens: row
+            .get("output_tokens")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(0),
+        cached_input_tokens: row
+            .get("cached_input_tokens")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(0),
+        cache_creation_input_tokens: row
+            .get("cache_creation_input_tokens")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(0),
+        cost_usd: row.get("cost_usd").and_then(|v| v.as_f64()).unwrap_or(0.0),
+        transitions: text("transitions")
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::models::{Session, SessionStatus, TaskStateTransition};
+    use crate::storage::ChatHistoryRepository;
+    use tempfile::TempDir;
+
+    async fn create_test_repo() -> (TasksRepository, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let db = Arc::new(Database::new(db_path.to_string_lossy().to_string()));
+        db.connect()
+            .await
+            .expect("Failed to connect to test database");
+
+        let migrations = super::super::migrations::chat_history_migrations();
+        let runner = super::super::migrations::MigrationRunner::new(&db, &migrations);
+        runner.migrate().await.expect("Failed to run migrations");
+
+        for session_id in ["sess-1", "sess-2"] {
+            ChatHistoryRepository::new(db.clone())
+                .create_session(&Session {
+                    id: session_id.to_string(),
+                    project_id: None,
+                    title: None,
+                    status: SessionStatus::Created,
+                    created_at: 0,
+                    updated_at: 0,
+                    last_event_id: None,
+                    metadata: None,
+                })
+                .await
+                .expect("Failed to create session");
+        }
+
+        (TasksRepository::new(db), temp_dir)
+    }
+
+    fn record(id: &str, session_id: &str, created_at: i64) -> TaskRecord {
+        TaskRecord {
+            id: id.to_string(),
+            session_id: session_id.to_string(),
+            agent_id: None,
+            state: "pending".to_string(),
+            created_at,
+            started_at: None,
+            completed_at: None,
+            error_message: None,
+            model: Some("test-model".to_string()),
+            input_tokens: 0,
+            output_tokens: 0,
+            cached_input_tokens: 0,
+            cache_creation_input_tokens: 0,
+            cost_usd: 0.0,
+            transitions: vec![TaskStateTransition {
+                state: "pending".to_string(),
+                at: created_at,
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_task_lifecycle() {
+        let (repo, _temp) = create_test_repo().await;
+        repo.create_task(&record("task-1", "sess-1", 100))
+            .await
+            .unwrap();
+
+        repo.update_state("task-1", "running", 101, false, None)
+            .await
+            .unwrap();
+        repo.update_usage(
+            "task-1",
+            &UsageTotals {
+                input_tokens: 120,
+                output_tokens: 30,
+                cached_input_tokens: 90,
+                cache_creation_input_tokens: 10,
+                cost_usd: 0.25,
+                requests: 2,
+            },
+        )
+        .await
+        .unwrap();
+        repo.update_state("task-1", "failed", 105, true, Some("boom"))
+            .await
+            .unwrap();
+
+        let task = repo.get_task("task-1").await.unwrap().unwrap();
+        assert_eq!(task.state, "failed");
+        assert_eq!(task.started_at, Some(101));
+        assert_eq!(task.completed_at, Some(105));
+        assert_eq!(task.error_message.as_deref(), Some("boom"));
+        assert_eq!(task.model.as_deref(), Some("test-model"));
+        assert_eq!((task.input_tokens, task.output_tokens), (120, 30));
+        assert_eq!(
+            (task.cached_input_tokens, task.cache_creation_input_tokens),
+            (90, 10)
+        );
+        assert_eq!(task.cache_hit_ratio(), Some(0.75));
+        assert_eq!(task.cost_usd, 0.25);
+        let states: Vec<&str> = task.transitions.iter().map(|t| t.state.as_str()).collect();
+        assert_eq!(states, vec!["pending", "running", "failed"]);
+    }
+
+    #[tokio::test]
+    async fn test_list_tasks_filters() {
+        let (repo, _temp) = create_test_repo().await;
+        repo.create_task(&record("task-1", "sess-1", 100))
+            .await
+            .unwrap();
+        repo.create_task(&record("task-2", "sess-1", 200))
+            .await
+            .unwrap();
+        repo.create_task(&record("task-3", "sess-2", 300))
+            .await
+            .unwrap();
+        repo.update_state("task-2", "completed", 250, true, None)
+            .await
+            .unwrap();
+
+        let ids = |tasks: Vec<TaskRecord>| tasks.into_iter().map(|t| t.id).collect::<Vec<_>>();
+
+        let by_session = repo
+            .list_tasks(&TaskFilter {
+                session_id: Some("sess-1".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(ids(by_session), vec!["task-2", "task-1"]);
+
+        let by_state = repo
+            .list_tasks(&TaskFilter {
+                state: Some("pending".to_string()),
+                created_after: Some(150),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(ids(by_state), vec!["task-3"]);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_state_updates_keep_all_transitions() {
+        let (repo, _temp) = create_test_repo().await;
+        repo.create_task(&record("task-1", "sess-1", 100))
+            .await
+            .unwrap();
+
+        let updates = (1..=5).map(|i| repo.update_state("task-1", "running", 100 + i, false, None));
+        for result in futures::future::join_all(updates).await {
+            result.unwrap();
+        }
+
+        let task = repo.get_task("task-1").await.unwrap().unwrap();
+        assert_eq!(task.transitions.len(), 6);
+        assert_eq!(task.started_at.map(|at| at > 100), Some(true));
+
+        assert!(repo
+            .update_state("missing", "running", 200, false, None)
+            .await
+            .is_err());
+    }
+}
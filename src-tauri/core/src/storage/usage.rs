@@ -0,0 +1,246 @@
+//! Usage Repository
+//! Handles per-call token usage and cost records in chat_history.db and
+//! aggregates them by task, session, project, model or day
+
+use crate::database::Database;
+use crate::storage::models::{
+    UsageFilter, UsageGroupBy, UsageRecord, UsageReportEntry, UsageTotals,
+};
+use std::sync::Arc;
+
+const TOTALS_COLUMNS: &str = r#"
+    COALESCE(SUM(input_tokens), 0) AS input_tokens,
+    COALESCE(SUM(output_tokens), 0) AS output_tokens,
+    COALESCE(SUM(cached_input_tokens), 0) AS cached_input_tokens,
+    COALESCE(SUM(cache_creation_input_tokens), 0) AS cache_creation_input_tokens,
+    TOTAL(cost_usd) AS cost_usd,
+    COUNT(*) AS requests
+"#;
+
+/// Repository for usage record operations
+#[derive(Clone)]
+pub struct UsageRepository {
+    db: Arc<Database>,
+}
+
+impl UsageRepository {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self { db }
+    }
+
+    /// Record the usage of one model call
+    pub async fn record_usage(&self, record: &UsageRecord) -> Result<(), String> {
+        let sql = r#"
+            INSERT INTO usage_records (id, task_id, session_id, project_id, model, input_tokens,
+                                       output_tokens, cached_input_tokens,
+                                       cache_creation_input_tokens, cost_usd, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#;
+
+        self.db
+            .execute(
+                sql,
+                vec![
+                    serde_json::json!(record.id),
+                    serde_json::json!(record.task_id),
+                    serde_json::json!(record.session_id),
+                    serde_json::json!(record.project_id),
+                    serde_json::json!(record.model),
+                    serde_json::json!(record.input_tokens),
+                    serde_json::json!(record.output_tokens),
+                    serde_json::json!(record.cached_input_tokens),
+                    serde_json::json!(record.cache_creation_input_tokens),
+                    serde_json::json!(record.cost_usd),
+                    serde_json::json!(record.created_at),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Totals over all usage matching the filter
+    pub async fn totals(&self, filter: &UsageFilter) -> Result<UsageTotals, String> {
+        let (conditions, params) = filter_clause(filter);
+        let sql = format!(
+            "SELECT {} FROM usage_records WHERE 1=1{}",
+            TOTALS_COLUMNS, conditions
+        );
+
+        let result = self.db.query(&sql, params).await?;
+
+        Ok(result.rows.first().map(row_to_totals).unwrap_or_default())
+    }
+
+    /// Totals per group, most expensive first
+    pub async fn report(
+        &self,
+        group_by: UsageGroupBy,
+        filter: &UsageFilter,
+    ) -> Result<Vec<UsageReportEntry>, String> {
+        let key = match group_by {
+            UsageGroupBy::Task => "task_id",
+            UsageGroupBy::Session => "session_id",
+            UsageGroupBy::Project => "project_id",
+            UsageGroupBy::Model => "model",
+            UsageGroupBy::Day => "strftime('%Y-%m-%d', created_at, 'unixepoch')",
+        };
+        let (conditions, params) = filter_clause(filter);
+        let sql = format!(
+            "SELECT {} AS group_key, {} FROM usage_records WHERE 1=1{} GROUP BY group_key ORDER BY cost_usd DESC, group_key",
+            key, TOTALS_COLUMNS, conditions
+        );
+
+        let result = self.db.query(&sql, params).await?;
+
+        Ok(result
+            .rows
+            .iter()
+            .map(|row| UsageReportEntry {
+                key: row
+                    .get("group_key")
+                    .and_then(|v| v.as_str())
+                    .map(String::from),
+                totals: row_to_totals(row),
+            })
+            .collect())
+    }
+}
+
+fn filter_clause(filter: &UsageFilter) -> (String, Vec<serde_json::Value>) {
+    let mut sql = String::new();
+    let mut params: Vec<serde_json::Value> = vec![];
+
+    let columns = [
+        ("task_id", &filter.task_id),
+        ("session_id", &filter.session_id),
+        ("project_id", &filter.project_id),
+        ("model", &filter.model),
+    ];
+    for (column, value) in columns {
+        if let Some(value) = value {
+            sql.push_str(&format!(" AND {} = ?", column));
+            params.push(serde_json::json!(value));
+        }
+    }
+
+    if let Some(since) = filter.since {
+        sql.push_str(" AND created_at >= ?");
+        params.push(serde_json::json!(since));
+    }
+
+    if let Some(until) = filter.until {
+        sql.push_str(" AND created_at < ?");
+        params.push(serde_json::json!(until));
+    }
+
+    (sql, params)
+}
+
+fn row_to_totals(row: &serde_json::Value) -> UsageTotals {
+    let tokens = |key: &str| row.get(key).and_then(|v| v.as_i64()).unwrap_or(0);
+
+    UsageTotals {
+        input_tokens: tokens("input_tokens"),
+        output_tokens: tokens("output_tokens"),
+        cached_input_tokens: tokens("cached_input_tokens"),
+        cache_creation_input_tokens: tokens("cache_creation_input_tokens"),
+        cost_usd: row.get("cost_usd").and_then(|v| v.as_f64()).unwrap_or(0.0),
+        requests: tokens("requests"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    async fn create_test_repo() -> (UsageRepository, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let db = Arc::new(Database::new(db_path.to_string_lossy().to_string()));
+        db.connect()
+            .await
+            .expect("Failed to connect to test database");
+
+        let migrations = super::super::migrations::chat_history_migrations();
+        let runner = super::super::migrations::MigrationRunner::new(&db, &migrations);
+        runner.migrate().await.expect("Failed to run migrations");
+
+        (UsageRepository::new(db), temp_dir)
+    }
+
+    fn record(
+        id: &str,
+        session_id: &str,
+        model: &str,
+        cost_usd: f64,
+        created_at: i64,
+    ) -> UsageRecord {
+        UsageRecord {
+            id: id.to_string(),
+            task_id: format!("task-{}", session_id),
+            session_id: session_id.to_string(),
+            project_id: Some("proj".to_string()),
+            model: Some(model.to_string()),
+            input_tokens: 100,
+            output_tokens: 10,
+            cached_input_tokens: 40,
+            cache_creation_input_tokens: 0,
+            cost_usd,
+            created_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_totals_and_report() {
+        let (repo, _temp) = create_test_repo().await;
+        repo.record_usage(&record("u1", "sess-1", "gpt-5", 0.5, 100))
+            .await
+            .unwrap();
+        repo.record_usage(&record("u2", "sess-1", "claude", 1.0, 200))
+            .await
+            .unwrap();
+        repo.record_usage(&record("u3", "sess-2", "claude", 2.0, 86_400 + 10))
+            .await
+            .unwrap();
+
+        let session = repo
+            .totals(&UsageFilter {
+                session_id: Some("sess-1".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(session.requests, 2);
+        assert_eq!(session.total_tokens(), 220);
+        assert_eq!(session.cached_input_tokens, 80);
+        assert_eq!(session.cost_usd, 1.5);
+
+        let empty = repo
+            .totals(&UsageFilter {
+                since: Some(1_000_000),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(empty, UsageTotals::default());
+
+        let by_model = repo
+            .report(UsageGroupBy::Model, &UsageFilter::default())
+            .await
+            .unwrap();
+        let models: Vec<(Option<&str>, f64)> = by_model
+            .iter()
+            .map(|e| (e.key.as_deref(), e.totals.cost_usd))
+            .collect();
+        assert_eq!(models, vec![(Some("claude"), 3.0), (Some("gpt-5"), 0.5)]);
+
+        let by_day = repo
+            .report(UsageGroupBy::Day, &UsageFilter::default())
+            .await
+            .unwrap();
+        let days: Vec<Option<&str>> = by_day.iter().map(|e| e.key.as_deref()).collect();
+        assert_eq!(days, vec![Some("1970-01-02"), Some("1970-01-01")]);
+    }
+}
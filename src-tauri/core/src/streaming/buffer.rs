@@ -1,14 +1,40 @@
 //! Event Buffer
 //!
 //! Buffers events for SSE streaming with resume capability.
-//! Persists events to storage and maintains in-memory cache.
+//! Maintains an in-memory cache and persists events to storage in the
+//! background, in batches, pruning old events as it goes.
 
 use crate::storage::models::{EventId, SessionEvent, SessionId};
 use crate::storage::Storage;
 use crate::streaming::events::StreamingEvent;
 use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::sync::{Arc, OnceLock};
+use tokio::sync::{mpsc, RwLock};
+
+/// Most events written to storage in one statement
+const WRITE_BATCH_SIZE: usize = 100;
+/// Writes to a session between two prunes of its persisted events
+const PRUNE_INTERVAL: usize = 500;
+/// Seconds between two sweeps for expired events
+const EXPIRY_INTERVAL_SECS: i64 = 60 * 60;
+
+/// How many persisted events are kept, and for how long
+#[derive(Debug, Clone, Copy)]
+pub struct EventRetention {
+    /// Newest events kept per session; older ones are pruned
+    pub max_events_per_session: usize,
+    /// Events older than this are deleted
+    pub max_age_secs: i64,
+}
+
+impl Default for EventRetention {
+    fn default() -> Self {
+        Self {
+            max_events_per_session: 10_000,
+            max_age_secs: 7 * 24 * 60 * 60,
+        }
+    }
+}
 
 /// Event buffer for managing streaming events
 pub struct EventBuffer {
@@ -18,6 +44,10 @@ pub struct EventBuffer {
     max_memory_events: usize,
     /// Storage for persistence
     storage: Option<Arc<Storage>>,
+    /// Retention for persisted events
+    retention: EventRetention,
+    /// Queue of the background writer, started on the first event
+    writer: OnceLock<mpsc::UnboundedSender<SessionEvent>>,
 }
 
 impl EventBuffer {
@@ -26,6 +56,8 @@ impl EventBuffer {
             cache: RwLock::new(HashMap::new()),
             max_memory_events,
             storage: None,
+            retention: EventRetention::default(),
+            writer: OnceLock::new(),
         }
     }
 
@@ -34,10 +66,22 @@ impl EventBuffer {
         self
     }
 
+    pub fn with_retention(mut self, retention: EventRetention) -> Self {
+        self.retention = retention;
+        self
+    }
+
     /// Add an event to the buffer
     pub async fn add_event(&self, event: StreamingEvent) -> Result<(), String> {
-        let session_event: SessionEvent = event.into();
+        self.add_session_event(event.into()).await;
+        Ok(())
+    }
 
+    /// Add an event to the buffer and queue it for storage
+    ///
+    /// Persistence happens on a background task, so this never waits on the
+    /// database.
+    pub async fn add_session_event(&self, session_event: SessionEvent) {
         // Add to in-memory cache
         {
             let mut cache = self.cache.write().await;
@@ -53,12 +97,15 @@ impl EventBuffer {
             }
         }
 
-        // Persist to storage if available
+        // Queue for storage if available
         if let Some(storage) = &self.storage {
-            storage.chat_history.create_event(&session_event).await?;
+            let writer = self.writer.get_or_init(|| {
+                let (sender, receiver) = mpsc::unbounded_channel();
+                tokio::spawn(write_events(storage.clone(), self.retention, receiver));
+                sender
+            });
+            let _ = writer.send(session_event);
         }
-
-        Ok(())
     }
 
     /// Get events for a session, optionally after a specific event ID
@@ -68,45 +115,84 @@ impl EventBuffer {
         after_event_id: Option<&str>,
         limit: Option<usize>,
     ) -> Result<Vec<StreamingEvent>, String> {
+        let events = self
+            .get_session_events(session_id, after_event_id, limit)
+            .await?;
+
+        Ok(events
+            .into_iter()
+            .filter_map(|e| e.try_into().ok())
+            .collect())
+    }
+
+    /// Get buffered events for a session in stored form, optionally after a
+    /// specific event ID
+    pub async fn get_session_events(
+        &self,
+        session_id: &str,
+        after_event_id: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<Vec<SessionEvent>, String> {
         // First check in-memory cache
         let cache = self.cache.read().await;
+        let cached = cache.get(session_id);
+
+        if let Some(events) = cached {
+            // The cache only holds the most recent events. It can answer on its own
+            // when the requested position is still inside it (or there is no storage).
+            let covered = match (after_event_id, events.first()) {
+                (Some(after_id), Some(first)) => first.id.as_str() <= after_id,
+                _ => false,
+            };
 
-        if let Some(events) = cache.get(session_id) {
-            let mut result: Vec<StreamingEvent> = events
-                .iter()
-                .filter(|e| after_event_id.is_none_or(|after_id| e.id.as_str() > after_id))
-                .cloned()
-                .filter_map(|e| e.try_into().ok())
-                .collect();
-
-            // Apply limit
-            if let Some(lim) = limit {
-                if result.len() > lim {
-                    result = result.split_off(result.len() - lim);
+            if covered || self.storage.is_none() {
+                let mut result: Vec<SessionEvent> = events
+                    .iter()
+                    .filter(|e| after_event_id.is_none_or(|after_id| e.id.as_str() > after_id))
+                    .cloned()
+                    .collect();
+
+                // Apply limit
+                if let Some(lim) = limit {
+                    if result.len() > lim {
+                        result = result.split_off(result.len() - lim);
+                    }
                 }
-            }
 
-            // If we have enough events from cache, return them
-            if !result.is_empty() {
                 return Ok(result);
             }
         }
-        drop(cache);
 
         // Fall back to storage
-        if let Some(storage) = &self.storage {
-            let events = storage
-                .chat_history
-                .get_events(session_id, after_event_id, limit)
-                .await?;
+        let Some(storage) = &self.storage else {
+            return Ok(vec![]);
+        };
+        let cached = cached.cloned().unwrap_or_default();
+        drop(cache);
 
-            return events
-                .into_iter()
-                .map(|e| e.try_into())
-                .collect::<Result<Vec<_>, _>>();
+        let mut events = storage
+            .chat_history
+            .get_events(session_id, after_event_id, limit)
+            .await?;
+
+        // The writer may not have stored the newest events yet; take them
+        // from the cache
+        if limit.is_none_or(|lim| events.len() < lim) {
+            let last = events
+                .last()
+                .map(|e| e.id.clone())
+                .or(after_event_id.map(str::to_string));
+            events.extend(
+                cached
+                    .into_iter()
+                    .filter(|e| last.as_deref().is_none_or(|last| e.id.as_str() > last)),
+            );
+            if let Some(lim) = limit {
+                events.truncate(lim);
+            }
         }
 
-        Ok(vec![])
+        Ok(events)
     }
 
     /// Get the last event ID for a session
@@ -140,6 +226,72 @@ impl EventBuffer {
     }
 }
 
+/// Persist queued events in batches and apply the retention policy
+async fn write_events(
+    storage: Arc<Storage>,
+    retention: EventRetention,
+    mut receiver: mpsc::UnboundedReceiver<SessionEvent>,
+) {
+    let mut writes: HashMap<SessionId, usize> = HashMap::new();
+    let mut last_expiry = 0;
+
+    while let Some(event) = receiver.recv().await {
+        let mut batch = vec![event];
+        while batch.len() < WRITE_BATCH_SIZE {
+            match receiver.try_recv() {
+                Ok(event) => batch.push(event),
+                Err(_) => break,
+            }
+        }
+
+        if let Err(e) = storage.chat_history.create_events(&batch).await {
+            // One bad row fails the whole statement; keep the others
+            log::warn!("[EventBuffer] Failed to store event batch: {}", e);
+            for event in &batch {
+                if let Err(e) = storage.chat_history.create_event(event).await {
+                    log::warn!("[EventBuffer] Failed to store event {}: {}", event.id, e);
+                }
+            }
+        }
+
+        for event in batch {
+            let count = writes.entry(event.session_id).or_insert(0);
+            *count += 1;
+        }
+        let due: Vec<SessionId> = writes
+            .iter()
+            .filter(|(_, count)| **count >= PRUNE_INTERVAL)
+            .map(|(session_id, _)| session_id.clone())
+            .collect();
+        for session_id in due {
+            writes.remove(&session_id);
+            if let Err(e) = storage
+                .chat_history
+                .prune_session_events(&session_id, retention.max_events_per_session)
+                .await
+            {
+                log::warn!(
+                    "[EventBuffer] Failed to prune events of session {}: {}",
+                    session_id,
+                    e
+                );
+            }
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        if now - last_expiry >= EXPIRY_INTERVAL_SECS {
+            last_expiry = now;
+            if let Err(e) = storage
+                .chat_history
+                .delete_expired_events(now - retention.max_age_secs)
+                .await
+            {
+                log::warn!("[EventBuffer] Failed to delete expired events: {}", e);
+            }
+        }
+    }
+}
+
 /// Buffer statistics
 #[derive(Debug, Clone)]
 pub struct BufferStats {
@@ -214,4 +366,82 @@ mod tests {
         let stats = buffer.get_stats().await;
         assert_eq!(stats.total_events, 3); // Trimmed to max
     }
+
+    #[tokio::test]
+    async fn test_storage_fallback_includes_unwritten_events() {
+        use crate::storage::models::{Session, SessionStatus};
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(
+            temp_dir.path().to_path_buf(),
+            temp_dir.path().join("attachments"),
+        )
+        .await
+        .expect("Failed to create storage");
+        storage
+            .chat_history
+            .create_session(&Session {
+                id: "sess-1".to_string(),
+                project_id: None,
+                title: None,
+                status: SessionStatus::Created,
+                created_at: 0,
+                updated_at: 0,
+                last_event_id: None,
+                metadata: None,
+            })
+            .await
+            .unwrap();
+        let storage = Arc::new(storage);
+        let buffer = EventBuffer::new(2).with_storage(storage.clone());
+
+        for i in 0..4 {
+            let event = StreamingEvent::Token {
+                event_id: format!("evt-{}", i),
+                session_id: "sess-1".to_string(),
+                data: TokenEventData {
+                    token: format!("token{}", i),
+                },
+            };
+            buffer.add_event(event).await.unwrap();
+        }
+
+        // Wait for the background writer
+        for _ in 0..100 {
+            let stored = storage
+                .chat_history
+                .get_events("sess-1", None, None)
+                .await
+                .unwrap();
+            if stored.len() == 4 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        // evt-0 has left the cache, so this is answered from storage
+        let events = buffer
+            .get_session_events("sess-1", Some("evt-0"), None)
+            .await
+            .unwrap();
+        let ids: Vec<_> = events.into_iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec!["evt-1", "evt-2", "evt-3"]);
+
+        // A new event is returned whether or not the writer has stored it yet
+        buffer
+            .add_session_event(SessionEvent {
+                id: "evt-4".to_string(),
+                session_id: "sess-1".to_string(),
+                event_type: crate::storage::models::EventType::Token,
+                payload: serde_json::json!({ "token": "token4" }),
+                created_at: chrono::Utc::now().timestamp(),
+            })
+            .await;
+        let events = buffer
+            .get_session_events("sess-1", Some("evt-0"), None)
+            .await
+            .unwrap();
+        assert_eq!(events.last().map(|e| e.id.as_str()), Some("evt-4"));
+    }
 }
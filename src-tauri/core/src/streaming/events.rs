@@ -2,8 +2,34 @@
 //!
 //! Defines event types for SSE streaming and conversion between internal and external formats.
 
+use crate::platform::types::OutputStream;
 use crate::storage::models::{EventId, EventType, SessionEvent, SessionId};
 use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+/// Generate a new event ID.
+///
+/// IDs are zero-padded and increase monotonically (seeded from the clock at startup),
+/// so comparing them as strings gives event order. SSE resume relies on this.
+pub fn next_event_id() -> EventId {
+    static COUNTER: OnceLock<AtomicU64> = OnceLock::new();
+    let counter =
+        COUNTER.get_or_init(|| AtomicU64::new(chrono::Utc::now().timestamp_micros().max(0) as u64));
+    format!("evt_{:020}", counter.fetch_add(1, Ordering::Relaxed))
+}
+
+/// Parse an event ID a client resumes from, such as a `Last-Event-ID` header.
+/// Returns it in the form `next_event_id` produces, or `None` when it is not an
+/// `evt_<digits>` ID and so would not order against live events.
+pub fn parse_event_id(id: &str) -> Option<EventId> {
+    let digits = id.trim().strip_prefix("evt_")?;
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let sequence: u64 = digits.parse().ok()?;
+    Some(format!("evt_{:020}", sequence))
+}
 
 /// Event envelope for streaming
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -36,6 +62,24 @@ pub enum StreamingEvent {
         session_id: SessionId,
         data: MessageFinalEventData,
     },
+    /// Model started writing a tool call
+    #[serde(rename = "tool.call.start")]
+    ToolCallStart {
+        #[serde(rename = "eventId")]
+        event_id: EventId,
+        #[serde(rename = "sessionId")]
+        session_id: SessionId,
+        data: ToolCallStartEventData,
+    },
+    /// Partial arguments of a tool call still being written
+    #[serde(rename = "tool.call.delta")]
+    ToolCallDelta {
+        #[serde(rename = "eventId")]
+        event_id: EventId,
+        #[serde(rename = "sessionId")]
+        session_id: SessionId,
+        data: ToolCallDeltaEventData,
+    },
     /// Tool call requested
     #[serde(rename = "tool.call")]
     ToolCall {
@@ -45,6 +89,15 @@ pub enum StreamingEvent {
         session_id: SessionId,
         data: ToolCallEventData,
     },
+    /// Output chunk from a tool that is still running
+    #[serde(rename = "tool.output")]
+    ToolOutput {
+        #[serde(rename = "eventId")]
+        event_id: EventId,
+        #[serde(rename = "sessionId")]
+        session_id: SessionId,
+        data: ToolOutputEventData,
+    },
     /// Tool execution result
     #[serde(rename = "tool.result")]
     ToolResult {
@@ -85,6 +138,22 @@ pub struct MessageFinalEventData {
     pub content: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolCallStartEventData {
+    pub tool_call_id: String,
+    pub name: String,
+}
+
+/// Clients append `arguments_delta` per tool call to show arguments (e.g. file content)
+/// as the model writes them; the complete input follows in `tool.call`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolCallDeltaEventData {
+    pub tool_call_id: String,
+    pub arguments_delta: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ToolCallEventData {
@@ -95,6 +164,16 @@ pub struct ToolCallEventData {
     pub provider_metadata: Option<serde_json::Value>,
 }
 
+/// Clients append `chunk` per tool call and stream to show output (e.g. of a bash
+/// command) while it runs; the complete, possibly truncated output follows in `tool.result`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolOutputEventData {
+    pub tool_call_id: String,
+    pub stream: OutputStream,
+    pub chunk: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ToolResultEventData {
@@ -117,7 +196,10 @@ impl StreamingEvent {
             StreamingEvent::Status { event_id, .. } => event_id,
             StreamingEvent::Token { event_id, .. } => event_id,
             StreamingEvent::MessageFinal { event_id, .. } => event_id,
+            StreamingEvent::ToolCallStart { event_id, .. } => event_id,
+            StreamingEvent::ToolCallDelta { event_id, .. } => event_id,
             StreamingEvent::ToolCall { event_id, .. } => event_id,
+            StreamingEvent::ToolOutput { event_id, .. } => event_id,
             StreamingEvent::ToolResult { event_id, .. } => event_id,
             StreamingEvent::Error { event_id, .. } => event_id,
         }
@@ -129,7 +211,10 @@ impl StreamingEvent {
             StreamingEvent::Status { session_id, .. } => Some(session_id),
             StreamingEvent::Token { session_id, .. } => Some(session_id),
             StreamingEvent::MessageFinal { session_id, .. } => Some(session_id),
+            StreamingEvent::ToolCallStart { session_id, .. } => Some(session_id),
+            StreamingEvent::ToolCallDelta { session_id, .. } => Some(session_id),
             StreamingEvent::ToolCall { session_id, .. } => Some(session_id),
+            StreamingEvent::ToolOutput { session_id, .. } => Some(session_id),
             StreamingEvent::ToolResult { session_id, .. } => Some(session_id),
             StreamingEvent::Error { session_id, .. } => session_id.as_ref(),
         }
@@ -141,7 +226,10 @@ impl StreamingEvent {
             StreamingEvent::Status { .. } => EventType::Status,
             StreamingEvent::Token { .. } => EventType::Token,
             StreamingEvent::MessageFinal { .. } => EventType::MessageFinal,
+            StreamingEvent::ToolCallStart { .. } => EventType::ToolCallStart,
+            StreamingEvent::ToolCallDelta { .. } => EventType::ToolCallDelta,
             StreamingEvent::ToolCall { .. } => EventType::ToolCall,
+            StreamingEvent::ToolOutput { .. } => EventType::ToolOutput,
             StreamingEvent::ToolResult { .. } => EventType::ToolResult,
             StreamingEvent::Error { .. } => EventType::Error,
         }
@@ -153,7 +241,10 @@ impl StreamingEvent {
             StreamingEvent::Status { .. } => "status",
             StreamingEvent::Token { .. } => "token",
             StreamingEvent::MessageFinal { .. } => "message.final",
+            StreamingEvent::ToolCallStart { .. } => "tool.call.start",
+            StreamingEvent::ToolCallDelta { .. } => "tool.call.delta",
             StreamingEvent::ToolCall { .. } => "tool.call",
+            StreamingEvent::ToolOutput { .. } => "tool.output",
             StreamingEvent::ToolResult { .. } => "tool.result",
             StreamingEvent::Error { .. } => "error",
         };
@@ -203,6 +294,24 @@ impl TryFrom<SessionEvent> for StreamingEvent {
                     data,
                 })
             }
+            EventType::ToolCallStart => {
+                let data: ToolCallStartEventData = serde_json::from_value(payload)
+                    .map_err(|e| format!("Failed to parse tool.call.start event: {}", e))?;
+                Ok(StreamingEvent::ToolCallStart {
+                    event_id: event.id,
+                    session_id: event.session_id,
+                    data,
+                })
+            }
+            EventType::ToolCallDelta => {
+                let data: ToolCallDeltaEventData = serde_json::from_value(payload)
+                    .map_err(|e| format!("Failed to parse tool.call.delta event: {}", e))?;
+                Ok(StreamingEvent::ToolCallDelta {
+                    event_id: event.id,
+                    session_id: event.session_id,
+                    data,
+                })
+            }
             EventType::ToolCall => {
                 let data: ToolCallEventData = serde_json::from_value(payload)
                     .map_err(|e| format!("Failed to parse tool.call event: {}", e))?;
@@ -212,6 +321,15 @@ impl TryFrom<SessionEvent> for StreamingEvent {
                     data,
                 })
             }
+            EventType::ToolOutput => {
+                let data: ToolOutputEventData = serde_json::from_value(payload)
+                    .map_err(|e| format!("Failed to parse tool.output event: {}", e))?;
+                Ok(StreamingEvent::ToolOutput {
+                    event_id: event.id,
+                    session_id: event.session_id,
+                    data,
+                })
+            }
             EventType::ToolResult => {
                 let data: ToolResultEventData = serde_json::from_value(payload)
                     .map_err(|e| format!("Failed to parse tool.result event: {}", e))?;
@@ -230,6 +348,7 @@ impl TryFrom<SessionEvent> for StreamingEvent {
                     data,
                 })
             }
+            other => Err(format!("{} events have no streaming form", other.as_str())),
         }
     }
 }
@@ -268,6 +387,26 @@ impl From<StreamingEvent> for SessionEvent {
                 EventType::MessageFinal,
                 serde_json::to_value(data).unwrap(),
             ),
+            StreamingEvent::ToolCallStart {
+                event_id,
+                session_id,
+                data,
+            } => (
+                event_id,
+                session_id,
+                EventType::ToolCallStart,
+                serde_json::to_value(data).unwrap(),
+            ),
+            StreamingEvent::ToolCallDelta {
+                event_id,
+                session_id,
+                data,
+            } => (
+                event_id,
+                session_id,
+                EventType::ToolCallDelta,
+                serde_json::to_value(data).unwrap(),
+            ),
             StreamingEvent::ToolCall {
                 event_id,
                 session_id,
@@ -278,6 +417,16 @@ impl From<StreamingEvent> for SessionEvent {
                 EventType::ToolCall,
                 serde_json::to_value(data).unwrap(),
             ),
+            StreamingEvent::ToolOutput {
+                event_id,
+                session_id,
+                data,
+            } => (
+                event_id,
+                session_id,
+                EventType::ToolOutput,
+                serde_json::to_value(data).unwrap(),
+            ),
             StreamingEvent::ToolResult {
                 event_id,
                 session_id,
@@ -333,6 +482,30 @@ mod tests {
         assert!(sse.contains("event: token"));
     }
 
+    #[test]
+    fn test_next_event_id_is_ordered() {
+        let first = next_event_id();
+        let second = next_event_id();
+
+        assert!(first.starts_with("evt_"));
+        assert_eq!(first.len(), second.len());
+        assert!(second > first);
+    }
+
+    #[test]
+    fn test_parse_event_id() {
+        let id = next_event_id();
+        assert_eq!(parse_event_id(&id), Some(id.clone()));
+        assert_eq!(
+            parse_event_id(" evt_42 "),
+            Some("evt_00000000000000000042".to_string())
+        );
+        assert_eq!(parse_event_id("zzz"), None);
+        assert_eq!(parse_event_id("evt_"), None);
+        assert_eq!(parse_event_id("evt_12ab"), None);
+        assert_eq!(parse_event_id("evt_99999999999999999999999"), None);
+    }
+
     #[test]
     fn test_streaming_event_to_session_event() {
         let streaming = StreamingEvent::Status {
@@ -348,4 +521,28 @@ mod tests {
         assert_eq!(session_event.session_id, "sess-2");
         assert_eq!(session_event.event_type, EventType::Status);
     }
+
+    #[test]
+    fn test_tool_call_delta_round_trips_through_session_event() {
+        let streaming = StreamingEvent::ToolCallDelta {
+            event_id: "evt-3".to_string(),
+            session_id: "sess-3".to_string(),
+            data: ToolCallDeltaEventData {
+                tool_call_id: "call_1".to_string(),
+                arguments_delta: "{\"content\":\"fn main".to_string(),
+            },
+        };
+        assert!(streaming.to_sse_string().contains("event: tool.call.delta"));
+
+        let session_event: SessionEvent = streaming.into();
+        assert_eq!(session_event.event_type, EventType::ToolCallDelta);
+        assert_eq!(
+            session_event.payload,
+            serde_json::json!({ "toolCallId": "call_1", "argumentsDelta": "{\"content\":\"fn main" })
+        );
+
+        let restored = StreamingEvent::try_from(session_event).unwrap();
+        assert_eq!(restored.event_type(), EventType::ToolCallDelta);
+        assert_eq!(restored.event_id(), "evt-3");
+    }
 }
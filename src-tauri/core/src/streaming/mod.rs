@@ -6,7 +6,7 @@ pub mod buffer;
 pub mod events;
 pub mod throttle;
 
-pub use buffer::{BufferStats, EventBuffer};
+pub use buffer::{BufferStats, EventBuffer, EventRetention};
 pub use events::*;
 pub use throttle::{EventThrottler, StreamingManager, ThrottleConfig};
 
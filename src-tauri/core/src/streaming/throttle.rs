@@ -160,6 +160,12 @@ impl StreamingManager {
         self
     }
 
+    /// Persist buffered events so sessions can be resumed after they leave memory
+    pub fn with_storage(mut self, storage: std::sync::Arc<crate::storage::Storage>) -> Self {
+        self.buffer = self.buffer.with_storage(storage);
+        self
+    }
+
     pub fn with_throttle_config(mut self, config: ThrottleConfig) -> Self {
         self.throttler = EventThrottler::new(config);
         self
@@ -25,6 +25,43 @@ struct PtySession {
     master: Box<dyn portable_pty::MasterPty + Send>,
 }
 
+/// A process spawned on its own PTY and driven by the caller instead of the frontend
+pub(crate) struct PtyProcess {
+    pub writer: Box<dyn Write + Send>,
+    pub reader: Box<dyn Read + Send>,
+    pub child: Box<dyn portable_pty::Child + Send + Sync>,
+    pub master: Box<dyn portable_pty::MasterPty + Send>,
+}
+
+/// Open a PTY of the given size and spawn `cmd` on it
+pub(crate) fn spawn_pty_process(cmd: CommandBuilder, size: PtySize) -> Result<PtyProcess, String> {
+    let pair = native_pty_system()
+        .openpty(size)
+        .map_err(|e| format!("Failed to open PTY: {}", e))?;
+    let child = pair
+        .slave
+        .spawn_command(cmd)
+        .map_err(|e| format!("Failed to spawn shell: {}", e))?;
+    // Once the child exits, reads on the master only hit EOF when no slave handle remains
+    drop(pair.slave);
+
+    let writer = pair
+        .master
+        .take_writer()
+        .map_err(|e| format!("Failed to take writer: {}", e))?;
+    let reader = pair
+        .master
+        .try_clone_reader()
+        .map_err(|e| format!("Failed to clone reader: {}", e))?;
+
+    Ok(PtyProcess {
+        writer,
+        reader,
+        child,
+        master: pair.master,
+    })
+}
+
 type PtyRegistry = Arc<Mutex<HashMap<String, PtySession>>>;
 
 lazy_static::lazy_static! {
@@ -0,0 +1,302 @@
+//! Bash Output Tool
+//!
+//! Reads what a command started with `runInBackground` has printed since the
+//! last check. Commands belong to the session that started them, so a later
+//! turn of the same session can still read them.
+
+use crate::background_tasks::{
+    get_background_task_output, get_background_task_status, kill_background_task,
+    BackgroundTaskStatus,
+};
+use crate::core::tools::ToolContext;
+use crate::tools::bash_tool::OutputExcerpt;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// A background command started by the bash tool
+#[derive(Debug, Clone)]
+struct TrackedCommand {
+    /// Session whose task started the command
+    session_id: String,
+    output_file: String,
+    error_file: String,
+    stdout_bytes_read: u64,
+    stderr_bytes_read: u64,
+}
+
+lazy_static::lazy_static! {
+    static ref TRACKED_COMMANDS: Mutex<HashMap<String, TrackedCommand>> =
+        Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BashOutputResult {
+    pub success: bool,
+    pub message: String,
+    pub task_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<BackgroundTaskStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stdout: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stderr: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_complete: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub truncated: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_file_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_file_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl BashOutputResult {
+    fn failure(task_id: &str, error: String) -> Self {
+        Self {
+            success: false,
+            message: error.clone(),
+            task_id: task_id.to_string(),
+            status: None,
+            exit_code: None,
+            stdout: None,
+            stderr: None,
+            is_complete: None,
+            truncated: None,
+            output_file_path: None,
+            error_file_path: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// Record a background command started in `session_id`
+pub(crate) fn track(
+    background_task_id: &str,
+    session_id: &str,
+    output_file: &str,
+    error_file: &str,
+) {
+    TRACKED_COMMANDS.lock().unwrap().insert(
+        background_task_id.to_string(),
+        TrackedCommand {
+            session_id: session_id.to_string(),
+            output_file: output_file.to_string(),
+            error_file: error_file.to_string(),
+            stdout_bytes_read: 0,
+            stderr_bytes_read: 0,
+        },
+    );
+}
+
+pub(crate) fn untrack(background_task_id: &str) {
+    TRACKED_COMMANDS.lock().unwrap().remove(background_task_id);
+}
+
+/// Whether the background command was started in `session_id`
+pub(crate) fn is_tracked_in(background_task_id: &str, session_id: &str) -> bool {
+    TRACKED_COMMANDS
+        .lock()
+        .unwrap()
+        .get(background_task_id)
+        .is_some_and(|command| command.session_id == session_id)
+}
+
+/// Kill and stop tracking every background command started in a session that
+/// is closed or will not run again
+pub async fn forget_session(session_id: &str) {
+    let mut forgotten = Vec::new();
+    TRACKED_COMMANDS.lock().unwrap().retain(|id, command| {
+        let keep = command.session_id != session_id;
+        if !keep {
+            forgotten.push(id.clone());
+        }
+        keep
+    });
+
+    for background_task_id in forgotten {
+        if let Err(e) = kill_background_task(background_task_id.clone()).await {
+            log::warn!(
+                "Failed to kill background command {} of session {}: {}",
+                background_task_id,
+                session_id,
+                e
+            );
+        }
+    }
+}
+
+/// Execute bashOutput tool: return output produced since the previous call
+pub async fn execute(background_task_id: &str, ctx: &ToolContext) -> BashOutputResult {
+    let tracked = TRACKED_COMMANDS
+        .lock()
+        .unwrap()
+        .get(background_task_id)
+        .filter(|command| command.session_id == ctx.session_id)
+        .cloned();
+    let Some(mut tracked) = tracked else {
+        return BashOutputResult::failure(
+            background_task_id,
+            format!(
+                "No background command with ID {} was started in this session",
+                background_task_id
+            ),
+        );
+    };
+
+    let status = match get_background_task_status(background_task_id.to_string()).await {
+        Ok(status) => status,
+        Err(e) => return BashOutputResult::failure(background_task_id, e),
+    };
+
+    // Read up to what had been written when the status was taken; each read is
+    // capped, and a chatty command would otherwise keep this loop going
+    let mut stdout = OutputExcerpt::default();
+    let mut stderr = OutputExcerpt::default();
+    while tracked.stdout_bytes_read < status.output_bytes
+        || tracked.stderr_bytes_read < status.error_bytes
+    {
+        let chunk = match get_background_task_output(
+            background_task_id.to_string(),
+            tracked.stdout_bytes_read,
+            tracked.stderr_bytes_read,
+        )
+        .await
+        {
+            Ok(chunk) => chunk,
+            Err(e) => return BashOutputResult::failure(background_task_id, e),
+        };
+        if chunk.stdout_bytes_read == tracked.stdout_bytes_read
+            && chunk.stderr_bytes_read == tracked.stderr_bytes_read
+        {
+            break;
+        }
+        stdout.push(&chunk.new_stdout);
+        stderr.push(&chunk.new_stderr);
+        tracked.stdout_bytes_read = chunk.stdout_bytes_read;
+        tracked.stderr_bytes_read = chunk.stderr_bytes_read;
+    }
+
+    if let Some(command) = TRACKED_COMMANDS.lock().unwrap().get_mut(background_task_id) {
+        command.stdout_bytes_read = tracked.stdout_bytes_read;
+        command.stderr_bytes_read = tracked.stderr_bytes_read;
+    }
+
+    let is_complete = status.status != BackgroundTaskStatus::Running;
+    let truncated = stdout.is_truncated() || stderr.is_truncated();
+    let stderr_text = stderr.render(Some(&tracked.error_file));
+    BashOutputResult {
+        success: true,
+        message: match (&status.status, status.exit_code) {
+            (BackgroundTaskStatus::Running, _) => "Command is still running".to_string(),
+            (BackgroundTaskStatus::Killed, _) => "Command was killed".to_string(),
+            (BackgroundTaskStatus::Timeout, _) => "Command timed out".to_string(),
+            (_, Some(code)) => format!("Command finished with exit code: {}", code),
+            (_, None) => "Command finished".to_string(),
+        },
+        task_id: background_task_id.to_string(),
+        status: Some(status.status),
+        exit_code: status.exit_code,
+        stdout: Some(stdout.render(Some(&tracked.output_file))),
+        stderr: if stderr_text.is_empty() {
+            None
+        } else {
+            Some(stderr_text)
+        },
+        is_complete: Some(is_complete),
+        truncated: truncated.then_some(true),
+        output_file_path: Some(tracked.output_file),
+        error_file_path: Some(tracked.error_file),
+        error: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(session_id: &str, task_id: &str) -> ToolContext {
+        ToolContext {
+            session_id: session_id.to_string(),
+            task_id: task_id.to_string(),
+            workspace_root: std::env::current_dir()
+                .unwrap()
+                .to_string_lossy()
+                .to_string(),
+            worktree_path: None,
+            settings: crate::storage::models::TaskSettings::default(),
+            llm_state: None,
+            event_sender: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejects_commands_from_other_sessions() {
+        track("bg_owned01", "bash-output-owner", "out.log", "err.log");
+
+        let result = execute("bg_owned01", &context("bash-output-other", "task")).await;
+        assert!(!result.success);
+        assert!(is_tracked_in("bg_owned01", "bash-output-owner"));
+
+        forget_session("bash-output-owner").await;
+        assert!(!is_tracked_in("bg_owned01", "bash-output-owner"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_later_task_in_session_reads_output() {
+        let first = context("bash-output-session", "bash-output-turn-1");
+        let started =
+            crate::tools::bash_tool::execute("echo across", true, false, &first, "").await;
+        let background_id = started.task_id.expect("background task id");
+
+        // The next turn runs as a new task in the same session
+        let second = context("bash-output-session", "bash-output-turn-2");
+        let mut stdout = String::new();
+        for _ in 0..50 {
+            let result = execute(&background_id, &second).await;
+            assert!(result.success, "{}", result.message);
+            stdout.push_str(result.stdout.as_deref().unwrap_or_default());
+            if stdout.contains("across") {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        assert_eq!(stdout, "across\n");
+        forget_session(&second.session_id).await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_reads_only_new_output() {
+        let ctx = context("bash-output-incremental", "task");
+        let started = crate::tools::bash_tool::execute("echo first", true, false, &ctx, "").await;
+        let background_id = started.task_id.expect("background task id");
+
+        let mut stdout = String::new();
+        let mut result = execute(&background_id, &ctx).await;
+        for _ in 0..50 {
+            stdout.push_str(result.stdout.as_deref().unwrap_or_default());
+            if result.is_complete == Some(true) {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            result = execute(&background_id, &ctx).await;
+        }
+        assert_eq!(result.exit_code, Some(0));
+        // Output can land just after the exit is recorded
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let rest = execute(&background_id, &ctx).await;
+        stdout.push_str(rest.stdout.as_deref().unwrap_or_default());
+        assert_eq!(stdout, "first\n");
+
+        let again = execute(&background_id, &ctx).await;
+        assert_eq!(again.stdout.as_deref(), Some(""));
+        forget_session(&ctx.session_id).await;
+    }
+}
@@ -1,12 +1,24 @@
 //! Bash Tool
 //!
 //! Execute shell commands safely on the system.
-//! Matches TypeScript bash-tool.tsx logic.
+//! Matches TypeScript bash-tool.tsx logic. Synchronous commands share one
+//! persistent shell per task where the platform supports it.
 
 use crate::core::tools::ToolContext;
+use crate::core::types::RuntimeEvent;
+use crate::platform::sandbox::{SandboxDenial, SandboxPolicy};
+use crate::platform::types::{OutputCallback, OutputStream};
+use crate::tools::web_fetch::build_tool_output_path;
 use serde::Serialize;
+use std::collections::VecDeque;
+use std::time::Duration;
 
-#[derive(Debug, Serialize)]
+/// Output longer than this many characters is cut down to a head/tail excerpt
+pub(crate) const MAX_INLINE_OUTPUT_LENGTH: usize = 10_000;
+/// Characters kept from each end of truncated output
+const EXCERPT_LENGTH: usize = MAX_INLINE_OUTPUT_LENGTH / 2;
+
+#[derive(Debug, Default, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct BashResult {
     pub success: bool,
@@ -32,6 +44,42 @@ pub struct BashResult {
     pub task_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub is_background: Option<bool>,
+    /// Set when output was too long and only a head/tail excerpt is inline
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub truncated: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sandbox_denied: Option<SandboxDenial>,
+    /// Whether the sandbox isolated the command; `false` when an optional
+    /// sandbox fell back to running it without full isolation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sandboxed: Option<bool>,
+    /// Why the command was not fully isolated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sandbox_fallback: Option<String>,
+}
+
+impl BashResult {
+    /// A foreground command that failed before it produced any output
+    fn failure(command: &str, message: impl Into<String>, error: Option<String>) -> Self {
+        Self {
+            success: false,
+            message: message.into(),
+            command: command.to_string(),
+            error,
+            is_background: Some(false),
+            ..Default::default()
+        }
+    }
+
+    /// Report whether `sandbox` actually isolated the command
+    fn with_sandbox_status(mut self, sandbox: Option<&SandboxPolicy>) -> Self {
+        if let Some(policy) = sandbox {
+            let fallback = policy.fallback_reason();
+            self.sandboxed = Some(fallback.is_none());
+            self.sandbox_fallback = fallback;
+        }
+        self
+    }
 }
 
 /// Check if command contains dangerous patterns
@@ -52,24 +100,49 @@ fn is_dangerous_command(command: &str) -> bool {
         .any(|pattern| lower_cmd.contains(pattern))
 }
 
-/// Execute bash tool
-pub async fn execute(command: &str, run_in_background: bool, ctx: &ToolContext) -> BashResult {
+/// Execute bash tool. `reset_session` starts the task's shell session afresh
+/// before running `command`; an empty command only resets it. Output is
+/// streamed as `RuntimeEvent::ToolOutput` under `tool_call_id` while it runs.
+pub async fn execute(
+    command: &str,
+    run_in_background: bool,
+    reset_session: bool,
+    ctx: &ToolContext,
+    tool_call_id: &str,
+) -> BashResult {
     // Check for dangerous commands
     if is_dangerous_command(command) {
+        return BashResult::failure(
+            command,
+            "Command contains potentially dangerous operations",
+            Some("Dangerous command detected".to_string()),
+        );
+    }
+
+    if reset_session {
+        crate::shell_session::close(&ctx.task_id);
+        if command.trim().is_empty() {
+            return BashResult {
+                success: true,
+                message: "Shell session reset".to_string(),
+                is_background: Some(false),
+                ..Default::default()
+            };
+        }
+    }
+
+    let sandbox = SandboxPolicy::from_settings(
+        ctx.settings.sandbox.as_ref(),
+        &ctx.workspace_root,
+        ctx.worktree_path.as_deref(),
+    );
+    if let Some(Err(denial)) = sandbox.as_ref().map(SandboxPolicy::ensure_available) {
+        let message = format!("Sandbox denied command: {}", denial.message);
+        let error = Some(denial.message.clone());
         return BashResult {
-            success: false,
-            message: "Command contains potentially dangerous operations".to_string(),
-            command: command.to_string(),
-            output: None,
-            error: Some("Dangerous command detected".to_string()),
-            output_file_path: None,
-            error_file_path: None,
-            exit_code: None,
-            timed_out: None,
-            idle_timed_out: None,
-            pid: None,
-            task_id: None,
-            is_background: Some(false),
+            is_background: Some(run_in_background),
+            sandbox_denied: Some(denial),
+            ..BashResult::failure(command, message, error)
         };
     }
 
@@ -77,95 +150,290 @@ pub async fn execute(command: &str, run_in_background: bool, ctx: &ToolContext)
         // Spawn background task
         let request = crate::background_tasks::SpawnBackgroundTaskRequest {
             command: command.to_string(),
-            cwd: Some(ctx.workspace_root.clone()),
+            cwd: Some(
+                ctx.worktree_path
+                    .clone()
+                    .unwrap_or_else(|| ctx.workspace_root.clone()),
+            ),
             max_timeout_ms: Some(7_200_000), // 2 hours default
+            sandbox: sandbox.clone(),
         };
 
         match crate::background_tasks::spawn_background_task(request).await {
-            Ok(response) => BashResult {
-                success: true,
-                message: format!(
-                    "Command running in background. Task ID: {}",
-                    response.task_id
-                ),
-                command: command.to_string(),
-                output: None,
-                error: None,
-                output_file_path: Some(response.output_file),
-                error_file_path: Some(response.error_file),
-                exit_code: None,
-                timed_out: None,
-                idle_timed_out: None,
-                pid: Some(response.pid),
-                task_id: Some(response.task_id),
-                is_background: Some(true),
-            },
-            Err(e) => BashResult {
-                success: false,
-                message: format!("Failed to start background task: {}", e),
-                command: command.to_string(),
-                output: None,
-                error: Some(e),
-                output_file_path: None,
-                error_file_path: None,
-                exit_code: None,
-                timed_out: None,
-                idle_timed_out: None,
-                pid: None,
-                task_id: None,
-                is_background: Some(false),
-            },
+            Ok(response) => {
+                crate::tools::bash_output::track(
+                    &response.task_id,
+                    &ctx.session_id,
+                    &response.output_file,
+                    &response.error_file,
+                );
+                BashResult {
+                    success: true,
+                    message: format!(
+                        "Command running in background. Task ID: {}",
+                        response.task_id
+                    ),
+                    command: command.to_string(),
+                    output_file_path: Some(response.output_file),
+                    error_file_path: Some(response.error_file),
+                    pid: Some(response.pid),
+                    task_id: Some(response.task_id),
+                    is_background: Some(true),
+                    ..Default::default()
+                }
+                .with_sandbox_status(sandbox.as_ref())
+            }
+            Err(e) => BashResult::failure(
+                command,
+                format!("Failed to start background task: {}", e),
+                Some(e),
+            ),
         }
     } else {
-        // Execute synchronously using platform shell
         let platform = crate::platform::Platform::new();
         let platform_ctx =
             platform.create_context(&ctx.workspace_root, ctx.worktree_path.as_deref());
 
-        let result = platform.shell.execute(command, None, &platform_ctx).await;
+        let emit = |stream: OutputStream, chunk: &str| {
+            if let Some(sender) = &ctx.event_sender {
+                let _ = sender.send(RuntimeEvent::ToolOutput {
+                    task_id: ctx.task_id.clone(),
+                    session_id: ctx.session_id.clone(),
+                    tool_call_id: tool_call_id.to_string(),
+                    stream,
+                    chunk: chunk.to_string(),
+                });
+            }
+        };
+        let on_output: Option<OutputCallback<'_>> = match ctx.event_sender {
+            Some(_) => Some(&emit),
+            None => None,
+        };
+
+        if crate::shell_session::is_supported() {
+            return execute_in_session(
+                command,
+                ctx,
+                tool_call_id,
+                &platform_ctx.effective_root().to_string_lossy(),
+                sandbox.as_ref(),
+                Duration::from_secs(platform_ctx.shell_timeout_secs),
+                on_output,
+            )
+            .await;
+        }
+
+        // Execute synchronously using platform shell
+        let result = platform
+            .shell
+            .execute_streaming(command, None, &platform_ctx, sandbox.as_ref(), on_output)
+            .await;
 
         match result.data {
-            Some(shell_result) => BashResult {
-                success: result.success && shell_result.exit_code == 0,
-                message: if result.success {
-                    "Command executed successfully".to_string()
-                } else {
-                    format!("Command failed with exit code: {}", shell_result.exit_code)
-                },
-                command: command.to_string(),
-                output: Some(shell_result.stdout),
-                error: if shell_result.stderr.is_empty() {
-                    None
-                } else {
-                    Some(shell_result.stderr)
-                },
-                output_file_path: None,
-                error_file_path: None,
-                exit_code: Some(shell_result.exit_code),
-                timed_out: Some(shell_result.timed_out),
-                idle_timed_out: None,
-                pid: None,
-                task_id: None,
-                is_background: Some(false),
-            },
-            None => BashResult {
-                success: false,
-                message: result
+            Some(shell_result) => {
+                let sandbox_denied = sandbox.as_ref().and_then(|policy| {
+                    policy.classify_failure(shell_result.exit_code, &shell_result.stderr)
+                });
+                let output = limit_output(&shell_result.stdout, ctx, tool_call_id, "bash").await;
+                let error =
+                    limit_output(&shell_result.stderr, ctx, tool_call_id, "bash-stderr").await;
+                let truncated = output.truncated || error.truncated;
+                BashResult {
+                    success: result.success && shell_result.exit_code == 0,
+                    message: match &sandbox_denied {
+                        Some(denial) => format!("Sandbox denied command: {}", denial.message),
+                        None if result.success => "Command executed successfully".to_string(),
+                        None => {
+                            format!("Command failed with exit code: {}", shell_result.exit_code)
+                        }
+                    },
+                    command: command.to_string(),
+                    output: Some(output.text),
+                    error: if error.text.is_empty() {
+                        None
+                    } else {
+                        Some(error.text)
+                    },
+                    output_file_path: output.spill_path,
+                    error_file_path: error.spill_path,
+                    exit_code: Some(shell_result.exit_code),
+                    timed_out: Some(shell_result.timed_out),
+                    is_background: Some(false),
+                    truncated: truncated.then_some(true),
+                    sandbox_denied,
+                    ..Default::default()
+                }
+                .with_sandbox_status(sandbox.as_ref())
+            }
+            None => BashResult::failure(
+                command,
+                result
                     .error
                     .clone()
                     .unwrap_or_else(|| "Unknown error".to_string()),
+                result.error,
+            ),
+        }
+    }
+}
+
+/// Run a command in the task's persistent shell session, started in `cwd`
+async fn execute_in_session(
+    command: &str,
+    ctx: &ToolContext,
+    tool_call_id: &str,
+    cwd: &str,
+    sandbox: Option<&SandboxPolicy>,
+    timeout: Duration,
+    on_output: Option<OutputCallback<'_>>,
+) -> BashResult {
+    let result =
+        crate::shell_session::run(&ctx.task_id, command, cwd, sandbox, timeout, on_output).await;
+
+    match result {
+        Ok(session_output) => {
+            // The terminal interleaves stderr with stdout, so classify the combined output
+            let sandbox_denied = sandbox.and_then(|policy| {
+                policy.classify_failure(session_output.exit_code, &session_output.output)
+            });
+            let success = session_output.exit_code == 0 && !session_output.timed_out;
+            let output = limit_output(&session_output.output, ctx, tool_call_id, "bash").await;
+            BashResult {
+                success,
+                message: match &sandbox_denied {
+                    Some(denial) => format!("Sandbox denied command: {}", denial.message),
+                    None if session_output.timed_out => format!(
+                        "Command timed out after {}s and was interrupted",
+                        timeout.as_secs()
+                    ),
+                    None if session_output.session_closed => {
+                        "Shell exited; the next command starts a new session".to_string()
+                    }
+                    None if success => "Command executed successfully".to_string(),
+                    None => format!(
+                        "Command failed with exit code: {}",
+                        session_output.exit_code
+                    ),
+                },
                 command: command.to_string(),
-                output: None,
-                error: result.error,
-                output_file_path: None,
-                error_file_path: None,
-                exit_code: None,
-                timed_out: None,
-                idle_timed_out: None,
-                pid: None,
-                task_id: None,
+                output: Some(output.text),
+                output_file_path: output.spill_path,
+                exit_code: Some(session_output.exit_code),
+                timed_out: Some(session_output.timed_out),
                 is_background: Some(false),
-            },
+                truncated: output.truncated.then_some(true),
+                sandbox_denied,
+                ..Default::default()
+            }
+            .with_sandbox_status(sandbox)
+        }
+        Err(e) => BashResult::failure(command, e.clone(), Some(e)),
+    }
+}
+
+/// Keeps the first and last `EXCERPT_LENGTH` characters of output that may
+/// be far too long to hold or return in full
+#[derive(Debug, Default)]
+pub(crate) struct OutputExcerpt {
+    head: String,
+    head_chars: usize,
+    tail: VecDeque<char>,
+    total_chars: usize,
+}
+
+impl OutputExcerpt {
+    pub(crate) fn push(&mut self, text: &str) {
+        for c in text.chars() {
+            self.total_chars += 1;
+            if self.head_chars < EXCERPT_LENGTH {
+                self.head.push(c);
+                self.head_chars += 1;
+                continue;
+            }
+            if self.tail.len() == EXCERPT_LENGTH {
+                self.tail.pop_front();
+            }
+            self.tail.push_back(c);
+        }
+    }
+
+    pub(crate) fn is_truncated(&self) -> bool {
+        self.total_chars > MAX_INLINE_OUTPUT_LENGTH
+    }
+
+    /// The full text when it fits, otherwise head and tail around a note
+    /// pointing at `spill_path` for the rest
+    pub(crate) fn render(&self, spill_path: Option<&str>) -> String {
+        let tail: String = self.tail.iter().collect();
+        if !self.is_truncated() {
+            return format!("{}{}", self.head, tail);
+        }
+        let omitted = self.total_chars - self.head_chars - self.tail.len();
+        let location = match spill_path {
+            Some(path) => format!(
+                " Full output saved to: {}. Use shell tools like `grep` or `tail` to inspect it.",
+                path
+            ),
+            None => String::new(),
+        };
+        format!(
+            "{}\n\n... [{} characters omitted.{}] ...\n\n{}",
+            self.head, omitted, location, tail
+        )
+    }
+}
+
+struct LimitedOutput {
+    text: String,
+    spill_path: Option<String>,
+    truncated: bool,
+}
+
+/// Cut `text` down to an excerpt if it is too long, saving the full text to
+/// the task's tool output directory first
+async fn limit_output(
+    text: &str,
+    ctx: &ToolContext,
+    tool_call_id: &str,
+    suffix: &str,
+) -> LimitedOutput {
+    let mut excerpt = OutputExcerpt::default();
+    excerpt.push(text);
+    if !excerpt.is_truncated() {
+        return LimitedOutput {
+            text: text.to_string(),
+            spill_path: None,
+            truncated: false,
+        };
+    }
+
+    let spill_path = if tool_call_id.is_empty() {
+        None
+    } else {
+        match build_tool_output_path(&ctx.workspace_root, &ctx.task_id, tool_call_id, suffix) {
+            Ok(path) => write_spill_file(&path, text).await,
+            Err(_) => None,
+        }
+    };
+    LimitedOutput {
+        text: excerpt.render(spill_path.as_deref()),
+        spill_path,
+        truncated: true,
+    }
+}
+
+async fn write_spill_file(path: &std::path::Path, text: &str) -> Option<String> {
+    if let Some(parent) = path.parent() {
+        if let Err(e) = tokio::fs::create_dir_all(parent).await {
+            log::warn!("Failed to create tool output directory: {}", e);
+            return None;
+        }
+    }
+    match tokio::fs::write(path, text).await {
+        Ok(()) => Some(path.to_string_lossy().to_string()),
+        Err(e) => {
+            log::warn!("Failed to write bash output to {}: {}", path.display(), e);
+            None
         }
     }
 }
@@ -178,7 +446,7 @@ mod tests {
     async fn test_echo_command() {
         let ctx = ToolContext {
             session_id: "test".to_string(),
-            task_id: "test".to_string(),
+            task_id: "bash-test-echo".to_string(),
             workspace_root: std::env::current_dir()
                 .unwrap()
                 .to_string_lossy()
@@ -186,9 +454,11 @@ mod tests {
             worktree_path: None,
             settings: crate::storage::models::TaskSettings::default(),
             llm_state: None,
+            event_sender: None,
         };
 
-        let result = execute("echo Hello", false, &ctx).await;
+        let result = execute("echo Hello", false, false, &ctx, "").await;
+        crate::shell_session::close(&ctx.task_id);
 
         assert!(result.success);
         assert!(result.output.unwrap().contains("Hello"));
@@ -206,11 +476,181 @@ mod tests {
             worktree_path: None,
             settings: crate::storage::models::TaskSettings::default(),
             llm_state: None,
+            event_sender: None,
         };
 
-        let result = execute("rm -rf /", false, &ctx).await;
+        let result = execute("rm -rf /", false, false, &ctx, "").await;
 
         assert!(!result.success);
         assert!(result.message.contains("dangerous"));
     }
+
+    #[tokio::test]
+    async fn test_required_sandbox_runs_or_reports_denial() {
+        let workspace = tempfile::TempDir::new().unwrap();
+        let ctx = ToolContext {
+            session_id: "test".to_string(),
+            task_id: "bash-test-sandbox".to_string(),
+            workspace_root: workspace.path().to_string_lossy().to_string(),
+            worktree_path: None,
+            settings: crate::storage::models::TaskSettings {
+                sandbox: Some(crate::storage::models::SandboxSettings {
+                    mode: Some(crate::storage::models::SandboxMode::Required),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            llm_state: None,
+            event_sender: None,
+        };
+
+        let result = execute(
+            "echo sandboxed > out.txt && cat out.txt",
+            false,
+            false,
+            &ctx,
+            "",
+        )
+        .await;
+        crate::shell_session::close(&ctx.task_id);
+
+        match result.sandbox_denied {
+            Some(denial) => {
+                assert!(!result.success);
+                assert_eq!(
+                    denial.kind,
+                    crate::platform::sandbox::SandboxDenialKind::Unavailable
+                );
+            }
+            None => {
+                assert!(result.success);
+                assert!(result.output.unwrap().contains("sandboxed"));
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_session_persists_until_reset() {
+        let workspace = tempfile::TempDir::new().unwrap();
+        let ctx = ToolContext {
+            session_id: "test".to_string(),
+            task_id: "bash-test-session".to_string(),
+            workspace_root: workspace.path().to_string_lossy().to_string(),
+            worktree_path: None,
+            settings: crate::storage::models::TaskSettings::default(),
+            llm_state: None,
+            event_sender: None,
+        };
+
+        execute("export TALKCODY_VALUE=kept", false, false, &ctx, "").await;
+        let kept = execute("echo \"[$TALKCODY_VALUE]\"", false, false, &ctx, "").await;
+        assert_eq!(kept.output.as_deref(), Some("[kept]"));
+
+        let reset = execute("", false, true, &ctx, "").await;
+        assert!(reset.success);
+        let fresh = execute("echo \"[$TALKCODY_VALUE]\"", false, false, &ctx, "").await;
+        assert_eq!(fresh.output.as_deref(), Some("[]"));
+        crate::shell_session::close(&ctx.task_id);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_commands_run_in_worktree() {
+        let workspace = tempfile::TempDir::new().unwrap();
+        let worktree = tempfile::TempDir::new().unwrap();
+        let ctx = ToolContext {
+            session_id: "test".to_string(),
+            task_id: "bash-test-worktree".to_string(),
+            workspace_root: workspace.path().to_string_lossy().to_string(),
+            worktree_path: Some(worktree.path().to_string_lossy().to_string()),
+            settings: crate::storage::models::TaskSettings::default(),
+            llm_state: None,
+            event_sender: None,
+        };
+
+        let result = execute("pwd -P", false, false, &ctx, "").await;
+        crate::shell_session::close(&ctx.task_id);
+        let expected = worktree.path().canonicalize().unwrap();
+        assert_eq!(
+            result.output.as_deref().map(str::trim),
+            Some(expected.to_string_lossy().as_ref())
+        );
+    }
+
+    #[test]
+    fn test_output_excerpt_keeps_head_and_tail() {
+        let mut short = OutputExcerpt::default();
+        short.push("line 1\n");
+        short.push("line 2\n");
+        assert!(!short.is_truncated());
+        assert_eq!(short.render(None), "line 1\nline 2\n");
+
+        let mut long = OutputExcerpt::default();
+        long.push("start");
+        long.push(&"x".repeat(MAX_INLINE_OUTPUT_LENGTH));
+        long.push("end");
+        assert!(long.is_truncated());
+        let rendered = long.render(Some("/tmp/out.txt"));
+        assert!(rendered.starts_with("start"));
+        assert!(rendered.ends_with("end"));
+        assert!(rendered.contains("[8 characters omitted. Full output saved to: /tmp/out.txt."));
+    }
+
+    #[tokio::test]
+    async fn test_long_output_is_spilled_to_file() {
+        let workspace = tempfile::TempDir::new().unwrap();
+        let ctx = ToolContext {
+            session_id: "test".to_string(),
+            task_id: "bash-test-spill".to_string(),
+            workspace_root: workspace.path().to_string_lossy().to_string(),
+            worktree_path: None,
+            settings: crate::storage::models::TaskSettings::default(),
+            llm_state: None,
+            event_sender: None,
+        };
+        let text = "0123456789".repeat(2_000);
+
+        let limited = limit_output(&text, &ctx, "call_1", "bash").await;
+
+        assert!(limited.truncated);
+        assert!(limited.text.len() < text.len());
+        let spill_path = limited.spill_path.expect("spill file");
+        assert!(spill_path.ends_with("call_1_bash.txt"));
+        assert_eq!(std::fs::read_to_string(&spill_path).unwrap(), text);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_output_is_streamed_as_events() {
+        let workspace = tempfile::TempDir::new().unwrap();
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let ctx = ToolContext {
+            session_id: "test".to_string(),
+            task_id: "bash-test-stream".to_string(),
+            workspace_root: workspace.path().to_string_lossy().to_string(),
+            worktree_path: None,
+            settings: crate::storage::models::TaskSettings::default(),
+            llm_state: None,
+            event_sender: Some(sender),
+        };
+
+        let result = execute("echo one; echo two", false, false, &ctx, "call_1").await;
+        crate::shell_session::close(&ctx.task_id);
+
+        let mut streamed = String::new();
+        while let Ok(event) = receiver.try_recv() {
+            if let RuntimeEvent::ToolOutput {
+                tool_call_id,
+                chunk,
+                ..
+            } = event
+            {
+                assert_eq!(tool_call_id, "call_1");
+                streamed.push_str(&chunk);
+            }
+        }
+        assert!(result.success);
+        assert_eq!(result.output.as_deref(), Some(streamed.as_str()));
+    }
 }
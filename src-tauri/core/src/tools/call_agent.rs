@@ -2,6 +2,9 @@
 //!
 //! Call a registered sub-agent for a focused task.
 //! Matches TypeScript call-agent-tool.tsx logic.
+//!
+//! The sub-agent runs as a child task of the calling task, so execution lives in
+//! `CoreRuntime::run_sub_agent`. This module parses the tool input and shapes the result.
 
 use crate::core::tools::ToolContext;
 use serde::Serialize;
@@ -14,32 +17,108 @@ pub struct CallAgentResult {
     pub message: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    /// Session the sub-agent ran in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+}
+
+impl CallAgentResult {
+    pub fn failure(error: String) -> Self {
+        Self {
+            success: false,
+            message: None,
+            error: Some(error),
+            session_id: None,
+        }
+    }
+}
+
+/// Parsed callAgent tool input
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CallAgentRequest {
+    pub agent_id: String,
+    pub task: String,
+    pub context: Option<String>,
+    pub targets: Option<Vec<String>>,
+}
+
+impl CallAgentRequest {
+    /// Parse the tool input sent by the model
+    pub fn from_input(input: &serde_json::Value) -> Self {
+        let text = |key: &str| input.get(key).and_then(|v| v.as_str()).map(String::from);
+
+        Self {
+            agent_id: text("agentId").unwrap_or_default(),
+            task: text("task").unwrap_or_default(),
+            context: text("context").filter(|c| !c.trim().is_empty()),
+            targets: input.get("targets").and_then(|v| v.as_array()).map(|arr| {
+                arr.iter()
+                    .filter_map(|v| v.as_str().map(String::from))
+                    .collect()
+            }),
+        }
+    }
+
+    /// Build the first user message for the sub-agent.
+    /// Sub-agents start with empty history, so everything they need goes in here.
+    pub fn prompt(&self) -> String {
+        let mut sections = vec![format!("## Task\n{}", self.task)];
+        if let Some(context) = &self.context {
+            sections.push(format!("## Context\n{}", context));
+        }
+        if let Some(targets) = self.targets.as_ref().filter(|t| !t.is_empty()) {
+            let list = targets
+                .iter()
+                .map(|t| format!("- {}", t))
+                .collect::<Vec<_>>()
+                .join("\n");
+            sections.push(format!("## Targets\n{}", list));
+        }
+        sections.join("\n\n")
+    }
 }
 
-/// Execute callAgent tool
+/// Execute callAgent tool outside the runtime
 ///
-/// In backend-only mode without full agent registry access,
-/// this returns an informative error about the limitation.
-pub async fn execute(
-    agent_id: &str,
-    task: &str,
-    _context: Option<&str>,
-    _targets: Option<Vec<String>>,
-    _ctx: &ToolContext,
-) -> CallAgentResult {
-    // In backend mode without full agent registry, we cannot call other agents
-    // This would require access to the AgentRegistry and the ability to spawn nested agent loops
-
-    CallAgentResult {
-        success: false,
-        message: None,
-        error: Some(format!(
-            "callAgent tool is not fully supported in backend-only mode. \
-            Would have called agent '{}' with task: {}. \
-            In a full application, this would spawn a nested agent execution. \
-            Consider implementing the task directly or using direct tool calls instead.",
-            agent_id,
-            if task.len() > 50 { &task[..50] } else { task }
-        )),
+/// Sub-agents need the core runtime to start a child task. Calls that reach the
+/// plain tool registry (e.g. direct dispatch) report that instead of running.
+pub async fn execute(request: CallAgentRequest, _ctx: &ToolContext) -> CallAgentResult {
+    CallAgentResult::failure(format!(
+        "callAgent for agent '{}' must be executed by the core runtime",
+        request.agent_id
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_from_input() {
+        let request = CallAgentRequest::from_input(&serde_json::json!({
+            "agentId": "explorer",
+            "task": "Find the config loader",
+            "context": "  ",
+            "targets": ["src/config.rs", 42]
+        }));
+
+        assert_eq!(request.agent_id, "explorer");
+        assert_eq!(request.context, None);
+        assert_eq!(request.targets, Some(vec!["src/config.rs".to_string()]));
+    }
+
+    #[test]
+    fn test_prompt_sections() {
+        let request = CallAgentRequest {
+            agent_id: "explorer".to_string(),
+            task: "Find the config loader".to_string(),
+            context: Some("Loader lives under src/".to_string()),
+            targets: Some(vec!["src/config.rs".to_string()]),
+        };
+
+        assert_eq!(
+            request.prompt(),
+            "## Task\nFind the config loader\n\n## Context\nLoader lives under src/\n\n## Targets\n- src/config.rs"
+        );
     }
 }
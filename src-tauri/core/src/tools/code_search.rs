@@ -111,6 +111,7 @@ mod tests {
             worktree_path: None,
             settings: crate::storage::models::TaskSettings::default(),
             llm_state: None,
+            event_sender: None,
         };
 
         let result = execute("Hello", temp_dir.path().to_str().unwrap(), None, &ctx).await;
@@ -131,6 +132,7 @@ mod tests {
             worktree_path: None,
             settings: crate::storage::models::TaskSettings::default(),
             llm_state: None,
+            event_sender: None,
         };
 
         let result = execute("xyz123", temp_dir.path().to_str().unwrap(), None, &ctx).await;
@@ -31,97 +31,122 @@ pub async fn execute(
     _review_mode: bool,
     ctx: &ToolContext,
 ) -> EditFileResult {
+    let path = match resolve_existing_file(file_path, &ctx.workspace_root) {
+        Ok(path) => path,
+        Err(message) => return failure(message),
+    };
+
+    // Read current content
+    let current_content = match tokio::fs::read_to_string(&path).await {
+        Ok(content) => normalize_string(&content),
+        Err(e) => {
+            return failure(format!(
+                "File not found: {}. This tool only edits existing files. Use create-file or write-file for new files. Error: {}",
+                file_path, e
+            ));
+        }
+    };
+
+    let (working_content, total_replacements) =
+        match apply_edits(&current_content, &edits, file_path) {
+            Ok(applied) => applied,
+            Err(message) => return failure(message),
+        };
+
+    // Write the modified content
+    match tokio::fs::write(&path, &working_content).await {
+        Ok(_) => EditFileResult {
+            success: true,
+            message: format!(
+                "Successfully applied {} edit{} to {} ({} total replacement{})",
+                edits.len(),
+                if edits.len() > 1 { "s" } else { "" },
+                file_path,
+                total_replacements,
+                if total_replacements > 1 { "s" } else { "" }
+            ),
+            edits_applied: Some(edits.len()),
+            total_replacements: Some(total_replacements),
+        },
+        Err(e) => failure(format!("Failed to write file: {}", e)),
+    }
+}
+
+fn failure(message: String) -> EditFileResult {
+    EditFileResult {
+        success: false,
+        message,
+        edits_applied: None,
+        total_replacements: None,
+    }
+}
+
+/// Resolve `file_path` to an existing file inside the workspace
+pub(crate) fn resolve_existing_file(
+    file_path: &str,
+    workspace_root: &str,
+) -> Result<String, String> {
     // Handle $RESOURCE prefix - not supported in backend
     if file_path.starts_with("$RESOURCE/") || file_path.starts_with("$RESOURCE\\") {
-        return EditFileResult {
-            success: false,
-            message: "Resource paths ($RESOURCE/) are not supported in backend mode".to_string(),
-            edits_applied: None,
-            total_replacements: None,
-        };
+        return Err("Resource paths ($RESOURCE/) are not supported in backend mode".to_string());
     }
 
     // Normalize path
     let path = if Path::new(file_path).is_absolute() {
         file_path.to_string()
     } else {
-        Path::new(&ctx.workspace_root)
+        Path::new(workspace_root)
             .join(file_path)
             .to_string_lossy()
             .to_string()
     };
 
     // Security check: ensure path is within workspace
-    let workspace_root = Path::new(&ctx.workspace_root)
+    let canonical_root = Path::new(workspace_root)
         .canonicalize()
-        .unwrap_or_else(|_| Path::new(&ctx.workspace_root).to_path_buf());
+        .unwrap_or_else(|_| Path::new(workspace_root).to_path_buf());
     let target_path = match Path::new(&path).canonicalize() {
         Ok(p) => p,
         Err(_) => {
             // File doesn't exist yet
-            return EditFileResult {
-                success: false,
-                message: format!(
-                    "File not found: {}. This tool only edits existing files. Use create-file or write-file for new files.",
-                    file_path
-                ),
-                edits_applied: None,
-                total_replacements: None,
-            };
+            return Err(format!(
+                "File not found: {}. This tool only edits existing files. Use create-file or write-file for new files.",
+                file_path
+            ));
         }
     };
 
     // Check if target is within workspace
-    if !target_path.starts_with(&workspace_root) {
-        return EditFileResult {
-            success: false,
-            message: format!(
-                "Security Error: File path \"{}\" is outside the allowed project directory \"{}\". Files can only be edited within the current project directory.",
-                path, ctx.workspace_root
-            ),
-            edits_applied: None,
-            total_replacements: None,
-        };
+    if !target_path.starts_with(&canonical_root) {
+        return Err(format!(
+            "Security Error: File path \"{}\" is outside the allowed project directory \"{}\". Files can only be edited within the current project directory.",
+            path, workspace_root
+        ));
     }
 
-    // Read current content
-    let current_content = match tokio::fs::read_to_string(&path).await {
-        Ok(content) => normalize_string(&content),
-        Err(e) => {
-            return EditFileResult {
-                success: false,
-                message: format!(
-                    "File not found: {}. This tool only edits existing files. Use create-file or write-file for new files. Error: {}",
-                    file_path, e
-                ),
-                edits_applied: None,
-                total_replacements: None,
-            };
-        }
-    };
+    Ok(path)
+}
 
+/// Apply `edits` in order to `current_content`, trying an exact match before
+/// smart matching for each one. Returns the new content and the number of
+/// replacements made.
+pub(crate) fn apply_edits(
+    current_content: &str,
+    edits: &[EditBlock],
+    file_path: &str,
+) -> Result<(String, usize), String> {
     // Validate edits
     if edits.is_empty() {
-        return EditFileResult {
-            success: false,
-            message: "At least one edit block is required.".to_string(),
-            edits_applied: None,
-            total_replacements: None,
-        };
+        return Err("At least one edit block is required.".to_string());
     }
 
     // Check for empty old_strings
     for (i, edit) in edits.iter().enumerate() {
         if edit.old_string.trim().is_empty() {
-            return EditFileResult {
-                success: false,
-                message: format!(
-                    "Edit {}: old_string cannot be empty. Use write-file or create-file for new content.",
-                    i + 1
-                ),
-                edits_applied: None,
-                total_replacements: None,
-            };
+            return Err(format!(
+                "Edit {}: old_string cannot be empty. Use write-file or create-file for new content.",
+                i + 1
+            ));
         }
     }
 
@@ -131,31 +156,24 @@ pub async fn execute(
         .map(|e| format!("{}:::{}", e.old_string, e.new_string))
         .collect();
     if unique_edits.len() != edits.len() {
-        return EditFileResult {
-            success: false,
-            message: "Duplicate edit blocks detected. Each edit should be unique. Remove duplicate edits.".to_string(),
-            edits_applied: None,
-            total_replacements: None,
-        };
+        return Err(
+            "Duplicate edit blocks detected. Each edit should be unique. Remove duplicate edits."
+                .to_string(),
+        );
     }
 
     // Check if old_string equals new_string for any edit
     for (i, edit) in edits.iter().enumerate() {
         if edit.old_string == edit.new_string {
-            return EditFileResult {
-                success: false,
-                message: format!(
-                    "Edit {}: No changes needed. The old_string and new_string are identical.",
-                    i + 1
-                ),
-                edits_applied: None,
-                total_replacements: None,
-            };
+            return Err(format!(
+                "Edit {}: No changes needed. The old_string and new_string are identical.",
+                i + 1
+            ));
         }
     }
 
     // Apply edits sequentially
-    let mut working_content = current_content.clone();
+    let mut working_content = current_content.to_string();
     let mut total_replacements = 0;
 
     for (i, edit) in edits.iter().enumerate() {
@@ -198,53 +216,26 @@ pub async fn execute(
                 }
             }
             MatchType::None => {
-                let error_msg = generate_edit_error_message(&current_content, i, edit, file_path);
-                return EditFileResult {
-                    success: false,
-                    message: error_msg,
-                    edits_applied: None,
-                    total_replacements: None,
-                };
+                return Err(generate_edit_error_message(
+                    current_content,
+                    i,
+                    edit,
+                    file_path,
+                ));
             }
         }
     }
 
     // Check if content changed
     if working_content == current_content {
-        return EditFileResult {
-            success: false,
-            message: "No changes applied. The content is identical after all replacements. This should not happen - please report this issue.".to_string(),
-            edits_applied: None,
-            total_replacements: None,
-        };
+        return Err("No changes applied. The content is identical after all replacements. This should not happen - please report this issue.".to_string());
     }
 
-    // Write the modified content
-    match tokio::fs::write(&path, &working_content).await {
-        Ok(_) => EditFileResult {
-            success: true,
-            message: format!(
-                "Successfully applied {} edit{} to {} ({} total replacement{})",
-                edits.len(),
-                if edits.len() > 1 { "s" } else { "" },
-                file_path,
-                total_replacements,
-                if total_replacements > 1 { "s" } else { "" }
-            ),
-            edits_applied: Some(edits.len()),
-            total_replacements: Some(total_replacements),
-        },
-        Err(e) => EditFileResult {
-            success: false,
-            message: format!("Failed to write file: {}", e),
-            edits_applied: None,
-            total_replacements: None,
-        },
-    }
+    Ok((working_content, total_replacements))
 }
 
 /// Normalize string line endings
-fn normalize_string(s: &str) -> String {
+pub(crate) fn normalize_string(s: &str) -> String {
     s.replace("\r\n", "\n").replace('\r', "\n")
 }
 
@@ -349,11 +340,11 @@ fn smart_match(content: &str, search_text: &str) -> SmartMatchResult {
     }
 
     for i in 0..=content_lines.len().saturating_sub(search_lines.len()) {
-        let candidate_lines = &content_lines[i..i + search_lines.len()];
-        let trimmed_candidate: Vec<&str> = candidate_lines.iter().map(|l| l.trim()).collect();
-        let trimmed_search: Vec<&str> = search_lines.iter().map(|l| l.trim()).collect();
+        let Some(candidate_lines) = content_lines.get(i..i + search_lines.len()) else {
+            break;
+        };
 
-        if trimmed_candidate == trimmed_search {
+        if lines_match_ignoring_whitespace(candidate_lines, &search_lines) {
             // Found match with different whitespace - extract the exact text from file
             let exact_text_from_file = candidate_lines.join("\n");
             return SmartMatchResult {
@@ -373,6 +364,20 @@ fn smart_match(content: &str, search_text: &str) -> SmartMatchResult {
     }
 }
 
+/// Whether two runs of lines are the same apart from whitespace around each
+/// line. The smart match that edit blocks and multiEdit diff hunks fall back to
+/// when there is no exact match.
+pub(crate) fn lines_match_ignoring_whitespace<A: AsRef<str>, B: AsRef<str>>(
+    candidate: &[A],
+    search: &[B],
+) -> bool {
+    candidate.len() == search.len()
+        && candidate
+            .iter()
+            .zip(search)
+            .all(|(line, wanted)| line.as_ref().trim() == wanted.as_ref().trim())
+}
+
 /// Generates detailed error message when an edit fails to match
 fn generate_edit_error_message(
     content: &str,
@@ -459,6 +464,7 @@ mod tests {
             worktree_path: None,
             settings: crate::storage::models::TaskSettings::default(),
             llm_state: None,
+            event_sender: None,
         };
 
         let edits = vec![EditBlock {
@@ -486,6 +492,7 @@ mod tests {
             worktree_path: None,
             settings: crate::storage::models::TaskSettings::default(),
             llm_state: None,
+            event_sender: None,
         };
 
         let edits = vec![EditBlock {
@@ -512,6 +519,7 @@ mod tests {
             worktree_path: None,
             settings: crate::storage::models::TaskSettings::default(),
             llm_state: None,
+            event_sender: None,
         };
 
         let edits = vec![EditBlock {
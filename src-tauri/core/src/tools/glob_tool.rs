@@ -118,6 +118,7 @@ mod tests {
             worktree_path: None,
             settings: crate::storage::models::TaskSettings::default(),
             llm_state: None,
+            event_sender: None,
         };
 
         let result = execute("*.txt", None, &ctx).await;
@@ -138,6 +139,7 @@ mod tests {
             worktree_path: None,
             settings: crate::storage::models::TaskSettings::default(),
             llm_state: None,
+            event_sender: None,
         };
 
         let result = execute("*.xyz", None, &ctx).await;
@@ -191,6 +191,7 @@ mod tests {
             worktree_path: None,
             settings: crate::storage::models::TaskSettings::default(),
             llm_state: None,
+            event_sender: None,
         };
 
         let result = execute("A cat", None, None, None, &ctx).await;
@@ -0,0 +1,103 @@
+//! Kill Bash Tool
+//!
+//! Stops a command started with `runInBackground`. A session can only kill
+//! the background commands its own tasks started.
+
+use crate::core::tools::ToolContext;
+use crate::tools::bash_output;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KillBashResult {
+    pub success: bool,
+    pub message: String,
+    pub task_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Execute killBash tool
+pub async fn execute(background_task_id: &str, ctx: &ToolContext) -> KillBashResult {
+    if !bash_output::is_tracked_in(background_task_id, &ctx.session_id) {
+        let error = format!(
+            "No background command with ID {} was started in this session",
+            background_task_id
+        );
+        return KillBashResult {
+            success: false,
+            message: error.clone(),
+            task_id: background_task_id.to_string(),
+            error: Some(error),
+        };
+    }
+
+    match crate::background_tasks::kill_background_task(background_task_id.to_string()).await {
+        Ok(killed) => {
+            bash_output::untrack(background_task_id);
+            KillBashResult {
+                success: true,
+                message: if killed {
+                    format!("Killed background command {}", background_task_id)
+                } else {
+                    format!(
+                        "Background command {} is no longer running",
+                        background_task_id
+                    )
+                },
+                task_id: background_task_id.to_string(),
+                error: None,
+            }
+        }
+        Err(e) => KillBashResult {
+            success: false,
+            message: format!("Failed to kill background command: {}", e),
+            task_id: background_task_id.to_string(),
+            error: Some(e),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_kills_background_command_from_same_session_only() {
+        let owner = ToolContext {
+            session_id: "kill-bash-session".to_string(),
+            task_id: "kill-bash-turn-1".to_string(),
+            workspace_root: std::env::current_dir()
+                .unwrap()
+                .to_string_lossy()
+                .to_string(),
+            worktree_path: None,
+            settings: crate::storage::models::TaskSettings::default(),
+            llm_state: None,
+            event_sender: None,
+        };
+        let other = ToolContext {
+            session_id: "kill-bash-other".to_string(),
+            ..owner.clone()
+        };
+        // A later turn of the same session runs as a new task
+        let next_turn = ToolContext {
+            task_id: "kill-bash-turn-2".to_string(),
+            ..owner.clone()
+        };
+
+        let started = crate::tools::bash_tool::execute("sleep 30", true, false, &owner, "").await;
+        let background_id = started.task_id.expect("background task id");
+
+        let denied = execute(&background_id, &other).await;
+        assert!(!denied.success);
+
+        let killed = execute(&background_id, &next_turn).await;
+        assert!(killed.success);
+        assert!(!bash_output::is_tracked_in(
+            &background_id,
+            &owner.session_id
+        ));
+    }
+}
@@ -93,6 +93,7 @@ mod tests {
             worktree_path: None,
             settings: crate::storage::models::TaskSettings::default(),
             llm_state: None,
+            event_sender: None,
         };
 
         let result = execute(temp_dir.path().to_str().unwrap(), Some(2), &ctx).await;
@@ -113,6 +114,7 @@ mod tests {
             worktree_path: None,
             settings: crate::storage::models::TaskSettings::default(),
             llm_state: None,
+            event_sender: None,
         };
 
         let result = execute("nonexistent_dir", Some(3), &ctx).await;
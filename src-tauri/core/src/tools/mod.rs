@@ -4,6 +4,7 @@
 //! Each module provides an execute function that matches the corresponding TypeScript tool logic.
 
 pub mod ask_user_questions;
+pub mod bash_output;
 pub mod bash_tool;
 pub mod call_agent;
 pub mod code_search;
@@ -13,7 +14,9 @@ pub mod github_pr;
 pub mod glob_tool;
 pub mod image_generation;
 pub mod install_skill;
+pub mod kill_bash;
 pub mod list_files;
+pub mod multi_edit;
 pub mod read_file;
 pub mod todo_write;
 pub mod web_fetch;
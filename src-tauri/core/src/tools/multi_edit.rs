@@ -0,0 +1,925 @@
+//! Multi Edit Tool
+//!
+//! Apply changes to several files as one unit, given either edit blocks per
+//! file or a unified diff. Every edit is checked against the current file
+//! contents before anything is written. New contents are written to temporary
+//! files next to their targets and renamed into place; if any step fails, the
+//! files already changed are restored and directories made for new files are
+//! removed.
+
+use crate::core::tools::ToolContext;
+use crate::tools::bash_tool::OutputExcerpt;
+use crate::tools::edit_file::{self, EditBlock};
+use crate::tools::write_file::ensure_within_workspace;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MultiEditResult {
+    pub success: bool,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files: Option<Vec<FileChange>>,
+    /// Combined unified diff of every file changed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Deleted,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileChange {
+    pub file_path: String,
+    pub change: ChangeKind,
+    pub additions: usize,
+    pub deletions: usize,
+}
+
+/// Edit blocks for one file
+#[derive(Debug, Deserialize)]
+pub struct FileEdits {
+    pub file_path: String,
+    pub edits: Vec<EditBlock>,
+}
+
+/// A file's contents before and after the change; `None` means no file
+#[derive(Debug)]
+struct StagedFile {
+    path: PathBuf,
+    display_path: String,
+    original: Option<String>,
+    updated: Option<String>,
+}
+
+impl StagedFile {
+    fn change(&self) -> ChangeKind {
+        match (&self.original, &self.updated) {
+            (None, _) => ChangeKind::Created,
+            (_, None) => ChangeKind::Deleted,
+            _ => ChangeKind::Modified,
+        }
+    }
+}
+
+/// One file section of a unified diff
+#[derive(Debug, Default)]
+struct FilePatch {
+    old_path: Option<String>,
+    new_path: Option<String>,
+    hunks: Vec<Hunk>,
+}
+
+#[derive(Debug, Default)]
+struct Hunk {
+    /// Index of the line the old lines start at, from the `@@` header
+    old_index: Option<usize>,
+    old_lines: Vec<String>,
+    new_lines: Vec<String>,
+    /// The old side ends without a trailing newline
+    old_missing_newline: bool,
+    /// The new side ends without a trailing newline
+    new_missing_newline: bool,
+}
+
+/// Execute multiEdit tool
+pub async fn execute(
+    files: Vec<FileEdits>,
+    patch: Option<&str>,
+    ctx: &ToolContext,
+) -> MultiEditResult {
+    let patch = patch.filter(|patch| !patch.trim().is_empty());
+    let staged = match (files.is_empty(), patch) {
+        (false, None) => stage_file_edits(files, &ctx.workspace_root).await,
+        (true, Some(patch)) => stage_patch(patch, &ctx.workspace_root).await,
+        (true, None) => Err("Provide either files with edits or a unified diff patch.".to_string()),
+        (false, Some(_)) => Err("Provide either files or patch, not both.".to_string()),
+    };
+    let staged = match staged {
+        Ok(staged) => staged,
+        Err(message) => return failure(format!("No files were changed. {}", message)),
+    };
+
+    let mut changes = Vec::new();
+    let mut diff = OutputExcerpt::default();
+    for file in &staged {
+        let (text, additions, deletions) = file_diff(file).unwrap_or_default();
+        diff.push(&text);
+        changes.push(FileChange {
+            file_path: file.display_path.clone(),
+            change: file.change(),
+            additions,
+            deletions,
+        });
+    }
+
+    if let Err(message) = commit(&staged).await {
+        return failure(message);
+    }
+
+    let count = |kind: ChangeKind| changes.iter().filter(|c| c.change == kind).count();
+    MultiEditResult {
+        success: true,
+        message: format!(
+            "Successfully changed {} file{} ({} created, {} modified, {} deleted)",
+            changes.len(),
+            if changes.len() == 1 { "" } else { "s" },
+            count(ChangeKind::Created),
+            count(ChangeKind::Modified),
+            count(ChangeKind::Deleted)
+        ),
+        files: Some(changes),
+        diff: Some(diff.render(None)),
+    }
+}
+
+fn failure(message: String) -> MultiEditResult {
+    MultiEditResult {
+        success: false,
+        message,
+        files: None,
+        diff: None,
+    }
+}
+
+async fn stage_file_edits(
+    files: Vec<FileEdits>,
+    workspace_root: &str,
+) -> Result<Vec<StagedFile>, String> {
+    let mut staged = Vec::new();
+    for file in files {
+        let index = stage_existing(&mut staged, &file.file_path, workspace_root).await?;
+        let entry = &mut staged[index];
+        let content = entry.updated.as_deref().unwrap_or_default();
+        let (updated, _) = edit_file::apply_edits(content, &file.edits, &file.file_path)?;
+        entry.updated = Some(updated);
+    }
+    Ok(staged)
+}
+
+async fn stage_patch(patch: &str, workspace_root: &str) -> Result<Vec<StagedFile>, String> {
+    let mut staged = Vec::new();
+    for file in parse_unified_diff(patch)? {
+        match (file.old_path, file.new_path) {
+            (None, None) => return Err("Patch has a file with no path".to_string()),
+            (Some(old_path), None) => {
+                let index = stage_existing(&mut staged, &old_path, workspace_root).await?;
+                staged[index].updated = None;
+            }
+            (None, Some(new_path)) => {
+                let content = created_content(&file.hunks);
+                stage_new(&mut staged, &new_path, content, workspace_root)?;
+            }
+            (Some(old_path), Some(new_path)) if old_path != new_path => {
+                return Err(format!(
+                    "Renaming {} to {} is not supported. Move the file with bash first.",
+                    old_path, new_path
+                ));
+            }
+            (Some(path), Some(_)) => {
+                let index = stage_existing(&mut staged, &path, workspace_root).await?;
+                let entry = &mut staged[index];
+                let content = entry.updated.as_deref().unwrap_or_default();
+                let updated = if content.is_empty()
+                    && file.hunks.iter().all(|hunk| hunk.old_lines.is_empty())
+                {
+                    created_content(&file.hunks)
+                } else {
+                    apply_hunks(content, &file.hunks, &path)?
+                };
+                entry.updated = Some(updated);
+            }
+        }
+    }
+    Ok(staged)
+}
+
+/// Index of the staged entry for an existing file, reading it on first use
+async fn stage_existing(
+    staged: &mut Vec<StagedFile>,
+    file_path: &str,
+    workspace_root: &str,
+) -> Result<usize, String> {
+    let resolved = edit_file::resolve_existing_file(file_path, workspace_root)?;
+    let path = Path::new(&resolved)
+        .canonicalize()
+        .unwrap_or_else(|_| PathBuf::from(&resolved));
+
+    if let Some(index) = staged.iter().position(|file| file.path == path) {
+        if staged[index].updated.is_none() {
+            return Err(format!("{} is deleted earlier in this change", file_path));
+        }
+        return Ok(index);
+    }
+
+    let content = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", file_path, e))?;
+    staged.push(StagedFile {
+        path,
+        display_path: display_path(file_path, workspace_root),
+        updated: Some(edit_file::normalize_string(&content)),
+        original: Some(content),
+    });
+    Ok(staged.len() - 1)
+}
+
+fn stage_new(
+    staged: &mut Vec<StagedFile>,
+    file_path: &str,
+    content: String,
+    workspace_root: &str,
+) -> Result<(), String> {
+    let path = if Path::new(file_path).is_absolute() {
+        PathBuf::from(file_path)
+    } else {
+        Path::new(workspace_root).join(file_path)
+    };
+    ensure_within_workspace(&path.to_string_lossy(), workspace_root)?;
+    if path.exists() || staged.iter().any(|file| file.path == path) {
+        return Err(format!(
+            "{} already exists. Use a diff against the existing file instead.",
+            file_path
+        ));
+    }
+
+    staged.push(StagedFile {
+        path,
+        display_path: display_path(file_path, workspace_root),
+        original: None,
+        updated: Some(content),
+    });
+    Ok(())
+}
+
+fn display_path(file_path: &str, workspace_root: &str) -> String {
+    Path::new(file_path)
+        .strip_prefix(workspace_root)
+        .map(|relative| relative.to_string_lossy().to_string())
+        .unwrap_or_else(|_| file_path.to_string())
+}
+
+/// Parse a unified diff leniently: line counts in hunk headers are ignored
+/// (only the start line is kept), and blank lines inside a hunk are read as
+/// empty context lines
+fn parse_unified_diff(patch: &str) -> Result<Vec<FilePatch>, String> {
+    let patch = edit_file::normalize_string(patch);
+    let lines: Vec<&str> = patch.lines().collect();
+    let mut files: Vec<FilePatch> = Vec::new();
+    let mut in_hunk = false;
+    // Blank lines only count as context if more hunk lines follow them
+    let mut pending_blank_lines = 0;
+    let mut last_line_kind = ' ';
+
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        i += 1;
+
+        if let (Some(old), Some(new)) = (
+            line.strip_prefix("--- "),
+            lines.get(i).and_then(|next| next.strip_prefix("+++ ")),
+        ) {
+            files.push(FilePatch {
+                old_path: parse_patch_path(old),
+                new_path: parse_patch_path(new),
+                hunks: Vec::new(),
+            });
+            in_hunk = false;
+            i += 1;
+            continue;
+        }
+
+        if line.starts_with("@@") {
+            let file = files
+                .last_mut()
+                .ok_or_else(|| "Patch has a hunk before any ---/+++ file header".to_string())?;
+            file.hunks.push(Hunk {
+                old_index: parse_hunk_start(line),
+                ..Hunk::default()
+            });
+            in_hunk = true;
+            pending_blank_lines = 0;
+            continue;
+        }
+
+        let Some(hunk) = files.last_mut().and_then(|file| file.hunks.last_mut()) else {
+            continue;
+        };
+        if !in_hunk {
+            continue;
+        }
+        if line.is_empty() {
+            pending_blank_lines += 1;
+            continue;
+        }
+
+        let kind = line.chars().next().unwrap_or(' ');
+        if matches!(kind, ' ' | '-' | '+') {
+            for _ in 0..pending_blank_lines {
+                hunk.old_lines.push(String::new());
+                hunk.new_lines.push(String::new());
+            }
+            pending_blank_lines = 0;
+        }
+        let text = line.get(1..).unwrap_or_default();
+        match kind {
+            ' ' => {
+                hunk.old_lines.push(text.to_string());
+                hunk.new_lines.push(text.to_string());
+            }
+            '-' => hunk.old_lines.push(text.to_string()),
+            '+' => hunk.new_lines.push(text.to_string()),
+            // "\ No newline at end of file" refers to the line before it
+            '\\' => {
+                if last_line_kind != '+' {
+                    hunk.old_missing_newline = true;
+                }
+                if last_line_kind != '-' {
+                    hunk.new_missing_newline = true;
+                }
+                continue;
+            }
+            // Git metadata such as "diff --git" or "index" ends the hunk
+            _ => {
+                in_hunk = false;
+                continue;
+            }
+        }
+        last_line_kind = kind;
+    }
+
+    if files.is_empty() {
+        return Err("Patch does not contain any ---/+++ file headers".to_string());
+    }
+    if let Some(file) = files
+        .iter()
+        .find(|file| file.hunks.is_empty() && file.new_path.is_some())
+    {
+        return Err(format!(
+            "Patch has no hunks for {}",
+            file.new_path.as_deref().unwrap_or_default()
+        ));
+    }
+    Ok(files)
+}
+
+/// Path from a `---`/`+++` header line; `None` for /dev/null
+fn parse_patch_path(raw: &str) -> Option<String> {
+    // Headers may carry a tab-separated timestamp
+    let path = raw.split('\t').next().unwrap_or_default().trim();
+    if path == "/dev/null" {
+        return None;
+    }
+    let path = path
+        .strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path);
+    Some(path.to_string())
+}
+
+/// Index of the first line a hunk header's old range covers, or `None` for a
+/// header without line numbers. An empty range (`-3,0`) inserts after its
+/// line, so the index is the line after it.
+fn parse_hunk_start(header: &str) -> Option<usize> {
+    let range = header
+        .strip_prefix("@@")?
+        .split_whitespace()
+        .next()?
+        .strip_prefix('-')?;
+    let (start, count) = match range.split_once(',') {
+        Some((start, count)) => (start.parse::<usize>().ok()?, count.parse::<usize>().ok()?),
+        None => (range.parse::<usize>().ok()?, 1),
+    };
+    Some(if count == 0 {
+        start
+    } else {
+        start.saturating_sub(1)
+    })
+}
+
+/// Apply a file's hunks top to bottom. A hunk goes where its header puts it
+/// (shifted by the hunks before it) if its old lines match there, otherwise
+/// at the nearest place they do. Lines are compared whole, exactly first and
+/// then with edit_file's smart match, which ignores whitespace around each line.
+fn apply_hunks(content: &str, hunks: &[Hunk], file_path: &str) -> Result<String, String> {
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let mut trailing_newline = content.ends_with('\n');
+    // Hunks may not overlap, so each one is looked for after the last
+    let mut from = 0;
+    let mut shift: isize = 0;
+
+    for (i, hunk) in hunks.iter().enumerate() {
+        let expected = hunk
+            .old_index
+            .map(|index| index.saturating_add_signed(shift));
+        let at = if hunk.old_lines.is_empty() {
+            // Nothing to match, so a pure insertion goes by its line number
+            let index = expected.ok_or_else(|| {
+                format!(
+                    "Hunk {} of {} only adds lines and has no line number to place them",
+                    i + 1,
+                    file_path
+                )
+            })?;
+            if index < from || index > lines.len() {
+                return Err(format!(
+                    "Hunk {} of {} adds lines at line {}, outside the file",
+                    i + 1,
+                    file_path,
+                    index
+                ));
+            }
+            index
+        } else {
+            locate_hunk(&lines, &hunk.old_lines, from, expected)
+                .map_err(|e| format!("Hunk {} of {} {}", i + 1, file_path, e))?
+        };
+
+        let end = at + hunk.old_lines.len();
+        if end == lines.len() {
+            if hunk.new_missing_newline {
+                trailing_newline = false;
+            } else if hunk.old_missing_newline {
+                trailing_newline = true;
+            }
+        }
+        lines.splice(at..end, hunk.new_lines.iter().cloned());
+        from = at + hunk.new_lines.len();
+        shift += hunk.new_lines.len() as isize - hunk.old_lines.len() as isize;
+    }
+
+    let mut updated = lines.join("\n");
+    if trailing_newline && !lines.is_empty() {
+        updated.push('\n');
+    }
+    Ok(updated)
+}
+
+/// Index at which `old` matches whole lines of `lines`, at or after `from`:
+/// the match nearest `expected`, or the only match when there is no line number
+fn locate_hunk(
+    lines: &[String],
+    old: &[String],
+    from: usize,
+    expected: Option<usize>,
+) -> Result<usize, String> {
+    let exact: fn(&[String], &[String]) -> bool = |window, old| window == old;
+    let smart: fn(&[String], &[String]) -> bool = edit_file::lines_match_ignoring_whitespace;
+
+    for same in [exact, smart] {
+        let matches: Vec<usize> = (from..=lines.len().saturating_sub(old.len()))
+            .filter(|&at| {
+                lines
+                    .get(at..at + old.len())
+                    .is_some_and(|window| same(window, old))
+            })
+            .collect();
+        match (expected, matches.as_slice()) {
+            (_, []) => continue,
+            (Some(expected), _) => {
+                return Ok(matches
+                    .into_iter()
+                    .min_by_key(|at| at.abs_diff(expected))
+                    .unwrap_or_default())
+            }
+            (None, [at]) => return Ok(*at),
+            (None, _) => {
+                return Err(
+                    "matches in several places. Add context lines or line numbers.".to_string(),
+                )
+            }
+        }
+    }
+
+    Err(format!(
+        "does not match the file. Expected these lines:\n{}",
+        old.join("\n")
+    ))
+}
+
+/// File contents described by the hunks of a new file
+fn created_content(hunks: &[Hunk]) -> String {
+    let lines: Vec<&str> = hunks
+        .iter()
+        .flat_map(|hunk| hunk.new_lines.iter().map(String::as_str))
+        .collect();
+    let mut content = lines.join("\n");
+    let missing_newline = hunks.last().is_some_and(|hunk| hunk.new_missing_newline);
+    if !lines.is_empty() && !missing_newline {
+        content.push('\n');
+    }
+    content
+}
+
+/// Unified diff of one staged file, with its added and deleted line counts
+fn file_diff(file: &StagedFile) -> Result<(String, usize, usize), git2::Error> {
+    unified_diff(
+        Path::new(&file.display_path),
+        file.original.as_deref().unwrap_or_default().as_bytes(),
+        file.updated.as_deref().unwrap_or_default().as_bytes(),
+    )
+}
+
+/// Unified diff between two versions of a file, with its added and deleted
+/// line counts
+pub(crate) fn unified_diff(
+    path: &Path,
+    old: &[u8],
+    new: &[u8],
+) -> Result<(String, usize, usize), git2::Error> {
+    let mut patch = git2::Patch::from_buffers(old, Some(path), new, Some(path), None)?;
+    let (_, additions, deletions) = patch.line_stats()?;
+    let text = String::from_utf8_lossy(&patch.to_buf()?).to_string();
+    Ok((text, additions, deletions))
+}
+
+/// Every path a unified diff names, old and new; empty if it does not parse
+pub(crate) fn patch_paths(patch: &str) -> Vec<String> {
+    parse_unified_diff(patch)
+        .unwrap_or_default()
+        .into_iter()
+        .flat_map(|file| [file.old_path, file.new_path])
+        .flatten()
+        .collect()
+}
+
+/// Write every staged file, or leave all of them as they were
+async fn commit(files: &[StagedFile]) -> Result<(), String> {
+    let suffix = format!("talkcody-{}", uuid::Uuid::new_v4().simple());
+
+    // Directories made for new files are removed again if the change fails
+    let mut created_dirs: Vec<PathBuf> = Vec::new();
+    for file in files.iter().filter(|file| file.original.is_none()) {
+        for dir in missing_parents(&file.path) {
+            if !created_dirs.contains(&dir) {
+                created_dirs.push(dir);
+            }
+        }
+    }
+
+    // Write new contents beside their targets so the renames stay on one filesystem
+    let mut temps: Vec<Option<PathBuf>> = Vec::new();
+    for file in files {
+        let Some(content) = &file.updated else {
+            temps.push(None);
+            continue;
+        };
+        match write_temp(&file.path, content, &suffix).await {
+            Ok(temp) => temps.push(Some(temp)),
+            Err(e) => {
+                remove_temps(&temps).await;
+                remove_created_dirs(&created_dirs).await;
+                return Err(format!(
+                    "No files were changed. Failed to write {}: {}",
+                    file.display_path, e
+                ));
+            }
+        }
+    }
+
+    // Swap them in; deleted files are moved aside so they can be put back
+    for (index, file) in files.iter().enumerate() {
+        let result = match &temps[index] {
+            Some(temp) => tokio::fs::rename(temp, &file.path).await,
+            None => tokio::fs::rename(&file.path, sibling_path(&file.path, &suffix, "bak")).await,
+        };
+        if let Err(e) = result {
+            remove_temps(&temps[index..]).await;
+            let rollback_errors = rollback(&files[..index], &suffix).await;
+            remove_created_dirs(&created_dirs).await;
+            let mut message = format!(
+                "Failed to update {}: {}. All changes were rolled back.",
+                file.display_path, e
+            );
+            if !rollback_errors.is_empty() {
+                message = format!(
+                    "Failed to update {}: {}. Rolling back also failed, so these files may be left changed: {}",
+                    file.display_path,
+                    e,
+                    rollback_errors.join("; ")
+                );
+            }
+            return Err(message);
+        }
+    }
+
+    for file in files.iter().filter(|file| file.updated.is_none()) {
+        let _ = tokio::fs::remove_file(sibling_path(&file.path, &suffix, "bak")).await;
+    }
+    Ok(())
+}
+
+/// Undo the files already swapped in, newest first
+async fn rollback(applied: &[StagedFile], suffix: &str) -> Vec<String> {
+    let mut errors = Vec::new();
+    for file in applied.iter().rev() {
+        let result = match (&file.original, &file.updated) {
+            (None, _) => tokio::fs::remove_file(&file.path).await,
+            (Some(_), None) => {
+                tokio::fs::rename(sibling_path(&file.path, suffix, "bak"), &file.path).await
+            }
+            (Some(original), Some(_)) => match write_temp(&file.path, original, suffix).await {
+                Ok(temp) => tokio::fs::rename(&temp, &file.path).await,
+                Err(e) => Err(e),
+            },
+        };
+        if let Err(e) = result {
+            errors.push(format!("{}: {}", file.display_path, e));
+        }
+    }
+    errors
+}
+
+/// Directories above `path` that do not exist yet
+fn missing_parents(path: &Path) -> Vec<PathBuf> {
+    path.ancestors()
+        .skip(1)
+        .take_while(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+        .map(Path::to_path_buf)
+        .collect()
+}
+
+/// Remove the directories made for new files, deepest first, where they are empty
+async fn remove_created_dirs(dirs: &[PathBuf]) {
+    let mut dirs = dirs.to_vec();
+    dirs.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+    for dir in dirs {
+        let _ = tokio::fs::remove_dir(&dir).await;
+    }
+}
+
+/// Write `content` to a temporary file next to `target`, copying the
+/// target's permissions when it exists
+async fn write_temp(target: &Path, content: &str, suffix: &str) -> std::io::Result<PathBuf> {
+    if let Some(parent) = target.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let temp = sibling_path(target, suffix, "tmp");
+    tokio::fs::write(&temp, content).await?;
+    if let Ok(metadata) = tokio::fs::metadata(target).await {
+        if let Err(e) = tokio::fs::set_permissions(&temp, metadata.permissions()).await {
+            let _ = tokio::fs::remove_file(&temp).await;
+            return Err(e);
+        }
+    }
+    Ok(temp)
+}
+
+async fn remove_temps(temps: &[Option<PathBuf>]) {
+    for temp in temps.iter().flatten() {
+        let _ = tokio::fs::remove_file(temp).await;
+    }
+}
+
+/// Hidden file beside `path`, e.g. `src/.main.rs.<suffix>.tmp`
+fn sibling_path(path: &Path, suffix: &str, extension: &str) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.{}", name, suffix, extension))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn context(workspace: &TempDir) -> ToolContext {
+        ToolContext {
+            session_id: "test".to_string(),
+            task_id: "test".to_string(),
+            workspace_root: workspace.path().to_string_lossy().to_string(),
+            worktree_path: None,
+            settings: crate::storage::models::TaskSettings::default(),
+            llm_state: None,
+            event_sender: None,
+        }
+    }
+
+    fn edit(old_string: &str, new_string: &str) -> EditBlock {
+        EditBlock {
+            old_string: old_string.to_string(),
+            new_string: new_string.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_edits_several_files() {
+        let workspace = TempDir::new().unwrap();
+        std::fs::write(workspace.path().join("a.txt"), "alpha\nbeta\n").unwrap();
+        std::fs::write(workspace.path().join("b.txt"), "gamma\n").unwrap();
+
+        let files = vec![
+            FileEdits {
+                file_path: "a.txt".to_string(),
+                edits: vec![edit("beta", "BETA")],
+            },
+            FileEdits {
+                file_path: "b.txt".to_string(),
+                edits: vec![edit("gamma", "GAMMA")],
+            },
+        ];
+        let result = execute(files, None, &context(&workspace)).await;
+
+        assert!(result.success, "{}", result.message);
+        assert_eq!(
+            std::fs::read_to_string(workspace.path().join("a.txt")).unwrap(),
+            "alpha\nBETA\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(workspace.path().join("b.txt")).unwrap(),
+            "GAMMA\n"
+        );
+        let diff = result.diff.unwrap();
+        assert!(diff.contains("-beta\n+BETA"));
+        assert!(diff.contains("-gamma\n+GAMMA"));
+    }
+
+    #[tokio::test]
+    async fn test_failed_match_changes_nothing() {
+        let workspace = TempDir::new().unwrap();
+        std::fs::write(workspace.path().join("a.txt"), "alpha\n").unwrap();
+        std::fs::write(workspace.path().join("b.txt"), "gamma\n").unwrap();
+
+        let files = vec![
+            FileEdits {
+                file_path: "a.txt".to_string(),
+                edits: vec![edit("alpha", "ALPHA")],
+            },
+            FileEdits {
+                file_path: "b.txt".to_string(),
+                edits: vec![edit("missing", "MISSING")],
+            },
+        ];
+        let result = execute(files, None, &context(&workspace)).await;
+
+        assert!(!result.success);
+        assert!(result.message.starts_with("No files were changed."));
+        assert_eq!(
+            std::fs::read_to_string(workspace.path().join("a.txt")).unwrap(),
+            "alpha\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_applies_unified_diff() {
+        let workspace = TempDir::new().unwrap();
+        std::fs::write(
+            workspace.path().join("main.rs"),
+            "fn main() {\n    println!(\"hi\");\n}\n",
+        )
+        .unwrap();
+        std::fs::write(workspace.path().join("old.txt"), "obsolete\n").unwrap();
+
+        let patch = "\
+diff --git a/main.rs b/main.rs
+--- a/main.rs
++++ b/main.rs
+@@ -1,3 +1,3 @@
+ fn main() {
+-    println!(\"hi\");
++    println!(\"hello\");
+ }
+--- /dev/null
++++ b/new.txt
+@@ -0,0 +1,2 @@
++first
++second
+--- a/old.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-obsolete
+";
+        let result = execute(Vec::new(), Some(patch), &context(&workspace)).await;
+
+        assert!(result.success, "{}", result.message);
+        assert_eq!(
+            std::fs::read_to_string(workspace.path().join("main.rs")).unwrap(),
+            "fn main() {\n    println!(\"hello\");\n}\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(workspace.path().join("new.txt")).unwrap(),
+            "first\nsecond\n"
+        );
+        assert!(!workspace.path().join("old.txt").exists());
+        let kinds: Vec<ChangeKind> = result.files.unwrap().iter().map(|f| f.change).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ChangeKind::Modified,
+                ChangeKind::Created,
+                ChangeKind::Deleted
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_rename_rolls_back_earlier_files() {
+        let workspace = TempDir::new().unwrap();
+        let modified = workspace.path().join("modified.txt");
+        let created = workspace.path().join("new/dir/created.txt");
+        std::fs::write(&modified, "before\n").unwrap();
+
+        let files = vec![
+            StagedFile {
+                path: modified.clone(),
+                display_path: "modified.txt".to_string(),
+                original: Some("before\n".to_string()),
+                updated: Some("after\n".to_string()),
+            },
+            StagedFile {
+                path: created.clone(),
+                display_path: "new/dir/created.txt".to_string(),
+                original: None,
+                updated: Some("new\n".to_string()),
+            },
+            // Deleting a file that is already gone fails after the others were swapped in
+            StagedFile {
+                path: workspace.path().join("missing.txt"),
+                display_path: "missing.txt".to_string(),
+                original: Some("gone\n".to_string()),
+                updated: None,
+            },
+        ];
+
+        let error = commit(&files).await.unwrap_err();
+
+        assert!(error.contains("rolled back"), "{}", error);
+        assert_eq!(std::fs::read_to_string(&modified).unwrap(), "before\n");
+        assert!(!created.exists());
+        // The directories made for the new file are gone too
+        let leftovers = std::fs::read_dir(workspace.path()).unwrap().count();
+        assert_eq!(leftovers, 1);
+    }
+
+    #[test]
+    fn test_parse_unified_diff_keeps_blank_context_lines() {
+        let patch = "--- a/x.txt\n+++ b/x.txt\n@@ -1,3 +1,3 @@\n one\n\n-two\n+TWO\n\n";
+        let files = parse_unified_diff(patch).unwrap();
+
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].old_path.as_deref(), Some("x.txt"));
+        let hunk = &files[0].hunks[0];
+        assert_eq!(hunk.old_lines, vec!["one", "", "two"]);
+        assert_eq!(hunk.new_lines, vec!["one", "", "TWO"]);
+    }
+
+    fn apply_patch(content: &str, patch: &str) -> Result<String, String> {
+        let files = parse_unified_diff(patch).unwrap();
+        apply_hunks(content, &files[0].hunks, "x.txt")
+    }
+
+    #[test]
+    fn test_applies_zero_context_hunks_by_line_number() {
+        // As produced by `diff -U0`: insertions carry no context at all
+        let patch = "--- a/x.txt\n+++ b/x.txt\n@@ -1,0 +2 @@\n+inserted\n@@ -3 +4 @@\n-c\n+C\n@@ -4,0 +6,2 @@\n+e\n+f\n";
+        assert_eq!(
+            apply_patch("a\nb\nc\nd\n", patch).unwrap(),
+            "a\ninserted\nb\nC\nd\ne\nf\n"
+        );
+
+        // Without a line number an insertion has nowhere to go
+        let patch = "--- a/x.txt\n+++ b/x.txt\n@@ @@\n+lost\n";
+        assert!(apply_patch("a\n", patch).is_err());
+    }
+
+    #[test]
+    fn test_hunks_match_whole_lines() {
+        // `foo` does not match inside `foobar`
+        let patch = "--- a/x.txt\n+++ b/x.txt\n@@\n-foo\n+baz\n";
+        assert_eq!(
+            apply_patch("foobar\nfoo\n", patch).unwrap(),
+            "foobar\nbaz\n"
+        );
+        assert!(apply_patch("foo\nfoo\n", patch).is_err());
+
+        // The line number picks between matches
+        let patch = "--- a/x.txt\n+++ b/x.txt\n@@ -3 +3 @@\n-foo\n+baz\n";
+        assert_eq!(
+            apply_patch("foo\nbar\nfoo\n", patch).unwrap(),
+            "foo\nbar\nbaz\n"
+        );
+
+        // Context that only differs in trailing whitespace still matches
+        let patch = "--- a/x.txt\n+++ b/x.txt\n@@ -1,2 +1,2 @@\n one\n-two\n+TWO\n";
+        assert_eq!(apply_patch("one  \ntwo\n", patch).unwrap(), "one\nTWO\n");
+
+        // Indentation drift is matched the way edit blocks match it
+        let patch =
+            "--- a/x.txt\n+++ b/x.txt\n@@ -1,2 +1,2 @@\n fn main() {\n-  run();\n+    start();\n";
+        let content = "fn main() {\n    run();\n}\n";
+        assert_eq!(
+            apply_patch(content, patch).unwrap(),
+            "fn main() {\n    start();\n}\n"
+        );
+    }
+}
@@ -224,6 +224,7 @@ mod tests {
             worktree_path: None,
             settings: crate::storage::models::TaskSettings::default(),
             llm_state: None,
+            event_sender: None,
         };
 
         let result = execute(file_path.to_str().unwrap(), None, None, &ctx).await;
@@ -243,6 +244,7 @@ mod tests {
             worktree_path: None,
             settings: crate::storage::models::TaskSettings::default(),
             llm_state: None,
+            event_sender: None,
         };
 
         let result = execute("nonexistent.txt", None, None, &ctx).await;
@@ -266,6 +268,7 @@ mod tests {
             worktree_path: None,
             settings: crate::storage::models::TaskSettings::default(),
             llm_state: None,
+            event_sender: None,
         };
 
         let result = execute(file_path.to_str().unwrap(), Some(2), Some(2), &ctx).await;
@@ -74,10 +74,13 @@ fn is_safe_task_id(task_id: &str) -> bool {
             .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
 }
 
-fn build_tool_output_path(
+/// Where a tool spills output too large to return inline:
+/// `<workspace>/.talkcody/tool/<task_id>/<tool_call_id>_<suffix>.txt`
+pub(crate) fn build_tool_output_path(
     workspace_root: &str,
     task_id: &str,
     tool_call_id: &str,
+    suffix: &str,
 ) -> Result<PathBuf, String> {
     if workspace_root.is_empty() {
         return Err("Workspace root is empty".to_string());
@@ -85,7 +88,7 @@ fn build_tool_output_path(
     if !is_safe_task_id(task_id) {
         return Err("Invalid task ID".to_string());
     }
-    let file_name = sanitize_file_name(&format!("{}_web-fetch.txt", tool_call_id));
+    let file_name = sanitize_file_name(&format!("{}_{}.txt", tool_call_id, suffix));
     Ok(Path::new(workspace_root)
         .join(".talkcody")
         .join("tool")
@@ -272,7 +275,12 @@ async fn handle_large_content(
         return Ok(result);
     }
 
-    let file_path = match build_tool_output_path(&ctx.workspace_root, &ctx.task_id, tool_call_id) {
+    let file_path = match build_tool_output_path(
+        &ctx.workspace_root,
+        &ctx.task_id,
+        tool_call_id,
+        "web-fetch",
+    ) {
         Ok(path) => path,
         Err(_) => {
             let truncated = truncate_content(&result.content);
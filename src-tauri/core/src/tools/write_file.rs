@@ -43,57 +43,10 @@ pub async fn execute(
     };
 
     // Security check: ensure path is within workspace
-    let workspace_root = Path::new(&ctx.workspace_root)
-        .canonicalize()
-        .unwrap_or_else(|_| Path::new(&ctx.workspace_root).to_path_buf());
-    let target_path = match Path::new(&path).canonicalize() {
-        Ok(p) => p,
-        Err(_) => {
-            // File doesn't exist yet, check parent directories
-            // Find the first existing parent directory to validate the path
-            let path_obj = Path::new(&path);
-            let mut existing_parent: Option<&Path> = None;
-
-            for ancestor in path_obj.ancestors() {
-                if ancestor.exists() {
-                    existing_parent = Some(ancestor);
-                    break;
-                }
-            }
-
-            if let Some(parent) = existing_parent {
-                match parent.canonicalize() {
-                    Ok(canon_parent) => {
-                        // Reconstruct the path with canonicalized parent
-                        let relative_part = path_obj.strip_prefix(parent).unwrap_or(path_obj);
-                        canon_parent.join(relative_part)
-                    }
-                    Err(e) => {
-                        return WriteFileResult {
-                            success: false,
-                            message: format!("Invalid path: {}", e),
-                            file_path: Some(path),
-                        };
-                    }
-                }
-            } else {
-                return WriteFileResult {
-                    success: false,
-                    message: "Invalid path: no parent directory exists".to_string(),
-                    file_path: Some(path),
-                };
-            }
-        }
-    };
-
-    // Check if target is within workspace
-    if !target_path.starts_with(&workspace_root) {
+    if let Err(message) = ensure_within_workspace(&path, &ctx.workspace_root) {
         return WriteFileResult {
             success: false,
-            message: format!(
-                "Security Error: File path \"{}\" is outside the allowed project directory \"{}\". Files can only be written within the current project directory.",
-                path, ctx.workspace_root
-            ),
+            message,
             file_path: Some(path),
         };
     }
@@ -137,6 +90,43 @@ pub async fn execute(
     }
 }
 
+/// Check that `path`, which need not exist yet, stays inside the workspace
+pub(crate) fn ensure_within_workspace(path: &str, workspace_root: &str) -> Result<(), String> {
+    let canonical_root = Path::new(workspace_root)
+        .canonicalize()
+        .unwrap_or_else(|_| Path::new(workspace_root).to_path_buf());
+    let target_path = match Path::new(path).canonicalize() {
+        Ok(p) => p,
+        Err(_) => {
+            // File doesn't exist yet, check parent directories
+            // Find the first existing parent directory to validate the path
+            let path_obj = Path::new(path);
+            let existing_parent = path_obj.ancestors().find(|ancestor| ancestor.exists());
+
+            match existing_parent {
+                Some(parent) => match parent.canonicalize() {
+                    Ok(canon_parent) => {
+                        // Reconstruct the path with canonicalized parent
+                        let relative_part = path_obj.strip_prefix(parent).unwrap_or(path_obj);
+                        canon_parent.join(relative_part)
+                    }
+                    Err(e) => return Err(format!("Invalid path: {}", e)),
+                },
+                None => return Err("Invalid path: no parent directory exists".to_string()),
+            }
+        }
+    };
+
+    // Check if target is within workspace
+    if !target_path.starts_with(&canonical_root) {
+        return Err(format!(
+            "Security Error: File path \"{}\" is outside the allowed project directory \"{}\". Files can only be written within the current project directory.",
+            path, workspace_root
+        ));
+    }
+    Ok(())
+}
+
 /// Normalize string line endings (like TS normalizeString)
 fn normalize_string(s: &str) -> String {
     s.replace("\r\n", "\n").replace('\r', "\n")
@@ -159,6 +149,7 @@ mod tests {
             worktree_path: None,
             settings: crate::storage::models::TaskSettings::default(),
             llm_state: None,
+            event_sender: None,
         };
 
         let result = execute(file_path.to_str().unwrap(), "Hello, World!", false, &ctx).await;
@@ -179,6 +170,7 @@ mod tests {
             worktree_path: None,
             settings: crate::storage::models::TaskSettings::default(),
             llm_state: None,
+            event_sender: None,
         };
 
         let result = execute(outside_path, "test content", false, &ctx).await;
@@ -199,6 +191,7 @@ mod tests {
             worktree_path: None,
             settings: crate::storage::models::TaskSettings::default(),
             llm_state: None,
+            event_sender: None,
         };
 
         let result = execute(file_path.to_str().unwrap(), "Nested content", false, &ctx).await;
@@ -162,6 +162,7 @@ impl AgentLoop {
             provider_options: None,
             request_id: Some(ctx.task_id.clone()),
             trace_context: None,
+            response_format: None,
         };
 
         // Run stream
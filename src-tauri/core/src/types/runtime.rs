@@ -227,6 +227,7 @@ impl CoreRuntime {
         // Emit state change event
         let _ = event_sender.send(RuntimeEvent::TaskStateChanged {
             task_id: task.id.clone(),
+            session_id: task.session_id.clone(),
             state: RuntimeTaskState::Running,
             previous_state: RuntimeTaskState::Pending,
         });
@@ -308,6 +309,8 @@ impl CoreRuntime {
                     .and_then(|v| v.as_str().map(|s| s.to_string()))
             }),
             llm_state: None,
+            system_prompt: None,
+            attachments: HashMap::new(),
         };
 
         // Get current messages and run agent loop
@@ -334,7 +337,7 @@ impl CoreRuntime {
             }
 
             match agent_loop.run_iteration(&ctx, &messages).await {
-                Ok(AgentLoopResult::Completed { message }) => {
+                Ok(AgentLoopResult::Completed { message, .. }) => {
                     let assistant_message = Message {
                         id: format!("msg_{}", uuid::Uuid::new_v4()),
                         session_id: task.session_id.clone(),
@@ -393,6 +396,7 @@ impl CoreRuntime {
                             id: call.tool_call_id.clone(),
                             name: call.name.clone(),
                             input: call.input.clone(),
+                            provider_metadata: call.provider_metadata.clone(),
                         })
                         .collect::<Vec<_>>();
                     let tool_calls_message = Message {
@@ -425,6 +429,7 @@ impl CoreRuntime {
                             worktree_path: ctx.worktree_path.clone(),
                             settings: ctx.settings.clone(),
                             llm_state: ctx.llm_state.clone(),
+                            event_sender: Some(event_sender.clone()),
                         };
 
                         let auto_approve = ctx.settings.auto_approve_edits.unwrap_or(false);
@@ -432,6 +437,7 @@ impl CoreRuntime {
                             *task_state.write().await = RuntimeTaskState::WaitingForUser;
                             let _ = event_sender.send(RuntimeEvent::ToolCallRequested {
                                 task_id: task.id.clone(),
+                                session_id: task.session_id.clone(),
                                 request: call,
                             });
                             return;
@@ -440,6 +446,7 @@ impl CoreRuntime {
                         let result = self.tool_registry.execute(call.clone(), tool_context).await;
                         let _ = event_sender.send(RuntimeEvent::ToolCallCompleted {
                             task_id: task.id.clone(),
+                            session_id: task.session_id.clone(),
                             result: result.clone(),
                         });
 
@@ -483,6 +490,7 @@ impl CoreRuntime {
                     *task_state.write().await = RuntimeTaskState::WaitingForUser;
                     let _ = event_sender.send(RuntimeEvent::ToolCallRequested {
                         task_id: task.id.clone(),
+                        session_id: task.session_id.clone(),
                         request,
                     });
                     break;
@@ -564,6 +572,7 @@ impl CoreRuntime {
         // Emit completion event
         let _ = event_sender.send(RuntimeEvent::TaskStateChanged {
             task_id: task.id.clone(),
+            session_id: task.session_id.clone(),
             state: final_state,
             previous_state,
         });
@@ -571,6 +580,7 @@ impl CoreRuntime {
         let _ = event_sender.send(RuntimeEvent::TaskCompleted {
             task_id: task.id.clone(),
             session_id: task.session_id.clone(),
+            stop_reason: None,
         });
 
         if let Some(err) = error {
@@ -634,6 +644,10 @@ mod tests {
             auto_approve_edits: Some(true),
             auto_approve_plan: Some(true),
             auto_code_review: None,
+            agent_loop: None,
+            completion_hooks: None,
+            budget: None,
+            sandbox: None,
             extra: HashMap::new(),
         };
         let result = validator.validate(&risky_settings);
@@ -1068,6 +1068,7 @@ pub fn run() {
             oauth_callback_server::start_oauth_callback_server,
             llm_commands::llm_stream_text,
             llm_commands::llm_list_available_models,
+            llm_commands::llm_refresh_local_models,
             llm_commands::llm_register_custom_provider,
             llm_commands::llm_check_model_updates,
             llm_commands::llm_get_provider_configs,
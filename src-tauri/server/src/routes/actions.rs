@@ -3,7 +3,13 @@ use axum::Json;
 
 use crate::state::ServerState;
 use crate::types::*;
-use talkcody_core::core::types::TaskAction;
+use talkcody_core::core::types::{TaskAction, TaskHandle};
+
+/// Find the active task running in a session
+pub async fn find_session_task(state: &ServerState, session_id: &str) -> Option<TaskHandle> {
+    let tasks = state.runtime().list_active_tasks().await;
+    tasks.into_iter().find(|t| t.session_id == session_id)
+}
 
 /// Create an action on a session (approve, reject, tool_result, cancel)
 pub async fn create_action(
@@ -12,10 +18,7 @@ pub async fn create_action(
     Json(payload): Json<CreateActionRequest>,
 ) -> Result<Json<CreateActionResponse>, Json<ErrorResponse>> {
     // Find the active task for this session
-    let tasks = state.runtime().list_active_tasks().await;
-    let task_handle = tasks.into_iter().find(|t| t.session_id == session_id);
-
-    let task_handle = match task_handle {
+    let task_handle = match find_session_task(&state, &session_id).await {
         Some(handle) => handle,
         None => {
             return Err(Json(ErrorResponse::new(
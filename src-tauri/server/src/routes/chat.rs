@@ -10,13 +10,20 @@ use axum::{
 use std::convert::Infallible;
 use tokio::sync::broadcast;
 
+use crate::routes::sessions::event_matches_session;
 use crate::state::ServerState;
+use crate::streaming_bridge::StreamEvent;
 use crate::types::*;
+use base64::{engine::general_purpose::STANDARD, Engine};
 use talkcody_core::core::types::{RuntimeEvent, TaskInput};
 use talkcody_core::storage::models::{
-    Message, MessageContent, MessageRole, SessionStatus, TaskSettings,
+    AgentLoopSettings, Attachment, AttachmentOrigin, Message, MessageContent, MessagePart,
+    MessageRole, SessionStatus, TaskSettings,
 };
 
+/// Prefix for referencing an uploaded attachment in an `image_url` part
+const ATTACHMENT_URL_PREFIX: &str = "attachment://";
+
 /// Chat request - OpenAI compatible format
 #[derive(Debug, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -307,24 +314,35 @@ pub async fn chat(
 
     log::info!("[CHAT] Found user message");
 
-    let user_content = match &user_message.content {
+    // Save user message to storage
+    let now = chrono::Utc::now().timestamp();
+    let message_id = format!("msg_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
+
+    let (user_content, attachments) = match &user_message.content {
         serde_json::Value::String(s) => {
             log::debug!("[CHAT] User content (string): {} chars", s.len());
-            s.clone()
+            (s.clone(), Vec::new())
+        }
+        serde_json::Value::Array(parts) => {
+            log::debug!("[CHAT] User content: {} parts", parts.len());
+            let parsed = parse_content_parts(parts)
+                .map_err(|e| Json(ErrorResponse::new("BAD_REQUEST", e)))?;
+            store_content_parts(&state, &session_id, &message_id, parsed)
+                .await
+                .map_err(|e| {
+                    log::error!("[CHAT] Failed to store attachments: {}", e);
+                    Json(ErrorResponse::new("INTERNAL_ERROR", e))
+                })?
         }
         _ => {
             log::debug!(
                 "[CHAT] User content (non-string): {:?}",
                 user_message.content
             );
-            user_message.content.to_string()
+            (user_message.content.to_string(), Vec::new())
         }
     };
 
-    // Save user message to storage
-    let now = chrono::Utc::now().timestamp();
-    let message_id = format!("msg_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
-
     let role = match user_message.role.as_str() {
         "system" => MessageRole::System,
         "assistant" => MessageRole::Assistant,
@@ -336,9 +354,7 @@ pub async fn chat(
         id: message_id.clone(),
         session_id: session_id.clone(),
         role,
-        content: MessageContent::Text {
-            text: user_content.clone(),
-        },
+        content: MessageContent::with_attachments(user_content.clone(), attachments.clone()),
         created_at: now,
         tool_call_id: user_message.tool_call_id.clone(),
         parent_id: None,
@@ -371,25 +387,23 @@ pub async fn chat(
             serde_json::Value::String(model.clone()),
         );
     }
-    if let Some(temp) = payload.temperature {
-        extra.insert(
-            "temperature".to_string(),
-            serde_json::Value::Number(
-                serde_json::Number::from_f64(temp as f64).unwrap_or(serde_json::Number::from(0)),
-            ),
-        );
-    }
-    if let Some(max_tok) = payload.max_tokens {
-        extra.insert(
-            "max_tokens".to_string(),
-            serde_json::Value::Number(serde_json::Number::from(max_tok)),
-        );
-    }
+
+    let agent_loop = (payload.temperature.is_some() || payload.max_tokens.is_some()).then(|| {
+        AgentLoopSettings {
+            temperature: payload.temperature,
+            max_tokens: payload.max_tokens.and_then(|t| u32::try_from(t).ok()),
+            ..Default::default()
+        }
+    });
 
     let settings = TaskSettings {
         auto_approve_edits: None,
         auto_approve_plan: None,
         auto_code_review: None,
+        agent_loop,
+        completion_hooks: None,
+        budget: None,
+        sandbox: None,
         extra,
     };
 
@@ -409,6 +423,7 @@ pub async fn chat(
         agent_id: payload.agent_id.clone(),
         project_id: payload.project_name.clone(),
         initial_message: user_content,
+        attachments,
         settings: Some(settings),
         workspace,
     };
@@ -448,27 +463,10 @@ pub async fn chat(
 
         loop {
             match rx.recv().await {
-                Ok(event) => {
+                Ok(StreamEvent { event, .. }) => {
                     event_count += 1;
                     // Filter events for this session
-                    let session_matches = match &event {
-                        RuntimeEvent::Token { session_id: s, .. } => s == &session_id_clone,
-                        RuntimeEvent::ReasoningStart { session_id: s, .. } => s == &session_id_clone,
-                        RuntimeEvent::ReasoningDelta { session_id: s, .. } => s == &session_id_clone,
-                        RuntimeEvent::ReasoningEnd { session_id: s, .. } => s == &session_id_clone,
-                        RuntimeEvent::MessageCreated { session_id: s, .. } => s == &session_id_clone,
-                        RuntimeEvent::Usage { session_id: s, .. } => s == &session_id_clone,
-                        RuntimeEvent::Done { session_id: s, .. } => s == &session_id_clone,
-                        RuntimeEvent::ToolCallRequested { task_id: _, .. } => true,
-                        RuntimeEvent::ToolCallCompleted { task_id: _, .. } => true,
-                        RuntimeEvent::TaskStateChanged { task_id: _, .. } => true,
-                        RuntimeEvent::TaskCompleted { session_id: s, .. } => s == &session_id_clone,
-                        RuntimeEvent::Error { session_id: s, .. } => {
-                            s.as_ref().map(|s| s == &session_id_clone).unwrap_or(false)
-                        }
-                    };
-
-                    if !session_matches {
+                    if !event_matches_session(&event, &session_id_clone) {
                         continue;
                     }
                     log::debug!("[CHAT] Event #{} matches session {}, converting to SSE", event_count, session_id_clone);
@@ -508,6 +506,193 @@ pub async fn chat(
     Ok(Sse::new(stream).keep_alive(KeepAlive::new()))
 }
 
+/// Kind of media carried by a chat content part
+#[derive(Debug, Clone, Copy)]
+enum MediaKind {
+    Image,
+    File,
+    Audio,
+}
+
+impl MediaKind {
+    fn part(self, attachment_id: String) -> MessagePart {
+        match self {
+            MediaKind::Image => MessagePart::Image { attachment_id },
+            MediaKind::File => MessagePart::File { attachment_id },
+            MediaKind::Audio => MessagePart::Audio { attachment_id },
+        }
+    }
+}
+
+/// A chat content part before inline data is stored as an attachment
+#[derive(Debug)]
+enum ParsedPart {
+    Text(String),
+    /// Existing attachment of the session
+    Attachment(MediaKind, String),
+    /// Inline data to store as a new attachment
+    Inline {
+        kind: MediaKind,
+        filename: String,
+        mime_type: String,
+        data: Vec<u8>,
+    },
+}
+
+/// Parse an OpenAI-style content array.
+/// Images and files are given as `data:` URLs or references to uploaded
+/// attachments (`attachment://<id>` or `file_id`); remote URLs are not fetched.
+fn parse_content_parts(parts: &[serde_json::Value]) -> Result<Vec<ParsedPart>, String> {
+    parts
+        .iter()
+        .map(|part| {
+            let part_type = part.get("type").and_then(|t| t.as_str()).unwrap_or("");
+            match part_type {
+                "text" | "input_text" => Ok(ParsedPart::Text(
+                    part.get("text")
+                        .and_then(|t| t.as_str())
+                        .unwrap_or_default()
+                        .to_string(),
+                )),
+                "image_url" => {
+                    // `image_url` is either `{ "url": ... }` or the URL itself
+                    let url = part
+                        .get("image_url")
+                        .and_then(|i| i.get("url").or(Some(i)))
+                        .and_then(|u| u.as_str())
+                        .ok_or("image_url part is missing a url")?;
+                    if let Some(id) = url.strip_prefix(ATTACHMENT_URL_PREFIX) {
+                        return Ok(ParsedPart::Attachment(MediaKind::Image, id.to_string()));
+                    }
+                    let (mime_type, data) = decode_data_url(url)?;
+                    Ok(ParsedPart::Inline {
+                        kind: MediaKind::Image,
+                        filename: format!("image.{}", extension(&mime_type)),
+                        mime_type,
+                        data,
+                    })
+                }
+                "input_audio" => {
+                    let audio = part
+                        .get("input_audio")
+                        .ok_or("input_audio part is missing input_audio")?;
+                    let format = audio
+                        .get("format")
+                        .and_then(|f| f.as_str())
+                        .unwrap_or("wav");
+                    let data = audio
+                        .get("data")
+                        .and_then(|d| d.as_str())
+                        .ok_or("input_audio part is missing data")?;
+                    Ok(ParsedPart::Inline {
+                        kind: MediaKind::Audio,
+                        filename: format!("audio.{}", format),
+                        mime_type: format!("audio/{}", format),
+                        data: decode_base64(data)?,
+                    })
+                }
+                "file" => {
+                    let file = part.get("file").ok_or("file part is missing file")?;
+                    if let Some(id) = file.get("file_id").and_then(|f| f.as_str()) {
+                        return Ok(ParsedPart::Attachment(MediaKind::File, id.to_string()));
+                    }
+                    let file_data = file
+                        .get("file_data")
+                        .and_then(|f| f.as_str())
+                        .ok_or("file part needs file_id or file_data")?;
+                    let (mime_type, data) = decode_data_url(file_data)?;
+                    let filename = file
+                        .get("filename")
+                        .and_then(|f| f.as_str())
+                        .map(String::from)
+                        .unwrap_or_else(|| format!("file.{}", extension(&mime_type)));
+                    Ok(ParsedPart::Inline {
+                        kind: MediaKind::File,
+                        filename,
+                        mime_type,
+                        data,
+                    })
+                }
+                other => Err(format!("Unsupported content part type '{}'", other)),
+            }
+        })
+        .collect()
+}
+
+/// Store inline parts as session attachments.
+/// Returns the joined text and the media parts referencing attachments.
+async fn store_content_parts(
+    state: &ServerState,
+    session_id: &str,
+    message_id: &str,
+    parts: Vec<ParsedPart>,
+) -> Result<(String, Vec<MessagePart>), String> {
+    let mut texts = Vec::new();
+    let mut media = Vec::new();
+    for part in parts {
+        match part {
+            ParsedPart::Text(text) => texts.push(text),
+            ParsedPart::Attachment(kind, id) => media.push(kind.part(id)),
+            ParsedPart::Inline {
+                kind,
+                filename,
+                mime_type,
+                data,
+            } => {
+                let attachment = Attachment {
+                    id: format!("att_{}", uuid::Uuid::new_v4().to_string().replace("-", "")),
+                    session_id: session_id.to_string(),
+                    message_id: Some(message_id.to_string()),
+                    filename,
+                    mime_type,
+                    size: data.len() as i64,
+                    path: String::new(), // Set by create_attachment
+                    created_at: chrono::Utc::now().timestamp(),
+                    origin: AttachmentOrigin::UserUpload,
+                };
+                state
+                    .storage()
+                    .attachments
+                    .create_attachment(&attachment, &data)
+                    .await?;
+                media.push(kind.part(attachment.id));
+            }
+        }
+    }
+    Ok((texts.join("\n"), media))
+}
+
+/// Split a base64 `data:` URL into its MIME type and bytes
+fn decode_data_url(url: &str) -> Result<(String, Vec<u8>), String> {
+    let (header, data) = url
+        .strip_prefix("data:")
+        .and_then(|rest| rest.split_once(','))
+        .ok_or_else(|| {
+            format!(
+                "Expected a data: URL or {}<id> reference",
+                ATTACHMENT_URL_PREFIX
+            )
+        })?;
+    let mime_type = header
+        .strip_suffix(";base64")
+        .ok_or("Only base64 data: URLs are supported")?;
+    Ok((mime_type.to_string(), decode_base64(data)?))
+}
+
+fn decode_base64(data: &str) -> Result<Vec<u8>, String> {
+    STANDARD
+        .decode(data)
+        .map_err(|e| format!("Invalid base64 data: {}", e))
+}
+
+/// File extension for a MIME type, used to name inline attachments
+fn extension(mime_type: &str) -> &str {
+    mime_type
+        .split_once('/')
+        .map(|(_, subtype)| subtype.split(['+', ';']).next().unwrap_or(subtype))
+        .unwrap_or("bin")
+}
+
 /// Convert RuntimeEvent to OpenAI-compatible SSE Event
 fn convert_runtime_event_to_openai_sse(
     event: &RuntimeEvent,
@@ -601,8 +786,14 @@ fn finalize_openai_stream(state: &OpenAiStreamState) -> Option<Event> {
 
 /// Convert RuntimeEvent to SSE Event
 pub fn convert_runtime_event_to_sse(event: &RuntimeEvent) -> Event {
+    let (name, payload) = runtime_event_payload(event);
+    Event::default().event(name).data(payload.to_string())
+}
+
+/// Convert RuntimeEvent to its event name and JSON payload (shared by SSE and WebSocket)
+pub fn runtime_event_payload(event: &RuntimeEvent) -> (&'static str, serde_json::Value) {
     log::trace!(
-        "[CHAT] Converting RuntimeEvent to SSE: {:?}",
+        "[CHAT] Converting RuntimeEvent: {:?}",
         std::mem::discriminant(event)
     );
     match event {
@@ -611,34 +802,33 @@ pub fn convert_runtime_event_to_sse(event: &RuntimeEvent) -> Event {
                 "[CHAT] Converting Token event, token length: {} chars",
                 token.len()
             );
-            Event::default().event("token").data(
+            (
+                "token",
                 serde_json::json!({
                     "type": "token",
                     "data": {
                         "token": token,
                         "sessionId": session_id
                     }
-                })
-                .to_string(),
-            )
-        }
-        RuntimeEvent::ReasoningStart { session_id, id } => {
-            Event::default().event("reasoning.start").data(
-                serde_json::json!({
-                    "type": "reasoning.start",
-                    "data": {
-                        "id": id,
-                        "sessionId": session_id
-                    }
-                })
-                .to_string(),
+                }),
             )
         }
+        RuntimeEvent::ReasoningStart { session_id, id } => (
+            "reasoning.start",
+            serde_json::json!({
+                "type": "reasoning.start",
+                "data": {
+                    "id": id,
+                    "sessionId": session_id
+                }
+            }),
+        ),
         RuntimeEvent::ReasoningDelta {
             session_id,
             id,
             text,
-        } => Event::default().event("reasoning.delta").data(
+        } => (
+            "reasoning.delta",
             serde_json::json!({
                 "type": "reasoning.delta",
                 "data": {
@@ -646,106 +836,164 @@ pub fn convert_runtime_event_to_sse(event: &RuntimeEvent) -> Event {
                     "text": text,
                     "sessionId": session_id
                 }
-            })
-            .to_string(),
+            }),
+        ),
+        RuntimeEvent::ReasoningEnd { session_id, id } => (
+            "reasoning.end",
+            serde_json::json!({
+                "type": "reasoning.end",
+                "data": {
+                    "id": id,
+                    "sessionId": session_id
+                }
+            }),
         ),
-        RuntimeEvent::ReasoningEnd { session_id, id } => {
-            Event::default().event("reasoning.end").data(
-                serde_json::json!({
-                    "type": "reasoning.end",
-                    "data": {
-                        "id": id,
-                        "sessionId": session_id
-                    }
-                })
-                .to_string(),
-            )
-        }
         RuntimeEvent::MessageCreated {
             session_id,
             message,
         } => {
-            let content = match &message.content {
-                MessageContent::Text { text } => text.clone(),
-                _ => serde_json::to_string(&message.content).unwrap_or_default(),
-            };
+            // Tool calls and results have their own events, so only text is sent here
+            let mut payload = serde_json::json!({
+                "type": "message.created",
+                "data": {
+                    "messageId": message.id,
+                    "role": message.role.as_str(),
+                    "content": message.content.text().unwrap_or_default(),
+                    "sessionId": session_id
+                }
+            });
+            if let Some(reasoning) = message.content.reasoning_text() {
+                payload["data"]["reasoning"] = serde_json::json!(reasoning);
+            }
 
-            Event::default().event("message.created").data(
-                serde_json::json!({
-                    "type": "message.created",
-                    "data": {
-                        "messageId": message.id,
-                        "role": message.role.as_str(),
-                        "content": content,
-                        "sessionId": session_id
-                    }
-                })
-                .to_string(),
-            )
-        }
-        RuntimeEvent::ToolCallRequested { task_id, request } => {
-            Event::default().event("tool.call").data(
-                serde_json::json!({
-                    "type": "tool.call",
-                    "data": {
-                        "toolCallId": request.tool_call_id,
-                        "name": request.name,
-                        "input": request.input,
-                        "taskId": task_id
-                    }
-                })
-                .to_string(),
-            )
-        }
-        RuntimeEvent::ToolCallCompleted { task_id, result } => {
-            Event::default().event("tool.result").data(
-                serde_json::json!({
-                    "type": "tool.result",
-                    "data": {
-                        "toolCallId": result.tool_call_id,
-                        "name": result.name,
-                        "success": result.success,
-                        "output": result.output,
-                        "error": result.error,
-                        "taskId": task_id
-                    }
-                })
-                .to_string(),
-            )
+            ("message.created", payload)
         }
+        RuntimeEvent::ToolCallStart {
+            task_id,
+            session_id,
+            tool_call_id,
+            name,
+        } => (
+            "tool.call.start",
+            serde_json::json!({
+                "type": "tool.call.start",
+                "data": {
+                    "toolCallId": tool_call_id,
+                    "name": name,
+                    "taskId": task_id,
+                    "sessionId": session_id
+                }
+            }),
+        ),
+        RuntimeEvent::ToolCallDelta {
+            task_id,
+            session_id,
+            tool_call_id,
+            arguments_delta,
+        } => (
+            "tool.call.delta",
+            serde_json::json!({
+                "type": "tool.call.delta",
+                "data": {
+                    "toolCallId": tool_call_id,
+                    "argumentsDelta": arguments_delta,
+                    "taskId": task_id,
+                    "sessionId": session_id
+                }
+            }),
+        ),
+        RuntimeEvent::ToolCallRequested {
+            task_id,
+            session_id,
+            request,
+        } => (
+            "tool.call",
+            serde_json::json!({
+                "type": "tool.call",
+                "data": {
+                    "toolCallId": request.tool_call_id,
+                    "name": request.name,
+                    "input": request.input,
+                    "taskId": task_id,
+                    "sessionId": session_id
+                }
+            }),
+        ),
+        RuntimeEvent::ToolOutput {
+            task_id,
+            session_id,
+            tool_call_id,
+            stream,
+            chunk,
+        } => (
+            "tool.output",
+            serde_json::json!({
+                "type": "tool.output",
+                "data": {
+                    "toolCallId": tool_call_id,
+                    "stream": stream,
+                    "chunk": chunk,
+                    "taskId": task_id,
+                    "sessionId": session_id
+                }
+            }),
+        ),
+        RuntimeEvent::ToolCallCompleted {
+            task_id,
+            session_id,
+            result,
+        } => (
+            "tool.result",
+            serde_json::json!({
+                "type": "tool.result",
+                "data": {
+                    "toolCallId": result.tool_call_id,
+                    "name": result.name,
+                    "success": result.success,
+                    "output": result.output,
+                    "error": result.error,
+                    "taskId": task_id,
+                    "sessionId": session_id
+                }
+            }),
+        ),
         RuntimeEvent::TaskStateChanged {
             task_id,
+            session_id,
             state,
             previous_state,
-        } => Event::default().event("task.state_changed").data(
+        } => (
+            "task.state_changed",
             serde_json::json!({
                 "type": "task.state_changed",
                 "data": {
                     "taskId": task_id,
+                    "sessionId": session_id,
                     "state": format!("{:?}", state).to_lowercase(),
                     "previousState": format!("{:?}", previous_state).to_lowercase()
                 }
-            })
-            .to_string(),
+            }),
         ),
         RuntimeEvent::TaskCompleted {
             task_id,
             session_id,
+            stop_reason,
         } => {
             log::debug!(
                 "[CHAT] Converting TaskCompleted event: task_id={}, session_id={}",
                 task_id,
                 session_id
             );
-            Event::default().event("task.completed").data(
+            (
+                "task.completed",
                 serde_json::json!({
                     "type": "task.completed",
                     "data": {
                         "taskId": task_id,
-                        "sessionId": session_id
+                        "sessionId": session_id,
+                        "stopReason": stop_reason
                     }
-                })
-                .to_string(),
+                }),
             )
         }
         RuntimeEvent::Error {
@@ -759,7 +1007,8 @@ pub fn convert_runtime_event_to_sse(event: &RuntimeEvent) -> Event {
                 session_id,
                 message
             );
-            Event::default().event("error").data(
+            (
+                "error",
                 serde_json::json!({
                     "type": "error",
                     "data": {
@@ -767,20 +1016,34 @@ pub fn convert_runtime_event_to_sse(event: &RuntimeEvent) -> Event {
                         "taskId": task_id,
                         "sessionId": session_id
                     }
-                })
-                .to_string(),
-            )
-        }
-        RuntimeEvent::Usage { .. } | RuntimeEvent::Done { .. } => {
-            Event::default().event("status").data(
-                serde_json::json!({
-                    "type": "status",
-                    "data": {
-                        "message": "Runtime event ignored for session SSE"
-                    }
-                })
-                .to_string(),
+                }),
             )
         }
+        RuntimeEvent::SubAgentEvent {
+            session_id,
+            task_id,
+            sub_agent_id,
+            event,
+        } => (
+            "subagent.event",
+            serde_json::json!({
+                "type": "subagent.event",
+                "data": {
+                    "sessionId": session_id,
+                    "taskId": task_id,
+                    "subAgentId": sub_agent_id,
+                    "event": event
+                }
+            }),
+        ),
+        RuntimeEvent::Usage { .. } | RuntimeEvent::Done { .. } => (
+            "status",
+            serde_json::json!({
+                "type": "status",
+                "data": {
+                    "message": "Runtime event ignored for session SSE"
+                }
+            }),
+        ),
     }
 }
@@ -0,0 +1,103 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+
+use crate::state::ServerState;
+use crate::types::*;
+use talkcody_core::core::checkpoints::{CheckpointDiff, RestoreError};
+use talkcody_core::storage::models::FileCheckpoint;
+
+/// List the file checkpoints taken during a task, oldest first
+pub async fn list_checkpoints(
+    State(state): State<ServerState>,
+    Path(task_id): Path<String>,
+) -> Result<Json<Vec<FileCheckpoint>>, Json<ErrorResponse>> {
+    match state.runtime().list_checkpoints(&task_id).await {
+        Ok(checkpoints) => Ok(Json(checkpoints)),
+        Err(e) => Err(Json(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            format!("Failed to list checkpoints: {}", e),
+        ))),
+    }
+}
+
+/// Diff a checkpoint against the current file. Answers 404 for an unknown checkpoint.
+pub async fn diff_checkpoint(
+    State(state): State<ServerState>,
+    Path((task_id, checkpoint_id)): Path<(String, String)>,
+) -> Result<Json<CheckpointDiff>, (StatusCode, Json<ErrorResponse>)> {
+    match state
+        .runtime()
+        .diff_checkpoint(&task_id, &checkpoint_id)
+        .await
+    {
+        Ok(Some(diff)) => Ok(Json(diff)),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(checkpoint_not_found(&task_id, &checkpoint_id)),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse::new(
+                "INTERNAL_ERROR",
+                format!("Failed to diff checkpoint: {}", e),
+            )),
+        )),
+    }
+}
+
+/// Restore a checkpoint's file, or the whole task's changes, to the checkpoint.
+/// Answers 404 for an unknown checkpoint and 409 while the task is still running.
+pub async fn restore_checkpoint(
+    State(state): State<ServerState>,
+    Path((task_id, checkpoint_id)): Path<(String, String)>,
+    Json(payload): Json<RestoreCheckpointRequest>,
+) -> Result<Json<RestoreCheckpointResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let runtime = state.runtime();
+    let restored = match payload.scope {
+        RestoreScope::File => runtime
+            .restore_checkpoint(&task_id, &checkpoint_id)
+            .await
+            .map(|checkpoint| vec![checkpoint]),
+        RestoreScope::Task => {
+            runtime
+                .restore_task_to_checkpoint(&task_id, &checkpoint_id)
+                .await
+        }
+    };
+
+    match restored {
+        Ok(restored) => Ok(Json(RestoreCheckpointResponse { restored })),
+        Err(RestoreError::NotFound) => Err((
+            StatusCode::NOT_FOUND,
+            Json(checkpoint_not_found(&task_id, &checkpoint_id)),
+        )),
+        Err(RestoreError::TaskRunning) => Err((
+            StatusCode::CONFLICT,
+            Json(ErrorResponse::new(
+                "CONFLICT",
+                format!(
+                    "Task '{}' is still running; cancel it before restoring files",
+                    task_id
+                ),
+            )),
+        )),
+        Err(RestoreError::Other(e)) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse::new(
+                "INTERNAL_ERROR",
+                format!("Failed to restore checkpoint: {}", e),
+            )),
+        )),
+    }
+}
+
+fn checkpoint_not_found(task_id: &str, checkpoint_id: &str) -> ErrorResponse {
+    ErrorResponse::new(
+        "NOT_FOUND",
+        format!(
+            "Checkpoint '{}' not found for task '{}'",
+            checkpoint_id, task_id
+        ),
+    )
+}
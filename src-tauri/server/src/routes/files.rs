@@ -1,4 +1,5 @@
-use axum::extract::{Path, State};
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap};
 use axum::{body::Bytes, Json};
 
 use crate::state::ServerState;
@@ -9,6 +10,8 @@ use talkcody_core::storage::models::{Attachment, AttachmentOrigin};
 pub async fn upload_file(
     State(state): State<ServerState>,
     Path(session_id): Path<String>,
+    Query(query): Query<UploadFileQuery>,
+    headers: HeaderMap,
     body: Bytes,
 ) -> Result<Json<UploadFileResponse>, Json<ErrorResponse>> {
     // Verify session exists
@@ -31,14 +34,19 @@ pub async fn upload_file(
     let now = chrono::Utc::now().timestamp();
     let attachment_id = format!("att_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
 
-    // Determine mime type from content (simplified)
-    let mime_type = "application/octet-stream".to_string();
+    // The request's Content-Type decides whether the model can see the file
+    let mime_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.split(';').next().unwrap_or(v).trim().to_string())
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| "application/octet-stream".to_string());
 
     let attachment = Attachment {
         id: attachment_id.clone(),
         session_id: session_id.clone(),
         message_id: None,
-        filename: "upload.bin".to_string(), // Would extract from multipart in real impl
+        filename: query.filename.unwrap_or_else(|| "upload.bin".to_string()),
         mime_type: mime_type.clone(),
         size: body.len() as i64,
         path: String::new(), // Will be set by create_attachment
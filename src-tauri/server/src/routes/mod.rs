@@ -5,11 +5,14 @@ use crate::state::ServerState;
 
 pub mod actions;
 pub mod chat;
+pub mod checkpoints;
 pub mod files;
 pub mod health;
 pub mod messages;
 pub mod sessions;
 pub mod tasks;
+pub mod usage;
+pub mod ws;
 
 pub fn router(state: ServerState) -> Router {
     Router::new()
@@ -39,8 +42,25 @@ pub fn router(state: ServerState) -> Router {
         .route("/v1/tasks", get(tasks::list_tasks))
         .route("/v1/tasks/:id", get(tasks::get_task))
         .route("/v1/tasks/:id", patch(tasks::patch_task))
+        // Checkpoints
+        .route(
+            "/v1/tasks/:id/checkpoints",
+            get(checkpoints::list_checkpoints),
+        )
+        .route(
+            "/v1/tasks/:id/checkpoints/:checkpoint_id/diff",
+            get(checkpoints::diff_checkpoint),
+        )
+        .route(
+            "/v1/tasks/:id/checkpoints/:checkpoint_id/restore",
+            post(checkpoints::restore_checkpoint),
+        )
+        // Usage
+        .route("/v1/usage", get(usage::get_usage))
         // Actions
         .route("/v1/sessions/:id/actions", post(actions::create_action))
+        // WebSocket
+        .route("/v1/ws", get(ws::ws_handler))
         // Files
         .route("/v1/sessions/:id/files", post(files::upload_file))
         .route("/v1/sessions/:id/files", get(files::list_files))
@@ -1,13 +1,17 @@
 use axum::extract::{Path, Query, State};
+use axum::http::HeaderMap;
 use axum::response::sse::{Event, KeepAlive, Sse};
 use axum::Json;
 use std::convert::Infallible;
 
-use crate::routes::chat::convert_runtime_event_to_sse;
 use crate::state::ServerState;
+use crate::streaming_bridge::{session_event, session_event_payload, StreamEvent};
 use crate::types::*;
 use talkcody_core::core::types::RuntimeEvent;
-use talkcody_core::storage::models::{Session, SessionStatus, TaskSettings};
+use talkcody_core::storage::models::{
+    Session, SessionEvent, SessionStatus, TaskSettings, UsageFilter,
+};
+use talkcody_core::streaming::parse_event_id;
 
 /// Create a new session
 pub async fn create_session(
@@ -57,7 +61,22 @@ pub async fn get_session(
     Path(session_id): Path<String>,
 ) -> Result<Json<SessionResponse>, Json<ErrorResponse>> {
     match state.storage().chat_history.get_session(&session_id).await {
-        Ok(Some(session)) => Ok(Json(SessionResponse::from(session))),
+        Ok(Some(session)) => {
+            let filter = UsageFilter {
+                session_id: Some(session_id.clone()),
+                ..Default::default()
+            };
+            let usage = state.storage().usage.totals(&filter).await.map_err(|e| {
+                Json(ErrorResponse::new(
+                    "INTERNAL_ERROR",
+                    format!("Failed to get session usage: {}", e),
+                ))
+            })?;
+            Ok(Json(SessionResponse {
+                usage: Some(usage),
+                ..SessionResponse::from(session)
+            }))
+        }
         Ok(None) => Err(Json(ErrorResponse::new(
             "NOT_FOUND",
             format!("Session '{}' not found", session_id),
@@ -164,49 +183,64 @@ pub async fn update_session_settings(
 }
 
 /// SSE endpoint for session events
+///
+/// Resumes after the `Last-Event-ID` header (or `?since=` event ID) by replaying
+/// buffered and persisted events before switching to the live broadcast.
 pub async fn session_events(
     Path(session_id): Path<String>,
+    Query(query): Query<SessionEventsQuery>,
+    headers: HeaderMap,
     State(state): State<ServerState>,
 ) -> Sse<impl tokio_stream::Stream<Item = Result<Event, Infallible>>> {
     use async_stream::stream;
     use tokio::sync::broadcast;
 
+    let resume_from = resume_cursor(&headers, query.since.as_deref());
+
     // Create SSE stream using a manual stream implementation
-    let session_id_clone = session_id.clone();
     let stream = stream! {
+        // Subscribe before replaying so events published meanwhile are not missed
         let mut rx = state.event_broadcast.subscribe();
+        let mut last_event_id = resume_from;
+
+        if let Some(after) = last_event_id.clone() {
+            for event in replay_events(&state, &session_id, &after).await {
+                let sse_event = session_event_to_sse(&event);
+                last_event_id = Some(event.id);
+                yield Ok::<_, Infallible>(sse_event);
+            }
+        }
+
         loop {
             match rx.recv().await {
-                Ok(event) => {
-                    // Filter events for this session
-                    let session_matches = match &event {
-                        RuntimeEvent::Token { session_id: s, .. } => s == &session_id_clone,
-                        RuntimeEvent::ReasoningStart { session_id: s, .. } => s == &session_id_clone,
-                        RuntimeEvent::ReasoningDelta { session_id: s, .. } => s == &session_id_clone,
-                        RuntimeEvent::ReasoningEnd { session_id: s, .. } => s == &session_id_clone,
-                        RuntimeEvent::MessageCreated { session_id: s, .. } => s == &session_id_clone,
-                        RuntimeEvent::Usage { session_id: s, .. } => s == &session_id_clone,
-                        RuntimeEvent::Done { session_id: s, .. } => s == &session_id_clone,
-                        RuntimeEvent::TaskCompleted { session_id: s, .. } => s == &session_id_clone,
-                        RuntimeEvent::Error { session_id: s, .. } => {
-                            s.as_ref().map(|s| s == &session_id_clone).unwrap_or(false)
-                        }
-                        RuntimeEvent::ToolCallRequested { task_id: _, .. } => true,
-                        RuntimeEvent::ToolCallCompleted { task_id: _, .. } => true,
-                        RuntimeEvent::TaskStateChanged { task_id: _, .. } => true,
-                    };
+                Ok(StreamEvent { id, event }) => {
+                    // Skip events already sent during replay
+                    if last_event_id.as_deref().is_some_and(|last| id.as_str() <= last) {
+                        continue;
+                    }
 
-                    if !session_matches {
+                    // Filter events for this session
+                    if !event_matches_session(&event, &session_id) {
                         continue;
                     }
 
-                    // Convert RuntimeEvent to SSE event
-                    let sse_event = convert_runtime_event_to_sse_session(&event);
-                    yield Ok::<_, Infallible>(sse_event);
+                    // Render through the buffered form so live and replayed frames match
+                    let sse_event = session_event(&id, &event).map(|e| session_event_to_sse(&e));
+                    last_event_id = Some(id);
+                    if let Some(sse_event) = sse_event {
+                        yield Ok::<_, Infallible>(sse_event);
+                    }
                 }
-                Err(broadcast::error::RecvError::Lagged(_)) => {
-                    // Skip lagged events
-                    continue;
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    // Recover the skipped events from the buffer
+                    log::warn!("[SSE] Session {} lagged, skipped {} events", session_id, skipped);
+                    if let Some(after) = last_event_id.clone() {
+                        for event in replay_events(&state, &session_id, &after).await {
+                            let sse_event = session_event_to_sse(&event);
+                            last_event_id = Some(event.id);
+                            yield Ok::<_, Infallible>(sse_event);
+                        }
+                    }
                 }
                 Err(broadcast::error::RecvError::Closed) => {
                     // Channel closed, break the loop
@@ -219,7 +253,99 @@ pub async fn session_events(
     Sse::new(stream).keep_alive(KeepAlive::default())
 }
 
-/// Convert RuntimeEvent to SSE Event for session events
-fn convert_runtime_event_to_sse_session(event: &RuntimeEvent) -> Event {
-    convert_runtime_event_to_sse(event)
+/// Event ID to resume after: the `Last-Event-ID` header, else `?since=`.
+/// A cursor that is not one of our event IDs is ignored rather than used as a
+/// filter, where it could sort after every live event.
+fn resume_cursor(headers: &HeaderMap, since: Option<&str>) -> Option<String> {
+    headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_event_id)
+        .or_else(|| since.and_then(parse_event_id))
+}
+
+/// Load buffered (or persisted) events for a session after an event ID
+pub async fn replay_events(
+    state: &ServerState,
+    session_id: &str,
+    after: &str,
+) -> Vec<SessionEvent> {
+    let streaming = state.streaming();
+    let manager = streaming.read().await;
+    match manager
+        .buffer
+        .get_session_events(session_id, Some(after), None)
+        .await
+    {
+        Ok(events) => events,
+        Err(e) => {
+            log::warn!(
+                "[SSE] Failed to replay events for session {}: {}",
+                session_id,
+                e
+            );
+            vec![]
+        }
+    }
+}
+
+/// Render a buffered event as an SSE Event
+fn session_event_to_sse(event: &SessionEvent) -> Event {
+    Event::default()
+        .id(event.id.as_str())
+        .event(event.event_type.as_str())
+        .data(session_event_payload(event).to_string())
+}
+
+/// Whether a runtime event belongs to the given session
+pub fn event_matches_session(event: &RuntimeEvent, session_id: &str) -> bool {
+    match event {
+        RuntimeEvent::Token { session_id: s, .. } => s == session_id,
+        RuntimeEvent::ReasoningStart { session_id: s, .. } => s == session_id,
+        RuntimeEvent::ReasoningDelta { session_id: s, .. } => s == session_id,
+        RuntimeEvent::ReasoningEnd { session_id: s, .. } => s == session_id,
+        RuntimeEvent::MessageCreated { session_id: s, .. } => s == session_id,
+        RuntimeEvent::Usage { session_id: s, .. } => s == session_id,
+        RuntimeEvent::Done { session_id: s, .. } => s == session_id,
+        RuntimeEvent::TaskCompleted { session_id: s, .. } => s == session_id,
+        RuntimeEvent::SubAgentEvent { session_id: s, .. } => s == session_id,
+        RuntimeEvent::Error { session_id: s, .. } => {
+            s.as_ref().map(|s| s == session_id).unwrap_or(false)
+        }
+        RuntimeEvent::ToolCallStart { session_id: s, .. } => s == session_id,
+        RuntimeEvent::ToolCallDelta { session_id: s, .. } => s == session_id,
+        RuntimeEvent::ToolCallRequested { session_id: s, .. } => s == session_id,
+        RuntimeEvent::ToolOutput { session_id: s, .. } => s == session_id,
+        RuntimeEvent::ToolCallCompleted { session_id: s, .. } => s == session_id,
+        RuntimeEvent::TaskStateChanged { session_id: s, .. } => s == session_id,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_bogus_last_event_id_is_ignored() {
+        let mut headers = HeaderMap::new();
+        headers.insert("last-event-id", HeaderValue::from_static("zzz"));
+        assert_eq!(resume_cursor(&headers, None), None);
+        assert_eq!(resume_cursor(&HeaderMap::new(), Some("zzz")), None);
+
+        // A valid ?since= still applies when the header is bogus
+        assert_eq!(
+            resume_cursor(&headers, Some("evt_7")),
+            Some("evt_00000000000000000007".to_string())
+        );
+
+        headers.insert(
+            "last-event-id",
+            HeaderValue::from_static("evt_00000000000000000009"),
+        );
+        assert_eq!(
+            resume_cursor(&headers, Some("evt_7")),
+            Some("evt_00000000000000000009".to_string())
+        );
+    }
 }
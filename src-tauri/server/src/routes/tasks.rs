@@ -1,10 +1,11 @@
-use axum::extract::{Path, State};
+use axum::extract::{Path, Query, State};
 use axum::Json;
 
 use crate::state::ServerState;
 use crate::types::*;
 use talkcody_core::core::types::TaskInput;
 use talkcody_core::storage::models::SessionStatus;
+use talkcody_core::storage::models::TaskFilter;
 use talkcody_core::storage::models::WorkspaceInfo;
 
 /// Create a new task (starts agent execution)
@@ -58,6 +59,7 @@ pub async fn create_task(
         agent_id: payload.agent_id,
         project_id: payload.project_id,
         initial_message: payload.initial_message,
+        attachments: payload.attachments,
         settings: payload.settings,
         workspace,
     };
@@ -77,30 +79,12 @@ pub async fn create_task(
     }
 }
 
-/// Get task by ID
+/// Get task by ID, including tasks that have already finished
 pub async fn get_task(
     State(state): State<ServerState>,
     Path(task_id): Path<String>,
 ) -> Result<Json<TaskResponse>, Json<ErrorResponse>> {
-    match state.runtime().get_task(&task_id).await {
-        Some(handle) => {
-            let state_guard = handle.state.read().await;
-            Ok(Json(TaskResponse {
-                id: handle.task_id.clone(),
-                session_id: handle.session_id.clone(),
-                agent_id: None, // Would need to get from task storage
-                state: format!("{:?}", *state_guard).to_lowercase(),
-                created_at: chrono::Utc::now().timestamp(),
-                started_at: None,
-                completed_at: None,
-                error_message: None,
-            }))
-        }
-        None => Err(Json(ErrorResponse::new(
-            "NOT_FOUND",
-            format!("Task '{}' not found", task_id),
-        ))),
-    }
+    load_task(&state, &task_id).await.map(Json).map_err(Json)
 }
 
 /// Patch/update task (e.g., cancel, update settings)
@@ -125,51 +109,43 @@ pub async fn patch_task(
     }
 
     // Get updated task info
-    match state.runtime().get_task(&task_id).await {
-        Some(handle) => {
-            let state_guard = handle.state.read().await;
-            Ok(Json(TaskResponse {
-                id: handle.task_id.clone(),
-                session_id: handle.session_id.clone(),
-                agent_id: None,
-                state: format!("{:?}", *state_guard).to_lowercase(),
-                created_at: chrono::Utc::now().timestamp(),
-                started_at: None,
-                completed_at: None,
-                error_message: None,
-            }))
-        }
-        None => Err(Json(ErrorResponse::new(
-            "NOT_FOUND",
-            format!("Task '{}' not found", task_id),
-        ))),
-    }
+    load_task(&state, &task_id).await.map(Json).map_err(Json)
 }
 
-/// List active tasks
+/// List task history, newest first
 pub async fn list_tasks(
     State(state): State<ServerState>,
+    Query(query): Query<ListTasksQuery>,
 ) -> Result<Json<Vec<TaskResponse>>, Json<ErrorResponse>> {
-    let tasks = state.runtime().list_active_tasks().await;
+    let filter = TaskFilter {
+        session_id: query.session_id,
+        state: query.state,
+        created_after: query.created_after,
+        created_before: query.created_before,
+        limit: query.limit,
+        offset: query.offset,
+    };
 
-    let responses: Vec<TaskResponse> = tasks
-        .into_iter()
-        .map(|handle| {
-            let state_guard = handle.state.try_read().ok();
-            TaskResponse {
-                id: handle.task_id.clone(),
-                session_id: handle.session_id.clone(),
-                agent_id: None,
-                state: state_guard
-                    .map(|s| format!("{:?}", *s).to_lowercase())
-                    .unwrap_or_else(|| "unknown".to_string()),
-                created_at: chrono::Utc::now().timestamp(),
-                started_at: None,
-                completed_at: None,
-                error_message: None,
-            }
-        })
-        .collect();
+    match state.storage().tasks.list_tasks(&filter).await {
+        Ok(tasks) => Ok(Json(tasks.into_iter().map(TaskResponse::from).collect())),
+        Err(e) => Err(Json(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            format!("Failed to list tasks: {}", e),
+        ))),
+    }
+}
 
-    Ok(Json(responses))
+/// Load a task record from storage
+async fn load_task(state: &ServerState, task_id: &str) -> Result<TaskResponse, ErrorResponse> {
+    match state.storage().tasks.get_task(task_id).await {
+        Ok(Some(task)) => Ok(TaskResponse::from(task)),
+        Ok(None) => Err(ErrorResponse::new(
+            "NOT_FOUND",
+            format!("Task '{}' not found", task_id),
+        )),
+        Err(e) => Err(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            format!("Failed to get task: {}", e),
+        )),
+    }
 }
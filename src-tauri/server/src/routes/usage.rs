@@ -0,0 +1,43 @@
+use axum::extract::{Query, State};
+use axum::Json;
+
+use crate::state::ServerState;
+use crate::types::*;
+use talkcody_core::storage::models::{UsageFilter, UsageGroupBy};
+
+/// Token and cost report over recorded usage, grouped by one dimension
+pub async fn get_usage(
+    State(state): State<ServerState>,
+    Query(query): Query<UsageQuery>,
+) -> Result<Json<UsageReportResponse>, Json<ErrorResponse>> {
+    let group_by = match query.group_by.as_deref() {
+        Some(group_by) => group_by
+            .parse()
+            .map_err(|e: String| Json(ErrorResponse::new("BAD_REQUEST", e)))?,
+        None => UsageGroupBy::Model,
+    };
+
+    let filter = UsageFilter {
+        task_id: query.task_id,
+        session_id: query.session_id,
+        project_id: query.project_id,
+        model: query.model,
+        since: query.since,
+        until: query.until,
+    };
+
+    let usage = &state.storage().usage;
+    let totals = usage.totals(&filter).await;
+    let groups = usage.report(group_by, &filter).await;
+    match (totals, groups) {
+        (Ok(totals), Ok(groups)) => Ok(Json(UsageReportResponse {
+            group_by,
+            totals,
+            groups,
+        })),
+        (Err(e), _) | (_, Err(e)) => Err(Json(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            format!("Failed to load usage: {}", e),
+        ))),
+    }
+}
@@ -1,13 +1,26 @@
 //! WebSocket route for bidirectional communication
 //!
-//! Provides real-time updates and remote channel edits
+//! Clients subscribe to sessions to receive runtime events, and send
+//! actions (approve, reject, tool_result, cancel) and new user messages
+//! over the same connection. Events a slow connection misses are replayed
+//! from the event buffer.
+
+use std::collections::HashSet;
 
 use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
 use axum::extract::State;
 use axum::response::IntoResponse;
+use tokio::sync::broadcast;
 
+use crate::routes::actions::find_session_task;
+use crate::routes::chat::runtime_event_payload;
+use crate::routes::sessions::{event_matches_session, replay_events};
 use crate::state::ServerState;
+use crate::streaming_bridge::session_event_payload;
 use crate::types::{WebSocketMessage, WebSocketResponse};
+use talkcody_core::core::types::{TaskAction, TaskInput};
+use talkcody_core::storage::models::{EventId, SessionEvent, SessionId, WorkspaceInfo};
+use talkcody_core::streaming::next_event_id;
 
 /// WebSocket handler
 pub async fn ws_handler(
@@ -18,49 +31,229 @@ pub async fn ws_handler(
 }
 
 /// Handle WebSocket connection
-async fn handle_socket(mut socket: WebSocket, _state: ServerState) {
-    // In a full implementation, this would:
-    // 1. Authenticate the connection
-    // 2. Maintain a map of session subscriptions
-    // 3. Forward runtime events to subscribed clients
-    // 4. Handle incoming messages (subscribe, unsubscribe, actions)
-
-    // Placeholder: echo back ping messages
-    while let Some(Ok(msg)) = socket.recv().await {
-        match msg {
-            Message::Text(text) => {
-                // Parse and handle message
-                match serde_json::from_str::<WebSocketMessage>(&text) {
-                    Ok(WebSocketMessage::Ping) => {
-                        let response = WebSocketResponse::Pong;
-                        let _ = socket
-                            .send(Message::Text(serde_json::to_string(&response).unwrap()))
-                            .await;
-                    }
-                    Ok(WebSocketMessage::Subscribe { session_id }) => {
-                        let response = WebSocketResponse::Subscribed { session_id };
-                        let _ = socket
-                            .send(Message::Text(serde_json::to_string(&response).unwrap()))
-                            .await;
-                    }
-                    Ok(WebSocketMessage::Unsubscribe { session_id }) => {
-                        let response = WebSocketResponse::Unsubscribed { session_id };
-                        let _ = socket
-                            .send(Message::Text(serde_json::to_string(&response).unwrap()))
-                            .await;
-                    }
-                    Err(_) => {
-                        let response = WebSocketResponse::Error {
-                            message: "Invalid message format".to_string(),
-                        };
-                        let _ = socket
-                            .send(Message::Text(serde_json::to_string(&response).unwrap()))
-                            .await;
+async fn handle_socket(mut socket: WebSocket, state: ServerState) {
+    let mut subscriptions: HashSet<SessionId> = HashSet::new();
+    let mut events = state.event_broadcast.subscribe();
+    // Last event this connection received, to recover from if it lags. An
+    // unused ID orders after every event published before the subscription.
+    let mut last_event_id = next_event_id();
+
+    'socket: loop {
+        tokio::select! {
+            msg = socket.recv() => {
+                let text = match msg {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                };
+
+                let response = match serde_json::from_str::<WebSocketMessage>(&text) {
+                    Ok(message) => handle_message(&state, &mut subscriptions, message).await,
+                    Err(e) => WebSocketResponse::Error {
+                        message: format!("Invalid message format: {}", e),
+                    },
+                };
+                if send_response(&mut socket, &response).await.is_err() {
+                    break;
+                }
+            }
+            event = events.recv() => {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        // Recover the skipped events from the buffer
+                        log::warn!("[WS] Connection lagged, skipped {} events", skipped);
+                        for event in missed_events(&state, &subscriptions, &last_event_id).await {
+                            last_event_id = event.id.clone();
+                            let response = WebSocketResponse::Event {
+                                event: session_event_payload(&event),
+                            };
+                            if send_response(&mut socket, &response).await.is_err() {
+                                break 'socket;
+                            }
+                        }
+                        continue;
                     }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+
+                // Skip events already sent while recovering from lag
+                if event.id <= last_event_id {
+                    continue;
+                }
+                last_event_id = event.id.clone();
+
+                if !subscriptions
+                    .iter()
+                    .any(|session_id| event_matches_session(&event.event, session_id))
+                {
+                    continue;
+                }
+
+                let (_, payload) = runtime_event_payload(&event.event);
+                let response = WebSocketResponse::Event { event: payload };
+                if send_response(&mut socket, &response).await.is_err() {
+                    break;
                 }
             }
-            Message::Close(_) => break,
-            _ => {}
         }
     }
 }
+
+/// Buffered events of the subscribed sessions published after `after`, in order
+async fn missed_events(
+    state: &ServerState,
+    subscriptions: &HashSet<SessionId>,
+    after: &EventId,
+) -> Vec<SessionEvent> {
+    let mut missed = Vec::new();
+    for session_id in subscriptions {
+        missed.extend(replay_events(state, session_id, after).await);
+    }
+    missed.sort_by(|a, b| a.id.cmp(&b.id));
+    missed
+}
+
+/// Handle a client message and build the reply
+async fn handle_message(
+    state: &ServerState,
+    subscriptions: &mut HashSet<SessionId>,
+    message: WebSocketMessage,
+) -> WebSocketResponse {
+    match message {
+        WebSocketMessage::Ping => WebSocketResponse::Pong,
+        WebSocketMessage::Subscribe { session_id } => {
+            subscriptions.insert(session_id.clone());
+            WebSocketResponse::Subscribed { session_id }
+        }
+        WebSocketMessage::Unsubscribe { session_id } => {
+            subscriptions.remove(&session_id);
+            WebSocketResponse::Unsubscribed { session_id }
+        }
+        WebSocketMessage::Approve {
+            session_id,
+            tool_call_id,
+        } => send_action(state, session_id, TaskAction::Approve { tool_call_id }).await,
+        WebSocketMessage::Reject {
+            session_id,
+            tool_call_id,
+            reason,
+        } => {
+            send_action(
+                state,
+                session_id,
+                TaskAction::Reject {
+                    tool_call_id,
+                    reason,
+                },
+            )
+            .await
+        }
+        WebSocketMessage::ToolResult {
+            session_id,
+            tool_call_id,
+            result,
+        } => {
+            send_action(
+                state,
+                session_id,
+                TaskAction::ToolResult {
+                    tool_call_id,
+                    result,
+                },
+            )
+            .await
+        }
+        WebSocketMessage::Cancel { session_id } => {
+            send_action(state, session_id, TaskAction::Cancel).await
+        }
+        WebSocketMessage::Message {
+            session_id,
+            content,
+            agent_id,
+            workspace,
+        } => {
+            let workspace = workspace.map(|w| WorkspaceInfo {
+                root_path: w.root_path,
+                worktree_path: w.worktree_path,
+                repository_url: w.repository_url,
+                branch: w.branch,
+            });
+            match start_message_task(state, &session_id, content, agent_id, workspace).await {
+                Ok(task_id) => WebSocketResponse::TaskStarted {
+                    session_id,
+                    task_id,
+                },
+                Err(message) => WebSocketResponse::Error { message },
+            }
+        }
+    }
+}
+
+/// Send an action to the task running in a session
+async fn send_action(
+    state: &ServerState,
+    session_id: SessionId,
+    action: TaskAction,
+) -> WebSocketResponse {
+    let Some(task_handle) = find_session_task(state, &session_id).await else {
+        return WebSocketResponse::Error {
+            message: format!("No active task found for session '{}'", session_id),
+        };
+    };
+
+    match task_handle.send_action(action) {
+        Ok(_) => WebSocketResponse::ActionSent { session_id },
+        Err(e) => WebSocketResponse::Error {
+            message: format!("Failed to send action: {}", e),
+        },
+    }
+}
+
+/// Start a task in an existing session with a new user message
+async fn start_message_task(
+    state: &ServerState,
+    session_id: &str,
+    content: String,
+    agent_id: Option<String>,
+    workspace: Option<WorkspaceInfo>,
+) -> Result<String, String> {
+    let session = state
+        .storage()
+        .chat_history
+        .get_session(session_id)
+        .await
+        .map_err(|e| format!("Failed to get session: {}", e))?
+        .ok_or_else(|| format!("Session '{}' not found", session_id))?;
+
+    let settings = state
+        .storage()
+        .settings
+        .get_task_settings(session_id)
+        .await
+        .unwrap_or_default();
+
+    let handle = state
+        .runtime()
+        .start_task_if_idle(TaskInput {
+            session_id: session.id,
+            agent_id,
+            project_id: session.project_id,
+            initial_message: content,
+            attachments: Vec::new(),
+            settings,
+            workspace,
+        })
+        .await
+        .map_err(|e| format!("Failed to start task: {}", e))?;
+
+    Ok(handle.task_id)
+}
+
+async fn send_response(
+    socket: &mut WebSocket,
+    response: &WebSocketResponse,
+) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(response).unwrap_or_default();
+    socket.send(Message::Text(text)).await
+}
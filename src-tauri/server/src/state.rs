@@ -10,6 +10,8 @@ use talkcody_core::storage::Storage;
 use talkcody_core::streaming::StreamingManager;
 use tokio::sync::{broadcast, mpsc, RwLock};
 
+use crate::streaming_bridge::{init_streaming_bridge, StreamEvent};
+
 /// Server state shared across all request handlers
 #[derive(Clone)]
 pub struct ServerState {
@@ -18,8 +20,8 @@ pub struct ServerState {
     pub storage: Storage,
     pub platform: Platform,
     pub streaming: Arc<RwLock<StreamingManager>>,
-    pub event_broadcast: broadcast::Sender<RuntimeEvent>,
-    pub event_receiver: Arc<tokio::sync::Mutex<broadcast::Receiver<RuntimeEvent>>>,
+    pub event_broadcast: broadcast::Sender<StreamEvent>,
+    pub event_receiver: Arc<tokio::sync::Mutex<broadcast::Receiver<StreamEvent>>>,
 }
 
 impl ServerState {
@@ -27,11 +29,13 @@ impl ServerState {
         config: super::config::ServerConfig,
         runtime: CoreRuntime,
         storage: Storage,
-        event_broadcast: broadcast::Sender<RuntimeEvent>,
-        event_receiver: broadcast::Receiver<RuntimeEvent>,
+        event_broadcast: broadcast::Sender<StreamEvent>,
+        event_receiver: broadcast::Receiver<StreamEvent>,
     ) -> Self {
         let platform = Platform::new();
-        let streaming = Arc::new(RwLock::new(StreamingManager::new()));
+        let streaming = Arc::new(RwLock::new(
+            StreamingManager::new().with_storage(Arc::new(storage.clone())),
+        ));
 
         Self {
             config,
@@ -183,25 +187,11 @@ impl ServerStateFactory {
         }
 
         // Create broadcast channel for SSE events
-        let (broadcast_tx, broadcast_rx) = broadcast::channel::<RuntimeEvent>(100);
-
-        // Create an event forwarding channel
-        // We need a receiver to forward events from the runtime to broadcast
-        let (forward_tx, mut forward_rx) = mpsc::unbounded_channel::<RuntimeEvent>();
-
-        // Clone broadcast_tx for the forwarding task
-        let broadcast_tx_for_task = broadcast_tx.clone();
-
-        // Create a task to forward event_sender events to broadcast channel
-        // The runtime will use event_sender, and we need to forward those to broadcast
-        tokio::spawn(async move {
-            while let Some(event) = forward_rx.recv().await {
-                let _ = broadcast_tx_for_task.send(event);
-            }
-        });
+        let (broadcast_tx, broadcast_rx) = broadcast::channel::<StreamEvent>(100);
 
-        // Create the event sender that will be used by runtime
-        let event_sender = forward_tx;
+        // The runtime sends events here; the streaming bridge buffers them
+        // and forwards them to the broadcast channel
+        let (event_sender, event_receiver) = mpsc::unbounded_channel::<RuntimeEvent>();
 
         // Create runtime
         let runtime = CoreRuntime::new(
@@ -212,12 +202,9 @@ impl ServerStateFactory {
         )
         .await?;
 
-        Ok(ServerState::new(
-            config,
-            runtime,
-            storage,
-            broadcast_tx,
-            broadcast_rx,
-        ))
+        let state = ServerState::new(config, runtime, storage, broadcast_tx, broadcast_rx);
+        init_streaming_bridge(state.clone(), event_receiver);
+
+        Ok(state)
     }
 }
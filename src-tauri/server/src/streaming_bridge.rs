@@ -2,10 +2,23 @@
 //!
 //! Bridges runtime events to the StreamingManager for SSE and WebSocket delivery.
 //! Implements Phase 4: Wire runtime events to streaming manager.
+//!
+//! Every runtime event gets a stream ID and is buffered before it is broadcast,
+//! so SSE clients can resume from any ID they have seen. Buffered events keep
+//! the payload live clients get, so replayed frames match live ones.
 
+use crate::routes::chat::runtime_event_payload;
 use crate::state::ServerState;
 use talkcody_core::core::types::{EventReceiver, RuntimeEvent};
-use talkcody_core::streaming::events::StreamingEvent;
+use talkcody_core::storage::models::{EventId, EventType, SessionEvent};
+use talkcody_core::streaming::events::next_event_id;
+
+/// Runtime event with the stream ID it was published under
+#[derive(Debug, Clone)]
+pub struct StreamEvent {
+    pub id: EventId,
+    pub event: RuntimeEvent,
+}
 
 /// Bridge that forwards runtime events to the streaming manager
 pub struct StreamingBridge {
@@ -17,110 +30,163 @@ impl StreamingBridge {
         Self { state }
     }
 
-    /// Start the bridge - spawns a task that forwards events
+    /// Start the bridge - spawns a task that buffers events, then broadcasts them
     pub fn start(self, mut event_receiver: EventReceiver) {
         tokio::spawn(async move {
             while let Some(event) = event_receiver.recv().await {
-                self.handle_event(event).await;
+                let event = StreamEvent {
+                    id: next_event_id(),
+                    event,
+                };
+                self.handle_event(&event).await;
+                let _ = self.state.event_broadcast.send(event);
             }
         });
     }
 
-    /// Handle a single runtime event
-    async fn handle_event(&self, event: RuntimeEvent) {
-        let streaming = self.state.streaming();
-        let manager = streaming.write().await;
-
-        let streaming_event = match event {
-            RuntimeEvent::Token { session_id, token } => Some(StreamingEvent::Token {
-                event_id: format!("evt_{}", uuid::Uuid::new_v4()),
-                session_id,
-                data: talkcody_core::streaming::events::TokenEventData { token },
-            }),
-            RuntimeEvent::ToolCallRequested { task_id, request } => {
-                // Extract session_id from task_id (simplified)
-                let session_id = task_id.clone();
-                Some(StreamingEvent::ToolCall {
-                    event_id: format!("evt_{}", uuid::Uuid::new_v4()),
-                    session_id,
-                    data: talkcody_core::streaming::events::ToolCallEventData {
-                        tool_call_id: request.tool_call_id,
-                        name: request.name,
-                        input: request.input,
-                        provider_metadata: request.provider_metadata,
-                    },
-                })
-            }
-            RuntimeEvent::ToolCallCompleted { task_id, result } => {
-                // Extract session_id from task_id (simplified)
-                let session_id = task_id.clone();
-                Some(StreamingEvent::ToolResult {
-                    event_id: format!("evt_{}", uuid::Uuid::new_v4()),
-                    session_id,
-                    data: talkcody_core::streaming::events::ToolResultEventData {
-                        tool_call_id: result.tool_call_id,
-                        name: result.name,
-                        output: result.output,
-                    },
-                })
-            }
-            RuntimeEvent::MessageCreated {
-                session_id,
-                message,
-            } => {
-                // Convert to final message event
-                let content = match message.content {
-                    talkcody_core::storage::models::MessageContent::Text { text } => text,
-                    _ => serde_json::to_string(&message.content).unwrap_or_default(),
-                };
-                Some(StreamingEvent::MessageFinal {
-                    event_id: format!("evt_{}", uuid::Uuid::new_v4()),
-                    session_id,
-                    data: talkcody_core::streaming::events::MessageFinalEventData {
-                        message_id: message.id,
-                        content,
-                    },
-                })
-            }
-            RuntimeEvent::TaskStateChanged {
-                task_id,
-                state,
-                previous_state,
-            } => {
-                // Emit status event
-                let session_id = task_id.clone();
-                Some(StreamingEvent::Status {
-                    event_id: format!("evt_{}", uuid::Uuid::new_v4()),
-                    session_id,
-                    data: talkcody_core::streaming::events::StatusEventData {
-                        message: format!("Task state: {:?} -> {:?}", previous_state, state),
-                    },
-                })
-            }
-            RuntimeEvent::Error {
-                task_id,
-                session_id,
-                message,
-            } => {
-                let sid = session_id.or(task_id).unwrap_or_default();
-                Some(StreamingEvent::Error {
-                    event_id: format!("evt_{}", uuid::Uuid::new_v4()),
-                    session_id: Some(sid),
-                    data: talkcody_core::streaming::events::ErrorEventData { message },
-                })
-            }
-            _ => None,
+    /// Buffer a runtime event for resume
+    ///
+    /// Only takes a read lock; the buffer persists events in the background.
+    async fn handle_event(&self, stream_event: &StreamEvent) {
+        let Some(event) = session_event(&stream_event.id, &stream_event.event) else {
+            return;
         };
 
-        if let Some(event) = streaming_event {
-            // Add to buffer (ignore errors for now)
-            let _ = manager.buffer.add_event(event).await;
-        }
+        let streaming = self.state.streaming();
+        let manager = streaming.read().await;
+        manager.buffer.add_session_event(event).await;
     }
 }
 
+/// The buffered form of a runtime event, holding the same payload live
+/// clients receive. Usage, done and session-less errors are not replayed.
+pub fn session_event(id: &EventId, event: &RuntimeEvent) -> Option<SessionEvent> {
+    let session_id = match event {
+        RuntimeEvent::Usage { .. } | RuntimeEvent::Done { .. } => return None,
+        RuntimeEvent::Error { session_id, .. } => session_id.clone()?,
+        RuntimeEvent::Token { session_id, .. }
+        | RuntimeEvent::ReasoningStart { session_id, .. }
+        | RuntimeEvent::ReasoningDelta { session_id, .. }
+        | RuntimeEvent::ReasoningEnd { session_id, .. }
+        | RuntimeEvent::MessageCreated { session_id, .. }
+        | RuntimeEvent::TaskCompleted { session_id, .. }
+        | RuntimeEvent::SubAgentEvent { session_id, .. }
+        | RuntimeEvent::ToolCallStart { session_id, .. }
+        | RuntimeEvent::ToolCallDelta { session_id, .. }
+        | RuntimeEvent::ToolCallRequested { session_id, .. }
+        | RuntimeEvent::ToolOutput { session_id, .. }
+        | RuntimeEvent::ToolCallCompleted { session_id, .. }
+        | RuntimeEvent::TaskStateChanged { session_id, .. } => session_id.clone(),
+    };
+
+    let (name, mut payload) = runtime_event_payload(event);
+    let event_type: EventType = name.parse().ok()?;
+
+    Some(SessionEvent {
+        id: id.clone(),
+        session_id,
+        event_type,
+        payload: payload["data"].take(),
+        created_at: chrono::Utc::now().timestamp(),
+    })
+}
+
+/// The JSON payload sent for a buffered event, identical to the live one
+pub fn session_event_payload(event: &SessionEvent) -> serde_json::Value {
+    serde_json::json!({
+        "type": event.event_type.as_str(),
+        "data": event.payload,
+    })
+}
+
 /// Initialize the streaming bridge for a server
 pub fn init_streaming_bridge(state: ServerState, event_receiver: EventReceiver) {
     let bridge = StreamingBridge::new(state);
     bridge.start(event_receiver);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use talkcody_core::core::types::RuntimeTaskState;
+    use talkcody_core::streaming::StreamingManager;
+
+    #[tokio::test]
+    async fn test_resume_after_completion_replays_task_completed() {
+        let manager = StreamingManager::new();
+        let events = vec![
+            RuntimeEvent::Token {
+                session_id: "sess_1".to_string(),
+                token: "Hello".to_string(),
+            },
+            RuntimeEvent::TaskStateChanged {
+                task_id: "task_1".to_string(),
+                session_id: "sess_1".to_string(),
+                state: RuntimeTaskState::Completed,
+                previous_state: RuntimeTaskState::Running,
+            },
+            RuntimeEvent::TaskCompleted {
+                task_id: "task_1".to_string(),
+                session_id: "sess_1".to_string(),
+                stop_reason: None,
+            },
+        ];
+
+        let mut ids = Vec::new();
+        for event in &events {
+            let id = next_event_id();
+            let buffered = session_event(&id, event).expect("event is buffered");
+            manager.buffer.add_session_event(buffered).await;
+            ids.push(id);
+        }
+
+        // Reconnect with the id of the first event as Last-Event-ID
+        let replayed = manager
+            .buffer
+            .get_session_events("sess_1", Some(&ids[0]), None)
+            .await
+            .unwrap();
+        assert_eq!(replayed.len(), 2);
+        assert_eq!(replayed[1].id, ids[2]);
+        assert_eq!(replayed[1].event_type, EventType::TaskCompleted);
+
+        // Replayed frames carry exactly the live payload
+        for (replayed, event) in replayed.iter().zip(&events[1..]) {
+            let (name, live) = runtime_event_payload(event);
+            assert_eq!(replayed.event_type.as_str(), name);
+            assert_eq!(
+                session_event_payload(replayed).to_string(),
+                live.to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn test_message_created_sends_text_and_reasoning_apart() {
+        use talkcody_core::storage::models::{Message, MessageContent, MessagePart, MessageRole};
+
+        let event = RuntimeEvent::MessageCreated {
+            session_id: "sess_1".to_string(),
+            message: Message {
+                id: "msg_1".to_string(),
+                session_id: "sess_1".to_string(),
+                role: MessageRole::Assistant,
+                content: MessageContent::with_reasoning(
+                    vec![MessagePart::Reasoning {
+                        text: "Check main.rs".to_string(),
+                        provider_metadata: None,
+                    }],
+                    "It starts in main.rs".to_string(),
+                ),
+                created_at: 0,
+                tool_call_id: None,
+                parent_id: None,
+            },
+        };
+
+        let buffered = session_event(&next_event_id(), &event).expect("event is buffered");
+        assert_eq!(buffered.event_type, EventType::MessageCreated);
+        assert_eq!(buffered.payload["content"], "It starts in main.rs");
+        assert_eq!(buffered.payload["reasoning"], "Check main.rs");
+    }
+}
@@ -34,6 +34,9 @@ pub struct SessionResponse {
     pub updated_at: i64,
     pub last_event_id: Option<String>,
     pub metadata: Option<serde_json::Value>,
+    /// Token and cost totals across the session's tasks (single-session lookups only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<UsageTotals>,
 }
 
 impl From<Session> for SessionResponse {
@@ -47,6 +50,7 @@ impl From<Session> for SessionResponse {
             updated_at: session.updated_at,
             last_event_id: session.last_event_id,
             metadata: session.metadata,
+            usage: None,
         }
     }
 }
@@ -109,6 +113,13 @@ pub struct ListMessagesQuery {
     pub before_id: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionEventsQuery {
+    /// Resume after this event ID (same as the `Last-Event-ID` header)
+    pub since: Option<String>,
+}
+
 // ============== Task Types ==============
 
 #[derive(Debug, Deserialize)]
@@ -118,6 +129,9 @@ pub struct CreateTaskRequest {
     pub project_id: Option<String>,
     pub agent_id: Option<AgentId>,
     pub initial_message: String,
+    /// Image, file and audio parts referencing uploaded attachments
+    #[serde(default)]
+    pub attachments: Vec<MessagePart>,
     pub settings: Option<TaskSettings>,
     pub workspace: Option<WorkspaceInfoRequest>,
 }
@@ -151,6 +165,52 @@ pub struct TaskResponse {
     pub started_at: Option<i64>,
     pub completed_at: Option<i64>,
     pub error_message: Option<String>,
+    pub model: Option<String>,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cached_input_tokens: i64,
+    pub cache_creation_input_tokens: i64,
+    /// Share of input tokens served from the prompt cache
+    pub cache_hit_ratio: Option<f64>,
+    pub cost_usd: f64,
+    pub transitions: Vec<TaskStateTransition>,
+}
+
+impl From<TaskRecord> for TaskResponse {
+    fn from(task: TaskRecord) -> Self {
+        let cache_hit_ratio = task.cache_hit_ratio();
+        Self {
+            id: task.id,
+            session_id: task.session_id,
+            agent_id: task.agent_id,
+            state: task.state,
+            created_at: task.created_at,
+            started_at: task.started_at,
+            completed_at: task.completed_at,
+            error_message: task.error_message,
+            model: task.model,
+            input_tokens: task.input_tokens,
+            output_tokens: task.output_tokens,
+            cached_input_tokens: task.cached_input_tokens,
+            cache_creation_input_tokens: task.cache_creation_input_tokens,
+            cache_hit_ratio,
+            cost_usd: task.cost_usd,
+            transitions: task.transitions,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListTasksQuery {
+    pub session_id: Option<String>,
+    pub state: Option<String>,
+    /// Unix timestamp (seconds); only tasks created at or after it
+    pub created_after: Option<i64>,
+    /// Unix timestamp (seconds); only tasks created before it
+    pub created_before: Option<i64>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -160,6 +220,58 @@ pub struct PatchTaskRequest {
     pub action: Option<String>, // "cancel"
 }
 
+// ============== Checkpoint Types ==============
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RestoreScope {
+    /// Restore only the checkpoint's file
+    #[default]
+    File,
+    /// Undo every file change the task made from the checkpoint onwards
+    Task,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreCheckpointRequest {
+    #[serde(default)]
+    pub scope: RestoreScope,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreCheckpointResponse {
+    /// Checkpoints written back, one per file
+    pub restored: Vec<FileCheckpoint>,
+}
+
+// ============== Usage Types ==============
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageQuery {
+    /// `task`, `session`, `project`, `model` (default) or `day`
+    pub group_by: Option<String>,
+    pub task_id: Option<String>,
+    pub session_id: Option<String>,
+    pub project_id: Option<String>,
+    pub model: Option<String>,
+    /// Unix timestamp (seconds); only usage recorded at or after it
+    pub since: Option<i64>,
+    /// Unix timestamp (seconds); only usage recorded before it
+    pub until: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageReportResponse {
+    pub group_by: UsageGroupBy,
+    /// Totals across all matching usage
+    pub totals: UsageTotals,
+    pub groups: Vec<UsageReportEntry>,
+}
+
 // ============== Action Types ==============
 
 #[derive(Debug, Deserialize)]
@@ -180,6 +292,13 @@ pub struct CreateActionResponse {
 
 // ============== File Types ==============
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadFileQuery {
+    /// Original file name, stored with the attachment
+    pub filename: Option<String>,
+}
+
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UploadFileResponse {
@@ -295,6 +414,37 @@ pub enum WebSocketMessage {
     Unsubscribe { session_id: SessionId },
     #[serde(rename = "ping")]
     Ping,
+    /// Approve a pending tool call
+    #[serde(rename = "approve")]
+    Approve {
+        session_id: SessionId,
+        tool_call_id: String,
+    },
+    /// Reject a pending tool call
+    #[serde(rename = "reject")]
+    Reject {
+        session_id: SessionId,
+        tool_call_id: String,
+        reason: Option<String>,
+    },
+    /// Provide the result of a client-side tool call
+    #[serde(rename = "tool_result")]
+    ToolResult {
+        session_id: SessionId,
+        tool_call_id: String,
+        result: serde_json::Value,
+    },
+    /// Cancel the active task
+    #[serde(rename = "cancel")]
+    Cancel { session_id: SessionId },
+    /// Send a user message, starting a new task in the session
+    #[serde(rename = "message")]
+    Message {
+        session_id: SessionId,
+        content: String,
+        agent_id: Option<String>,
+        workspace: Option<WorkspaceInfoRequest>,
+    },
 }
 
 #[derive(Debug, Serialize)]
@@ -304,8 +454,16 @@ pub enum WebSocketResponse {
     Subscribed { session_id: SessionId },
     #[serde(rename = "unsubscribed")]
     Unsubscribed { session_id: SessionId },
+    /// Runtime event in the same `{type, data}` shape as the SSE payloads
     #[serde(rename = "event")]
-    Event { event: SseEvent },
+    Event { event: serde_json::Value },
+    #[serde(rename = "actionSent")]
+    ActionSent { session_id: SessionId },
+    #[serde(rename = "taskStarted")]
+    TaskStarted {
+        session_id: SessionId,
+        task_id: String,
+    },
     #[serde(rename = "pong")]
     Pong,
     #[serde(rename = "error")]
@@ -169,6 +169,10 @@ export class LlmClient {
     return invoke<AvailableModel[]>('llm_list_available_models');
   }
 
+  async refreshLocalModels(): Promise<AvailableModel[]> {
+    return invoke<AvailableModel[]>('llm_refresh_local_models');
+  }
+
   async getProviderConfigs(): Promise<ProviderConfig[]> {
     return invoke<ProviderConfig[]>('llm_get_provider_configs');
   }
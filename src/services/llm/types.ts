@@ -93,6 +93,8 @@ export type StreamResponse = {
 export type StreamEvent =
   | { type: 'text-start' }
   | { type: 'text-delta'; text: string }
+  | { type: 'tool-call-start'; toolCallId: string; toolName: string }
+  | { type: 'tool-call-delta'; toolCallId: string; argumentsDelta: string }
   | {
       type: 'tool-call';
       toolCallId: string;
@@ -125,7 +127,8 @@ export type StreamEvent =
     }
   | { type: 'done'; finish_reason?: string | null }
   | { type: 'error'; message: string; name?: string }
-  | { type: 'raw'; raw_value: string };
+  | { type: 'raw'; raw_value: string }
+  | { type: 'model-selected'; model: string; provider_id: string; fallback: boolean };
 
 export type AvailableModel = {
   key: string;
@@ -137,6 +140,8 @@ export type AvailableModel = {
   audioInput: boolean;
   videoInput: boolean;
   inputPricing?: string;
+  contextLength?: number;
+  toolCall?: boolean;
 };
 
 export type ProviderConfig = {
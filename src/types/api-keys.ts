@@ -28,6 +28,8 @@ export interface AvailableModel {
   audioInput: boolean;
   videoInput: boolean;
   inputPricing?: string;
+  contextLength?: number;
+  toolCall?: boolean;
 }
 
 // Custom provider API key mapping